@@ -0,0 +1,114 @@
+use crate::collate;
+use crate::xm::XMInfo;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The episodes of one album found in a set of files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumEpisodes {
+    /// The album name, empty if the files carry no album.
+    pub album: String,
+    /// The distinct track numbers that are present.
+    pub present: BTreeSet<u64>,
+    /// The track numbers between 1 and the highest present track that are missing.
+    pub missing: Vec<u64>,
+}
+
+impl AlbumEpisodes {
+    /// Returns true if no episode up to the highest present track number is missing.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for AlbumEpisodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last = self.present.iter().next_back().copied().unwrap_or(0);
+        write!(
+            f,
+            "{}: {} of {} episodes",
+            self.album,
+            self.present.len(),
+            last
+        )?;
+        if !self.is_complete() {
+            write!(f, ", missing {}", format_ranges(&self.missing))?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups the infos by album and reports which track numbers are missing from each album.
+///
+/// Files without a track number are ignored. Albums are returned sorted by name with
+/// [`collate::compare`].
+pub fn audit_episodes<'a>(infos: impl IntoIterator<Item = &'a XMInfo>) -> Vec<AlbumEpisodes> {
+    let mut albums = BTreeMap::<String, BTreeSet<u64>>::new();
+    for info in infos {
+        if info.tracknumber == 0 {
+            continue;
+        }
+        albums
+            .entry(info.album.clone().unwrap_or_default())
+            .or_default()
+            .insert(info.tracknumber);
+    }
+    let mut albums: Vec<_> = albums.into_iter().collect();
+    albums.sort_by(|(a, _), (b, _)| collate::compare(a, b));
+    albums
+        .into_iter()
+        .map(|(album, present)| {
+            let last = present.iter().next_back().copied().unwrap_or(0);
+            let missing = (1..=last).filter(|n| !present.contains(n)).collect();
+            AlbumEpisodes {
+                album,
+                present,
+                missing,
+            }
+        })
+        .collect()
+}
+
+/// Formats sorted numbers, collapsing consecutive runs, e.g. `3, 7-9, 12`.
+fn format_ranges(numbers: &[u64]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < numbers.len() {
+        let start = numbers[i];
+        let mut end = start;
+        while i + 1 < numbers.len() && numbers[i + 1] == end + 1 {
+            i += 1;
+            end = numbers[i];
+        }
+        parts.push(match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        });
+        i += 1;
+    }
+    parts.join(", ")
+}
+
+#[test]
+fn test_audit_episodes() {
+    let info = |album: &str, tracknumber| {
+        let mut info = XMInfo::default();
+        info.album = Some(album.to_string());
+        info.tracknumber = tracknumber;
+        info
+    };
+    let infos = [
+        info("b", 2),
+        info("a", 1),
+        info("a", 4),
+        info("a", 5),
+        info("b", 1),
+        info("a", 0),
+    ];
+    let albums = audit_episodes(&infos);
+    assert_eq!(albums.len(), 2);
+    assert_eq!(albums[0].album, "a");
+    assert_eq!(albums[0].missing, vec![2, 3]);
+    assert_eq!(albums[0].to_string(), "a: 3 of 5 episodes, missing 2-3");
+    assert!(albums[1].is_complete());
+}
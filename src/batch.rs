@@ -0,0 +1,269 @@
+use crate::collate;
+use crate::naming::{numbered, CollisionState, NameTemplate};
+use crate::xm::{self, Decryptor, Format, IvSource, XMInfo};
+use crate::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What [`decrypt_all`] does when an output name is taken, by an existing file or by an earlier
+/// output of the same call. Names are compared like [`crate::naming::fold_name`] does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// Leave the existing file alone and don't write the output.
+    Skip,
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Write the output under a new name with a numeric suffix.
+    Rename,
+    /// Fail the file.
+    Error,
+}
+
+/// How [`decrypt_all`] finds inputs and names and writes outputs.
+#[derive(Debug, Clone)]
+pub struct DecryptOptions {
+    /// Where outputs are written, recreating the directories below each input directory. Without
+    /// it the outputs are written next to the inputs.
+    pub output_dir: Option<PathBuf>,
+    pub template: NameTemplate,
+    pub on_conflict: Conflict,
+    /// Whether input directories are scanned recursively or only their own files are taken.
+    pub recursive: bool,
+    /// The AES key to decrypt xm files with instead of [`xm::XM_KEY`], for when the client
+    /// changes it.
+    pub key: Option<[u8; 32]>,
+    pub iv_source: IvSource,
+    /// Whether inputs that are already plain audio, such as an m4a file renamed to `.xm`, are
+    /// copied to the output like decrypted files. Otherwise they fail as not encrypted.
+    pub copy_plain: bool,
+    /// The track id to decrypt xm files with instead of the one in their `TRCK` frame, for a
+    /// single file whose tag is corrupt. Without it a failing file is retried with the numbers in
+    /// its name, see [`Decryptor::decrypt_trying_track_ids`].
+    pub track_id: Option<u64>,
+}
+
+impl Default for DecryptOptions {
+    fn default() -> Self {
+        DecryptOptions {
+            output_dir: None,
+            template: NameTemplate::default(),
+            on_conflict: Conflict::default(),
+            recursive: true,
+            key: None,
+            iv_source: IvSource::default(),
+            copy_plain: false,
+            track_id: None,
+        }
+    }
+}
+
+/// The outcome for one input of [`decrypt_all`].
+#[derive(Debug)]
+pub struct FileResult {
+    /// The xm file, or the directory that couldn't be scanned.
+    pub source: PathBuf,
+    /// The path of the output, `None` if it was skipped under [`Conflict::Skip`].
+    pub result: Result<Option<PathBuf>>,
+}
+
+/// Decrypts the xm, x2m and x3m files among `paths`, and those in the directories among them, in
+/// natural order. Each file is named from `options.template` and tagged like the command-line tool
+/// does.
+///
+/// A file that fails doesn't stop the others, every file and every directory that can't be scanned
+/// gets a [`FileResult`].
+pub fn decrypt_all(
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    options: &DecryptOptions,
+) -> Vec<FileResult> {
+    let mut inputs = Vec::new();
+    let mut results = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            inputs.push((path.to_path_buf(), root));
+            continue;
+        }
+        let mut files = Vec::new();
+        if let Err(e) = find_xm_files(path, options.recursive, &mut files, &mut results) {
+            results.push(FileResult {
+                source: path.to_path_buf(),
+                result: Err(e),
+            });
+        }
+        files.sort_by(|a, b| collate::compare_paths(a, b));
+        inputs.extend(files.into_iter().map(|file| (file, path.to_path_buf())));
+    }
+
+    let decryptor = match Decryptor::new() {
+        Ok(decryptor) => {
+            let decryptor = decryptor.with_iv_source(options.iv_source);
+            match options.key {
+                Some(key) => decryptor.with_key(key),
+                None => decryptor,
+            }
+        }
+        Err(e) => {
+            let message = e.to_string();
+            results.extend(inputs.into_iter().map(|(source, _)| FileResult {
+                source,
+                result: Err(message.clone().into()),
+            }));
+            return results;
+        }
+    };
+    let mut names = CollisionState::new();
+    for (source, root) in inputs {
+        let result = decrypt_file(&decryptor, &source, &root, options, &mut names);
+        results.push(FileResult { source, result });
+    }
+    results
+}
+
+/// Adds the xm, x2m and x3m files in `dir` to `files`. A subdirectory that can't be read is recorded in
+/// `results` and skipped.
+fn find_xm_files(
+    dir: &Path,
+    recursive: bool,
+    files: &mut Vec<PathBuf>,
+    results: &mut Vec<FileResult>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if !recursive {
+                continue;
+            }
+            if let Err(e) = find_xm_files(&path, recursive, files, results) {
+                results.push(FileResult {
+                    source: path,
+                    result: Err(e),
+                });
+            }
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("xm" | "x2m" | "x3m")
+        ) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn decrypt_file(
+    decryptor: &Decryptor,
+    source: &Path,
+    root: &Path,
+    options: &DecryptOptions,
+    names: &mut CollisionState,
+) -> Result<Option<PathBuf>> {
+    let content = fs::read(source)?;
+    let title = source.file_stem().unwrap_or_default().to_string_lossy();
+    let (xm_info, audio) = match xm::detect(&content) {
+        xm::Source::Audio(_) if options.copy_plain => (XMInfo::plain(&content), content.clone()),
+        detected => match detected.format()? {
+            Format::Xm => {
+                let mut xm_info = xm::extract_xm_info(&content[..])?;
+                let audio = match options.track_id {
+                    Some(track_id) => {
+                        xm_info.set_tracknumber(track_id);
+                        decryptor.decrypt(&xm_info, &content)?
+                    }
+                    None => {
+                        let candidates = xm::track_ids_in_name(&title);
+                        let (audio, track_id) =
+                            decryptor.decrypt_trying_track_ids(&xm_info, &content, &candidates)?;
+                        xm_info.set_tracknumber(track_id);
+                        audio
+                    }
+                };
+                (xm_info, audio)
+            }
+            format => {
+                let xm_info = XMInfo::untagged(&title, content.len());
+                (xm_info, xm::decrypt_scrambled(format, &content)?)
+            }
+        },
+    };
+    let header = &audio[..audio.len().min(0xFF)];
+
+    let parent = source.parent().unwrap_or(Path::new(""));
+    let target_dir = match &options.output_dir {
+        Some(output_dir) => output_dir.join(parent.strip_prefix(root).unwrap_or(Path::new(""))),
+        None => parent.to_path_buf(),
+    };
+    // The template may contain directory separators.
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let path = target_dir.join(xm_info.file_name_or(&options.template, header, &stem));
+    let dir = path.parent().expect("no parent dir");
+    fs::create_dir_all(dir)?;
+    if !names.knows_dir(dir) {
+        let files = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        names.add_dir(dir, files);
+    }
+
+    let mut target = path.clone();
+    for n in 1.. {
+        let Some(existing) = names.claim(&target) else {
+            break;
+        };
+        match options.on_conflict {
+            Conflict::Skip => return Ok(None),
+            Conflict::Error => return Err(format!("{} already exists", existing.display()).into()),
+            Conflict::Overwrite => {
+                target = existing;
+                break;
+            }
+            Conflict::Rename => target = numbered(&path, n),
+        }
+    }
+    fs::write(&target, &audio)?;
+    xm_info.write_tag(&target, header)?;
+    Ok(Some(target))
+}
+
+#[test]
+fn test_decrypt_all() {
+    use crate::id3::{Tag, TagLike, Version};
+
+    let root = std::env::temp_dir().join(format!("xm_decryptor_batch_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("in/sub")).unwrap();
+    let mut tag = Tag::new();
+    tag.set_title("promo");
+    tag.set_text("TSIZ", "0");
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+    content.extend_from_slice(b"ID3 mp3 audio");
+    for file in ["in/a.xm", "in/c.xm", "in/sub/b.xm"] {
+        fs::write(root.join(file), &content).unwrap();
+    }
+    fs::write(root.join("in/broken.xm"), b"not a tag").unwrap();
+
+    let options = DecryptOptions {
+        output_dir: Some(root.join("out")),
+        template: "{title}".parse().unwrap(),
+        on_conflict: Conflict::Rename,
+        ..Default::default()
+    };
+    let results = decrypt_all([root.join("in")], &options);
+    let outputs: Vec<_> = results
+        .iter()
+        .map(|r| r.result.as_ref().ok().cloned().flatten())
+        .collect();
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(
+        outputs,
+        [
+            Some(root.join("out/promo.mp3")),
+            None,
+            Some(root.join("out/promo (1).mp3")),
+            Some(root.join("out/sub/promo.mp3")),
+        ]
+    );
+    assert!(results[1].result.is_err());
+}
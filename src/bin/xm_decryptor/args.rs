@@ -0,0 +1,580 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use xm_decryptor::naming::{NameTemplate, DEFAULT_MAX_NAME_BYTES};
+use xm_decryptor::xm::IvSource;
+
+use crate::filter::Filters;
+use crate::hooks::Hook;
+use crate::lang::{self, Lang};
+use crate::logging::LogFormat;
+use crate::report;
+use xm_decryptor::Result;
+
+/// What to do when the output file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Leave the existing file alone and don't write the output.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Write the output under a new name with a numeric suffix.
+    Rename,
+    /// Fail the file.
+    Error,
+    /// Ask on the terminal, set by `--interactive`.
+    Ask,
+}
+
+impl FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(OnConflict::Skip),
+            "overwrite" => Ok(OnConflict::Overwrite),
+            "rename" => Ok(OnConflict::Rename),
+            "error" => Ok(OnConflict::Error),
+            _ => Err(format!(
+                "invalid --on-conflict value {}, expected skip, overwrite, rename or error",
+                s
+            )),
+        }
+    }
+}
+
+/// How hard to try to get the outputs onto disk before the run finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Durability {
+    /// Leave flushing to the operating system.
+    None,
+    /// Sync the outputs in batches, every few files or seconds.
+    Batched,
+    /// Sync every output before moving on to the next file.
+    PerFile,
+}
+
+impl FromStr for Durability {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Durability::None),
+            "batched" => Ok(Durability::Batched),
+            "per-file" => Ok(Durability::PerFile),
+            _ => Err(format!(
+                "invalid --durability value {}, expected none, batched or per-file",
+                s
+            )),
+        }
+    }
+}
+
+/// How often the cover art of a folder of outputs is stored, set by `--cover-once`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverOnce {
+    /// Embed it only in the first output written to the folder.
+    First,
+    /// Embed it in none of the outputs, save it as a file in the folder instead.
+    FolderOnly,
+}
+
+impl FromStr for CoverOnce {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(CoverOnce::First),
+            "folder-only" => Ok(CoverOnce::FolderOnly),
+            _ => Err(format!(
+                "invalid --cover-once value {}, expected first or folder-only",
+                s
+            )),
+        }
+    }
+}
+
+/// What happens to a source file after it has been decrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceAction {
+    Keep,
+    Delete,
+    /// Move it below the given directory.
+    Archive(PathBuf),
+}
+
+/// The usage text printed by `--help`.
+const USAGE: &str = "\
+Decrypts Ximalaya .xm, .x2m and .x3m downloads.
+
+Usage: xm_decryptor [COMMAND] [OPTIONS] <PATH>
+       xm_decryptor [COMMAND] [OPTIONS] --files-from <LIST>
+
+PATH is an .xm, .x2m or .x3m file or a directory, which is scanned recursively. A
+LIST of - reads the paths from stdin, a plain - as PATH does the same. Built with the
+http feature, PATH and the lines of LIST may also be http:// or https:// URLs.
+
+Commands:
+  decrypt              Decrypt the files (default)
+  info                 Print the information stored in the xm tags without decrypting
+  verify               Decrypt the files in memory and check the audio is intact
+  doctor               Score the health of the xm tags and explain each stage of
+                       decrypting the files, up to the one that fails
+  bench                Time the stages of decrypting the files, without keeping outputs
+  retry-failures       Decrypt again the files whose last attempt failed, as recorded in
+                       the --index of the output root
+  pause                Make the runs writing to the output root of PATH wait after the
+                       files being decrypted, --watch included
+  resume               Let the paused runs go on
+  gui                  Open a page in the browser to pick a folder, start decrypting it
+                       and watch the files, with the OPTIONS for every run. Needs a
+                       build with the gui feature
+  install-shell-ext    Add \"Decrypt .xm here\" to the Windows Explorer context menu
+  uninstall-shell-ext  Remove the context menu entries again
+
+Options:
+      --files-from <LIST>         Read the paths to process from LIST, one per line
+      --max-depth <N>             Scan at most N directory levels, 1 is the directory itself
+      --include <GLOB>            Only process files matching GLOB, may be repeated
+      --exclude <GLOB>            Skip files and directories matching GLOB, may be repeated
+  -j, --jobs <N>                  Decrypt N files at the same time [default: 1]
+      --group-by-album            Decrypt each album on one worker, in track order
+      --output-dir <DIR>          Write the outputs below DIR instead of next to the inputs
+      --map <IN=OUT>              Process the directory IN with outputs below OUT, may be
+                                  repeated instead of PATH and --output-dir
+      --name-template <TEMPLATE>  Output file name [default: {artist} - {album} - {title}]
+      --max-name-bytes <N>        Shorten album, artist and title to keep names within N bytes
+                                  [default: 255]
+      --on-conflict <POLICY>      skip, overwrite, rename or error [default: overwrite]
+      --interactive               Ask what to do about name collisions and missing titles
+      --durability <MODE>         none, batched or per-file [default: none]
+      --delete-source             Delete each .xm file once its output is written
+      --archive-dir <DIR>         Move each .xm file below DIR once its output is written
+      --report <FILE>             Write a JSON report of the run to FILE
+      --report-schema             Print the JSON Schema of the report
+      --audit                     Only list missing episodes per album
+      --watch                     Keep decrypting new files that appear in the directory
+      --tui                       Show the files full screen with their status and errors, with
+                                  keys to pause, retry a failed file, skip a file and quit
+      --force                     Decrypt files an earlier run already decrypted
+      --index                     Keep an index of the decrypted tracks in the output root and
+                                  skip tracks it already holds, also from other copies
+      --fix-tags                  Correct the ID3 tags of the outputs
+      --write-tags                Write title, artist, album, track, year, genre, comment and
+                                  lyrics into MP3, M4A and FLAC outputs
+      --copy-plain                Copy .xm files that are already plain audio to the output
+                                  instead of skipping them as not encrypted
+      --track-id <N>              Decrypt with track id N instead of the one in the tag, for a
+                                  single file whose TRCK frame is corrupt. The input must be
+                                  that file
+      --extract-cover             Also save the cover art of the xm tag as cover.jpg next to
+                                  the outputs
+      --cover-once <MODE>         Store the cover of each output folder once: embed it only in
+                                  the first output (first), or in none and save it as
+                                  cover.jpg instead (folder-only)
+      --embed-source-tag          Keep the original xm tag in the outputs
+      --preserve-times            Give the outputs the modification time of the .xm files
+      --key-file <FILE>           Decrypt xm files with the AES key in FILE, 32 characters or
+                                  64 hex digits, for clients that changed the key
+      --iv <SOURCE>               Take the AES IV from tag (TSRC, else TENC), tsrc, tenc, or
+                                  use the given 32 hex digits [default: tag]
+      --verify                    Check the decrypted audio is intact before writing it
+      --dump-intermediate <DIR>   Write the AES, wasm and base64 text of each .xm file that
+                                  fails to decrypt into DIR
+      --fix-vbr-header            Correct the frame and byte counts of MP3 VBR headers
+      --hook <EXT=COMMAND>        Run COMMAND on each output with extension EXT after tagging,
+                                  {} is the output path, may be repeated
+      --metadata <CSV>            Take titles, albums, narrators and cover links from CSV
+      --frames                    With info, print every frame of the tags
+      --json                      With info, print one JSON object per file
+  -v, -vv                         Log debug, or also trace, details of every file
+      --lang <LANG>               Write messages in zh or en [default: from the locale]
+      --log-format <FORMAT>       text or json log lines on stderr [default: text]
+  -h, --help                      Print this help
+  -V, --version                   Print the version
+";
+
+/// What to do with the input files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Decrypt the files and write the outputs.
+    Decrypt,
+    /// Print the information stored in the xm tags.
+    Info,
+    /// Decrypt the files without writing anything and check the result.
+    Verify,
+    /// Score the health of the xm tags.
+    Doctor,
+    /// Decrypt the files without keeping the outputs and time each stage.
+    Bench,
+    /// Decrypt the files that failed in an earlier run with `--index`.
+    RetryFailures,
+    /// Make the runs writing to the output root wait.
+    Pause,
+    /// Let the paused runs go on.
+    Resume,
+    /// Serve the web UI, which takes the inputs.
+    Gui,
+    /// Add the Explorer context menu entries.
+    InstallShellExt,
+    /// Remove the Explorer context menu entries.
+    UninstallShellExt,
+}
+
+impl Command {
+    /// Returns false for the commands that don't process input files.
+    fn takes_inputs(self) -> bool {
+        !matches!(
+            self,
+            Command::Gui | Command::InstallShellExt | Command::UninstallShellExt
+        )
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "decrypt" => Ok(Command::Decrypt),
+            "info" => Ok(Command::Info),
+            "verify" => Ok(Command::Verify),
+            "doctor" => Ok(Command::Doctor),
+            "bench" => Ok(Command::Bench),
+            "retry-failures" => Ok(Command::RetryFailures),
+            "pause" => Ok(Command::Pause),
+            "resume" => Ok(Command::Resume),
+            "gui" => Ok(Command::Gui),
+            "install-shell-ext" => Ok(Command::InstallShellExt),
+            "uninstall-shell-ext" => Ok(Command::UninstallShellExt),
+            _ => Err(lang::format("unknown command {}", &[&s])),
+        }
+    }
+}
+
+/// Parses the value of `--iv`: `tag`, `tsrc`, `tenc` or an IV of 32 hex digits.
+fn parse_iv_source(s: &str) -> std::result::Result<IvSource, String> {
+    match s {
+        "tag" => Ok(IvSource::Tag),
+        "tsrc" => Ok(IvSource::Isrc),
+        "tenc" => Ok(IvSource::EncodedBy),
+        _ => hex::decode(s)
+            .ok()
+            .and_then(|iv| iv.try_into().ok())
+            .map(IvSource::Fixed)
+            .ok_or_else(|| {
+                format!(
+                    "invalid --iv value {}, expected tag, tsrc, tenc or 32 hex digits",
+                    s
+                )
+            }),
+    }
+}
+
+/// An input root and the output root its outputs are written below, given as `--map IN=OUT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootMap {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+impl FromStr for RootMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((input, output)) if !input.is_empty() && !output.is_empty() => Ok(RootMap {
+                input: PathBuf::from(input),
+                output: PathBuf::from(output),
+            }),
+            _ => Err(lang::format("invalid --map {}, expected IN=OUT", &[&s])),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Args {
+    pub command: Command,
+    /// The file or directory to decrypt, unset when the files are listed with `--files-from`.
+    pub path: Option<PathBuf>,
+    /// A file that lists the files to decrypt, one per line, `-` for stdin.
+    pub files_from: Option<PathBuf>,
+    /// The input roots processed one after the other, each with its own output root, instead of
+    /// `path` and `output_dir`.
+    pub maps: Vec<RootMap>,
+    pub max_depth: Option<usize>,
+    pub output_dir: Option<PathBuf>,
+    pub jobs: usize,
+    pub group_by_album: bool,
+    pub audit: bool,
+    pub on_conflict: OnConflict,
+    pub interactive: bool,
+    pub name_template: NameTemplate,
+    pub report: Option<PathBuf>,
+    pub durability: Durability,
+    pub source_action: SourceAction,
+    pub filters: Filters,
+    pub watch: bool,
+    /// Show the run full screen, with keys to pause, retry and skip.
+    pub tui: bool,
+    pub fix_tags: bool,
+    pub force: bool,
+    /// Keep the index of decrypted tracks, always on for retry-failures.
+    pub index: bool,
+    /// Tag MP3, M4A and FLAC outputs too, not only WAV and AIFF.
+    pub write_tags: bool,
+    /// Copy inputs that are already plain audio instead of skipping them.
+    pub copy_plain: bool,
+    /// The track id to decrypt with instead of the one in the tag.
+    pub track_id: Option<u64>,
+    pub extract_cover: bool,
+    pub cover_once: Option<CoverOnce>,
+    pub embed_source_tag: bool,
+    pub preserve_times: bool,
+    pub fix_vbr_header: bool,
+    /// Check the structure of the decrypted audio before writing it.
+    pub verify: bool,
+    pub key_file: Option<PathBuf>,
+    /// Where to write the intermediate text of xm files that fail to decrypt.
+    pub dump_intermediate: Option<PathBuf>,
+    pub iv_source: IvSource,
+    pub hooks: Vec<Hook>,
+    pub metadata: Option<PathBuf>,
+    pub frames: bool,
+    pub json: bool,
+    pub verbosity: u8,
+    pub log_format: LogFormat,
+    pub lang: Option<Lang>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Args> {
+        Args::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses `args`, the command line without the program name.
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Args> {
+        let mut command = Command::Decrypt;
+        let mut path = None;
+        let mut files_from = None;
+        let mut max_depth = None;
+        let mut output_dir = None;
+        let mut maps = Vec::new();
+        let mut jobs = 1;
+        let mut group_by_album = false;
+        let mut audit = false;
+        let mut on_conflict = OnConflict::Overwrite;
+        let mut interactive = false;
+        let mut name_template = NameTemplate::default();
+        let mut max_name_bytes = DEFAULT_MAX_NAME_BYTES;
+        let mut report = None;
+        let mut durability = Durability::None;
+        let mut source_action = SourceAction::Keep;
+        let mut filters = Filters::default();
+        let mut watch = false;
+        let mut tui = false;
+        let mut fix_tags = false;
+        let mut force = false;
+        let mut index = false;
+        let mut write_tags = false;
+        let mut copy_plain = false;
+        let mut track_id = None;
+        let mut extract_cover = false;
+        let mut cover_once = None;
+        let mut embed_source_tag = false;
+        let mut preserve_times = false;
+        let mut fix_vbr_header = false;
+        let mut verify = false;
+        let mut key_file = None;
+        let mut dump_intermediate = None;
+        let mut iv_source = IvSource::default();
+        let mut hooks = Vec::new();
+        let mut metadata = None;
+        let mut frames = false;
+        let mut json = false;
+        let mut verbosity = 0;
+        let mut log_format = LogFormat::Text;
+        let mut lang = None;
+        let mut args = args.into_iter().peekable();
+        // Without a command the arguments are those of decrypt, as before commands existed.
+        if let Some(parsed) = args.peek().and_then(|arg| arg.parse().ok()) {
+            command = parsed;
+            args.next();
+        }
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or(lang::format("{} requires a value", &[&arg]))
+            };
+            match arg.as_str() {
+                "--files-from" => files_from = Some(PathBuf::from(value()?)),
+                "--max-depth" => max_depth = Some(value()?.parse()?),
+                "--output-dir" => output_dir = Some(PathBuf::from(value()?)),
+                "--map" => maps.push(value()?.parse()?),
+                "--jobs" | "-j" => {
+                    jobs = value()?.parse()?;
+                    if jobs == 0 {
+                        return Err(lang::tr("--jobs must be at least 1").into());
+                    }
+                }
+                "--group-by-album" => group_by_album = true,
+                "--audit" => audit = true,
+                "--on-conflict" => on_conflict = value()?.parse()?,
+                "--interactive" => interactive = true,
+                "--name-template" => name_template = value()?.parse()?,
+                "--max-name-bytes" => max_name_bytes = value()?.parse()?,
+                "--report" => report = Some(PathBuf::from(value()?)),
+                "--durability" => durability = value()?.parse()?,
+                "--delete-source" | "--archive-dir" if source_action != SourceAction::Keep => {
+                    return Err(
+                        lang::tr("--delete-source and --archive-dir can't be combined").into(),
+                    )
+                }
+                "--delete-source" => source_action = SourceAction::Delete,
+                "--archive-dir" => source_action = SourceAction::Archive(PathBuf::from(value()?)),
+                "--include" => filters.include.push(value()?.parse()?),
+                "--exclude" => filters.exclude.push(value()?.parse()?),
+                "--watch" => watch = true,
+                "--tui" => tui = true,
+                "--fix-tags" => fix_tags = true,
+                "--force" => force = true,
+                "--index" => index = true,
+                "--write-tags" => write_tags = true,
+                "--copy-plain" => copy_plain = true,
+                "--track-id" => track_id = Some(value()?.parse()?),
+                "--extract-cover" => extract_cover = true,
+                "--cover-once" => cover_once = Some(value()?.parse()?),
+                "--embed-source-tag" => embed_source_tag = true,
+                "--preserve-times" => preserve_times = true,
+                "--fix-vbr-header" => fix_vbr_header = true,
+                "--verify" => verify = true,
+                "--key-file" => key_file = Some(PathBuf::from(value()?)),
+                "--dump-intermediate" => dump_intermediate = Some(PathBuf::from(value()?)),
+                "--iv" => iv_source = parse_iv_source(&value()?)?,
+                "--hook" => hooks.push(value()?.parse()?),
+                "--metadata" => metadata = Some(PathBuf::from(value()?)),
+                "--frames" => frames = true,
+                "--json" => json = true,
+                "-v" => verbosity += 1,
+                "-vv" => verbosity += 2,
+                "--log-format" => log_format = value()?.parse()?,
+                "--lang" => lang = Some(value()?.parse()?),
+                "--help" | "-h" => {
+                    print!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                "--version" | "-V" => {
+                    println!("xm_decryptor {}", env!("CARGO_PKG_VERSION"));
+                    std::process::exit(0);
+                }
+                "--report-schema" => {
+                    print!("{}", report::REPORT_SCHEMA);
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(lang::format("unknown option {}", &[&arg]).into())
+                }
+                "-" => files_from = Some(PathBuf::from(arg)),
+                _ if path.is_some() => {
+                    return Err(lang::format("unexpected argument {}", &[&arg]).into())
+                }
+                _ => path = Some(PathBuf::from(arg)),
+            }
+        }
+        if !maps.is_empty() && (path.is_some() || files_from.is_some() || output_dir.is_some()) {
+            return Err(lang::tr(
+                "--map can't be combined with an input path, a file list or --output-dir",
+            )
+            .into());
+        }
+        if !maps.is_empty() && watch {
+            return Err(lang::tr("--watch can't be combined with --map").into());
+        }
+        match (&path, &files_from) {
+            (None, None) if command.takes_inputs() && maps.is_empty() => {
+                return Err(lang::tr("no input path").into())
+            }
+            (Some(_), Some(_)) => {
+                return Err(lang::tr("an input path can't be combined with a file list").into())
+            }
+            _ => {}
+        }
+        if interactive {
+            if files_from.as_deref() == Some(Path::new("-")) {
+                return Err(lang::tr(
+                    "--interactive needs stdin for the answers, not for a file list",
+                )
+                .into());
+            }
+            on_conflict = OnConflict::Ask;
+        }
+        // The TUI takes the screen and the keys, and ends with the run.
+        if tui
+            && (watch
+                || !maps.is_empty()
+                || interactive
+                || files_from.as_deref() == Some(Path::new("-")))
+        {
+            return Err(lang::tr(
+                "--tui can't be combined with --watch, --map, --interactive or a file list on stdin",
+            )
+            .into());
+        }
+        // The page picks the inputs, and shows the run in place of the TUI.
+        if command == Command::Gui
+            && (path.is_some()
+                || files_from.is_some()
+                || !maps.is_empty()
+                || watch
+                || interactive
+                || tui)
+        {
+            return Err(lang::tr(
+                "gui takes no input path, file list, --map, --watch, --interactive or --tui",
+            )
+            .into());
+        }
+        Ok(Args {
+            command,
+            path,
+            files_from,
+            maps,
+            max_depth,
+            output_dir,
+            jobs,
+            group_by_album,
+            audit,
+            on_conflict,
+            interactive,
+            name_template: name_template.with_max_bytes(max_name_bytes),
+            report,
+            durability,
+            source_action,
+            filters,
+            watch,
+            tui,
+            fix_tags,
+            force,
+            index: index || command == Command::RetryFailures,
+            write_tags,
+            copy_plain,
+            track_id,
+            extract_cover,
+            cover_once,
+            embed_source_tag,
+            preserve_times,
+            fix_vbr_header,
+            verify,
+            key_file,
+            dump_intermediate,
+            iv_source,
+            hooks,
+            metadata,
+            frames,
+            json,
+            verbosity,
+            log_format,
+            lang,
+        })
+    }
+}
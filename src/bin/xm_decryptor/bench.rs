@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use xm_decryptor::xm::{self, StageTimes};
+use xm_decryptor::Result;
+
+use crate::lang;
+
+/// The time spent in each stage, summed over the files.
+#[derive(Default)]
+struct Totals {
+    files: usize,
+    bytes: u64,
+    read: Duration,
+    id3: Duration,
+    decrypt: StageTimes,
+    write: Duration,
+}
+
+/// Decrypts `files` like `decrypt` does and prints the time spent reading, parsing the ID3 tag,
+/// in AES, in the wasm transform, in base64 decoding and writing, summed over the files.
+///
+/// The outputs are written to one temporary file, which is removed again.
+pub fn bench_files(files: &[PathBuf]) -> Result<()> {
+    let target = std::env::temp_dir().join(format!("xm_decryptor_bench_{}", std::process::id()));
+    let mut totals = Totals::default();
+    let result = files
+        .iter()
+        .try_for_each(|file| bench_file(file, &target, &mut totals));
+    let _ = std::fs::remove_file(&target);
+    result?;
+
+    let stages = [
+        ("read", totals.read),
+        ("id3 parse", totals.id3),
+        ("aes", totals.decrypt.aes),
+        ("wasm compile", totals.decrypt.wasm_compile),
+        ("wasm", totals.decrypt.wasm),
+        ("base64", totals.decrypt.base64),
+        ("write", totals.write),
+    ];
+    let total: Duration = stages.iter().map(|(_, time)| *time).sum();
+    println!(
+        "{}",
+        lang::format(
+            "{} files, {} MB",
+            &[
+                &totals.files,
+                &format!("{:.1}", totals.bytes as f64 / 1_000_000.0)
+            ]
+        )
+    );
+    for (stage, time) in stages.into_iter().chain([("total", total)]) {
+        let share = match total.is_zero() {
+            true => 0.0,
+            false => time.as_secs_f64() / total.as_secs_f64() * 100.0,
+        };
+        println!(
+            "{:<14}{:>10.1} ms {:>5.1}%",
+            stage,
+            time.as_secs_f64() * 1000.0,
+            share
+        );
+    }
+    Ok(())
+}
+
+fn bench_file(file: &Path, target: &Path, totals: &mut Totals) -> Result<()> {
+    let start = Instant::now();
+    let content = std::fs::read(file)?;
+    totals.read += start.elapsed();
+
+    let start = Instant::now();
+    let xm_info = xm::extract_xm_info(&content[..])?;
+    totals.id3 += start.elapsed();
+
+    let (audio, times) = xm::decrypt_timed(&xm_info, &content)?;
+    totals.decrypt.aes += times.aes;
+    totals.decrypt.wasm_compile += times.wasm_compile;
+    totals.decrypt.wasm += times.wasm;
+    totals.decrypt.base64 += times.base64;
+
+    let start = Instant::now();
+    std::fs::write(target, &audio)?;
+    totals.write += start.elapsed();
+
+    totals.files += 1;
+    totals.bytes += audio.len() as u64;
+    Ok(())
+}
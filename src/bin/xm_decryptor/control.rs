@@ -0,0 +1,169 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tracing::info;
+use xm_decryptor::Result;
+
+use crate::lang;
+
+/// The marker file in the output root that pauses the runs writing there, see [`pause`].
+const PAUSE_FILE: &str = ".xm_decryptor_paused";
+
+/// How often a paused run looks whether it has been resumed or stopped.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Set by the first Ctrl+C or SIGTERM.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Set by [`stop`], which only ends the run and not the `gui` command.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Set while a run waits for [`resume`], so pausing and resuming is logged once.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl+C and SIGTERM, or Ctrl+Break and closing the console on Windows, ask the run to
+/// stop once the files being decrypted are done, instead of killing it. A second signal kills it
+/// at once.
+pub fn install() {
+    install_handler();
+}
+
+/// Returns true once the run has been asked to stop.
+pub fn stopping() -> bool {
+    STOP.load(Ordering::SeqCst) || REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Returns true once Ctrl+C or a signal asked the process to stop, not only the run.
+#[cfg(feature = "gui")]
+pub fn interrupted() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+/// Asks the run to stop like the first Ctrl+C does, such as when the TUI is quit.
+pub fn stop() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Forgets the [`stop`] of the run that ended, so the next run of the `gui` command goes on.
+#[cfg(feature = "gui")]
+pub fn clear_stop() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Sleeps for `duration`, returning early if the run is asked to stop. Returns false if it was.
+pub fn sleep(duration: Duration) -> bool {
+    let mut left = duration;
+    while !stopping() && !left.is_zero() {
+        let slice = left.min(PAUSE_POLL_INTERVAL);
+        std::thread::sleep(slice);
+        left -= slice;
+    }
+    !stopping()
+}
+
+/// Pauses the runs whose output root is `dir`: they finish the files being decrypted and wait,
+/// until [`resume`] or a stop.
+pub fn pause(dir: &Path) -> Result<()> {
+    std::fs::write(dir.join(PAUSE_FILE), b"")?;
+    info!(dir = %dir.display(), "paused");
+    Ok(())
+}
+
+/// Resumes the runs paused by [`pause`].
+pub fn resume(dir: &Path) -> Result<()> {
+    match std::fs::remove_file(dir.join(PAUSE_FILE)) {
+        Ok(()) => {
+            info!(dir = %dir.display(), "resumed");
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(lang::format("{} is not paused", &[&dir.display()]).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns true if the runs whose output root is `dir` are paused.
+pub fn is_paused(dir: &Path) -> bool {
+    dir.join(PAUSE_FILE).exists()
+}
+
+/// Waits while the runs whose output root is `dir` are paused, unless the run is asked to stop.
+pub fn wait_while_paused(dir: &Path) {
+    if !is_paused(dir) {
+        return;
+    }
+    if !PAUSED.swap(true, Ordering::SeqCst) {
+        info!(dir = %dir.display(), "paused");
+    }
+    while is_paused(dir) && sleep(PAUSE_POLL_INTERVAL) {}
+    if PAUSED.swap(false, Ordering::SeqCst) && !stopping() {
+        info!(dir = %dir.display(), "resumed");
+    }
+}
+
+#[cfg(unix)]
+fn install_handler() {
+    use std::os::raw::c_int;
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+    /// The exit status of a process killed by SIGINT.
+    const INTERRUPTED: c_int = 130;
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+        fn _exit(status: c_int) -> !;
+    }
+    extern "C" fn handle(_: c_int) {
+        if STOP.swap(true, Ordering::SeqCst) {
+            // SAFETY: _exit is async-signal-safe, unlike the exit of the standard library.
+            unsafe { _exit(INTERRUPTED) };
+        }
+    }
+    let handler = handle as extern "C" fn(c_int) as usize;
+    // SAFETY: the handler only touches an atomic and calls an async-signal-safe function.
+    unsafe {
+        signal(SIGINT, handler);
+        signal(SIGTERM, handler);
+    }
+}
+
+#[cfg(windows)]
+fn install_handler() {
+    extern "system" {
+        fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+    }
+    unsafe extern "system" fn handle(_: u32) -> i32 {
+        // Unhandled, the default handler ends the process.
+        match STOP.swap(true, Ordering::SeqCst) {
+            true => 0,
+            false => 1,
+        }
+    }
+    // SAFETY: the handler only touches an atomic.
+    unsafe { SetConsoleCtrlHandler(Some(handle), 1) };
+}
+
+#[cfg(not(any(unix, windows)))]
+fn install_handler() {}
+
+#[test]
+fn test_pause() {
+    let dir = std::env::temp_dir().join(format!("xm_decryptor_pause_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    assert!(!is_paused(&dir));
+    pause(&dir).unwrap();
+    let paused = is_paused(&dir);
+    resume(&dir).unwrap();
+    let resumed = !is_paused(&dir);
+    let again = resume(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(paused);
+    assert!(resumed);
+    assert!(again.is_err());
+    wait_while_paused(&dir);
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// A shell-style wildcard pattern.
+///
+/// `*` matches any run of characters and `?` a single character, neither crosses a `/`. `[abc]`,
+/// `[a-z]` and `[!abc]` match one character from, or not from, a set. A pattern without a `/` is
+/// matched against the file name, otherwise against the path relative to the scanned directory.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    full_path: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    /// Returns true if `path`, relative to the scanned directory, matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        let subject = match self.full_path {
+            true => path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            false => path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into(),
+        };
+        let subject: Vec<char> = subject.chars().collect();
+        match_tokens(&self.tokens, &subject)
+    }
+}
+
+fn match_tokens(tokens: &[Token], subject: &[char]) -> bool {
+    match tokens.split_first() {
+        None => subject.is_empty(),
+        Some((Token::Star, rest)) => {
+            for i in 0..=subject.len() {
+                if match_tokens(rest, &subject[i..]) {
+                    return true;
+                }
+                if subject.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some((token, rest)) => match subject.split_first() {
+            Some((&c, subject)) if token.matches(c) => match_tokens(rest, subject),
+            _ => false,
+        },
+    }
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(t) => *t == c,
+            Token::Any => c != '/',
+            Token::Star => unreachable!(),
+            Token::Class { negated, ranges } => {
+                c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+        }
+    }
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => {
+                    let mut class = chars.clone();
+                    let negated = class.as_str().starts_with('!');
+                    if negated {
+                        class.next();
+                    }
+                    let mut ranges = Vec::new();
+                    // A `]` right after the opening bracket is part of the set.
+                    let mut first = true;
+                    loop {
+                        let lo = match class.next() {
+                            Some(']') if !first => break,
+                            Some(lo) => lo,
+                            None => return Err(format!("unclosed [ in pattern {}", s)),
+                        };
+                        first = false;
+                        let rest = class.as_str();
+                        match rest.strip_prefix('-') {
+                            Some(after) if !after.starts_with(']') && !after.is_empty() => {
+                                class.next();
+                                let hi = class.next().expect("checked above");
+                                ranges.push((lo, hi));
+                            }
+                            _ => ranges.push((lo, lo)),
+                        }
+                    }
+                    chars = class;
+                    Token::Class { negated, ranges }
+                }
+                c => Token::Char(c),
+            });
+        }
+        Ok(Glob {
+            tokens,
+            full_path: s.contains('/'),
+        })
+    }
+}
+
+/// The `--include` and `--exclude` patterns of a directory scan.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    pub include: Vec<Glob>,
+    pub exclude: Vec<Glob>,
+}
+
+impl Filters {
+    /// Returns true if the file or directory at `path`, relative to the scanned directory, is
+    /// excluded. Excluded directories are not descended into.
+    pub fn excludes(&self, path: &Path) -> bool {
+        self.exclude.iter().any(|glob| glob.matches(path))
+    }
+
+    /// Returns true if the file at `path`, relative to the scanned directory, matches an include
+    /// pattern. Every file is included when there are no include patterns.
+    pub fn includes(&self, path: &Path) -> bool {
+        self.include.is_empty() || self.include.iter().any(|glob| glob.matches(path))
+    }
+}
+
+#[test]
+fn test_glob() {
+    let glob = |s: &str| s.parse::<Glob>().unwrap();
+    assert!(glob("*.xm").matches(Path::new("a/b/c.xm")));
+    assert!(!glob("*.xm").matches(Path::new("c.m4a")));
+    assert!(glob("*试听*").matches(Path::new("a/第1集 试听.xm")));
+    assert!(glob("a/*/c.xm").matches(Path::new("a/b/c.xm")));
+    assert!(!glob("a/*.xm").matches(Path::new("a/b/c.xm")));
+    assert!(glob("[0-9][!a]?.xm").matches(Path::new("1bc.xm")));
+    assert!(!glob("[0-9][!a]?.xm").matches(Path::new("1ac.xm")));
+    assert!(glob("[]]").matches(Path::new("]")));
+    assert!("[ab".parse::<Glob>().is_err());
+}
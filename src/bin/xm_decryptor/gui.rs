@@ -0,0 +1,519 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{self, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tracing::{error, info, warn};
+use xm_decryptor::json::json_string;
+use xm_decryptor::{collate, Result};
+
+use crate::args::{Args, Command};
+use crate::tui::{self, Action};
+use crate::{control, lang};
+
+/// The page of the web UI. `{{text}}` is replaced with the translation of `text`.
+const PAGE: &str = include_str!("gui.html");
+
+/// The texts of [`PAGE`].
+const PAGE_TEXTS: [&str; 14] = [
+    "Retry",
+    "Skip",
+    "Stop",
+    "Finish",
+    "The browser didn't tell where the dropped folder is, pick it below or paste its path",
+    "Drop a folder here, or pick it below",
+    "Input folder",
+    "Output folder",
+    "next to the inputs",
+    "Up",
+    "Use as input",
+    "Use as output",
+    "Start",
+    "Pause",
+];
+
+/// How often the server looks whether Ctrl+C was pressed while no page connects.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a page may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest request the server reads, headers and body each.
+const MAX_REQUEST_SIZE: u64 = 64 * 1024;
+
+/// The number of log lines the page is sent at most at once.
+const MAX_LOG_LINES: usize = 1000;
+
+struct Server {
+    /// The options of the command line, which every run starts from.
+    args: Args,
+    /// The first part of every path, so only the page opened by the command can use the server,
+    /// not other sites open in the browser or other users of the computer.
+    token: String,
+    port: u16,
+    run: Mutex<RunState>,
+}
+
+#[derive(Default)]
+struct RunState {
+    /// The number of runs started, which tells the page when a new one starts.
+    started: u64,
+    running: bool,
+    /// The error of the run that ended last.
+    outcome: Option<String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    host: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Serves the web UI of the `gui` command on 127.0.0.1 and opens it in the browser: a folder to
+/// decrypt, dropped or picked from a list of folders, an output folder, a button to start and
+/// the files of the run as the TUI shows them, with buttons to pause, retry, skip and stop.
+///
+/// Each run decrypts like the command line with `args`, the page shows it through a TUI without
+/// a terminal. The server ends with Ctrl+C, after the files being decrypted.
+pub fn serve(args: &Args) -> Result<()> {
+    tui::detach();
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let server = Arc::new(Server {
+        args: args.clone(),
+        token: token(),
+        port: listener.local_addr()?.port(),
+        run: Mutex::new(RunState::default()),
+    });
+    let url = format!("http://127.0.0.1:{}/{}/", server.port, server.token);
+    info!(url = %url, "serving the web UI");
+    open_browser(&url);
+    // Without blocking, so Ctrl+C is seen.
+    listener.set_nonblocking(true)?;
+    while !control::interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let server = server.clone();
+                std::thread::spawn(move || server.handle(stream));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(ACCEPT_POLL_INTERVAL)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    // Ctrl+C stopped the run too, which finishes the files being decrypted.
+    while server.lock().running {
+        std::thread::sleep(ACCEPT_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Returns 32 random hex digits, from the random keys of the standard library's hash maps.
+fn token() -> String {
+    let random = |n: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(n);
+        hasher.write_u32(std::process::id());
+        if let Ok(time) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            hasher.write_u128(time.as_nanos());
+        }
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", random(0), random(1))
+}
+
+/// Opens `url` in the default browser. The URL is logged for when that fails.
+fn open_browser(url: &str) {
+    let mut command = match () {
+        // start takes its first quoted argument as the title of the window.
+        _ if cfg!(windows) => {
+            let mut command = process::Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        _ if cfg!(target_os = "macos") => process::Command::new("open"),
+        _ => process::Command::new("xdg-open"),
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    std::thread::spawn(move || match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!(status = %status, "can't open the browser"),
+        Err(e) => warn!(error = %e, "can't open the browser"),
+    });
+}
+
+impl Server {
+    fn lock(&self) -> std::sync::MutexGuard<'_, RunState> {
+        self.run.lock().expect("gui poisoned")
+    }
+
+    /// Answers the request on `stream`.
+    fn handle(self: Arc<Self>, mut stream: TcpStream) {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+        let response = match read_request(&stream) {
+            Ok(request) => self.respond(&request),
+            Err(e) => Response::text(400, e.to_string()),
+        };
+        // The page asks again if the answer is lost.
+        let _ = response.write_to(&mut stream);
+    }
+
+    fn respond(self: &Arc<Self>, request: &Request) -> Response {
+        // Pages of other sites can reach 127.0.0.1 under their own host name.
+        let hosts = [
+            format!("127.0.0.1:{}", self.port),
+            format!("localhost:{}", self.port),
+        ];
+        if !request
+            .host
+            .as_ref()
+            .is_some_and(|host| hosts.contains(host))
+        {
+            return Response::text(403, "forbidden".to_string());
+        }
+        let prefix = format!("/{}/", self.token);
+        let Some(route) = request.path.strip_prefix(&prefix) else {
+            return Response::text(404, "not found".to_string());
+        };
+        let form = || parse_form(&request.body);
+        match (request.method.as_str(), route) {
+            ("GET", "") => Response {
+                status: 200,
+                content_type: "text/html; charset=utf-8",
+                body: page(),
+            },
+            ("GET", "dirs") => self.dirs(request.query.get("path").map_or("", String::as_str)),
+            ("GET", "status") => self.status(
+                request
+                    .query
+                    .get("log_from")
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0),
+            ),
+            ("POST", "start") => self.start(&form()),
+            ("POST", "action") => self.act(&form()),
+            _ => Response::text(404, "not found".to_string()),
+        }
+    }
+
+    /// Lists the folders in `path`, the home folder if it is empty.
+    fn dirs(&self, path: &str) -> Response {
+        let dir = match path {
+            "" => home_dir(),
+            path => PathBuf::from(path),
+        };
+        let dir = std::path::absolute(&dir).unwrap_or(dir);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => return Response::text(400, format!("{}: {}", dir.display(), e)),
+        };
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        dirs.sort_by(|a, b| collate::compare_paths(a, b));
+        let dirs: Vec<String> = dirs
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!(
+                    "{{\"name\":{},\"path\":{}}}",
+                    json_string(&name),
+                    json_string(&path.to_string_lossy())
+                )
+            })
+            .collect();
+        let parent = dir.parent().map_or("null".to_string(), |parent| {
+            json_string(&parent.to_string_lossy())
+        });
+        Response::json(format!(
+            "{{\"path\":{},\"parent\":{},\"dirs\":[{}]}}",
+            json_string(&dir.to_string_lossy()),
+            parent,
+            dirs.join(",")
+        ))
+    }
+
+    /// Returns the run as the page shows it, with the log lines from `log_from` on.
+    fn status(&self, log_from: usize) -> Response {
+        let (started, running, outcome) = {
+            let run = self.lock();
+            (run.started, run.running, run.outcome.clone())
+        };
+        let snapshot = tui::snapshot(log_from);
+        let rows: Vec<String> = snapshot
+            .iter()
+            .flat_map(|snapshot| &snapshot.rows)
+            .map(|row| {
+                format!(
+                    "{{\"path\":{},\"status\":\"{}\",\"error\":{}}}",
+                    json_string(&row.path),
+                    row.status,
+                    optional_json_string(row.error.as_deref())
+                )
+            })
+            .collect();
+        let log: Vec<String> = snapshot
+            .iter()
+            .flat_map(|snapshot| &snapshot.log)
+            .take(MAX_LOG_LINES)
+            .map(|line| json_string(line))
+            .collect();
+        Response::json(format!(
+            "{{\"run\":{},\"running\":{},\"outcome\":{},\"header\":{},\"finished\":{},\
+             \"closed\":{},\"rows\":[{}],\"log\":[{}]}}",
+            started,
+            running,
+            optional_json_string(outcome.as_deref()),
+            json_string(snapshot.as_ref().map_or("", |snapshot| &snapshot.header)),
+            snapshot.as_ref().is_some_and(|snapshot| snapshot.finished),
+            snapshot.as_ref().is_some_and(|snapshot| snapshot.closed),
+            rows.join(","),
+            log.join(",")
+        ))
+    }
+
+    /// Starts decrypting the folder `input` of the form, into the folder `output` if it is set.
+    fn start(self: &Arc<Self>, form: &HashMap<String, String>) -> Response {
+        let input = form.get("input").map_or("", |input| input.trim());
+        if input.is_empty() {
+            return Response::text(400, lang::tr("no input path").to_string());
+        }
+        let input = PathBuf::from(input);
+        let output = form.get("output").map(|output| output.trim());
+        let args = Args {
+            command: Command::Decrypt,
+            path: Some(input.clone()),
+            output_dir: output
+                .filter(|output| !output.is_empty())
+                .map(PathBuf::from),
+            tui: true,
+            ..self.args.clone()
+        };
+        let mut run = self.lock();
+        if run.running {
+            return Response::text(409, lang::tr("a run is going on").to_string());
+        }
+        run.started += 1;
+        run.running = true;
+        run.outcome = None;
+        let server = self.clone();
+        std::thread::spawn(move || {
+            info!(input = %input.display(), "processing root");
+            // The summary is in the log.
+            let outcome = match crate::run_root(&args, &mut Vec::new()) {
+                Ok(()) => None,
+                Err(e) => {
+                    error!(error = %e, "failed");
+                    Some(e.to_string())
+                }
+            };
+            control::clear_stop();
+            let mut run = server.lock();
+            run.running = false;
+            run.outcome = outcome;
+        });
+        Response::text(200, String::new())
+    }
+
+    /// Does what a button of the page asks of the run.
+    fn act(&self, form: &HashMap<String, String>) -> Response {
+        let row = form.get("row").and_then(|row| row.parse().ok());
+        let action = match (form.get("action").map(String::as_str), row) {
+            (Some("retry"), Some(row)) => Action::Retry(row),
+            (Some("skip"), Some(row)) => Action::Skip(row),
+            (Some("pause"), _) => Action::Pause,
+            (Some("quit"), _) => Action::Quit,
+            _ => return Response::text(400, "unknown action".to_string()),
+        };
+        tui::act(action);
+        Response::text(200, String::new())
+    }
+}
+
+impl Response {
+    fn text(status: u16, body: String) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body,
+        }
+    }
+
+    fn json(body: String) -> Response {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            _ => "Conflict",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Cache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        stream.flush()
+    }
+}
+
+/// Reads an HTTP/1.1 request from `stream`, its body as long as its Content-Length says.
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("bad request".into());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: String::from_utf8_lossy(&percent_decode(path)).into_owned(),
+        query: parse_form(query.as_bytes()),
+        host: None,
+        body: Vec::new(),
+    };
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err("bad request".into());
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            break;
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "host" => request.host = Some(value.trim().to_string()),
+            "content-length" => length = value.trim().parse()?,
+            _ => {}
+        }
+    }
+    // What is left of the limit after the headers.
+    reader.get_mut().set_limit(MAX_REQUEST_SIZE);
+    request.body = vec![0; length.min(MAX_REQUEST_SIZE as usize)];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+/// Parses the fields of a form or query, `name=value` pairs joined by `&`.
+fn parse_form(form: &[u8]) -> HashMap<String, String> {
+    form.split(|&b| b == b'&')
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let (name, value) = match field.iter().position(|&b| b == b'=') {
+                Some(i) => (&field[..i], &field[i + 1..]),
+                None => (field, &[][..]),
+            };
+            let decode = |s: &[u8]| {
+                let s: Vec<u8> = s
+                    .iter()
+                    .map(|&b| if b == b'+' { b' ' } else { b })
+                    .collect();
+                String::from_utf8_lossy(&percent_decode(&s)).into_owned()
+            };
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+/// Decodes the `%XX` escapes of `s`, leaving malformed ones as they are.
+fn percent_decode(s: impl AsRef<[u8]>) -> Vec<u8> {
+    let s = s.as_ref();
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (s[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Returns the page with its texts translated.
+fn page() -> String {
+    PAGE_TEXTS.iter().fold(PAGE.to_string(), |page, text| {
+        page.replace(&format!("{{{{{}}}}}", text), &escape_html(lang::tr(text)))
+    })
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn optional_json_string(s: Option<&str>) -> String {
+    s.map_or("null".to_string(), json_string)
+}
+
+/// Returns the home folder of the user, the working directory if it isn't known.
+fn home_dir() -> PathBuf {
+    ["HOME", "USERPROFILE"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|home| !home.is_empty()))
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_parse_form() {
+    let form = parse_form(b"input=%2Fhome%2F%E5%A3%B0+book&output=&row=3&bad=%zz");
+    assert_eq!(form["input"], "/home/声 book");
+    assert_eq!(form["output"], "");
+    assert_eq!(form["row"], "3");
+    assert_eq!(form["bad"], "%zz");
+}
+
+#[test]
+fn test_page_texts() {
+    let page = page();
+    assert!(!page.contains("{{"), "untranslated text left in the page");
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::lang;
+
+/// An external command run on every output with a given extension, such as a validator.
+///
+/// Written as `EXT=COMMAND`, for example `mp3=mp3val -f {}`. The command is split into words at
+/// spaces, double quotes keep a word together. `{}` in a word is replaced by the output path, or
+/// the path is added as the last argument if no word contains it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    /// The lowercase extension of the outputs the command is run on.
+    pub extension: String,
+    words: Vec<String>,
+}
+
+/// The outcome of running a hook on one output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRun {
+    /// The command line, with the output path filled in.
+    pub command: String,
+    /// The exit code, `None` if the command couldn't be started or was killed by a signal.
+    pub code: Option<i32>,
+    /// What the command wrote to stdout and stderr, or why it couldn't be started.
+    pub output: String,
+}
+
+impl HookRun {
+    /// Returns true if the command ran and exited with 0.
+    pub fn succeeded(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl FromStr for Hook {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || lang::format("invalid --hook {}, expected EXT=COMMAND", &[&s]);
+        let (extension, command) = s.split_once('=').ok_or_else(invalid)?;
+        let extension = extension.trim().trim_start_matches('.').to_lowercase();
+        let words = split_words(command);
+        if extension.is_empty() || words.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Hook { extension, words })
+    }
+}
+
+impl Hook {
+    /// Returns the program and arguments to run on `output`.
+    fn command_line(&self, output: &Path) -> Vec<String> {
+        let path = output.to_string_lossy();
+        let mut words: Vec<String> = self
+            .words
+            .iter()
+            .map(|word| word.replace("{}", &path))
+            .collect();
+        if !self.words.iter().any(|word| word.contains("{}")) {
+            words.push(path.into_owned());
+        }
+        words
+    }
+
+    /// Runs the command on `output` and waits for it.
+    fn run(&self, output: &Path) -> HookRun {
+        let words = self.command_line(output);
+        let result = Command::new(&words[0]).args(&words[1..]).output();
+        let command = words.join(" ");
+        match result {
+            Ok(out) => {
+                let mut output = String::from_utf8_lossy(&out.stdout).into_owned();
+                output.push_str(&String::from_utf8_lossy(&out.stderr));
+                HookRun {
+                    command,
+                    code: out.status.code(),
+                    output: output.trim_end().to_string(),
+                }
+            }
+            Err(e) => HookRun {
+                command,
+                code: None,
+                output: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Runs the `hooks` for the extension of `output` on it, in the order they were given.
+pub fn run_hooks(hooks: &[Hook], output: &Path) -> Vec<HookRun> {
+    let extension = output
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    hooks
+        .iter()
+        .filter(|hook| hook.extension == extension)
+        .map(|hook| hook.run(output))
+        .collect()
+}
+
+/// Splits `command` into words at spaces, keeping text in double quotes together.
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[test]
+fn test_hook() {
+    let hook: Hook = r#".MP3=mp3val -f "{}" -l "my log.txt""#.parse().unwrap();
+    assert_eq!(hook.extension, "mp3");
+    assert_eq!(
+        hook.command_line(Path::new("a b.mp3")),
+        ["mp3val", "-f", "a b.mp3", "-l", "my log.txt"]
+    );
+    let hook: Hook = "m4a=mp4box -info".parse().unwrap();
+    assert_eq!(
+        hook.command_line(Path::new("a.m4a")),
+        ["mp4box", "-info", "a.m4a"]
+    );
+    assert!("mp3val".parse::<Hook>().is_err());
+    assert!("mp3= ".parse::<Hook>().is_err());
+}
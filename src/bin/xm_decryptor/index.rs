@@ -0,0 +1,261 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use xm_decryptor::id3::{Tag, TagLike};
+use xm_decryptor::xm::XMInfo;
+use xm_decryptor::Result;
+
+use crate::journal::parse_string;
+use crate::report::json_string;
+
+/// The name of the index file, kept in the output root.
+const INDEX_FILE: &str = ".xm_decryptor_index.jsonl";
+
+/// An index of the processed tracks, keyed by the Ximalaya track id, for archives too large to
+/// scan the outputs of.
+///
+/// The index holds one JSON object per line and is only appended to, a later line for the same
+/// track or source replaces an earlier one. A decrypted track is stored with its source, a hash
+/// of the source, its output and the title, artist and album it was tagged with. A failed source
+/// is stored with its path only.
+pub struct Index {
+    file: Mutex<File>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    tracks: HashMap<u64, Entry>,
+    failed: BTreeSet<PathBuf>,
+}
+
+/// A decrypted track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub track_id: u64,
+    pub source: PathBuf,
+    /// The [`hash`] of the source.
+    pub hash: u64,
+    pub output: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl Entry {
+    /// Returns the info the track was tagged with, such as for `--audit`.
+    pub fn info(&self) -> XMInfo {
+        let mut tag = Tag::new();
+        tag.set_text("TRCK", self.track_id.to_string());
+        for (id, value) in [
+            ("TIT2", &self.title),
+            ("TPE1", &self.artist),
+            ("TALB", &self.album),
+        ] {
+            if let Some(value) = value {
+                tag.set_text(id, value);
+            }
+        }
+        tag.into()
+    }
+
+    fn to_line(&self) -> String {
+        let text = |value: &Option<String>| value.as_deref().map_or("null".into(), json_string);
+        format!(
+            "{{\"track_id\": {}, \"source\": {}, \"hash\": \"{:016x}\", \"status\": \"ok\", \
+             \"output\": {}, \"title\": {}, \"artist\": {}, \"album\": {}}}\n",
+            self.track_id,
+            json_string(&self.source.to_string_lossy()),
+            self.hash,
+            json_string(&self.output.to_string_lossy()),
+            text(&self.title),
+            text(&self.artist),
+            text(&self.album)
+        )
+    }
+}
+
+/// A line of the index.
+#[derive(Debug, PartialEq, Eq)]
+enum Line {
+    Done(Entry),
+    Failed(PathBuf),
+}
+
+impl Index {
+    /// Opens the index in `dir`, creating it if it doesn't exist. Lines that can't be parsed,
+    /// such as one cut short by a crash, are ignored.
+    pub fn open(dir: &Path) -> Result<Index> {
+        let path = dir.join(INDEX_FILE);
+        let mut state = State::default();
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                match parse_line(line) {
+                    Some(Line::Done(entry)) => state.done(entry),
+                    Some(Line::Failed(source)) => {
+                        state.failed.insert(source);
+                    }
+                    None => {}
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Index {
+            file: Mutex::new(file),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Returns the entry that makes decrypting `source`, holding the track `track_id` and hashing
+    /// to `hash`, unnecessary: the track was decrypted before, its output still exists, and it
+    /// came either from another copy of the track or from `source` unchanged.
+    pub fn find(&self, track_id: u64, source: &Path, hash: u64) -> Option<Entry> {
+        let source = fs::canonicalize(source).ok()?;
+        let state = self.state.lock().expect("index poisoned");
+        state
+            .tracks
+            .get(&track_id)
+            .filter(|entry| entry.source != source || entry.hash == hash)
+            .filter(|entry| entry.output.exists())
+            .cloned()
+    }
+
+    /// Records that `source`, hashing to `hash`, was decrypted into `output` with the tags of
+    /// `xm_info`.
+    pub fn record_done(
+        &self,
+        source: &Path,
+        hash: u64,
+        xm_info: &XMInfo,
+        output: &Path,
+    ) -> Result<()> {
+        let entry = Entry {
+            track_id: xm_info.tracknumber(),
+            source: fs::canonicalize(source)?,
+            hash,
+            output: fs::canonicalize(output)?,
+            title: xm_info.title().map(str::to_string),
+            artist: xm_info.artist().map(str::to_string),
+            album: xm_info.album().map(str::to_string),
+        };
+        self.append(&entry.to_line())?;
+        self.state.lock().expect("index poisoned").done(entry);
+        Ok(())
+    }
+
+    /// Records that decrypting `source` failed.
+    pub fn record_failed(&self, source: &Path) -> Result<()> {
+        let source = fs::canonicalize(source)?;
+        self.append(&format!(
+            "{{\"source\": {}, \"status\": \"failed\"}}\n",
+            json_string(&source.to_string_lossy())
+        ))?;
+        let mut state = self.state.lock().expect("index poisoned");
+        state.failed.insert(source);
+        Ok(())
+    }
+
+    /// Returns the sources whose last attempt failed and that still exist, sorted by path.
+    pub fn failed(&self) -> Vec<PathBuf> {
+        let state = self.state.lock().expect("index poisoned");
+        state
+            .failed
+            .iter()
+            .filter(|p| p.exists())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the decrypted tracks.
+    pub fn entries(&self) -> Vec<Entry> {
+        let state = self.state.lock().expect("index poisoned");
+        state.tracks.values().cloned().collect()
+    }
+
+    fn append(&self, line: &str) -> Result<()> {
+        let mut file = self.file.lock().expect("index poisoned");
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl State {
+    fn done(&mut self, entry: Entry) {
+        self.failed.remove(&entry.source);
+        self.tracks.insert(entry.track_id, entry);
+    }
+}
+
+/// Returns the FNV-1a hash of the source `content`. Unlike the hasher of the standard library it
+/// stays the same across Rust versions, so the index can be kept.
+pub fn hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Parses an index line, a flat JSON object of strings, numbers and nulls.
+fn parse_line(line: &str) -> Option<Line> {
+    let mut rest = line
+        .trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')?
+        .trim_start();
+    let mut fields = HashMap::new();
+    while !rest.is_empty() {
+        let (key, after) = parse_string(rest)?;
+        rest = after.trim_start().strip_prefix(':')?.trim_start();
+        if rest.starts_with('"') {
+            let (value, after) = parse_string(rest)?;
+            fields.insert(key, value);
+            rest = after;
+        } else {
+            let end = rest.find([',', ' ']).unwrap_or(rest.len());
+            if &rest[..end] != "null" {
+                fields.insert(key, rest[..end].to_string());
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    let source = PathBuf::from(fields.remove("source")?);
+    match fields.get("status")?.as_str() {
+        "failed" => Some(Line::Failed(source)),
+        "ok" => Some(Line::Done(Entry {
+            track_id: fields.get("track_id")?.parse().ok()?,
+            source,
+            hash: u64::from_str_radix(fields.get("hash")?, 16).ok()?,
+            output: PathBuf::from(fields.remove("output")?),
+            title: fields.remove("title"),
+            artist: fields.remove("artist"),
+            album: fields.remove("album"),
+        })),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_line() {
+    let entry = Entry {
+        track_id: 123,
+        source: PathBuf::from("/in/\"1\".xm"),
+        hash: hash(b"xm"),
+        output: PathBuf::from("/out/1.m4a"),
+        title: Some("第1集, 开始".to_string()),
+        artist: None,
+        album: Some("album".to_string()),
+    };
+    assert_eq!(parse_line(&entry.to_line()), Some(Line::Done(entry)));
+    assert_eq!(
+        parse_line("{\"source\": \"/in/2.xm\", \"status\": \"failed\"}"),
+        Some(Line::Failed(PathBuf::from("/in/2.xm")))
+    );
+    assert_eq!(parse_line("{\"source\": \"/in/2.xm\", \"stat"), None);
+    assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+}
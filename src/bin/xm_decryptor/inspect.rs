@@ -0,0 +1,304 @@
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, error};
+use xm_decryptor::id3::{Tag, TagLike};
+use xm_decryptor::xm::{stages, XMInfo};
+use xm_decryptor::{health, xm, Result};
+
+use crate::report::json_string;
+use crate::{lang, remote};
+
+/// Prints the xm information of every file, as text or as one JSON object per line. With
+/// `all_frames` every frame of the tag follows the text.
+pub fn info_files(files: &[PathBuf], all_frames: bool, json: bool) -> Result<()> {
+    for file in files {
+        let tag = match read_tag(file) {
+            Ok(tag) => tag,
+            Err(e) => {
+                error!(file = %file.display(), error = %e, "failed");
+                continue;
+            }
+        };
+        let info = XMInfo::from(tag.clone());
+        if json {
+            println!("{}", info_json(file, &info));
+            continue;
+        }
+        print_info(file, &info);
+        if all_frames {
+            println!("  frames:");
+            for frame in tag.frames() {
+                println!("    {}", frame);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The fields `info` shows, with their names.
+fn info_fields(info: &XMInfo) -> [(&'static str, Option<&str>); 12] {
+    [
+        ("title", info.title()),
+        ("artist", info.artist()),
+        ("album", info.album()),
+        ("album_artist", info.album_artist()),
+        ("disc", info.disc()),
+        ("year", info.year()),
+        ("genre", info.genre()),
+        ("comment", info.comment()),
+        ("lyrics", info.lyrics()),
+        ("isrc", info.isrc()),
+        ("tenc", info.encodedby()),
+        ("tsse", info.encoding_technology()),
+    ]
+}
+
+fn print_info(file: &Path, info: &XMInfo) {
+    println!("{}", file.display());
+    println!("  track: {}", info.tracknumber());
+    for (name, value) in info_fields(info) {
+        // Lyrics and comments may span lines, which are indented under the name.
+        if let Some(value) = value {
+            println!("  {}: {}", name, value.trim_end().replace('\n', "\n    "));
+        }
+    }
+}
+
+fn info_json(file: &Path, info: &XMInfo) -> String {
+    format!(
+        "{{\"source\": {}, {}",
+        json_string(&file.to_string_lossy()),
+        &info.to_json()[1..]
+    )
+}
+
+/// Decrypts every file in memory and checks the audio with [`xm::validate_audio`]. Fails if any
+/// file doesn't pass.
+pub fn verify_files(files: &[PathBuf], decryptor: &xm::Decryptor) -> Result<()> {
+    let mut failed = 0;
+    for file in files {
+        match verify_file(file, decryptor) {
+            Ok((format, size)) => println!("ok: {:?} {}, {} bytes", file, format, size),
+            Err(e) => {
+                error!(file = %file.display(), error = %e, "failed");
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(lang::format(
+            "{} of {} files failed verification",
+            &[&failed, &files.len()],
+        )
+        .into()),
+    }
+}
+
+/// Decrypts `file` and returns the recognized format and the size of the audio.
+fn verify_file(file: &Path, decryptor: &xm::Decryptor) -> Result<(&'static str, usize)> {
+    let content = remote::read(file)?;
+    let audio = match xm::detect(&content).format()? {
+        xm::Format::Xm => decryptor.decrypt(&xm::extract_xm_info(&content[..])?, &content)?,
+        format => xm::decrypt_scrambled(format, &content)?,
+    };
+    let extension = xm::validate_audio(&audio)
+        .map_err(|e| lang::format("the decrypted audio is damaged: {}", &[&e]))?
+        .extension()
+        .expect("validated audio has a known format");
+    Ok((extension, audio.len()))
+}
+
+/// Writes what the stages of decrypting the xm file `file` produce into `dir`, for a file that
+/// doesn't decrypt: `<stem>.aes.txt` holds the AES decrypted text, `<stem>.wasm.txt` the output
+/// of the wasm transform and `<stem>.base64.txt` that output with `TSSE` in front, as it is
+/// decoded. Stops at the first stage that fails and returns its error, the files of the stages
+/// before are kept.
+pub fn dump_intermediate(file: &Path, dir: &Path, decryptor: &xm::Decryptor) -> Result<()> {
+    let content = std::fs::read(file)?;
+    let xm_info = xm::extract_xm_info(&content[..])?;
+    std::fs::create_dir_all(dir)?;
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let write = |stage: &str, text: &str| {
+        let path = dir.join(format!("{}.{}.txt", stem, stage));
+        std::fs::write(&path, text)?;
+        debug!(file = %file.display(), output = %path.display(), stage, "dumped stage");
+        Result::Ok(())
+    };
+    let range =
+        stages::find_encrypted_range(&xm_info, &content, decryptor.key(), decryptor.iv_source())?;
+    let decrypted = stages::aes_decrypt(
+        &xm_info,
+        &content[range],
+        decryptor.key(),
+        decryptor.iv_source(),
+    )?;
+    write("aes", &decrypted)?;
+    let transformed = decryptor.transform_prefix(&xm_info, &decrypted)?;
+    write("wasm", &transformed)?;
+    let base64 = format!(
+        "{}{}",
+        xm_info.encoding_technology().unwrap_or_default(),
+        transformed
+    );
+    write("base64", &base64)?;
+    stages::base64_decode(&xm_info, &transformed)?;
+    Ok(())
+}
+
+/// Prints the health score and issues of the tag of every xm file, followed by what each stage of
+/// decrypting it with `decryptor` gives, up to the stage that fails.
+pub fn doctor_files(files: &[PathBuf], decryptor: &xm::Decryptor) -> Result<()> {
+    for file in files {
+        let tag = remote::read(file).and_then(|content| {
+            let format = xm::detect(&content).format()?;
+            Ok((format, Tag::read_from(&content[..]), content))
+        });
+        match tag {
+            Ok((xm::Format::Xm, Ok(tag), content)) => {
+                println!("{}: {}", file.display(), health::analyze(&tag));
+                for (name, value) in diagnose(&content, tag, decryptor) {
+                    println!("  {}: {}", name, value);
+                }
+            }
+            Ok((xm::Format::Xm, Err(e), _)) => {
+                error!(file = %file.display(), error = %e, "failed")
+            }
+            Ok((format, _, _)) => println!("{}: {:?}, there is no tag", file.display(), format),
+            Err(e) => error!(file = %file.display(), error = %e, "failed"),
+        }
+    }
+    Ok(())
+}
+
+/// Runs the stages of decrypting the xm file `content` with the tag `tag` one by one and
+/// describes what each gives, up to the stage that fails.
+fn diagnose(content: &[u8], tag: Tag, decryptor: &xm::Decryptor) -> Vec<(&'static str, String)> {
+    let ids: Vec<_> = tag.frames().map(|frame| frame.id()).collect();
+    let mut lines = vec![("frames", ids.join(" "))];
+    let tsiz = tag.get("TSIZ").and_then(|frame| frame.content().text());
+    let info = XMInfo::from(tag.clone());
+    let after_tag = content.len().saturating_sub(info.header_size());
+    lines.push((
+        "tsiz",
+        match tsiz {
+            Some(tsiz) => format!("{}, {} bytes follow the tag", tsiz, after_tag),
+            None => format!("missing, {} bytes follow the tag", after_tag),
+        },
+    ));
+    for (name, hex) in [("tsrc iv", info.isrc()), ("tenc iv", info.encodedby())] {
+        let iv = match hex.map(hex::decode) {
+            None => "missing".to_string(),
+            Some(Ok(iv)) if iv.len() == 16 => "valid".to_string(),
+            Some(Ok(iv)) => format!("{} bytes of hex, not 16", iv.len()),
+            Some(Err(e)) => format!("not hex, {}", e),
+        };
+        lines.push((name, iv));
+    }
+    lines.push(("iv source", format!("{:?}", decryptor.iv_source())));
+    if !info.is_encrypted() {
+        lines.push((
+            "region",
+            "none, TSIZ is 0 and the audio isn't encrypted".to_string(),
+        ));
+        return lines;
+    }
+    let (key, iv_source) = (decryptor.key(), decryptor.iv_source());
+    let range = match stages::find_encrypted_range(&info, content, key, iv_source) {
+        Ok(range) => range,
+        Err(e) => {
+            lines.push(("region", format!("failed, {}", e)));
+            return lines;
+        }
+    };
+    let origin = match tsiz.and_then(|tsiz| tsiz.parse().ok()) == Some(range.len()) {
+        true => "as TSIZ says",
+        false => "found by the AES padding",
+    };
+    lines.push((
+        "region",
+        format!("{} bytes at {}, {}", range.len(), range.start, origin),
+    ));
+    let decrypted = match stages::aes_decrypt(&info, &content[range.clone()], key, iv_source) {
+        Ok(decrypted) => decrypted,
+        Err(e) => {
+            lines.push(("aes", format!("failed, {}", e)));
+            return lines;
+        }
+    };
+    lines.push((
+        "aes",
+        format!("valid padding, {} characters", decrypted.len()),
+    ));
+    let transformed = match decryptor.transform_prefix(&info, &decrypted) {
+        Ok(transformed) => transformed,
+        Err(e) => {
+            // Traps come with a backtrace of the wasm functions.
+            let e = e.to_string();
+            let e = e.lines().next().unwrap_or_default();
+            lines.push(("wasm", format!("failed, {}", e)));
+            return lines;
+        }
+    };
+    lines.push(("wasm", format!("{} characters", transformed.len())));
+    let tsse = info.encoding_technology().unwrap_or_default().len();
+    let prefix = match stages::base64_decode(&info, &transformed) {
+        Ok(prefix) => prefix,
+        Err(e) => {
+            lines.push((
+                "base64",
+                format!("invalid with {} characters of TSSE, {}", tsse, e),
+            ));
+            return lines;
+        }
+    };
+    lines.push((
+        "base64",
+        format!(
+            "valid with {} characters of TSSE, {} bytes",
+            tsse,
+            prefix.len()
+        ),
+    ));
+    let mut audio = prefix;
+    audio.extend_from_slice(&content[range.end..]);
+    lines.push((
+        "audio",
+        match xm::validate_audio(&audio) {
+            Ok(format) => format!("{} bytes of intact {:?}", audio.len(), format),
+            Err(e) => format!("{} bytes, damaged, {}", audio.len(), e),
+        },
+    ));
+    lines
+}
+
+#[test]
+fn test_diagnose() {
+    use xm_decryptor::id3::Version;
+
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    tag.set_text("TSIZ", "16");
+    tag.set_text("TSRC", "not hex");
+    tag.set_text("TENC", "00");
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+    content.extend_from_slice(&[0; 16]);
+    let tag = Tag::read_from(&content[..]).unwrap();
+    let lines = diagnose(&content, tag, &xm::Decryptor::new().unwrap());
+    let line = |name| &lines.iter().find(|line| line.0 == name).unwrap().1;
+    assert_eq!(line("frames"), "TIT2 TSIZ TSRC TENC");
+    assert_eq!(line("tsiz"), "16, 16 bytes follow the tag");
+    assert!(line("tsrc iv").starts_with("not hex"));
+    assert_eq!(line("tenc iv"), "1 bytes of hex, not 16");
+    assert!(line("region").starts_with("failed"));
+    assert!(lines.iter().all(|line| line.0 != "aes"));
+}
+
+fn read_tag(file: &Path) -> Result<Tag> {
+    if remote::is_url(file) {
+        return Ok(Tag::read_from(&remote::read(file)?[..])?);
+    }
+    let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+    Ok(Tag::read_from(reader)?)
+}
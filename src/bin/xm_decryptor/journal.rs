@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use xm_decryptor::Result;
+
+use crate::report::json_string;
+
+/// The name of the journal file, kept in the output root.
+const JOURNAL_FILE: &str = ".xm_decryptor_state.json";
+
+/// Remembers which inputs have been decrypted so an interrupted run can be resumed.
+///
+/// The journal holds one JSON object per line with the canonical source path, its size and its
+/// modification time. A line is appended as soon as a file is done, so the journal survives the
+/// run being killed. An input counts as done while its size and modification time are unchanged.
+pub struct Journal {
+    file: Mutex<File>,
+    done: HashMap<PathBuf, Stamp>,
+}
+
+/// The size and modification time of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    mtime_ms: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Result<Stamp> {
+        let metadata = fs::metadata(path)?;
+        Ok(Stamp {
+            size: metadata.len(),
+            mtime_ms: metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        })
+    }
+}
+
+impl Journal {
+    /// Opens the journal in `dir`, creating it if it doesn't exist. Lines that can't be parsed,
+    /// such as one cut short by a crash, are ignored.
+    pub fn open(dir: &Path) -> Result<Journal> {
+        let path = dir.join(JOURNAL_FILE);
+        let mut done = HashMap::new();
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                if let Some((source, stamp)) = parse_line(line) {
+                    done.insert(source, stamp);
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Journal {
+            file: Mutex::new(file),
+            done,
+        })
+    }
+
+    /// Returns true if `file` was decrypted by an earlier run and hasn't changed since.
+    pub fn is_done(&self, file: &Path) -> bool {
+        let (Ok(source), Ok(stamp)) = (fs::canonicalize(file), Stamp::of(file)) else {
+            return false;
+        };
+        self.done.get(&source) == Some(&stamp)
+    }
+
+    /// Records that `file` has been decrypted.
+    pub fn record(&self, file: &Path) -> Result<()> {
+        let source = fs::canonicalize(file)?;
+        let stamp = Stamp::of(file)?;
+        let line = format!(
+            "{{\"source\": {}, \"size\": {}, \"mtime_ms\": {}}}\n",
+            json_string(&source.to_string_lossy()),
+            stamp.size,
+            stamp.mtime_ms
+        );
+        let mut journal = self.file.lock().expect("journal poisoned");
+        journal.write_all(line.as_bytes())?;
+        journal.flush()?;
+        Ok(())
+    }
+
+    /// Gets the recorded lines onto disk, before an interrupted run exits.
+    pub fn sync(&self) -> Result<()> {
+        self.file.lock().expect("journal poisoned").sync_all()?;
+        Ok(())
+    }
+}
+
+/// Parses a journal line of the form `{"source": "...", "size": 1, "mtime_ms": 2}`.
+fn parse_line(line: &str) -> Option<(PathBuf, Stamp)> {
+    let mut rest = line
+        .trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')?
+        .trim_start();
+    let mut source = None;
+    let mut size = None;
+    let mut mtime_ms = None;
+    while !rest.is_empty() {
+        let (key, after) = parse_string(rest)?;
+        rest = after.trim_start().strip_prefix(':')?.trim_start();
+        match key.as_str() {
+            "source" => {
+                let (value, after) = parse_string(rest)?;
+                source = Some(PathBuf::from(value));
+                rest = after;
+            }
+            _ => {
+                let end = rest.find([',', ' ']).unwrap_or(rest.len());
+                let value = rest[..end].parse().ok()?;
+                match key.as_str() {
+                    "size" => size = Some(value),
+                    "mtime_ms" => mtime_ms = Some(value),
+                    _ => {}
+                }
+                rest = &rest[end..];
+            }
+        }
+        rest = rest.trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    Some((
+        source?,
+        Stamp {
+            size: size?,
+            mtime_ms: mtime_ms?,
+        },
+    ))
+}
+
+/// Parses the JSON string at the start of `s`, returning it and the rest of `s`.
+pub fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'u' => {
+                    let hex: String = (0..4)
+                        .filter_map(|_| chars.next())
+                        .map(|(_, c)| c)
+                        .collect();
+                    value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[test]
+fn test_parse_line() {
+    let line = format!(
+        "{{\"source\": {}, \"size\": 12, \"mtime_ms\": 34}}",
+        json_string("/a/\"b\"\\c\u{1}.xm")
+    );
+    let (source, stamp) = parse_line(&line).unwrap();
+    assert_eq!(source, PathBuf::from("/a/\"b\"\\c\u{1}.xm"));
+    assert_eq!(
+        stamp,
+        Stamp {
+            size: 12,
+            mtime_ms: 34
+        }
+    );
+    assert_eq!(parse_line("{\"source\": \"/a.xm\", \"size\": 1"), None);
+}
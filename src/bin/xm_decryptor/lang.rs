@@ -0,0 +1,304 @@
+use std::fmt::{Display, Write as _};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// The language of the messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Zh,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Lang::En),
+            "zh" => Ok(Lang::Zh),
+            _ => Err(format!("invalid --lang value {}, expected zh or en", s)),
+        }
+    }
+}
+
+static LANG: RwLock<Option<Lang>> = RwLock::new(None);
+
+/// Sets the language of the messages, or picks it from the system locale if `lang` is `None`.
+pub fn init(lang: Option<Lang>) {
+    *LANG.write().expect("lang poisoned") = Some(lang.unwrap_or_else(detect));
+}
+
+/// Returns the language of the messages. Before [`init`], such as while parsing the arguments,
+/// this is the language of the system locale.
+pub fn current() -> Lang {
+    LANG.read().expect("lang poisoned").unwrap_or_else(detect)
+}
+
+/// Returns Chinese if the system locale is Chinese, otherwise English.
+fn detect() -> Lang {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) if locale.starts_with("zh") => Lang::Zh,
+        Some(_) => Lang::En,
+        None => system_lang(),
+    }
+}
+
+/// Returns the language of the Windows user interface, where the locale variables are usually
+/// not set.
+#[cfg(windows)]
+fn system_lang() -> Lang {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetUserDefaultUILanguage() -> u16;
+    }
+    /// The primary language ID of Chinese, in the low 10 bits of a language ID.
+    const LANG_CHINESE: u16 = 0x04;
+    // SAFETY: the function takes no arguments and only returns a value.
+    let lang_id = unsafe { GetUserDefaultUILanguage() };
+    match lang_id & 0x3FF == LANG_CHINESE {
+        true => Lang::Zh,
+        false => Lang::En,
+    }
+}
+
+#[cfg(not(windows))]
+fn system_lang() -> Lang {
+    Lang::En
+}
+
+/// The Chinese translations of the fixed messages. A `{}` stands for a value that is filled in.
+const MESSAGES: &[(&str, &str)] = &[
+    // Log levels and messages.
+    ("warn", "警告"),
+    ("info", "信息"),
+    ("debug", "调试"),
+    ("trace", "跟踪"),
+    ("added the context menu entries", "已添加右键菜单"),
+    ("archived source", "已归档xm文件"),
+    ("can't hold the source tag", "无法保存原始标签"),
+    ("can't open the browser", "无法打开浏览器"),
+    ("can't decrypt the new files", "无法解密新文件"),
+    ("can't append to the run history", "无法写入运行历史"),
+    ("can't open the journal", "无法打开处理记录"),
+    ("can't pause or resume", "无法暂停或继续"),
+    ("can't record in the journal", "无法写入处理记录"),
+    ("can't read the directory", "无法读取目录"),
+    ("can't remove the source", "无法移走xm文件"),
+    ("copied, not encrypted", "未加密，已复制"),
+    ("decrypted", "已解密"),
+    (
+        "decrypted with a track id from the file name",
+        "已用文件名中的曲目ID解密",
+    ),
+    ("deleted source", "已删除xm文件"),
+    ("dumped the intermediate text", "已写出解密中间结果"),
+    (
+        "dumped the intermediate text up to the failing stage",
+        "已写出出错步骤之前的解密中间结果",
+    ),
+    ("failed", "失败"),
+    ("fixed tags", "已修正标签"),
+    ("paused", "已暂停"),
+    ("read metadata", "已读取曲目信息"),
+    ("removed the context menu entries", "已删除右键菜单"),
+    ("resumed", "已继续"),
+    ("serving the web UI", "网页界面已启动"),
+    ("read xm info", "已读取xm信息"),
+    ("skipped by the user", "已按要求跳过"),
+    ("skipped, decrypted by an earlier run", "跳过，之前已解密"),
+    ("skipped, no title", "跳过，没有标题"),
+    ("skipped, not encrypted", "跳过，未加密"),
+    ("skipped, output already exists", "跳过，输出文件已存在"),
+    ("tagged", "已写入标签"),
+    ("watching", "正在监视"),
+    // Errors.
+    ("error", "错误"),
+    ("--jobs must be at least 1", "--jobs 至少为1"),
+    (
+        "--delete-source and --archive-dir can't be combined",
+        "--delete-source 和 --archive-dir 不能同时使用",
+    ),
+    ("no input path", "没有指定输入路径"),
+    (
+        "an input path can't be combined with a file list",
+        "输入路径不能与文件列表同时使用",
+    ),
+    (
+        "--interactive needs stdin for the answers, not for a file list",
+        "--interactive 需要从标准输入读取回答，不能同时从标准输入读取文件列表",
+    ),
+    (
+        "--watch requires an input directory",
+        "--watch 需要输入目录",
+    ),
+    (
+        "--tui can't be combined with --watch, --map, --interactive or a file list on stdin",
+        "--tui 不能与 --watch、--map、--interactive 或从标准输入读取的文件列表同时使用",
+    ),
+    ("--tui needs a terminal", "--tui 需要在终端中运行"),
+    (
+        "gui takes no input path, file list, --map, --watch, --interactive or --tui",
+        "gui 不接受输入路径、文件列表、--map、--watch、--interactive 或 --tui",
+    ),
+    (
+        "the gui command needs a build with the gui feature",
+        "gui 命令需要编译时启用 gui 特性",
+    ),
+    ("a run is going on", "正在运行中"),
+    (
+        "--track-id needs a single input file",
+        "--track-id 只能用于单个输入文件",
+    ),
+    (
+        "--archive-dir can't be inside the input directory",
+        "--archive-dir 不能在输入目录内",
+    ),
+    (
+        "the decrypted audio is damaged: {}",
+        "解密后的音频已损坏: {}",
+    ),
+    ("no answer, stdin is closed", "没有回答，标准输入已关闭"),
+    (
+        "{} must hold a key of 32 characters or 64 hex digits",
+        "{} 中的密钥必须是32个字符或64位十六进制数",
+    ),
+    ("{} is only available on Windows", "{} 只能在Windows上使用"),
+    (
+        "{} is a URL, which needs a build with the http feature",
+        "{} 是网址，需要编译时启用 http 特性",
+    ),
+    (
+        "invalid --hook {}, expected EXT=COMMAND",
+        "无效的 --hook {}，应为 扩展名=命令",
+    ),
+    (
+        "invalid --map {}, expected IN=OUT",
+        "无效的 --map {}，应为 输入目录=输出目录",
+    ),
+    (
+        "--map can't be combined with an input path, a file list or --output-dir",
+        "--map 不能与输入路径、文件列表或 --output-dir 同时使用",
+    ),
+    (
+        "--watch can't be combined with --map",
+        "--watch 不能与 --map 同时使用",
+    ),
+    ("{} of {} input roots failed", "{} 个输入目录失败，共 {} 个"),
+    (
+        "the output {} of --map is inside the input {} of another --map",
+        "--map 的输出目录 {} 在另一个 --map 的输入目录 {} 中",
+    ),
+    ("unknown option {}", "未知选项 {}"),
+    ("unknown command {}", "未知命令 {}"),
+    ("unexpected argument {}", "多余的参数 {}"),
+    ("{} requires a value", "{} 需要一个值"),
+    ("{} already exists", "{} 已存在"),
+    ("{} is not paused", "{} 没有暂停"),
+    ("interrupted", "已中断"),
+    ("{} of {} files failed", "{} 个文件失败，共 {} 个"),
+    (
+        "{} of {} files failed verification",
+        "{} 个文件验证失败，共 {} 个",
+    ),
+    // Summary and progress.
+    (
+        "{} succeeded, {} skipped, {} failed, {} MB in {} s",
+        "成功 {}，跳过 {}，失败 {}，共 {} MB，用时 {} 秒",
+    ),
+    ("ETA", "剩余"),
+    ("stopping", "正在停止"),
+    ("done", "完成"),
+    (
+        "↑↓ PgUp PgDn: move  End: follow  p: pause  r: retry  s: skip  q: quit",
+        "↑↓ PgUp PgDn: 移动  End: 跟随  p: 暂停/继续  r: 重试  s: 跳过  q: 退出",
+    ),
+    ("{} files, {} MB", "{} 个文件，共 {} MB"),
+    // The page of the gui command.
+    (
+        "Drop a folder here, or pick it below",
+        "把文件夹拖到这里，或在下面选择",
+    ),
+    (
+        "The browser didn't tell where the dropped folder is, pick it below or paste its path",
+        "浏览器没有提供拖入文件夹的位置，请在下面选择或粘贴其路径",
+    ),
+    ("Input folder", "输入文件夹"),
+    ("Output folder", "输出文件夹"),
+    ("next to the inputs", "与输入文件相同"),
+    ("Up", "上一级"),
+    ("Use as input", "用作输入"),
+    ("Use as output", "用作输出"),
+    ("Start", "开始"),
+    ("Pause", "暂停/继续"),
+    ("Stop", "停止"),
+    ("Finish", "结束"),
+    ("Retry", "重试"),
+    ("Skip", "跳过"),
+    // Questions.
+    (
+        "{} collides with {}. (o)verwrite, (r)ename, (s)kip or (e)dit the name? ",
+        "{} 与 {} 同名。覆盖(o)、另存(r)、跳过(s)还是修改文件名(e)？",
+    ),
+    (
+        "{} has no title and would be written as {}. (k)eep the name, (e)dit it or (s)kip? ",
+        "{} 没有标题，将保存为 {}。使用该文件名(k)、修改(e)还是跳过(s)？",
+    ),
+    ("new file name: ", "新文件名："),
+    // Context menu.
+    ("Decrypt .xm here", "在此解密 .xm 文件"),
+];
+
+/// Returns the message `s` in the language of the messages. Messages without a translation are
+/// returned as they are.
+pub fn tr(s: &str) -> &str {
+    tr_in(current(), s)
+}
+
+/// Returns the message `s` in `lang`.
+pub fn tr_in(lang: Lang, s: &str) -> &str {
+    match lang {
+        Lang::En => s,
+        Lang::Zh => MESSAGES
+            .iter()
+            .find(|(en, _)| *en == s)
+            .map_or(s, |(_, zh)| zh),
+    }
+}
+
+/// Translates `template` like [`tr`] and replaces each `{}` in it with the next of `values`.
+pub fn format(template: &str, values: &[&dyn Display]) -> String {
+    format_in(current(), template, values)
+}
+
+/// Translates `template` into `lang` and replaces each `{}` in it with the next of `values`.
+pub fn format_in(lang: Lang, template: &str, values: &[&dyn Display]) -> String {
+    let mut values = values.iter();
+    let mut out = String::new();
+    for (i, part) in tr_in(lang, template).split("{}").enumerate() {
+        if i > 0 {
+            if let Some(value) = values.next() {
+                let _ = write!(out, "{}", value);
+            }
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+#[test]
+fn test_format() {
+    let (failed, total) = (2, 5);
+    assert_eq!(
+        format_in(Lang::En, "{} of {} files failed", &[&failed, &total]),
+        "2 of 5 files failed"
+    );
+    assert_eq!(
+        format_in(Lang::Zh, "{} of {} files failed", &[&failed, &total]),
+        "2 个文件失败，共 5 个"
+    );
+    assert_eq!(tr_in(Lang::Zh, "not translated"), "not translated");
+}
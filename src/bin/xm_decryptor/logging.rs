@@ -0,0 +1,173 @@
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::report::json_string;
+use crate::{lang, progress};
+
+/// How log lines are written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `level: message key=value ...`
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "invalid --log-format value {}, expected text or json",
+                s
+            )),
+        }
+    }
+}
+
+/// Installs a subscriber that writes the events of this crate up to the level selected by
+/// `verbosity` to stderr: info by default, debug with `-v` and trace with `-vv`. Dependencies
+/// only get to log warnings and errors.
+pub fn init(verbosity: u8, format: LogFormat) {
+    let max_level = match verbosity {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let logger = Logger {
+        max_level,
+        format,
+        next_span: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(logger).expect("logger already installed");
+}
+
+struct Logger {
+    max_level: Level,
+    format: LogFormat,
+    next_span: AtomicU64,
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // More verbose levels compare greater.
+        let ours = metadata.target().starts_with("xm_decryptor");
+        metadata.level() <= &self.max_level && (ours || metadata.level() <= &Level::WARN)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let level = event.metadata().level().as_str().to_ascii_lowercase();
+        let mut line = String::new();
+        match self.format {
+            LogFormat::Text => {
+                line.push_str(lang::tr(&level));
+                line.push_str(": ");
+                line.push_str(lang::tr(&fields.message));
+                for (name, value) in &fields.values {
+                    let _ = write!(line, " {}={}", name, value.text());
+                }
+            }
+            LogFormat::Json => {
+                let _ = write!(
+                    line,
+                    "{{\"level\": \"{}\", \"message\": {}",
+                    level,
+                    json_string(lang::tr(&fields.message))
+                );
+                for (name, value) in &fields.values {
+                    let _ = write!(line, ", {}: {}", json_string(name), value.json());
+                }
+                line.push('}');
+            }
+        }
+        line.push('\n');
+        progress::write_log(&line);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// The message and fields of an event.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    values: Vec<(&'static str, Value)>,
+}
+
+enum Value {
+    /// A number or boolean, written without quotes.
+    Plain(String),
+    /// Text, quoted in JSON.
+    Text(String),
+}
+
+impl Value {
+    /// Returns the value for a text line, quoted if it would otherwise run into the next field.
+    fn text(&self) -> String {
+        match self {
+            Value::Text(s) if s.is_empty() || s.contains(char::is_whitespace) => {
+                format!("{:?}", s)
+            }
+            Value::Plain(s) | Value::Text(s) => s.clone(),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Plain(s) => s.clone(),
+            Value::Text(s) => json_string(s),
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = format!("{:?}", value);
+        match field.name() {
+            "message" => self.message = value,
+            name => self.values.push((name, Value::Text(value))),
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.values.push((name, Value::Text(value.to_string()))),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.values
+            .push((field.name(), Value::Plain(value.to_string())));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.values
+            .push((field.name(), Value::Plain(value.to_string())));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.values
+            .push((field.name(), Value::Plain(value.to_string())));
+    }
+}
@@ -0,0 +1,910 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error, info, warn};
+use xm_decryptor::metadata::MetadataDb;
+use xm_decryptor::{audit, collate, mp3, xm, Result};
+
+use crate::args::{Args, Command, CoverOnce, RootMap, SourceAction};
+use crate::hooks::HookRun;
+use crate::index::Index;
+use crate::journal::Journal;
+use crate::logging::LogFormat;
+use crate::output::{
+    copy_times, dispose_source, long_path, target_dir, write_output, OutputNames, Syncer,
+};
+use crate::progress::Progress;
+use crate::report::{FileReport, Status, Summary};
+use crate::scan::{group_by_album, is_xm, nested_dir, read_file_list, Scan};
+use crate::tags::Covers;
+use crate::tui::Tui;
+
+mod args;
+mod bench;
+mod control;
+mod filter;
+#[cfg(feature = "gui")]
+mod gui;
+mod hooks;
+mod index;
+mod inspect;
+mod journal;
+mod lang;
+mod logging;
+mod output;
+mod progress;
+mod prompt;
+mod remote;
+mod report;
+mod scan;
+mod shell_ext;
+mod tags;
+mod tui;
+mod watch;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            progress::write_log(&format!("{}: {}\n", lang::tr("error"), e));
+            match control::stopping() {
+                // The status of a process killed by SIGINT, so scripts see the interruption.
+                true => ExitCode::from(130),
+                false => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let args = Args::parse()?;
+    lang::init(args.lang);
+    logging::init(args.verbosity, args.log_format);
+    match args.command {
+        Command::InstallShellExt => return shell_ext::install(),
+        Command::UninstallShellExt => return shell_ext::uninstall(),
+        #[cfg(feature = "gui")]
+        Command::Gui => {
+            control::install();
+            return gui::serve(&args);
+        }
+        #[cfg(not(feature = "gui"))]
+        Command::Gui => {
+            return Err(lang::tr("the gui command needs a build with the gui feature").into())
+        }
+        // Ctrl+C lets the files being decrypted finish and reports what was done.
+        Command::Decrypt | Command::RetryFailures => control::install(),
+        _ => {}
+    }
+    let mut reports = Vec::new();
+    if args.maps.is_empty() {
+        return run_root(&args, &mut reports);
+    }
+    check_maps(&args.maps)?;
+    let mut failed = 0;
+    for map in &args.maps {
+        if control::stopping() {
+            break;
+        }
+        let root_args = Args {
+            path: Some(map.input.clone()),
+            output_dir: Some(map.output.clone()),
+            maps: Vec::new(),
+            ..args.clone()
+        };
+        info!(input = %map.input.display(), output = %map.output.display(), "processing root");
+        if let Err(e) = run_root(&root_args, &mut reports) {
+            error!(input = %map.input.display(), error = %e, "failed");
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        failed => {
+            Err(lang::format("{} of {} input roots failed", &[&failed, &args.maps.len()]).into())
+        }
+    }
+}
+
+/// Refuses `--map` roots whose outputs would land in the input of another root, where its scan
+/// would walk through them. Outputs inside their own input are skipped by the scan.
+fn check_maps(maps: &[RootMap]) -> Result<()> {
+    for (i, map) in maps.iter().enumerate() {
+        for (j, other) in maps.iter().enumerate() {
+            let same = || {
+                let output = std::fs::canonicalize(&map.output);
+                output.is_ok() && output.ok() == std::fs::canonicalize(&other.input).ok()
+            };
+            if i != j && (nested_dir(&other.input, &map.output).is_some() || same()) {
+                return Err(lang::format(
+                    "the output {} of --map is inside the input {} of another --map",
+                    &[&map.output.display(), &other.input.display()],
+                )
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the command on the input root `args.path`, or on the files of `args.files_from`. The
+/// outcome for every decrypted file is added to `all_reports`, which `--report` is written from.
+fn run_root(args: &Args, all_reports: &mut Vec<FileReport>) -> Result<()> {
+    let scan = args
+        .path
+        .as_deref()
+        .filter(|path| path.is_dir())
+        .map(|root| Scan {
+            root,
+            max_depth: args.max_depth,
+            filters: &args.filters,
+            skip_dir: args
+                .output_dir
+                .as_deref()
+                .and_then(|output_dir| nested_dir(root, output_dir)),
+        });
+    if args.watch && scan.is_none() {
+        return Err(lang::tr("--watch requires an input directory").into());
+    }
+    // The track id of one file would decrypt every other file wrongly.
+    if args.track_id.is_some()
+        && (scan.is_some() || args.files_from.is_some() || args.command == Command::RetryFailures)
+    {
+        return Err(lang::tr("--track-id needs a single input file").into());
+    }
+    let root = match (&args.files_from, &args.path) {
+        (Some(_), _) => None,
+        // Outputs of a URL go to the working directory, unless --output-dir says otherwise.
+        (None, Some(path)) if remote::is_url(path) => None,
+        (None, Some(path)) if path.is_file() => {
+            Some(path.parent().expect("no parent dir").to_path_buf())
+        }
+        (None, Some(path)) => Some(path.clone()),
+        (None, None) => unreachable!("checked when parsing the arguments"),
+    };
+    match args.command {
+        Command::Pause => return control::pause(output_root(args, root.as_deref())),
+        Command::Resume => return control::resume(output_root(args, root.as_deref())),
+        _ => {}
+    }
+    let index = match args.index {
+        true => {
+            let dir = output_root(args, root.as_deref());
+            std::fs::create_dir_all(dir)?;
+            Some(Index::open(dir)?)
+        }
+        false => None,
+    };
+    match args.command {
+        Command::Info => {
+            let files = collect_inputs(args, scan.as_ref())?;
+            return inspect::info_files(&files, args.frames, args.json);
+        }
+        Command::Verify => {
+            let files = collect_inputs(args, scan.as_ref())?;
+            return inspect::verify_files(&files, &decryptor(args)?);
+        }
+        Command::Doctor => {
+            let files = collect_inputs(args, scan.as_ref())?;
+            return inspect::doctor_files(&files, &decryptor(args)?);
+        }
+        Command::Bench => return bench::bench_files(&collect_inputs(args, scan.as_ref())?),
+        Command::Decrypt | Command::RetryFailures if args.audit => {
+            return audit_files(&collect_inputs(args, scan.as_ref())?, index.as_ref())
+        }
+        Command::Decrypt | Command::RetryFailures => {}
+        Command::Pause
+        | Command::Resume
+        | Command::Gui
+        | Command::InstallShellExt
+        | Command::UninstallShellExt => unreachable!("handled above"),
+    }
+    if let (SourceAction::Archive(archive_dir), Some(scan)) = (&args.source_action, &scan) {
+        // Archived files would be found again by the walk or by --watch.
+        std::fs::create_dir_all(archive_dir)?;
+        if std::fs::canonicalize(archive_dir)?.starts_with(std::fs::canonicalize(scan.root)?) {
+            return Err(lang::tr("--archive-dir can't be inside the input directory").into());
+        }
+    }
+    let metadata = match &args.metadata {
+        Some(path) => {
+            let metadata = MetadataDb::from_csv(std::fs::File::open(path)?)?;
+            debug!(tracks = metadata.len() as u64, "read metadata");
+            Some(metadata)
+        }
+        None => None,
+    };
+    let retry = match (args.command, &index) {
+        (Command::RetryFailures, Some(index)) => Some(index.failed()),
+        _ => None,
+    };
+    let tui = match args.tui {
+        true => Some(Tui::open(
+            output_root(args, root.as_deref()),
+            root.as_deref(),
+        )?),
+        false => None,
+    };
+    let run = Run {
+        args,
+        decryptor: decryptor(args)?,
+        names: OutputNames::default(),
+        syncer: Syncer::new(args.durability),
+        journal: open_journal(output_root(args, root.as_deref())),
+        covers: Covers::default(),
+        index,
+        root,
+        metadata,
+        progress: Progress::new(
+            std::io::stderr().is_terminal()
+                && args.log_format == LogFormat::Text
+                && !args.json
+                && !args.interactive
+                && !args.tui,
+        ),
+        tui,
+    };
+    let start = Instant::now();
+    // Files are decrypted while the directory is still being walked.
+    let (sender, receiver) = mpsc::channel();
+    let (found, mut reports) = std::thread::scope(|scope| {
+        let (scan, progress, tui) = (scan.as_ref(), &run.progress, run.tui.as_ref());
+        let finder = scope.spawn(move || {
+            let found = |file: PathBuf| {
+                progress.found();
+                if let Some(tui) = tui {
+                    tui.found(&file);
+                }
+                // Fails once decryption has stopped, which ends the walk.
+                sender.send(file).is_ok()
+            };
+            let found = match retry {
+                Some(files) => {
+                    files.into_iter().all(found);
+                    Ok(Vec::new())
+                }
+                None => find_inputs(args, scan, found),
+            };
+            progress.found_all();
+            if let Some(tui) = tui {
+                tui.found_all();
+            }
+            found
+        });
+        let reports = match args.group_by_album {
+            // Albums are only complete once the walk has finished.
+            true => decrypt_files(
+                group_by_album(receiver.into_iter().collect()).into_iter(),
+                &run,
+            ),
+            false => decrypt_files(receiver.into_iter().map(|file| vec![file]), &run),
+        };
+        (finder.join().expect("scan panicked"), reports)
+    });
+    // A directory that couldn't be read fails like a file would.
+    if let Ok(unreadable) = &found {
+        reports.extend(unreadable.iter().map(|(dir, error)| FileReport {
+            source: dir.clone(),
+            target: None,
+            status: Status::Error,
+            error: Some(error.clone()),
+            size: 0,
+            elapsed: Duration::ZERO,
+            hooks: Vec::new(),
+        }));
+    }
+    run.progress.finish();
+    if let Some(tui) = &run.tui {
+        tui.close();
+    }
+    run.syncer.finish()?;
+    let summary = Summary::of(&reports, start.elapsed());
+    info!("{}", summary);
+    record_history(&run, &summary);
+    all_reports.extend(reports.iter().cloned());
+    if let Some(report_path) = &args.report {
+        report::write_report(report_path, all_reports)?;
+    }
+    found?;
+    if let Some(scan) = scan.filter(|_| args.watch && !control::stopping()) {
+        // The outputs too, in case they are written where the scan looks.
+        let seen = reports
+            .into_iter()
+            .flat_map(|report| std::iter::once(report.source).chain(report.target))
+            .collect();
+        let dir = output_root(args, run.root.as_deref());
+        watch::watch(&scan, dir, seen, |files| {
+            let start = Instant::now();
+            let reports = match args.group_by_album {
+                true => decrypt_files(group_by_album(files.to_vec()).into_iter(), &run),
+                false => decrypt_files(files.iter().map(|file| vec![file.clone()]), &run),
+            };
+            run.syncer.finish()?;
+            let summary = Summary::of(&reports, start.elapsed());
+            info!("{}", summary);
+            record_history(&run, &summary);
+            Ok(reports
+                .into_iter()
+                .filter_map(|report| report.target)
+                .collect())
+        })?;
+    }
+    if control::stopping() {
+        if let Some(journal) = &run.journal {
+            journal.sync()?;
+        }
+        return Err(lang::tr("interrupted").into());
+    }
+    match summary.failed {
+        0 => Ok(()),
+        failed => Err(lang::format("{} of {} files failed", &[&failed, &summary.total()]).into()),
+    }
+}
+
+/// Compiles the decryptor, or loads it from the cache of an earlier run, with the key of
+/// `--key-file` and the IV source of `--iv`.
+fn decryptor(args: &Args) -> Result<xm::Decryptor> {
+    #[cfg(feature = "cranelift")]
+    let decryptor = match cache_dir() {
+        Some(dir) => xm::Decryptor::new_cached(&dir)?,
+        None => xm::Decryptor::new()?,
+    };
+    #[cfg(not(feature = "cranelift"))]
+    let decryptor = xm::Decryptor::new()?;
+    let decryptor = decryptor.with_iv_source(args.iv_source);
+    let Some(path) = &args.key_file else {
+        return Ok(decryptor);
+    };
+    // The key of the client is text, a key file may hold the same text or its hex.
+    let text = std::fs::read_to_string(path)?;
+    let text = text.trim();
+    let key = match text.len() {
+        64 => hex::decode(text).ok(),
+        _ => Some(text.as_bytes().to_vec()),
+    };
+    match key.and_then(|key| <[u8; 32]>::try_from(key).ok()) {
+        Some(key) => Ok(decryptor.with_key(key)),
+        None => Err(lang::format(
+            "{} must hold a key of 32 characters or 64 hex digits",
+            &[&path.display()],
+        )
+        .into()),
+    }
+}
+
+/// Returns the directory of the compiled `xm.wasm` in the cache directory of the user.
+#[cfg(feature = "cranelift")]
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        PathBuf::from(var("LOCALAPPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Caches")
+    } else {
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(var("HOME")?).join(".cache")))?
+    };
+    Some(base.join("xm_decryptor"))
+}
+
+/// Returns the output root, the output directory or else the input root `root`.
+fn output_root<'a>(args: &'a Args, root: Option<&'a Path>) -> &'a Path {
+    match (&args.output_dir, root) {
+        (Some(dir), _) => dir,
+        (None, Some(dir)) => dir,
+        (None, None) => Path::new("."),
+    }
+}
+
+/// Appends `summary` to the run history in the output root. A history that can't be written only
+/// costs a warning.
+fn record_history(run: &Run, summary: &Summary) {
+    let args = run.args;
+    let dir = output_root(args, run.root.as_deref());
+    let inputs = match (&args.path, &args.files_from) {
+        (Some(path), _) | (None, Some(path)) => path.to_string_lossy(),
+        (None, None) => Default::default(),
+    };
+    let command_line: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = report::append_history(dir, &command_line, &inputs, summary) {
+        warn!(dir = %dir.display(), error = %e, "can't append to the run history");
+    }
+}
+
+/// Opens the journal in the output root `dir`. A journal that can't be opened, such as in a
+/// read-only directory, only costs a warning, the run goes on without skipping earlier outputs.
+fn open_journal(dir: &Path) -> Option<Journal> {
+    let journal = std::fs::create_dir_all(dir)
+        .map_err(Into::into)
+        .and_then(|()| Journal::open(dir));
+    match journal {
+        Ok(journal) => Some(journal),
+        Err(e) => {
+            warn!(dir = %dir.display(), error = %e, "can't open the journal");
+            None
+        }
+    }
+}
+
+/// The state shared by every file of a decrypt run.
+struct Run<'a> {
+    args: &'a Args,
+    /// Compiled once, since compiling takes longer than decrypting most tracks.
+    decryptor: xm::Decryptor,
+    /// The directory outputs are placed relative to, none for a file list.
+    root: Option<PathBuf>,
+    names: OutputNames,
+    syncer: Syncer,
+    /// None if the journal couldn't be opened.
+    journal: Option<Journal>,
+    /// The covers shared by the outputs, and with --cover-once the folders that have theirs.
+    covers: Covers,
+    /// The index of decrypted tracks, with `--index`.
+    index: Option<Index>,
+    metadata: Option<MetadataDb>,
+    /// Only drawn for the first batch, not for those of --watch.
+    progress: Progress,
+    /// Shown instead of the progress line with --tui.
+    tui: Option<Tui>,
+}
+
+/// Passes the `.xm`, `.x2m` and `.x3m` inputs and the URLs to `found`. An input directory is walked
+/// in parallel and each file is passed on as soon as it is seen. Returns the directories of the
+/// walk that couldn't be read, with their errors. Stops passing inputs once `found` returns false.
+fn find_inputs(
+    args: &Args,
+    scan: Option<&Scan>,
+    found: impl Fn(PathBuf) -> bool + Sync,
+) -> Result<Vec<(PathBuf, String)>> {
+    let found = |file: PathBuf| !(is_xm(&file) || remote::is_url(&file)) || found(file);
+    match (&args.files_from, scan) {
+        (Some(list), _) => read_file_list(list)?.into_iter().all(found),
+        (None, Some(scan)) => return Ok(scan.walk(found)),
+        (None, None) => args
+            .path
+            .iter()
+            .filter(|path| path.is_file() || remote::is_url(path))
+            .cloned()
+            .all(found),
+    };
+    Ok(Vec::new())
+}
+
+/// Returns the `.xm`, `.x2m` and `.x3m` inputs, those of an input directory sorted by path.
+fn collect_inputs(args: &Args, scan: Option<&Scan>) -> Result<Vec<PathBuf>> {
+    let files = Mutex::new(Vec::new());
+    find_inputs(args, scan, |file| {
+        files.lock().expect("inputs poisoned").push(file);
+        true
+    })?;
+    let mut files = files.into_inner().expect("inputs poisoned");
+    if scan.is_some() {
+        files.sort_by(|a, b| collate::compare_paths(a, b));
+    }
+    Ok(files)
+}
+
+/// Decrypts the groups of files with `args.jobs` workers and returns the outcome for each file, in
+/// the order of `groups`. The files of a group are decrypted one after the other by the same
+/// worker. Workers start on the first groups while later ones are still being produced.
+///
+/// While the output root is paused, workers wait before their next file. Once the run is asked to
+/// stop they take no further files, those left are not reported. With the TUI, workers decrypt
+/// the files queued for a retry first, and wait for more once the groups are done until the TUI
+/// is quit. A retried file is reported once, with its last outcome.
+fn decrypt_files(groups: impl Iterator<Item = Vec<PathBuf>> + Send, run: &Run) -> Vec<FileReport> {
+    // Every worker takes the next unprocessed group until none are left, counting the files
+    // handed out to number the reports. Workers share the decryptor, which keeps a pool of wasm
+    // instances: each transform takes a free one or instantiates another and puts it back, so
+    // there are at most as many instances as jobs.
+    let groups = Mutex::new((0, groups));
+    let done = AtomicUsize::new(0);
+    let mut reports = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..run.args.jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let dir = output_root(run.args, run.root.as_deref());
+                    let mut reports = Vec::new();
+                    'groups: loop {
+                        let retry = run.tui.as_ref().and_then(Tui::next_retry);
+                        let next = retry.map(|(index, file)| (index, vec![file])).or_else(|| {
+                            let mut queue = groups.lock().expect("queue poisoned");
+                            let group = queue.1.next()?;
+                            let first = queue.0;
+                            queue.0 += group.len();
+                            Some((first, group))
+                        });
+                        let (first, group) = match next {
+                            Some(next) => next,
+                            None => match run.tui.as_ref().and_then(Tui::wait_for_retry) {
+                                Some((index, file)) => (index, vec![file]),
+                                None => break,
+                            },
+                        };
+                        for (i, file) in group.into_iter().enumerate() {
+                            control::wait_while_paused(dir);
+                            if control::stopping() {
+                                break 'groups;
+                            }
+                            let index = first + i;
+                            let report = match &run.tui {
+                                Some(tui) if tui.skips(&file) => skipped_by_user(&file),
+                                tui => {
+                                    run.progress.started(&file);
+                                    if let Some(tui) = tui {
+                                        tui.started(&file, index);
+                                    }
+                                    process_file(&file, run)
+                                }
+                            };
+                            run.progress.finished(report.size);
+                            if let Some(tui) = &run.tui {
+                                tui.finished(index, &report);
+                            }
+                            reports.push((index, done.fetch_add(1, Ordering::Relaxed), report));
+                        }
+                    }
+                    reports
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("worker panicked"))
+            .collect::<Vec<_>>()
+    });
+    // The latest report of a file comes first and is kept.
+    reports.sort_by_key(|(i, done, _)| (*i, std::cmp::Reverse(*done)));
+    reports.dedup_by_key(|(i, _, _)| *i);
+    reports.into_iter().map(|(_, _, report)| report).collect()
+}
+
+/// Returns the report of a file skipped with the TUI.
+fn skipped_by_user(file: &Path) -> FileReport {
+    info!(file = %file.display(), "skipped by the user");
+    FileReport {
+        source: file.to_path_buf(),
+        target: None,
+        status: Status::Skipped,
+        error: None,
+        size: 0,
+        elapsed: Duration::ZERO,
+        hooks: Vec::new(),
+    }
+}
+
+/// Decrypts one file and records the outcome.
+fn process_file(file: &Path, run: &Run) -> FileReport {
+    let start = Instant::now();
+    if !run.args.force
+        && run
+            .journal
+            .as_ref()
+            .is_some_and(|journal| journal.is_done(file))
+    {
+        info!(file = %file.display(), "skipped, decrypted by an earlier run");
+        return FileReport {
+            source: file.to_path_buf(),
+            target: None,
+            status: Status::Skipped,
+            error: None,
+            size: 0,
+            elapsed: start.elapsed(),
+            hooks: Vec::new(),
+        };
+    }
+    let remote = remote::is_url(file);
+    let target_dir = match (remote, &run.args.output_dir) {
+        (true, Some(output_dir)) => long_path(output_dir),
+        (true, None) => PathBuf::from("."),
+        (false, _) => long_path(&target_dir(
+            file,
+            run.root.as_deref(),
+            run.args.output_dir.as_deref(),
+        )),
+    };
+    let mut hooks = Vec::new();
+    let (status, target, size, error) = match decrypt_file(file, &target_dir, run, &mut hooks) {
+        Ok((Status::NotEncrypted, _, _)) => (Status::NotEncrypted, None, 0, None),
+        // A URL is neither recorded in the journal nor moved.
+        Ok((Status::Ok, target, size)) if remote => (Status::Ok, Some(target), size, None),
+        Ok((Status::Ok, target, size)) => {
+            if let Some(Err(e)) = run.journal.as_ref().map(|journal| journal.record(file)) {
+                error!(file = %file.display(), error = %e, "can't record in the journal");
+            }
+            let action = &run.args.source_action;
+            match dispose_source(file, &target, size, run.root.as_deref(), action) {
+                Ok(archived) => {
+                    if let Some(archived) = archived {
+                        info!(file = %file.display(), archive = %archived.display(), "archived source");
+                    } else if *action == SourceAction::Delete {
+                        info!(file = %file.display(), "deleted source");
+                    }
+                    (Status::Ok, Some(target), size, None)
+                }
+                // The output is fine, but the source is left behind.
+                Err(e) => {
+                    error!(file = %file.display(), error = %e, "can't remove the source");
+                    (Status::Error, Some(target), size, Some(e.to_string()))
+                }
+            }
+        }
+        Ok((status, target, size)) => (status, Some(target), size, None),
+        Err(e) => {
+            error!(file = %file.display(), error = %e, "failed");
+            if let Some(Err(e)) = run.index.as_ref().map(|index| index.record_failed(file)) {
+                error!(file = %file.display(), error = %e, "can't record in the index");
+            }
+            if let Some(dir) = &run.args.dump_intermediate {
+                match inspect::dump_intermediate(file, dir, &run.decryptor) {
+                    Ok(()) => {
+                        info!(file = %file.display(), dir = %dir.display(), "dumped the intermediate text")
+                    }
+                    Err(e) => {
+                        info!(file = %file.display(), dir = %dir.display(), error = %e, "dumped the intermediate text up to the failing stage")
+                    }
+                }
+            }
+            (Status::Error, None, 0, Some(e.to_string()))
+        }
+    };
+    FileReport {
+        source: file.to_path_buf(),
+        target,
+        status,
+        error,
+        size,
+        elapsed: start.elapsed(),
+        hooks,
+    }
+}
+
+/// Reports missing episodes per album without decrypting anything. The tracks in `index` count
+/// as present, even if their sources are gone.
+fn audit_files(files: &[PathBuf], index: Option<&Index>) -> Result<()> {
+    let mut infos: Vec<_> = index
+        .into_iter()
+        .flat_map(Index::entries)
+        .map(|entry| entry.info())
+        .collect();
+    for file in files {
+        let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+        match xm::extract_xm_info(reader) {
+            Ok(info) => infos.push(info),
+            Err(e) => error!(file = %file.display(), error = %e, "failed"),
+        }
+    }
+    for album in audit::audit_episodes(&infos) {
+        println!("{}", album);
+    }
+    Ok(())
+}
+
+/// Decrypts `file` into `target_dir`. Returns whether the output was written, its path and the
+/// number of bytes written. The `--hook` commands run on the output are added to `hooks`.
+fn decrypt_file(
+    file: &Path,
+    target_dir: &Path,
+    run: &Run,
+    hooks: &mut Vec<HookRun>,
+) -> Result<(Status, PathBuf, u64)> {
+    let args = run.args;
+    let remote = remote::is_url(file);
+    let content = remote::read(file)?;
+
+    // None for plain audio that is copied.
+    let format = match xm::detect(&content) {
+        xm::Source::Audio(format) if !args.copy_plain => {
+            info!(file = %file.display(), ?format, "skipped, not encrypted");
+            return Ok((Status::NotEncrypted, target_dir.to_path_buf(), 0));
+        }
+        xm::Source::Audio(_) => None,
+        detected => Some(detected.format()?),
+    };
+    let title = file.file_stem().unwrap_or_default().to_string_lossy();
+    let mut xm_info = match format {
+        Some(xm::Format::Xm) => xm::extract_xm_info(&content[..])?,
+        Some(xm::Format::X2m | xm::Format::X3m) => xm::XMInfo::untagged(&title, content.len()),
+        None => xm::XMInfo::plain(&content),
+    };
+    if let Some(track_id) = args.track_id.filter(|_| format == Some(xm::Format::Xm)) {
+        xm_info.set_tracknumber(track_id);
+    }
+    if let Some(metadata) = run
+        .metadata
+        .as_ref()
+        .and_then(|db| db.get(xm_info.tracknumber()))
+    {
+        xm_info.apply_metadata(metadata);
+    }
+    run.covers.share(&mut xm_info);
+    debug!(file = %file.display(), ?xm_info, "read xm info");
+    // Tracks without an id can't be indexed, nor can URLs.
+    let indexed = run
+        .index
+        .as_ref()
+        .filter(|_| xm_info.tracknumber() != 0 && !remote)
+        .map(|index| (index, index::hash(&content)));
+    if let Some((index, hash)) = indexed.filter(|_| !args.force) {
+        if let Some(entry) = index.find(xm_info.tracknumber(), file, hash) {
+            info!(file = %file.display(), output = %entry.output.display(), source = %entry.source.display(), "skipped, track already in the index");
+            return Ok((Status::Skipped, entry.output, 0));
+        }
+    }
+
+    let mut audio = match format {
+        Some(xm::Format::Xm) if args.track_id.is_some() => {
+            run.decryptor.decrypt(&xm_info, &content[..])?
+        }
+        Some(xm::Format::Xm) => {
+            let candidates = xm::track_ids_in_name(&title);
+            let (audio, track_id) =
+                run.decryptor
+                    .decrypt_trying_track_ids(&xm_info, &content[..], &candidates)?;
+            if track_id != xm_info.tracknumber() {
+                info!(file = %file.display(), track_id, "decrypted with a track id from the file name");
+                xm_info.set_tracknumber(track_id);
+            }
+            audio
+        }
+        Some(scrambled) => xm::decrypt_scrambled(scrambled, &content)?,
+        None => content.clone(),
+    };
+    if args.verify {
+        let format = xm::validate_audio(&audio)
+            .map_err(|e| lang::format("the decrypted audio is damaged: {}", &[&e]))?;
+        debug!(file = %file.display(), ?format, "verified audio");
+    }
+    if args.fix_vbr_header && mp3::fix_vbr_header(&mut audio) {
+        debug!(file = %file.display(), "fixed vbr header");
+    }
+    let header = &audio[..audio.len().min(0xFF)];
+    let mut file_name = xm_info.file_name_or(&args.name_template, header, &title);
+    if args.interactive && xm_info.title().is_none_or(str::is_empty) {
+        match prompt::name_untitled(file, &file_name)? {
+            Some(name) => file_name = name,
+            None => {
+                info!(file = %file.display(), "skipped, no title");
+                return Ok((Status::Skipped, target_dir.join(file_name), 0));
+            }
+        }
+    }
+
+    // The template may contain directory separators.
+    let target_path = target_dir.join(file_name);
+    // Kept for saving as a file even where it isn't embedded.
+    let cover = xm_info.shared_cover().cloned();
+    if let (Some(cover), Some(mode)) = (&cover, args.cover_once) {
+        if !run.covers.embeds(&target_path, cover, mode) {
+            xm_info.set_cover(None);
+        }
+    }
+    if args.write_tags && xm_info.write_tags(&mut audio)? {
+        debug!(file = %file.display(), "tagged");
+    }
+
+    std::fs::create_dir_all(target_path.parent().expect("no parent dir"))?;
+    match write_output(&target_path, &audio, args.on_conflict, &run.names)? {
+        Some(written) => {
+            if !args.write_tags && xm_info.write_tag(&written, &audio)? {
+                debug!(output = %written.display(), "tagged");
+            }
+            if let Some(length) = mp3::duration(&audio) {
+                let gapless = mp3::gapless(&audio);
+                tags::write_timing(&written, length, gapless)?;
+                debug!(output = %written.display(), length_ms = length.as_millis() as u64, gapless = gapless.is_some(), "wrote length");
+            }
+            let save_cover = args.extract_cover || args.cover_once == Some(CoverOnce::FolderOnly);
+            if let Some(cover) = cover.filter(|_| save_cover) {
+                if let Some(cover_path) = tags::extract_cover(&written, &cover)? {
+                    debug!(output = %written.display(), cover = %cover_path.display(), "extracted cover");
+                }
+            }
+            if args.embed_source_tag {
+                let source_tag = xm_info.source_tag(&content);
+                if !tags::embed_source_tag(&written, &audio, file, source_tag)? {
+                    warn!(output = %written.display(), "can't hold the source tag");
+                }
+            }
+            if args.fix_tags {
+                if let Some((before, after)) = tags::fix_tags(&written, &xm_info, &audio)? {
+                    info!(output = %written.display(), before, after, "fixed tags");
+                }
+            }
+            for hook in hooks::run_hooks(&args.hooks, &written) {
+                match hook.succeeded() {
+                    true => {
+                        debug!(output = %written.display(), command = %hook.command, "ran hook")
+                    }
+                    false => {
+                        warn!(output = %written.display(), command = %hook.command, code = ?hook.code, output = %hook.output, "hook failed")
+                    }
+                }
+                hooks.push(hook);
+            }
+            // Last, since tagging and hooks change the modification time.
+            if args.preserve_times && !remote {
+                copy_times(file, &written)?;
+            }
+            run.syncer.written(&written)?;
+            if let Some((index, hash)) = indexed {
+                if let Err(e) = index.record_done(file, hash, &xm_info, &written) {
+                    error!(file = %file.display(), error = %e, "can't record in the index");
+                }
+            }
+            match xm_info.is_encrypted() {
+                true => info!(file = %file.display(), output = %written.display(), "decrypted"),
+                false => {
+                    info!(file = %file.display(), output = %written.display(), "copied, not encrypted")
+                }
+            }
+            Ok((Status::Ok, written, audio.len() as u64))
+        }
+        None => {
+            info!(file = %file.display(), output = %target_path.display(), "skipped, output already exists");
+            Ok((Status::Skipped, target_path, 0))
+        }
+    }
+}
+
+#[test]
+fn test_copy_short_plain_file() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_short_{}", std::process::id()));
+    let (input, output) = (root.join("in"), root.join("out"));
+    std::fs::create_dir_all(&input).unwrap();
+    // Plain audio shorter than the header the format is told by.
+    std::fs::write(input.join("1.xm"), b"fLaC\0\0\0\x22").unwrap();
+    let args = Args::parse_from(
+        [
+            "--copy-plain",
+            "--output-dir",
+            output.to_str().unwrap(),
+            input.to_str().unwrap(),
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let result = run_root(&args, &mut Vec::new());
+    let copied = std::fs::read(output.join("1.flac"));
+    std::fs::remove_dir_all(&root).unwrap();
+    result.unwrap();
+    assert_eq!(copied.unwrap(), b"fLaC\0\0\0\x22");
+}
+
+#[test]
+fn test_journal_in_output_root() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_journal_{}", std::process::id()));
+    let (input, output) = (root.join("in"), root.join("out"));
+    std::fs::create_dir_all(&input).unwrap();
+    let args = Args::parse_from(
+        [
+            "--output-dir",
+            output.to_str().unwrap(),
+            input.to_str().unwrap(),
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let result = run_root(&args, &mut Vec::new());
+    let in_output = output.join(".xm_decryptor_state.json").exists();
+    let in_input = input.join(".xm_decryptor_state.json").exists();
+    std::fs::remove_dir_all(&root).unwrap();
+    result.unwrap();
+    assert!(in_output);
+    assert!(!in_input);
+}
+
+#[test]
+fn test_track_id_single_file() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_track_id_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let args = Args::parse_from(["--track-id", "123", root.to_str().unwrap()].map(String::from));
+    let result = run_root(&args.unwrap(), &mut Vec::new());
+    std::fs::remove_dir_all(&root).unwrap();
+    assert!(result.is_err());
+}
@@ -0,0 +1,332 @@
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use xm_decryptor::naming::{numbered, CollisionState};
+use xm_decryptor::Result;
+
+use crate::args::{Durability, OnConflict, SourceAction};
+use crate::lang;
+use crate::prompt::{self, Resolution};
+
+/// Returns the directory the output for `file` is written to. Without an output directory this is
+/// the directory of the source file. Otherwise the source directory relative to `root` is
+/// recreated below `output_dir`, or the output goes directly into `output_dir` if there is no
+/// root.
+pub fn target_dir(file: &Path, root: Option<&Path>, output_dir: Option<&Path>) -> PathBuf {
+    let parent = file.parent().expect("no parent dir");
+    match (output_dir, root) {
+        (Some(output_dir), Some(root)) => {
+            output_dir.join(parent.strip_prefix(root).unwrap_or(Path::new("")))
+        }
+        (Some(output_dir), None) => output_dir.to_path_buf(),
+        (None, _) => parent.to_path_buf(),
+    }
+}
+
+/// Returns `path` in the form Windows accepts beyond the 260 character `MAX_PATH` limit, which long
+/// album titles easily exceed: absolute and with the `\\?\` prefix, or `\\?\UNC\` for a network
+/// share. Other platforms have no such limit and get `path` back as it is.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    // Verbatim paths are used as they are, so `..` and `/` have to be resolved first.
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return absolute;
+    };
+    let mut long = OsString::from(r"\\?\");
+    match prefix.kind() {
+        Prefix::Disk(_) => long.push(prefix.as_os_str()),
+        Prefix::UNC(server, share) => {
+            long.push(r"UNC\");
+            long.push(server);
+            long.push(r"\");
+            long.push(share);
+        }
+        // Already verbatim or a device path.
+        _ => return absolute,
+    }
+    long.push(components.as_path());
+    PathBuf::from(long)
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Tracks the names that exist in each output directory, including outputs written during this
+/// run. Each directory is listed when the first output is written to it.
+#[derive(Default)]
+pub struct OutputNames {
+    state: Mutex<CollisionState>,
+}
+
+impl OutputNames {
+    /// Claims `path` for writing. Returns the path of an existing or already claimed file whose
+    /// name collides with it, in which case nothing is claimed.
+    fn claim(&self, path: &Path) -> Result<Option<PathBuf>> {
+        let dir = path.parent().expect("no parent dir");
+        let mut state = self.state.lock().expect("output names poisoned");
+        if !state.knows_dir(dir) {
+            let files = fs::read_dir(dir)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            state.add_dir(dir, files);
+        }
+        Ok(state.claim(path))
+    }
+}
+
+/// Writes `data` to `path`, resolving a collision with an existing file or another output of this
+/// run according to `on_conflict`.
+///
+/// Returns the path that was written or `None` if the output was skipped. Except for
+/// [`OnConflict::Overwrite`] the file is created exclusively, so a file that another process
+/// created after the directory was listed is treated as a collision rather than overwritten.
+pub fn write_output(
+    path: &Path,
+    data: &[u8],
+    on_conflict: OnConflict,
+    names: &OutputNames,
+) -> Result<Option<PathBuf>> {
+    let mut candidate = path.to_path_buf();
+    for n in 1.. {
+        let existing = match names.claim(&candidate)? {
+            Some(existing) => existing,
+            None if on_conflict == OnConflict::Overwrite => {
+                fs::write(&candidate, data)?;
+                return Ok(Some(candidate));
+            }
+            None => match create_new(&candidate, data) {
+                Ok(()) => return Ok(Some(candidate)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => candidate.clone(),
+                Err(e) => return Err(e.into()),
+            },
+        };
+        match on_conflict {
+            OnConflict::Skip => return Ok(None),
+            OnConflict::Error => {
+                return Err(lang::format("{} already exists", &[&existing.display()]).into())
+            }
+            OnConflict::Overwrite => {
+                fs::write(&existing, data)?;
+                return Ok(Some(existing));
+            }
+            OnConflict::Rename => candidate = numbered(path, n),
+            OnConflict::Ask => match prompt::resolve_conflict(&candidate, &existing)? {
+                Resolution::Overwrite => {
+                    fs::write(&existing, data)?;
+                    return Ok(Some(existing));
+                }
+                Resolution::Rename => candidate = numbered(path, n),
+                Resolution::Skip => return Ok(None),
+                Resolution::Name(name) => candidate = path.with_file_name(name),
+            },
+        }
+    }
+    unreachable!()
+}
+
+/// Writes `data` to `path`, failing with `AlreadyExists` if the file exists.
+fn create_new(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(data)
+}
+
+/// The number of outputs after which a batch is synced.
+const BATCH_FILES: usize = 32;
+/// The time after which a batch is synced, however few outputs it holds.
+const BATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Syncs written outputs to disk according to the configured durability.
+///
+/// In batched mode the worker that fills a batch syncs it before writing anything else, so a
+/// slow disk holds back the writers instead of piling up unsynced data.
+pub struct Syncer {
+    durability: Durability,
+    batch: Mutex<Batch>,
+}
+
+struct Batch {
+    files: Vec<PathBuf>,
+    started: Instant,
+}
+
+impl Syncer {
+    pub fn new(durability: Durability) -> Syncer {
+        Syncer {
+            durability,
+            batch: Mutex::new(Batch {
+                files: Vec::new(),
+                started: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records that `path` has been written and syncs it if the durability setting requires it.
+    pub fn written(&self, path: &Path) -> Result<()> {
+        match self.durability {
+            Durability::None => Ok(()),
+            Durability::PerFile => sync_files(&[path.to_path_buf()]),
+            Durability::Batched => {
+                let mut batch = self.batch.lock().expect("sync batch poisoned");
+                if batch.files.is_empty() {
+                    batch.started = Instant::now();
+                }
+                batch.files.push(path.to_path_buf());
+                if batch.files.len() >= BATCH_FILES || batch.started.elapsed() >= BATCH_INTERVAL {
+                    sync_files(&std::mem::take(&mut batch.files))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Syncs the outputs that are still waiting in the current batch.
+    pub fn finish(&self) -> Result<()> {
+        let mut batch = self.batch.lock().expect("sync batch poisoned");
+        sync_files(&std::mem::take(&mut batch.files))
+    }
+}
+
+/// Syncs the contents of `files`, then the directories that contain them so that the new entries
+/// survive a crash as well.
+fn sync_files(files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        fs::File::open(file)?.sync_all()?;
+    }
+    // Directories can't be opened for syncing on Windows, where the entries are made durable
+    // together with the file.
+    if cfg!(unix) {
+        let dirs: BTreeSet<_> = files.iter().filter_map(|f| f.parent()).collect();
+        for dir in dirs {
+            fs::File::open(dir)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// Gives `target` the modification time of `source`, and on Windows also its creation time.
+pub fn copy_times(source: &Path, target: &Path) -> Result<()> {
+    let metadata = fs::metadata(source)?;
+    let times = fs::FileTimes::new().set_modified(metadata.modified()?);
+    #[cfg(windows)]
+    let times = {
+        use std::os::windows::fs::FileTimesExt;
+        times.set_created(metadata.created()?)
+    };
+    fs::OpenOptions::new()
+        .write(true)
+        .open(target)?
+        .set_times(times)?;
+    Ok(())
+}
+
+/// Deletes or archives the source `file` according to `action` once its output `written` is
+/// confirmed to hold at least the `size` decrypted bytes. The output is synced first, so a crash
+/// can't lose both. Archived files keep their directory relative to `root`, like the outputs.
+///
+/// Returns where the source was moved to, if it was archived.
+pub fn dispose_source(
+    file: &Path,
+    written: &Path,
+    size: u64,
+    root: Option<&Path>,
+    action: &SourceAction,
+) -> Result<Option<PathBuf>> {
+    if *action == SourceAction::Keep {
+        return Ok(None);
+    }
+    let output_size = fs::metadata(written)?.len();
+    if size == 0 || output_size < size {
+        return Err(format!(
+            "output has {} bytes, expected at least {}, keeping the source",
+            output_size, size
+        )
+        .into());
+    }
+    sync_files(&[written.to_path_buf()])?;
+    let SourceAction::Archive(archive_dir) = action else {
+        fs::remove_file(file)?;
+        return Ok(None);
+    };
+    let dir = long_path(&target_dir(file, root, Some(archive_dir)));
+    fs::create_dir_all(&dir)?;
+    let mut archived = dir.join(file.file_name().expect("no file name"));
+    for n in 1.. {
+        if !archived.exists() {
+            break;
+        }
+        archived = numbered(&dir.join(file.file_name().expect("no file name")), n);
+    }
+    // Renaming fails across file systems, where the file has to be copied instead.
+    if fs::rename(file, &archived).is_err() {
+        fs::copy(file, &archived)?;
+        sync_files(&[archived.clone()])?;
+        fs::remove_file(file)?;
+    }
+    Ok(Some(archived))
+}
+
+#[test]
+fn test_dispose_source() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_dispose_{}", std::process::id()));
+    let archive_dir = root.join("archive");
+    fs::create_dir_all(root.join("in/a")).unwrap();
+    let (file, written) = (root.join("in/a/1.xm"), root.join("in/a/1.m4a"));
+    fs::write(&file, b"xm").unwrap();
+    fs::write(&written, b"audio").unwrap();
+    let action = SourceAction::Archive(archive_dir.clone());
+
+    let too_short = dispose_source(&file, &written, 6, Some(&root.join("in")), &action);
+    let archived = dispose_source(&file, &written, 5, Some(&root.join("in")), &action);
+    let source_left = file.exists();
+    fs::remove_dir_all(&root).unwrap();
+    assert!(too_short.is_err());
+    assert_eq!(archived.unwrap(), Some(archive_dir.join("a/1.xm")));
+    assert!(!source_left);
+}
+
+#[test]
+fn test_write_output_created_elsewhere() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_output_{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let names = OutputNames::default();
+    // Lists the directory before another process creates the file.
+    let first = write_output(&root.join("a.m4a"), b"a", OnConflict::Skip, &names);
+    fs::write(root.join("b.m4a"), b"other").unwrap();
+    let skipped = write_output(&root.join("b.m4a"), b"b", OnConflict::Skip, &names);
+    let renamed = write_output(&root.join("b.m4a"), b"b", OnConflict::Rename, &names);
+    let error = write_output(&root.join("b.m4a"), b"b", OnConflict::Error, &names);
+    let kept = fs::read(root.join("b.m4a"));
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(first.unwrap(), Some(root.join("a.m4a")));
+    assert_eq!(skipped.unwrap(), None);
+    assert_eq!(renamed.unwrap(), Some(root.join("b (1).m4a")));
+    assert!(error.is_err());
+    assert_eq!(kept.unwrap(), b"other");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_long_path() {
+    assert_eq!(
+        long_path(Path::new(r"C:\a\..\b/c.m4a")),
+        Path::new(r"\\?\C:\b\c.m4a")
+    );
+    assert_eq!(
+        long_path(Path::new(r"\\server\share\a")),
+        Path::new(r"\\?\UNC\server\share\a")
+    );
+    assert_eq!(long_path(Path::new(r"\\?\C:\a")), Path::new(r"\\?\C:\a"));
+}
@@ -0,0 +1,201 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::{lang, tui};
+
+/// The shortest time between two redraws of the progress line.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The width of the progress line when `COLUMNS` doesn't tell the terminal width.
+const DEFAULT_WIDTH: usize = 80;
+
+/// The width of the progress line on screen, 0 if none is shown. Log lines erase it first.
+static DRAWN_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// A progress line on stderr showing the files done, the throughput, the ETA and the file being
+/// decrypted.
+///
+/// The total grows while the input directory is still being walked, the ETA is only shown once
+/// the walk has finished.
+pub struct Progress {
+    start: Instant,
+    state: Mutex<State>,
+}
+
+struct State {
+    enabled: bool,
+    /// Set once every input has been found.
+    total_known: bool,
+    total: usize,
+    done: usize,
+    bytes: u64,
+    current: String,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    /// Creates a progress line that is only drawn if `enabled`.
+    pub fn new(enabled: bool) -> Progress {
+        Progress {
+            start: Instant::now(),
+            state: Mutex::new(State {
+                enabled,
+                total_known: false,
+                total: 0,
+                done: 0,
+                bytes: 0,
+                current: String::new(),
+                last_draw: None,
+            }),
+        }
+    }
+
+    /// Counts another input.
+    pub fn found(&self) {
+        self.update(|state| state.total += 1);
+    }
+
+    /// Marks the total as final.
+    pub fn found_all(&self) {
+        self.update(|state| state.total_known = true);
+    }
+
+    /// Shows `file` as being decrypted.
+    pub fn started(&self, file: &Path) {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        self.update(|state| state.current = name.into_owned());
+    }
+
+    /// Counts a processed file that wrote `bytes`.
+    pub fn finished(&self, bytes: u64) {
+        self.update(|state| {
+            state.done += 1;
+            state.bytes += bytes;
+        });
+    }
+
+    /// Erases the progress line and stops drawing it.
+    pub fn finish(&self) {
+        self.state.lock().expect("progress poisoned").enabled = false;
+        erase(&mut std::io::stderr().lock());
+    }
+
+    fn update(&self, change: impl FnOnce(&mut State)) {
+        let mut state = self.state.lock().expect("progress poisoned");
+        change(&mut state);
+        if !state.enabled
+            || state
+                .last_draw
+                .is_some_and(|t| t.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        state.last_draw = Some(Instant::now());
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        // The last column is left free so the cursor doesn't wrap.
+        let line = state.line(self.start.elapsed(), width.saturating_sub(1));
+        let mut stderr = std::io::stderr().lock();
+        erase(&mut stderr);
+        let _ = write!(stderr, "{}", line);
+        let _ = stderr.flush();
+        DRAWN_WIDTH.store(line_width(&line), Ordering::Relaxed);
+    }
+}
+
+impl State {
+    /// Returns the progress line, at most `width` columns wide.
+    fn line(&self, elapsed: Duration, width: usize) -> String {
+        let seconds = elapsed.as_secs_f64();
+        let throughput = match seconds > 0.0 {
+            true => self.bytes as f64 / 1_000_000.0 / seconds,
+            false => 0.0,
+        };
+        let eta = match (self.total_known, self.done) {
+            (true, done) if done > 0 => {
+                let left = elapsed.mul_f64((self.total - done) as f64 / done as f64);
+                let secs = left.as_secs();
+                format!("{:02}:{:02}", secs / 60, secs % 60)
+            }
+            _ => "--:--".to_string(),
+        };
+        let total = match self.total_known {
+            true => self.total.to_string(),
+            false => format!("{}+", self.total),
+        };
+        let mut line = format!(
+            "[{}/{}] {:.1} MB/s {} {} ",
+            self.done,
+            total,
+            throughput,
+            lang::tr("ETA"),
+            eta
+        );
+        let room = width.saturating_sub(line_width(&line));
+        line.push_str(&truncate(&self.current, room));
+        line
+    }
+}
+
+/// Writes a log line to stderr, erasing the progress line first. The progress line comes back
+/// with the next update. While the TUI is open it takes the line instead.
+pub fn write_log(line: &str) {
+    if tui::log(line) {
+        return;
+    }
+    let mut stderr = std::io::stderr().lock();
+    erase(&mut stderr);
+    // Logging must not fail the run, a closed stderr only loses the log.
+    let _ = stderr.write_all(line.as_bytes());
+}
+
+/// Overwrites the progress line with spaces, if one is shown. Plain spaces work on every
+/// terminal, unlike escape sequences on older Windows consoles.
+fn erase(stderr: &mut impl Write) {
+    let width = DRAWN_WIDTH.swap(0, Ordering::Relaxed);
+    if width > 0 {
+        let _ = write!(stderr, "\r{:width$}\r", "", width = width);
+    }
+}
+
+/// Returns the number of terminal columns `s` takes, counting CJK characters as two.
+pub fn line_width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// Returns the start of `s` that fits into `width` columns, ending in `…` if it was cut.
+pub fn truncate(s: &str, width: usize) -> String {
+    if line_width(s) <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        // Room is kept for the ellipsis, which is one column wide.
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("第1集.xm", 10), "第1集.xm");
+    assert_eq!(truncate("第1集 开始.xm", 8), "第1集 …");
+    assert_eq!(truncate("第1集", 2), "…");
+    assert_eq!(truncate("abc", 0), "");
+}
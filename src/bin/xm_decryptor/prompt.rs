@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use xm_decryptor::naming::sanitize;
+use xm_decryptor::Result;
+
+use crate::{lang, progress};
+
+/// Held while a question is on screen, so the workers ask one at a time.
+static PROMPT: Mutex<()> = Mutex::new(());
+
+/// How to resolve a collision with an existing output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Overwrite,
+    Rename,
+    Skip,
+    /// Write the output under this file name instead.
+    Name(String),
+}
+
+/// Asks how to resolve the collision of the output `path` with `existing`.
+pub fn resolve_conflict(path: &Path, existing: &Path) -> Result<Resolution> {
+    let _prompt = PROMPT.lock().expect("prompt poisoned");
+    let question = lang::format(
+        "{} collides with {}. (o)verwrite, (r)ename, (s)kip or (e)dit the name? ",
+        &[&path.display(), &existing.display()],
+    );
+    Ok(match choose(&question, "orse")? {
+        'o' => Resolution::Overwrite,
+        'r' => Resolution::Rename,
+        's' => Resolution::Skip,
+        _ => Resolution::Name(ask_name(path)?),
+    })
+}
+
+/// Asks what to call the output of `file`, whose xm info has no title, instead of `name`.
+/// Returns `None` if the file should be skipped.
+pub fn name_untitled(file: &Path, name: &str) -> Result<Option<String>> {
+    let _prompt = PROMPT.lock().expect("prompt poisoned");
+    let question = lang::format(
+        "{} has no title and would be written as {}. (k)eep the name, (e)dit it or (s)kip? ",
+        &[&file.display(), &name],
+    );
+    Ok(match choose(&question, "kes")? {
+        'k' => Some(name.to_string()),
+        'e' => Some(ask_name(Path::new(name))?),
+        _ => None,
+    })
+}
+
+/// Asks for a new file name for `path`. Characters not allowed in file names are removed and the
+/// extension of `path` is added if the answer has none.
+fn ask_name(path: &Path) -> Result<String> {
+    loop {
+        let name = sanitize(ask(lang::tr("new file name: "))?.trim());
+        if name.is_empty() {
+            continue;
+        }
+        return Ok(match (Path::new(&name).extension(), path.extension()) {
+            (None, Some(ext)) => format!("{}.{}", name, ext.to_string_lossy()),
+            _ => name,
+        });
+    }
+}
+
+/// Asks `question` until the answer starts with one of the letters in `keys` and returns it.
+fn choose(question: &str, keys: &str) -> Result<char> {
+    loop {
+        let answer = ask(question)?.trim().to_lowercase();
+        if let Some(key) = answer.chars().next().filter(|c| keys.contains(*c)) {
+            return Ok(key);
+        }
+    }
+}
+
+/// Writes `question` to stderr and returns the line typed in reply.
+fn ask(question: &str) -> Result<String> {
+    progress::write_log(question);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Err(lang::tr("no answer, stdin is closed").into());
+    }
+    Ok(answer)
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use xm_decryptor::Result;
+
+/// Returns true if the input `path` is an `http://` or `https://` URL rather than a file.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Reads the input `file` into memory, fetching it if it is a URL.
+pub fn read(file: &Path) -> Result<Vec<u8>> {
+    match file.to_str().filter(|_| is_url(file)) {
+        Some(url) => fetch(url),
+        None => Ok(std::fs::read(file)?),
+    }
+}
+
+#[cfg(feature = "http")]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut content = Vec::new();
+    xm_decryptor::http::open(url)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    Err(crate::lang::format(
+        "{} is a URL, which needs a build with the http feature",
+        &[&url],
+    )
+    .into())
+}
+
+#[test]
+fn test_is_url() {
+    assert!(is_url(Path::new("http://nas/a.xm")));
+    assert!(is_url(Path::new("https://nas/a.xm?token=1")));
+    assert!(!is_url(Path::new("http/a.xm")));
+}
@@ -0,0 +1,349 @@
+use std::fmt::{self, Write as _};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use xm_decryptor::json::json_string;
+use xm_decryptor::{collate, Result};
+
+use crate::hooks::HookRun;
+use crate::lang::{self, Lang};
+
+/// The outcome of processing one input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Skipped,
+    /// Skipped, the input is already plain audio.
+    NotEncrypted,
+    Error,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Skipped => "skipped",
+            Status::NotEncrypted => "not_encrypted",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// What happened to one input file during a batch run.
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub source: PathBuf,
+    pub target: Option<PathBuf>,
+    pub status: Status,
+    pub error: Option<String>,
+    /// Bytes written to the target.
+    pub size: u64,
+    pub elapsed: Duration,
+    /// The commands run on the target by `--hook`.
+    pub hooks: Vec<HookRun>,
+}
+
+/// The totals of a batch run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// Bytes written to the targets.
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    /// Adds up `reports` of a run that took `elapsed`.
+    pub fn of(reports: &[FileReport], elapsed: Duration) -> Summary {
+        let count = |status| reports.iter().filter(|r| r.status == status).count();
+        Summary {
+            succeeded: count(Status::Ok),
+            skipped: count(Status::Skipped) + count(Status::NotEncrypted),
+            failed: count(Status::Error),
+            bytes: reports.iter().map(|r| r.size).sum(),
+            elapsed,
+        }
+    }
+
+    /// The number of files in the run.
+    pub fn total(&self) -> usize {
+        self.succeeded + self.skipped + self.failed
+    }
+}
+
+impl Summary {
+    /// Returns the summary as a sentence in `lang`.
+    pub fn describe(&self, lang: Lang) -> String {
+        let megabytes = self.bytes as f64 / 1_000_000.0;
+        let seconds = self.elapsed.as_secs_f64();
+        let mut text = lang::format_in(
+            lang,
+            "{} succeeded, {} skipped, {} failed, {} MB in {} s",
+            &[
+                &self.succeeded,
+                &self.skipped,
+                &self.failed,
+                &format!("{:.1}", megabytes),
+                &format!("{:.1}", seconds),
+            ],
+        );
+        if seconds > 0.0 {
+            let _ = write!(text, " ({:.1} MB/s)", megabytes / seconds);
+        }
+        text
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(lang::current()))
+    }
+}
+
+/// The version of the report format, raised whenever a field is renamed, removed or changes its
+/// meaning. Adding a field keeps the version.
+pub const REPORT_VERSION: u32 = 1;
+
+/// The JSON Schema of the report, printed by `--report-schema`.
+pub const REPORT_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/jupitergao18/xm_decryptor/report/v1",
+  "title": "xm_decryptor report",
+  "type": "object",
+  "required": ["version", "files"],
+  "properties": {
+    "version": {"const": 1},
+    "files": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["source", "target", "status", "error", "size", "elapsed_ms", "hooks"],
+        "properties": {
+          "source": {"type": "string", "description": "The xm file"},
+          "target": {"type": ["string", "null"], "description": "The output, null if none was written"},
+          "status": {"enum": ["ok", "skipped", "not_encrypted", "error"]},
+          "error": {"type": ["string", "null"]},
+          "size": {"type": "integer", "minimum": 0, "description": "Bytes written to the target"},
+          "elapsed_ms": {"type": "integer", "minimum": 0},
+          "hooks": {
+            "type": "array",
+            "items": {
+              "type": "object",
+              "required": ["command", "code", "output"],
+              "properties": {
+                "command": {"type": "string"},
+                "code": {"type": ["integer", "null"], "description": "null if the command couldn't be started or was killed"},
+                "output": {"type": "string", "description": "stdout and stderr of the command"}
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Writes the reports as a JSON document of the form `{"version": 1, "files": [...]}`, as
+/// described by [`REPORT_SCHEMA`]. The files are sorted by source path, not in the order they were
+/// done in, so the reports of two runs can be compared.
+pub fn write_report(path: &Path, reports: &[FileReport]) -> Result<()> {
+    let mut reports: Vec<_> = reports.iter().collect();
+    reports.sort_by(|a, b| collate::compare_paths(&a.source, &b.source));
+    let mut json = format!("{{\n  \"version\": {},\n  \"files\": [", REPORT_VERSION);
+    for (i, report) in reports.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(
+            json,
+            "\n    {{\"source\": {}, \"target\": {}, \"status\": \"{}\", \"error\": {}, \
+             \"size\": {}, \"elapsed_ms\": {}, \"hooks\": [{}]}}",
+            json_string(&report.source.to_string_lossy()),
+            report
+                .target
+                .as_ref()
+                .map(|t| json_string(&t.to_string_lossy()))
+                .unwrap_or_else(|| "null".to_string()),
+            report.status.as_str(),
+            report
+                .error
+                .as_deref()
+                .map(json_string)
+                .unwrap_or_else(|| "null".to_string()),
+            report.size,
+            report.elapsed.as_millis(),
+            report
+                .hooks
+                .iter()
+                .map(|hook| format!(
+                    "{{\"command\": {}, \"code\": {}, \"output\": {}}}",
+                    json_string(&hook.command),
+                    hook.code
+                        .map(|code| code.to_string())
+                        .unwrap_or_else(|| "null".to_string()),
+                    json_string(&hook.output)
+                ))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )?;
+    }
+    json.push_str("\n  ]\n}\n");
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// The name of the run history, kept in the output root.
+const HISTORY_FILE: &str = ".xm_decryptor_history.jsonl";
+
+/// Appends a line for a run to the history in `dir`: when it finished, the version, the
+/// command-line `args`, the `inputs` and the totals of `summary`.
+pub fn append_history(dir: &Path, args: &[String], inputs: &str, summary: &Summary) -> Result<()> {
+    let args: Vec<String> = args.iter().map(|arg| json_string(arg)).collect();
+    let line = format!(
+        "{{\"time\": \"{}\", \"version\": \"{}\", \"args\": [{}], \"inputs\": {}, \
+         \"succeeded\": {}, \"skipped\": {}, \"failed\": {}, \"bytes\": {}, \"elapsed_ms\": {}}}\n",
+        utc_timestamp(SystemTime::now()),
+        env!("CARGO_PKG_VERSION"),
+        args.join(", "),
+        json_string(inputs),
+        summary.succeeded,
+        summary.skipped,
+        summary.failed,
+        summary.bytes,
+        summary.elapsed.as_millis(),
+    );
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(HISTORY_FILE))?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Returns `time` as an RFC 3339 timestamp in UTC, to the second.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    // Converts days since 1970-01-01 to a date, counting in 400 year eras from 0000-03-01.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+#[test]
+fn test_summary() {
+    let report = |status, size| FileReport {
+        source: PathBuf::from("a.xm"),
+        target: None,
+        status,
+        error: None,
+        size,
+        elapsed: Duration::ZERO,
+        hooks: Vec::new(),
+    };
+    let reports = [
+        report(Status::Ok, 3_000_000),
+        report(Status::Ok, 1_000_000),
+        report(Status::Skipped, 0),
+        report(Status::NotEncrypted, 0),
+        report(Status::Error, 0),
+    ];
+    let summary = Summary::of(&reports, Duration::from_secs(2));
+    assert_eq!(
+        summary.describe(Lang::En),
+        "2 succeeded, 2 skipped, 1 failed, 4.0 MB in 2.0 s (2.0 MB/s)"
+    );
+    assert_eq!(
+        summary.describe(Lang::Zh),
+        "成功 2，跳过 2，失败 1，共 4.0 MB，用时 2.0 秒 (2.0 MB/s)"
+    );
+}
+
+#[test]
+fn test_report_schema() {
+    let path = std::env::temp_dir().join(format!("xm_decryptor_report_{}", std::process::id()));
+    let report = FileReport {
+        source: PathBuf::from("a.xm"),
+        target: Some(PathBuf::from("a.mp3")),
+        status: Status::Ok,
+        error: None,
+        size: 1,
+        elapsed: Duration::ZERO,
+        hooks: vec![HookRun {
+            command: "true a.mp3".to_string(),
+            code: Some(0),
+            output: String::new(),
+        }],
+    };
+    write_report(&path, &[report]).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(REPORT_SCHEMA.contains(&format!("\"version\": {{\"const\": {}}}", REPORT_VERSION)));
+    // Every key of the report is described by the schema.
+    for key in json.split('"').skip(1).step_by(2) {
+        if json.contains(&format!("\"{}\":", key)) {
+            assert!(
+                REPORT_SCHEMA.contains(&format!("\"{}\": {{", key)),
+                "{}",
+                key
+            );
+        }
+    }
+}
+
+#[test]
+fn test_report_order() {
+    let path =
+        std::env::temp_dir().join(format!("xm_decryptor_report_order_{}", std::process::id()));
+    let report = |source: &str| FileReport {
+        source: PathBuf::from(source),
+        target: None,
+        status: Status::Error,
+        error: None,
+        size: 0,
+        elapsed: Duration::ZERO,
+        hooks: Vec::new(),
+    };
+    write_report(&path, &[report("b/10.xm"), report("a"), report("b/2.xm")]).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let position = |source| json.find(&format!("\"source\": \"{}\"", source)).unwrap();
+    assert!(position("a") < position("b/2.xm"));
+    assert!(position("b/2.xm") < position("b/10.xm"));
+}
+
+#[test]
+fn test_utc_timestamp() {
+    let time = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+    assert_eq!(utc_timestamp(time(0)), "1970-01-01T00:00:00Z");
+    assert_eq!(utc_timestamp(time(951_782_400)), "2000-02-29T00:00:00Z");
+    assert_eq!(utc_timestamp(time(1_792_146_245)), "2026-10-16T10:24:05Z");
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use tracing::error;
+use xm_decryptor::{collate, xm, Result};
+
+use crate::control;
+use crate::filter::Filters;
+
+/// The number of directories read at the same time. Reading a directory mostly waits on the
+/// file system, so this is worth more than the number of cores on network storage.
+const SCAN_THREADS: usize = 8;
+
+/// The settings of a directory scan.
+pub struct Scan<'a> {
+    pub root: &'a Path,
+    pub max_depth: Option<usize>,
+    pub filters: &'a Filters,
+    /// A directory below the root that is not walked, the output directory if it is inside the
+    /// input directory, so outputs are never taken as inputs. See [`nested_dir`].
+    pub skip_dir: Option<PathBuf>,
+}
+
+/// Returns `dir` as a path below `root` if it is inside it, without being `root` itself. Both are
+/// compared as absolute paths with links resolved, `dir` need not exist yet.
+pub fn nested_dir(root: &Path, dir: &Path) -> Option<PathBuf> {
+    let absolute_root = std::fs::canonicalize(root).ok()?;
+    let absolute_dir = std::fs::canonicalize(dir)
+        .or_else(|_| std::path::absolute(dir))
+        .ok()?;
+    match absolute_dir.strip_prefix(absolute_root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => Some(root.join(relative)),
+        _ => None,
+    }
+}
+
+/// The directories still to be read by a [`Scan::walk`].
+struct Pending {
+    /// Directories with their depth, taken from the end.
+    dirs: Vec<(PathBuf, usize)>,
+    /// The number of directories being read.
+    reading: usize,
+    /// The directories that couldn't be read, with their errors.
+    unreadable: Vec<(PathBuf, String)>,
+}
+
+impl Scan<'_> {
+    /// Walks the root with several threads and passes every file to `found` as soon as it is
+    /// seen, so the files can be processed while the walk goes on. The order is roughly that of
+    /// the names. A directory that can't be read is logged and skipped, the walk goes on with the
+    /// others. The walk ends early once `found` returns false or the run is asked to stop.
+    /// Returns the skipped directories with their errors, sorted by path.
+    pub fn walk(&self, found: impl Fn(PathBuf) -> bool + Sync) -> Vec<(PathBuf, String)> {
+        let pending = Mutex::new(Pending {
+            dirs: vec![(self.root.to_path_buf(), 1)],
+            reading: 0,
+            unreadable: Vec::new(),
+        });
+        let changed = Condvar::new();
+        let ended = AtomicBool::new(false);
+        let found = |file| {
+            if !found(file) {
+                ended.store(true, Ordering::SeqCst);
+            }
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..SCAN_THREADS {
+                scope.spawn(|| self.walk_dirs(&pending, &changed, &ended, &found));
+            }
+        });
+        let mut unreadable = pending.into_inner().expect("scan poisoned").unreadable;
+        unreadable.sort_by(|(a, _), (b, _)| collate::compare_paths(a, b));
+        unreadable
+    }
+
+    /// Collects the files below the root, sorted by path. Directories that can't be read are only
+    /// logged.
+    pub fn collect_files(&self) -> Vec<PathBuf> {
+        let files = Mutex::new(Vec::new());
+        self.walk(|file| {
+            files.lock().expect("scan poisoned").push(file);
+            true
+        });
+        let mut files = files.into_inner().expect("scan poisoned");
+        files.sort_by(|a, b| collate::compare_paths(a, b));
+        files
+    }
+
+    /// Reads pending directories until there are none left and no other thread can add more, or
+    /// until the walk has `ended` or the run is asked to stop.
+    fn walk_dirs(
+        &self,
+        pending: &Mutex<Pending>,
+        changed: &Condvar,
+        ended: &AtomicBool,
+        found: &impl Fn(PathBuf),
+    ) {
+        loop {
+            let (dir, depth) = {
+                let mut pending = pending.lock().expect("scan poisoned");
+                loop {
+                    if ended.load(Ordering::SeqCst) || control::stopping() {
+                        return;
+                    }
+                    if let Some(next) = pending.dirs.pop() {
+                        pending.reading += 1;
+                        break next;
+                    }
+                    if pending.reading == 0 {
+                        return;
+                    }
+                    pending = changed.wait(pending).expect("scan poisoned");
+                }
+            };
+            let result = self.read_dir(&dir, depth, found);
+            let mut pending = pending.lock().expect("scan poisoned");
+            pending.reading -= 1;
+            match result {
+                // Reversed so the first subdirectory is taken next.
+                Ok(subdirs) => pending
+                    .dirs
+                    .extend(subdirs.into_iter().rev().map(|d| (d, depth + 1))),
+                Err(e) => {
+                    error!(dir = %dir.display(), error = %e, "can't read the directory");
+                    pending.unreadable.push((dir, e.to_string()));
+                }
+            }
+            changed.notify_all();
+        }
+    }
+
+    /// Passes the files in `dir` to `found` and returns its subdirectories, unless the maximum
+    /// depth is exceeded. Entries directly inside the root are at depth 1. Excluded entries are
+    /// skipped without descending into them.
+    fn read_dir(&self, dir: &Path, depth: usize, found: &impl Fn(PathBuf)) -> Result<Vec<PathBuf>> {
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return Ok(Vec::new());
+        }
+        let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| {
+            collate::compare(
+                &a.file_name().to_string_lossy(),
+                &b.file_name().to_string_lossy(),
+            )
+        });
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let relative = path.strip_prefix(self.root).unwrap_or(&path);
+            if self.filters.excludes(relative) {
+                continue;
+            }
+            // The entry type comes with the listing, only symlinks need another lookup.
+            let Ok(mut file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_symlink() {
+                match std::fs::metadata(&path) {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    Err(_) => continue,
+                }
+            }
+            if file_type.is_file() {
+                if self.filters.includes(relative) {
+                    found(path);
+                }
+            } else if file_type.is_dir() && self.skip_dir.as_ref() != Some(&path) {
+                subdirs.push(path);
+            }
+        }
+        Ok(subdirs)
+    }
+}
+
+/// Reads the paths listed in `list`, one per line, or from stdin if `list` is `-`. Empty lines are
+/// ignored.
+pub fn read_file_list(list: &Path) -> Result<Vec<PathBuf>> {
+    let content = match list == Path::new("-") {
+        true => std::io::read_to_string(std::io::stdin())?,
+        false => std::fs::read_to_string(list)?,
+    };
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Groups `files` by album, each group sorted by track number. Groups are in the order their
+/// first file appears in `files`. Files without an album are grouped by directory, and files
+/// whose xm info can't be read form groups of their own, so their errors are reported as usual.
+pub fn group_by_album(files: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<(u64, PathBuf)>> = Vec::new();
+    let mut albums = HashMap::new();
+    for file in files {
+        let info = File::open(&file).map_err(Into::into).and_then(|f| {
+            // Only the tag is read, not the audio.
+            xm::extract_xm_info(BufReader::new(f))
+        });
+        let Ok(info) = info else {
+            groups.push(vec![(0, file)]);
+            continue;
+        };
+        let key = match info.album() {
+            Some(album) => album.to_string(),
+            None => format!("{}", file.parent().unwrap_or(Path::new("")).display()),
+        };
+        let group = *albums.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push((info.tracknumber(), file));
+    }
+    groups
+        .into_iter()
+        .map(|mut group| {
+            group.sort_by(|(track_a, a), (track_b, b)| {
+                track_a
+                    .cmp(track_b)
+                    .then_with(|| collate::compare_paths(a, b))
+            });
+            group.into_iter().map(|(_, file)| file).collect()
+        })
+        .collect()
+}
+
+/// Returns true if `path` has the `.xm`, `.x2m` or `.x3m` extension.
+pub fn is_xm(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("xm" | "x2m" | "x3m")
+    )
+}
+
+#[test]
+fn test_collect_files() {
+    let root = std::env::temp_dir().join(format!("xm_decryptor_scan_{}", std::process::id()));
+    for dir in ["a/b/c", "d", "skip", "out"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    for file in [
+        "1.xm",
+        "a/2.xm",
+        "a/b/3.xm",
+        "a/b/c/4.xm",
+        "d/5.xm",
+        "skip/6.xm",
+        "out/7.xm",
+    ] {
+        std::fs::write(root.join(file), b"").unwrap();
+    }
+    let filters = Filters {
+        include: Vec::new(),
+        exclude: vec!["skip".parse().unwrap()],
+    };
+    let scan = Scan {
+        root: &root,
+        max_depth: Some(3),
+        filters: &filters,
+        skip_dir: nested_dir(&root, &root.join("a/../out")),
+    };
+    assert_eq!(nested_dir(&root, &root), None);
+    assert_eq!(nested_dir(&root.join("a"), &root), None);
+    let files = scan.collect_files();
+    std::fs::remove_dir_all(&root).unwrap();
+    let expected: Vec<_> = ["1.xm", "a/2.xm", "a/b/3.xm", "d/5.xm"]
+        .iter()
+        .map(|file| root.join(file))
+        .collect();
+    assert_eq!(files, expected);
+}
+
+#[test]
+fn test_walk_unreadable() {
+    let root = std::env::temp_dir().join(format!(
+        "xm_decryptor_scan_unreadable_{}",
+        std::process::id()
+    ));
+    let filters = Filters {
+        include: Vec::new(),
+        exclude: Vec::new(),
+    };
+    let scan = Scan {
+        root: &root,
+        max_depth: None,
+        filters: &filters,
+        skip_dir: None,
+    };
+    let unreadable = scan.walk(|_| true);
+    assert_eq!(unreadable.len(), 1);
+    assert_eq!(unreadable[0].0, root);
+
+    for dir in ["a", "locked", "z"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+        std::fs::write(root.join(dir).join("1.xm"), b"").unwrap();
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let locked = root.join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+    }
+    // Root may read the locked directory anyway.
+    let locked = std::fs::read_dir(root.join("locked")).is_err();
+    let files = Mutex::new(Vec::new());
+    let unreadable = scan.walk(|file| {
+        files.lock().unwrap().push(file);
+        true
+    });
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let locked = root.join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+    let mut files = files.into_inner().unwrap();
+    files.sort_by(|a, b| collate::compare_paths(a, b));
+    assert_eq!(files.first(), Some(&root.join("a/1.xm")));
+    assert_eq!(files.last(), Some(&root.join("z/1.xm")));
+    match locked {
+        true => assert_eq!(
+            unreadable.iter().map(|(dir, _)| dir).collect::<Vec<_>>(),
+            [&root.join("locked")]
+        ),
+        false => assert!(unreadable.is_empty()),
+    }
+}
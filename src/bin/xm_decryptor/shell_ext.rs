@@ -0,0 +1,88 @@
+use std::process::{Command, Stdio};
+
+use tracing::info;
+use xm_decryptor::Result;
+
+use crate::lang;
+
+/// The registry keys of the context menu entries, below `HKCU\Software\Classes`, and the
+/// placeholder Explorer replaces with the clicked file or folder.
+const ENTRIES: [(&str, &str); 3] = [
+    (r"SystemFileAssociations\.xm\shell\xm_decryptor", "%1"),
+    (r"Directory\shell\xm_decryptor", "%1"),
+    (r"Directory\Background\shell\xm_decryptor", "%V"),
+];
+
+/// Adds a "Decrypt .xm here" entry to the Explorer context menu of .xm files, of folders and of
+/// the background of folders, for the current user.
+///
+/// The entry decrypts next to the inputs, numbers the names of outputs that already exist and
+/// keeps the console open until a key is pressed, so the summary can be read.
+pub fn install() -> Result<()> {
+    require_windows("install-shell-ext")?;
+    let exe = std::env::current_exe()?;
+    let label = lang::tr("Decrypt .xm here");
+    for (key, placeholder) in ENTRIES {
+        let key = format!(r"HKCU\Software\Classes\{}", key);
+        // cmd strips the outer quotes and runs the rest as is.
+        let command = format!(
+            r#"cmd.exe /c ""{}" --on-conflict rename "{}" & pause""#,
+            exe.display(),
+            placeholder
+        );
+        reg(&["add", &key, "/ve", "/d", label, "/f"])?;
+        reg(&[
+            "add",
+            &key,
+            "/v",
+            "Icon",
+            "/d",
+            &exe.to_string_lossy(),
+            "/f",
+        ])?;
+        reg(&[
+            "add",
+            &format!(r"{}\command", key),
+            "/ve",
+            "/d",
+            &command,
+            "/f",
+        ])?;
+    }
+    info!("added the context menu entries");
+    Ok(())
+}
+
+/// Removes the context menu entries added by [`install`]. Entries that don't exist are skipped.
+pub fn uninstall() -> Result<()> {
+    require_windows("uninstall-shell-ext")?;
+    for (key, _) in ENTRIES {
+        let key = format!(r"HKCU\Software\Classes\{}", key);
+        if reg(&["query", &key]).is_ok() {
+            reg(&["delete", &key, "/f"])?;
+        }
+    }
+    info!("removed the context menu entries");
+    Ok(())
+}
+
+fn require_windows(command: &str) -> Result<()> {
+    match cfg!(windows) {
+        true => Ok(()),
+        false => Err(lang::format("{} is only available on Windows", &[&command]).into()),
+    }
+}
+
+/// Runs `reg.exe` with `args`, which needs no extra dependency and no administrator rights for
+/// the keys of the current user.
+fn reg(args: &[&str]) -> Result<()> {
+    let status = Command::new("reg.exe")
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("reg {} failed with {}", args.join(" "), status).into()),
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use xm_decryptor::health::{self, ESSENTIAL_FRAMES};
+use xm_decryptor::id3::frame::{EncapsulatedObject, ExtendedText, Picture};
+use xm_decryptor::id3::{v1v2, ErrorKind, Tag, TagLike, Version};
+use xm_decryptor::mp3::Gapless;
+use xm_decryptor::xm::{self, AudioFormat, XMInfo};
+use xm_decryptor::Result;
+
+use crate::args::CoverOnce;
+
+/// Where an output keeps its ID3 tag.
+#[derive(Clone, Copy)]
+enum TagStorage {
+    /// At the start of the file, as in MP3 files.
+    Plain,
+    Wav,
+    Aiff,
+}
+
+impl TagStorage {
+    /// Returns where the output that starts with `header` keeps its ID3 tag, or `None` if the
+    /// format has no place for one.
+    fn detect(header: &[u8]) -> Option<TagStorage> {
+        match xm::detect_format(header) {
+            AudioFormat::Mp3 | AudioFormat::Aac => Some(TagStorage::Plain),
+            AudioFormat::Wav => Some(TagStorage::Wav),
+            AudioFormat::Aiff => Some(TagStorage::Aiff),
+            _ => None,
+        }
+    }
+
+    /// Reads the tag of the output at `path`, `None` if it has none. An ID3v1 tag at the end of
+    /// an MP3 output fills in what its ID3v2 tag lacks.
+    fn read(self, path: &Path) -> Result<Option<Tag>> {
+        let read = match self {
+            TagStorage::Plain => v1v2::read_from_path_merged(path),
+            TagStorage::Wav => Tag::read_from_wav_path(path),
+            TagStorage::Aiff => Tag::read_from_aiff_path(path),
+        };
+        match read {
+            Ok(tag) => Ok(Some(tag)),
+            Err(e) if matches!(e.kind, ErrorKind::NoTag) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes `tag` to the output at `path`, replacing any tag it has. MP3 outputs are left
+    /// untouched if the tag didn't change since it was read.
+    fn write(self, path: &Path, tag: &mut Tag) -> Result<()> {
+        match self {
+            TagStorage::Plain => {
+                tag.write_changes_to_path(path, tag.version())?;
+            }
+            TagStorage::Wav => tag.write_to_wav_path(path, tag.version())?,
+            TagStorage::Aiff => tag.write_to_aiff_path(path, tag.version())?,
+        }
+        Ok(())
+    }
+}
+
+/// The description of the frame that holds the source tag.
+const SOURCE_TAG_DESCRIPTION: &str = "xm source tag";
+
+/// Stores `source_tag`, the untouched ID3 tag of the xm file `source`, as a `GEOB` frame in the
+/// output at `path`. `header` is the start of the decrypted audio.
+///
+/// Returns false if the output format can't hold an ID3 tag.
+pub fn embed_source_tag(
+    path: &Path,
+    header: &[u8],
+    source: &Path,
+    source_tag: &[u8],
+) -> Result<bool> {
+    let Some(storage) = TagStorage::detect(header) else {
+        return Ok(false);
+    };
+    let mut tag = storage
+        .read(path)?
+        .unwrap_or_else(|| Tag::with_version(Version::Id3v24));
+    tag.add_frame(EncapsulatedObject {
+        mime_type: "application/x-id3".to_string(),
+        filename: source
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        description: SOURCE_TAG_DESCRIPTION.to_string(),
+        data: source_tag.to_vec(),
+    });
+    storage.write(path, &mut tag)?;
+    Ok(true)
+}
+
+/// Writes `cover` as `cover.jpg` or `cover.png` next to the output at `path`, unless a file of
+/// that name is there already, such as the cover of an earlier episode of the album.
+///
+/// Returns the path of the written file, `None` if nothing was written.
+pub fn extract_cover(path: &Path, cover: &Picture) -> Result<Option<PathBuf>> {
+    let Some(extension) = xm::cover_extension(cover) else {
+        return Ok(None);
+    };
+    let cover_path = path.with_file_name("cover").with_extension(extension);
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&cover_path)
+    {
+        Ok(mut file) => file.write_all(&cover.data)?,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some(cover_path))
+}
+
+/// The most covers a [`Covers`] holds on to, enough for the albums the workers are on.
+const SHARED_COVERS: usize = 16;
+
+/// The cover art of a batch. Equal covers of different episodes are held once, and with
+/// `--cover-once` it tells which outputs still get the cover of their folder.
+#[derive(Default)]
+pub struct Covers {
+    /// The covers seen last, the most recent at the end.
+    recent: Mutex<Vec<Arc<Picture>>>,
+    /// The output folders whose cover has been embedded.
+    folders: Mutex<HashSet<PathBuf>>,
+}
+
+impl Covers {
+    /// Replaces the cover of `info` with an equal one seen before, so the copy read from its tag
+    /// is freed at once.
+    pub fn share(&self, info: &mut XMInfo) {
+        let Some(cover) = info.shared_cover().cloned() else {
+            return;
+        };
+        let mut recent = self.recent.lock().expect("covers poisoned");
+        let shared = match recent.iter().position(|seen| *seen == cover) {
+            Some(i) => recent.remove(i),
+            None => cover,
+        };
+        if recent.len() == SHARED_COVERS {
+            recent.remove(0);
+        }
+        recent.push(Arc::clone(&shared));
+        info.set_cover(Some(shared));
+    }
+
+    /// Returns true if the output at `path` gets `cover` embedded under `mode`: with `First` only
+    /// the first output of each folder, with `FolderOnly` none, unless the cover can't be saved
+    /// as an image file.
+    pub fn embeds(&self, path: &Path, cover: &Picture, mode: CoverOnce) -> bool {
+        match mode {
+            CoverOnce::First => {
+                let folder = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                self.folders.lock().expect("covers poisoned").insert(folder)
+            }
+            CoverOnce::FolderOnly => xm::cover_extension(cover).is_none(),
+        }
+    }
+}
+
+/// The description of the `TXXX` frame that holds the gapless info of MP3 outputs.
+const GAPLESS_DESCRIPTION: &str = "iTunSMPB";
+
+/// Sets the `TLEN` frame of the MP3 output at `path` to `length` and, if known, stores its
+/// `gapless` info as an `iTunSMPB` `TXXX` frame. A tag is added if the output has none.
+pub fn write_timing(path: &Path, length: Duration, gapless: Option<Gapless>) -> Result<()> {
+    let storage = TagStorage::Plain;
+    let mut tag = storage
+        .read(path)?
+        .unwrap_or_else(|| Tag::with_version(Version::Id3v24));
+    tag.set_text("TLEN", length.as_millis().to_string());
+    if let Some(gapless) = gapless {
+        tag.add_frame(ExtendedText {
+            description: GAPLESS_DESCRIPTION.to_string(),
+            value: gapless.itunsmpb(),
+        });
+    }
+    storage.write(path, &mut tag)
+}
+
+/// Applies the safe corrections of [`health::fix`] to the ID3 tag of the output at `path` and
+/// fills missing essential frames from `info`. `header` is the start of the decrypted audio.
+///
+/// Returns the tag scores before and after the corrections, or `None` if the output has no ID3
+/// tag. The file is only rewritten if something was corrected.
+pub fn fix_tags(path: &Path, info: &XMInfo, header: &[u8]) -> Result<Option<(u8, u8)>> {
+    let Some(storage) = TagStorage::detect(header) else {
+        return Ok(None);
+    };
+    let Some(mut tag) = storage.read(path)? else {
+        return Ok(None);
+    };
+
+    let before = health::analyze(&tag).score();
+    let mut fixes = health::fix(&mut tag);
+    let xm_tag = info.tag();
+    for id in ESSENTIAL_FRAMES {
+        if let (None, Some(frame)) = (tag.get(id), xm_tag.get(id)) {
+            tag.add_frame(frame.clone());
+            fixes += 1;
+        }
+    }
+    if fixes > 0 {
+        storage.write(path, &mut tag)?;
+    }
+    Ok(Some((before, health::analyze(&tag).score())))
+}
+
+#[test]
+fn test_embed_source_tag() {
+    let mp3 = [0xFF, 0xFB, 0x90, 0x64, 0, 0, 0, 0];
+    let path = std::env::temp_dir().join(format!("xm_decryptor_{}.mp3", std::process::id()));
+    std::fs::write(&path, mp3).unwrap();
+
+    assert!(embed_source_tag(&path, &mp3, Path::new("a.xm"), b"ID3source").unwrap());
+    assert!(!embed_source_tag(&path, b"\0\0\0\x20ftypM4A ", Path::new("a.xm"), b"").unwrap());
+    let tag = Tag::read_from_path(&path).unwrap();
+    let content = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let object = tag.encapsulated_objects().next().unwrap();
+    assert_eq!(object.filename, "a.xm");
+    assert_eq!(object.data, b"ID3source");
+    assert!(content.ends_with(&mp3));
+}
+
+#[test]
+fn test_covers() {
+    let info = || {
+        let mut tag = Tag::new();
+        tag.add_frame(Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: xm_decryptor::id3::frame::PictureType::CoverFront,
+            description: String::new(),
+            data: b"\xFF\xD8\xFFcover".to_vec(),
+        });
+        XMInfo::from(tag)
+    };
+    let covers = Covers::default();
+    let (mut first, mut second) = (info(), info());
+    covers.share(&mut first);
+    covers.share(&mut second);
+    let shared = first.shared_cover().unwrap();
+    assert!(Arc::ptr_eq(shared, second.shared_cover().unwrap()));
+
+    let cover = first.cover().unwrap();
+    assert!(covers.embeds(Path::new("a/1.mp3"), cover, CoverOnce::First));
+    assert!(!covers.embeds(Path::new("a/2.mp3"), cover, CoverOnce::First));
+    assert!(covers.embeds(Path::new("b/1.mp3"), cover, CoverOnce::First));
+    assert!(!covers.embeds(Path::new("c/1.mp3"), cover, CoverOnce::FolderOnly));
+}
@@ -0,0 +1,905 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tracing::error;
+use xm_decryptor::Result;
+
+use crate::progress::{line_width, truncate};
+use crate::report::{FileReport, Status};
+use crate::{control, lang};
+
+/// The shortest time between two redraws for changes of the run. Keys redraw at once.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the size of the terminal is looked up again.
+const RESIZE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a worker waiting for a retry looks whether the run has been asked to stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The columns and rows of the screen when the terminal doesn't tell them.
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+/// The number of latest log lines shown below the list of files.
+const LOG_LINES: usize = 3;
+
+/// The TUI on screen, which takes the log lines instead of stderr. A detached TUI stays here
+/// once closed, so the outcome of its run can still be looked at.
+static OPEN: Mutex<Option<Arc<Shared>>> = Mutex::new(None);
+
+/// Set by [`detach`].
+static DETACHED: AtomicBool = AtomicBool::new(false);
+
+/// A full screen view of a decrypt run for `--tui`, on the alternate screen of the terminal: the
+/// files with their status and errors, the latest log lines, and keys to move through the list,
+/// pause and resume the run, decrypt a failed file again, skip a file before it is decrypted and
+/// quit.
+///
+/// Once every file is done the TUI stays open for retries until it is quit. The log of the run
+/// is printed to stderr when it is closed.
+pub struct Tui {
+    shared: Arc<Shared>,
+    /// The settings of the terminal, none for a detached TUI.
+    saved: Option<term::Saved>,
+}
+
+struct Shared {
+    /// Set for a TUI without a terminal, see [`detach`].
+    detached: bool,
+    /// The output root, which the `p` key pauses.
+    dir: PathBuf,
+    /// The directory the files are shown relative to, none for a file list.
+    root: Option<PathBuf>,
+    state: Mutex<State>,
+    /// Signalled when a file is queued for a retry and when the TUI is quit.
+    changed: Condvar,
+}
+
+struct State {
+    rows: Vec<Row>,
+    /// The row of each file.
+    by_file: HashMap<PathBuf, usize>,
+    /// Set once every input has been found.
+    total_known: bool,
+    selected: usize,
+    /// The first row on screen.
+    top: usize,
+    /// Set while the selection follows the file started last, until the selection is moved.
+    follow: bool,
+    last_started: Option<usize>,
+    /// The files to decrypt again, with the number of their report.
+    retries: VecDeque<(usize, PathBuf)>,
+    /// Every log line, printed once the TUI is closed.
+    log: Vec<String>,
+    /// Set once the user is done with the TUI, or its keys can't be read anymore.
+    quit: bool,
+    /// Set once the terminal is restored, nothing is drawn anymore.
+    closed: bool,
+    /// Set if the `p` key paused the output root, which is resumed when the TUI is closed.
+    paused: bool,
+    dirty: bool,
+    /// The columns and rows of the terminal, and when they were looked up.
+    size: (usize, usize),
+    sized: Option<Instant>,
+}
+
+struct Row {
+    file: PathBuf,
+    status: RowStatus,
+    error: Option<String>,
+    /// The number of the report of the file, once a worker took it.
+    index: Option<usize>,
+    /// Set by the `s` key while the file waits.
+    skip: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowStatus {
+    Queued,
+    Decrypting,
+    Done(Status),
+}
+
+/// A key pressed in the TUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    /// A character, in lower case.
+    Char(char),
+}
+
+impl Tui {
+    /// Switches the terminal to the TUI. `dir` is the output root, `root` the directory the files
+    /// are shown relative to. After [`detach`] the terminal is left alone.
+    pub fn open(dir: &Path, root: Option<&Path>) -> Result<Tui> {
+        let detached = DETACHED.load(Ordering::SeqCst);
+        if !detached && (!std::io::stdin().is_terminal() || !std::io::stderr().is_terminal()) {
+            return Err(lang::tr("--tui needs a terminal").into());
+        }
+        let saved = match detached {
+            true => None,
+            false => Some(term::enter()?),
+        };
+        let shared = Arc::new(Shared {
+            detached,
+            dir: dir.to_path_buf(),
+            root: root.map(Path::to_path_buf),
+            state: Mutex::new(State {
+                rows: Vec::new(),
+                by_file: HashMap::new(),
+                total_known: false,
+                selected: 0,
+                top: 0,
+                follow: true,
+                last_started: None,
+                retries: VecDeque::new(),
+                log: Vec::new(),
+                quit: false,
+                closed: false,
+                paused: false,
+                dirty: true,
+                size: DEFAULT_SIZE,
+                sized: None,
+            }),
+            changed: Condvar::new(),
+        });
+        *OPEN.lock().expect("tui poisoned") = Some(shared.clone());
+        if detached {
+            return Ok(Tui { shared, saved });
+        }
+        // The alternate screen keeps the scrollback of the terminal, the cursor is hidden.
+        let _ = write!(std::io::stderr(), "\x1b[?1049h\x1b[?25l");
+        let keys = shared.clone();
+        std::thread::spawn(move || {
+            term::read_keys(&|| !keys.lock().closed, &mut |key| keys.key(key));
+            // Without keys the TUI can't be quit, so it ends with the run.
+            keys.lock().quit = true;
+            keys.changed.notify_all();
+        });
+        let screen = shared.clone();
+        std::thread::spawn(move || {
+            while screen.draw_if_changed() {
+                std::thread::sleep(REDRAW_INTERVAL);
+            }
+        });
+        Ok(Tui { shared, saved })
+    }
+
+    /// Adds `file` to the list, waiting to be decrypted.
+    pub fn found(&self, file: &Path) {
+        let mut state = self.shared.lock();
+        let row = state.rows.len();
+        state.by_file.insert(file.to_path_buf(), row);
+        state.rows.push(Row {
+            file: file.to_path_buf(),
+            status: RowStatus::Queued,
+            error: None,
+            index: None,
+            skip: false,
+        });
+        state.dirty = true;
+    }
+
+    /// Marks the list as complete.
+    pub fn found_all(&self) {
+        let mut state = self.shared.lock();
+        state.total_known = true;
+        state.dirty = true;
+    }
+
+    /// Returns true if `file` is to be skipped instead of decrypted, as asked for with `s`.
+    pub fn skips(&self, file: &Path) -> bool {
+        let state = self.shared.lock();
+        state
+            .by_file
+            .get(file)
+            .is_some_and(|&row| state.rows[row].skip)
+    }
+
+    /// Shows `file` as being decrypted, its report numbered `index`.
+    pub fn started(&self, file: &Path, index: usize) {
+        let mut state = self.shared.lock();
+        let Some(&row) = state.by_file.get(file) else {
+            return;
+        };
+        state.rows[row].status = RowStatus::Decrypting;
+        state.rows[row].index = Some(index);
+        state.last_started = Some(row);
+        if state.follow {
+            state.selected = row;
+        }
+        state.dirty = true;
+    }
+
+    /// Shows the outcome of the file of `report`, numbered `index`.
+    pub fn finished(&self, index: usize, report: &FileReport) {
+        let mut state = self.shared.lock();
+        let Some(&row) = state.by_file.get(&report.source) else {
+            return;
+        };
+        let row = &mut state.rows[row];
+        row.status = RowStatus::Done(report.status);
+        row.error = report.error.clone();
+        row.index = Some(index);
+        row.skip = false;
+        state.dirty = true;
+    }
+
+    /// Returns the next file queued for a retry with `r`, with the number of its report.
+    pub fn next_retry(&self) -> Option<(usize, PathBuf)> {
+        self.shared.lock().retries.pop_front()
+    }
+
+    /// Waits for a file to be queued for a retry, once a worker has nothing left to do. Returns
+    /// `None` once the TUI is quit or the run is asked to stop.
+    pub fn wait_for_retry(&self) -> Option<(usize, PathBuf)> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(retry) = state.retries.pop_front() {
+                return Some(retry);
+            }
+            if state.quit || control::stopping() {
+                return None;
+            }
+            // A stop is only seen by looking, a signal handler can't notify.
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, STOP_POLL_INTERVAL)
+                .expect("tui poisoned")
+                .0;
+        }
+    }
+
+    /// Restores the terminal and prints the log of the run to stderr. Resumes the output root if
+    /// the TUI paused it.
+    pub fn close(&self) {
+        if let Some(saved) = &self.saved {
+            OPEN.lock().expect("tui poisoned").take();
+            let log = {
+                let mut state = self.shared.lock();
+                if state.closed {
+                    return;
+                }
+                state.closed = true;
+                // Under the lock, so nothing is drawn after.
+                let mut stderr = std::io::stderr().lock();
+                let _ = write!(stderr, "\x1b[?25h\x1b[?1049l");
+                let _ = stderr.flush();
+                std::mem::take(&mut state.log)
+            };
+            term::leave(saved);
+            let mut stderr = std::io::stderr().lock();
+            for line in log {
+                let _ = stderr.write_all(line.as_bytes());
+            }
+        }
+        let paused = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.quit = true;
+            std::mem::take(&mut state.paused)
+        };
+        self.shared.changed.notify_all();
+        if paused && control::is_paused(&self.shared.dir) {
+            if let Err(e) = control::resume(&self.shared.dir) {
+                error!(error = %e, "can't pause or resume");
+            }
+        }
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Keeps the log `line` for the open TUI, which shows the latest lines below the files and
+/// prints them all once closed. Returns false if the line is to be written to stderr, as it is
+/// when no TUI is open or it is detached.
+pub fn log(line: &str) -> bool {
+    let open = OPEN.lock().expect("tui poisoned");
+    let Some(shared) = open.as_ref() else {
+        return false;
+    };
+    let mut state = shared.lock();
+    state.log.push(line.to_string());
+    state.dirty = true;
+    !shared.detached
+}
+
+/// Makes the TUIs opened from now on run without a terminal, for the web UI of the `gui`
+/// command, which shows them with [`snapshot`] and works them with [`act`].
+#[cfg(feature = "gui")]
+pub fn detach() {
+    DETACHED.store(true, Ordering::SeqCst);
+}
+
+/// What the web UI shows of the TUI opened last.
+#[cfg(feature = "gui")]
+pub struct Snapshot {
+    /// The top line of the TUI, with the counts.
+    pub header: String,
+    pub rows: Vec<SnapshotRow>,
+    /// The log lines from the `log_from`th on.
+    pub log: Vec<String>,
+    /// Set once every file is done and the run waits for retries.
+    pub finished: bool,
+    /// Set once the run has ended.
+    pub closed: bool,
+}
+
+#[cfg(feature = "gui")]
+pub struct SnapshotRow {
+    /// The path of the file, relative to the input root.
+    pub path: String,
+    /// `queued`, `skip`, `decrypting`, `ok`, `skipped` or `failed`.
+    pub status: &'static str,
+    pub error: Option<String>,
+}
+
+/// Returns what the TUI opened last shows, with its log from the line `log_from` on. Returns
+/// `None` if no TUI has been opened.
+#[cfg(feature = "gui")]
+pub fn snapshot(log_from: usize) -> Option<Snapshot> {
+    let open = OPEN.lock().expect("tui poisoned");
+    let shared = open.as_ref()?;
+    let state = shared.lock();
+    let rows = state
+        .rows
+        .iter()
+        .map(|row| SnapshotRow {
+            path: shared.relative(&row.file).display().to_string(),
+            status: match (row.status, row.skip) {
+                (RowStatus::Queued, false) => "queued",
+                (RowStatus::Queued, true) => "skip",
+                (RowStatus::Decrypting, _) => "decrypting",
+                (RowStatus::Done(Status::Ok), _) => "ok",
+                (RowStatus::Done(Status::Skipped | Status::NotEncrypted), _) => "skipped",
+                (RowStatus::Done(Status::Error), _) => "failed",
+            },
+            error: row.error.clone(),
+        })
+        .collect();
+    Some(Snapshot {
+        header: shared.header(&state),
+        rows,
+        log: state.log.iter().skip(log_from).cloned().collect(),
+        finished: state.finished(),
+        closed: state.closed,
+    })
+}
+
+/// What the web UI asks of the open TUI, like the keys of the terminal.
+#[cfg(feature = "gui")]
+pub enum Action {
+    /// Decrypts the failed file of the row again, like `r`.
+    Retry(usize),
+    /// Skips the waiting file of the row, or no longer skips it, like `s`.
+    Skip(usize),
+    /// Pauses or resumes the run, like `p`.
+    Pause,
+    /// Stops the run, or ends it once every file is done, like `q`.
+    Quit,
+}
+
+/// Does `action` in the open TUI.
+#[cfg(feature = "gui")]
+pub fn act(action: Action) {
+    let Some(shared) = OPEN.lock().expect("tui poisoned").clone() else {
+        return;
+    };
+    if shared.lock().closed {
+        return;
+    }
+    match action {
+        Action::Retry(row) => shared.retry(&mut shared.lock(), row),
+        Action::Skip(row) => shared.lock().toggle_skip(row),
+        Action::Pause => shared.toggle_pause(),
+        Action::Quit => shared.quit(&mut shared.lock()),
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("tui poisoned")
+    }
+
+    fn key(&self, key: Key) {
+        let mut state = self.lock();
+        let (selected, page) = (state.selected, state.list_height());
+        match key {
+            Key::Up | Key::Char('k') => state.select(selected.saturating_sub(1)),
+            Key::Down | Key::Char('j') => state.select(selected + 1),
+            Key::PageUp => state.select(selected.saturating_sub(page)),
+            Key::PageDown => state.select(selected + page),
+            Key::Home => state.select(0),
+            Key::End => {
+                state.selected = match (state.finished(), state.last_started) {
+                    (false, Some(row)) => row,
+                    _ => state.rows.len().saturating_sub(1),
+                };
+                state.follow = true;
+            }
+            Key::Char('r') => self.retry(&mut state, selected),
+            Key::Char('s') => state.toggle_skip(selected),
+            // Pausing logs, which needs the state.
+            Key::Char('p') => {
+                drop(state);
+                self.toggle_pause();
+                state = self.lock();
+            }
+            Key::Char('q') => self.quit(&mut state),
+            _ => {}
+        }
+        if !state.closed {
+            self.draw(&mut state);
+        }
+    }
+
+    /// Queues the file of `row` for a retry, if it failed.
+    fn retry(&self, state: &mut State, row: usize) {
+        let Some(row) = state.rows.get_mut(row) else {
+            return;
+        };
+        let (RowStatus::Done(Status::Error), Some(index)) = (row.status, row.index) else {
+            return;
+        };
+        row.status = RowStatus::Queued;
+        row.error = None;
+        let file = row.file.clone();
+        state.retries.push_back((index, file));
+        state.dirty = true;
+        self.changed.notify_all();
+    }
+
+    /// Ends the TUI once every file is done, else asks the run to stop.
+    fn quit(&self, state: &mut State) {
+        match state.finished() {
+            true => {
+                state.quit = true;
+                self.changed.notify_all();
+            }
+            // Like Ctrl+C, the files being decrypted are finished first.
+            false => control::stop(),
+        }
+    }
+
+    /// Pauses the output root, or resumes it if it is paused.
+    fn toggle_pause(&self) {
+        let result = match control::is_paused(&self.dir) {
+            true => control::resume(&self.dir).map(|()| false),
+            false => std::fs::create_dir_all(&self.dir)
+                .map_err(Into::into)
+                .and_then(|()| control::pause(&self.dir))
+                .map(|()| true),
+        };
+        match result {
+            Ok(paused) => self.lock().paused = paused,
+            Err(e) => error!(error = %e, "can't pause or resume"),
+        }
+    }
+
+    /// Draws the screen if anything changed. Returns false once the TUI is closed.
+    fn draw_if_changed(&self) -> bool {
+        let mut state = self.lock();
+        if state.closed {
+            return false;
+        }
+        if state.dirty {
+            self.draw(&mut state);
+        }
+        true
+    }
+
+    /// Draws the whole screen at once: the counts, the files, the latest log lines and the keys.
+    fn draw(&self, state: &mut State) {
+        if state.sized.is_none_or(|t| t.elapsed() >= RESIZE_INTERVAL) {
+            // A terminal that doesn't know its size, such as a bare pty, says 0.
+            state.size = term::size()
+                .filter(|&(columns, rows)| columns > 0 && rows > 0)
+                .unwrap_or(DEFAULT_SIZE);
+            state.sized = Some(Instant::now());
+        }
+        // The last column is left free so the cursor doesn't wrap.
+        let width = state.size.0.saturating_sub(1);
+        let list_height = state.list_height();
+        state.scroll(list_height);
+        let mut lines = vec![("1".to_string(), self.header(state))];
+        for (i, row) in state
+            .rows
+            .iter()
+            .enumerate()
+            .skip(state.top)
+            .take(list_height)
+        {
+            let style = match (row.status, row.skip) {
+                (RowStatus::Done(Status::Error), _) => "31",
+                (RowStatus::Decrypting, _) => "1",
+                (RowStatus::Done(Status::Skipped | Status::NotEncrypted), _) | (_, true) => "2",
+                _ => "0",
+            };
+            let style = match i == state.selected {
+                true => format!("7;{}", style),
+                false => style.to_string(),
+            };
+            lines.push((style, self.row_text(row)));
+        }
+        lines.resize(list_height + 1, ("0".to_string(), String::new()));
+        let log = &state.log[state.log.len().saturating_sub(LOG_LINES)..];
+        for i in 0..LOG_LINES {
+            let line = log.get(i).map_or("", |line| line.trim_end());
+            lines.push(("2".to_string(), line.to_string()));
+        }
+        lines.push((
+            "7".to_string(),
+            lang::tr("↑↓ PgUp PgDn: move  End: follow  p: pause  r: retry  s: skip  q: quit")
+                .to_string(),
+        ));
+        let mut screen = String::from("\x1b[H");
+        for (i, (style, text)) in lines.iter().enumerate() {
+            if i > 0 {
+                screen.push_str("\r\n");
+            }
+            let text = truncate(&text.replace(['\n', '\r'], " "), width);
+            // Padded, so a selected row is highlighted across the screen.
+            let pad = width.saturating_sub(line_width(&text));
+            screen.push_str(&format!("\x1b[{}m{}{:pad$}\x1b[0m\x1b[K", style, text, ""));
+        }
+        screen.push_str("\x1b[J");
+        let mut stderr = std::io::stderr().lock();
+        let _ = stderr.write_all(screen.as_bytes());
+        let _ = stderr.flush();
+        state.dirty = false;
+    }
+
+    /// Returns the top line: the files done and found, the outcomes and whether the run waits.
+    fn header(&self, state: &State) -> String {
+        let (mut ok, mut skipped, mut failed) = (0, 0, 0);
+        for row in &state.rows {
+            match row.status {
+                RowStatus::Done(Status::Ok) => ok += 1,
+                RowStatus::Done(Status::Skipped | Status::NotEncrypted) => skipped += 1,
+                RowStatus::Done(Status::Error) => failed += 1,
+                RowStatus::Queued | RowStatus::Decrypting => {}
+            }
+        }
+        let total = match state.total_known {
+            true => state.rows.len().to_string(),
+            false => format!("{}+", state.rows.len()),
+        };
+        let mut header = format!(
+            "[{}/{}] ✓ {}  - {}  ✗ {}",
+            ok + skipped + failed,
+            total,
+            ok,
+            skipped,
+            failed
+        );
+        let waiting = if control::stopping() {
+            Some("stopping")
+        } else if control::is_paused(&self.dir) {
+            Some("paused")
+        } else if state.finished() {
+            Some("done")
+        } else {
+            None
+        };
+        if let Some(waiting) = waiting {
+            header.push_str("  ");
+            header.push_str(lang::tr(waiting));
+        }
+        header
+    }
+
+    /// Returns the line of a file: a mark of its status, its path and its error.
+    fn row_text(&self, row: &Row) -> String {
+        let mark = match (row.status, row.skip) {
+            (RowStatus::Queued, false) => " ",
+            (RowStatus::Queued, true) => "-",
+            (RowStatus::Decrypting, _) => ">",
+            (RowStatus::Done(Status::Ok), _) => "✓",
+            (RowStatus::Done(Status::Skipped | Status::NotEncrypted), _) => "-",
+            (RowStatus::Done(Status::Error), _) => "✗",
+        };
+        let path = self.relative(&row.file);
+        match &row.error {
+            Some(error) => format!("{} {}  {}", mark, path.display(), error),
+            None => format!("{} {}", mark, path.display()),
+        }
+    }
+
+    /// Returns `file` relative to the directory the files are shown relative to.
+    fn relative<'a>(&self, file: &'a Path) -> &'a Path {
+        self.root
+            .as_deref()
+            .and_then(|root| file.strip_prefix(root).ok())
+            .unwrap_or(file)
+    }
+}
+
+impl State {
+    /// Returns the number of files on screen, below the counts and above the log and the keys.
+    fn list_height(&self) -> usize {
+        self.size.1.saturating_sub(LOG_LINES + 2).max(1)
+    }
+
+    /// Returns true once every file has been found and done.
+    fn finished(&self) -> bool {
+        self.total_known
+            && self
+                .rows
+                .iter()
+                .all(|row| matches!(row.status, RowStatus::Done(_)))
+    }
+
+    /// Selects the row `row`, which stops following the files started.
+    fn select(&mut self, row: usize) {
+        self.selected = row.min(self.rows.len().saturating_sub(1));
+        self.follow = false;
+    }
+
+    /// Scrolls the list so the selected row is on screen.
+    fn scroll(&mut self, list_height: usize) {
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+        if self.selected < self.top {
+            self.top = self.selected;
+        }
+        if self.selected >= self.top + list_height {
+            self.top = self.selected + 1 - list_height;
+        }
+    }
+
+    /// Skips the file of `row` while it waits, or no longer skips it.
+    fn toggle_skip(&mut self, row: usize) {
+        if let Some(row) = self
+            .rows
+            .get_mut(row)
+            .filter(|row| row.status == RowStatus::Queued)
+        {
+            row.skip = !row.skip;
+            self.dirty = true;
+        }
+    }
+}
+
+/// Reads the next key from the bytes a terminal sends, `None` at the end of the input. Escape
+/// sequences of keys the TUI doesn't use are returned as the escape character.
+fn parse_key(bytes: &mut impl Iterator<Item = u8>) -> Option<Key> {
+    let byte = bytes.next()?;
+    if byte != 0x1b {
+        return Some(Key::Char(char::from(byte).to_ascii_lowercase()));
+    }
+    if !matches!(bytes.next()?, b'[' | b'O') {
+        return Some(Key::Char('\x1b'));
+    }
+    // The parameters and the final byte of the sequence, such as `5~` of Page Up.
+    let mut sequence = Vec::new();
+    loop {
+        let byte = bytes.next()?;
+        sequence.push(byte);
+        if (0x40..=0x7e).contains(&byte) {
+            break;
+        }
+    }
+    Some(match &sequence[..] {
+        b"A" => Key::Up,
+        b"B" => Key::Down,
+        b"5~" => Key::PageUp,
+        b"6~" => Key::PageDown,
+        b"H" | b"1~" | b"7~" => Key::Home,
+        b"F" | b"4~" | b"8~" => Key::End,
+        _ => Key::Char('\x1b'),
+    })
+}
+
+#[cfg(unix)]
+mod term {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+
+    use xm_decryptor::Result;
+
+    use super::{parse_key, Key};
+    use crate::lang;
+
+    /// The settings of the terminal before the TUI changed them, as printed by `stty -g`.
+    pub struct Saved(String);
+
+    /// Makes the terminal pass on each key at once without echoing it. Ctrl+C still stops the
+    /// run.
+    pub fn enter() -> Result<Saved> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1", "time", "0"])?;
+        Ok(Saved(saved.trim().to_string()))
+    }
+
+    pub fn leave(saved: &Saved) {
+        // Nothing more can be done if stty fails now, after it worked before.
+        let _ = stty(&[&saved.0]);
+    }
+
+    /// Returns the columns and rows of the terminal.
+    pub fn size() -> Option<(usize, usize)> {
+        let size = stty(&["size"]).ok()?;
+        let mut numbers = size.split_whitespace().map(|n| n.parse::<usize>().ok());
+        let (rows, columns) = (numbers.next()??, numbers.next()??);
+        Some((columns, rows))
+    }
+
+    /// Calls `key` with each key pressed while `open` returns true and the input lasts.
+    pub fn read_keys(open: &dyn Fn() -> bool, key: &mut dyn FnMut(Key)) {
+        let mut bytes = std::io::stdin().lock().bytes().map_while(|byte| byte.ok());
+        while open() {
+            match parse_key(&mut bytes) {
+                Some(k) => key(k),
+                None => break,
+            }
+        }
+    }
+
+    /// Runs `stty` on the terminal of stdin and returns what it prints.
+    fn stty(args: &[&str]) -> Result<String> {
+        let output = Command::new("stty")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(lang::tr("--tui needs a terminal").into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(windows)]
+mod term {
+    use std::ffi::c_void;
+    use std::time::Duration;
+
+    use xm_decryptor::Result;
+
+    use super::Key;
+    use crate::lang;
+
+    extern "C" {
+        fn _kbhit() -> i32;
+        fn _getch() -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn GetConsoleScreenBufferInfo(console: *mut c_void, info: *mut ScreenBufferInfo) -> i32;
+    }
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x4;
+
+    /// How often the keyboard is looked at, so the keys stop being read once the TUI is closed.
+    const KEY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// CONSOLE_SCREEN_BUFFER_INFO.
+    #[repr(C)]
+    #[derive(Default)]
+    struct ScreenBufferInfo {
+        size: [i16; 2],
+        cursor_position: [i16; 2],
+        attributes: u16,
+        /// Left, top, right and bottom.
+        window: [i16; 4],
+        maximum_window_size: [i16; 2],
+    }
+
+    /// The mode of the console before the TUI changed it.
+    pub struct Saved(u32);
+
+    /// Turns on the escape sequences of the console on stderr, which the TUI is drawn with.
+    pub fn enter() -> Result<Saved> {
+        let mut mode = 0;
+        // SAFETY: GetConsoleMode fails for a handle that isn't a console.
+        let ok = unsafe {
+            let console = GetStdHandle(STD_ERROR_HANDLE);
+            GetConsoleMode(console, &mut mode) != 0
+                && SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        };
+        match ok {
+            true => Ok(Saved(mode)),
+            false => Err(lang::tr("--tui needs a terminal").into()),
+        }
+    }
+
+    pub fn leave(saved: &Saved) {
+        // SAFETY: the mode was read from the same console.
+        unsafe { SetConsoleMode(GetStdHandle(STD_ERROR_HANDLE), saved.0) };
+    }
+
+    /// Returns the columns and rows of the console window.
+    pub fn size() -> Option<(usize, usize)> {
+        let mut info = ScreenBufferInfo::default();
+        // SAFETY: the struct has the layout of CONSOLE_SCREEN_BUFFER_INFO.
+        let ok = unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_ERROR_HANDLE), &mut info) };
+        let [left, top, right, bottom] = info.window.map(usize::try_from);
+        match (ok != 0, left, top, right, bottom) {
+            (true, Ok(left), Ok(top), Ok(right), Ok(bottom)) => {
+                Some((right + 1 - left, bottom + 1 - top))
+            }
+            _ => None,
+        }
+    }
+
+    /// Calls `key` with each key pressed while `open` returns true.
+    pub fn read_keys(open: &dyn Fn() -> bool, key: &mut dyn FnMut(Key)) {
+        while open() {
+            // SAFETY: both functions only read the keyboard of the console.
+            if unsafe { _kbhit() } == 0 {
+                std::thread::sleep(KEY_POLL_INTERVAL);
+                continue;
+            }
+            // Arrows and the other special keys come as 0 or 0xE0 and a scan code.
+            let k = match unsafe { _getch() } {
+                0 | 0xE0 => match unsafe { _getch() } {
+                    72 => Key::Up,
+                    80 => Key::Down,
+                    73 => Key::PageUp,
+                    81 => Key::PageDown,
+                    71 => Key::Home,
+                    79 => Key::End,
+                    _ => continue,
+                },
+                code => Key::Char(char::from(code as u8).to_ascii_lowercase()),
+            };
+            key(k);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod term {
+    use xm_decryptor::Result;
+
+    use super::Key;
+    use crate::lang;
+
+    pub struct Saved;
+
+    pub fn enter() -> Result<Saved> {
+        Err(lang::tr("--tui needs a terminal").into())
+    }
+
+    pub fn leave(_: &Saved) {}
+
+    pub fn size() -> Option<(usize, usize)> {
+        None
+    }
+
+    pub fn read_keys(_: &dyn Fn() -> bool, _: &mut dyn FnMut(Key)) {}
+}
+
+#[test]
+fn test_parse_key() {
+    let mut bytes = b"jQ\x1b[A\x1b[6~\x1bOF\x1b[2~".iter().copied();
+    let keys: Vec<_> = std::iter::from_fn(|| parse_key(&mut bytes)).collect();
+    assert_eq!(
+        keys,
+        [
+            Key::Char('j'),
+            Key::Char('q'),
+            Key::Up,
+            Key::PageDown,
+            Key::End,
+            Key::Char('\x1b'),
+        ]
+    );
+}
@@ -0,0 +1,62 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::{error, info};
+use xm_decryptor::Result;
+
+use crate::control;
+use crate::scan::{is_xm, Scan};
+
+/// How often the input directory is scanned for new files.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Scans the input directory every [`POLL_INTERVAL`] and passes the `.xm` files that are not in
+/// `seen` to `decrypt` once they have stopped growing. `decrypt` returns the outputs it wrote,
+/// which are added to `seen` so they are never passed on. Runs until the run is asked to stop, a
+/// batch that `decrypt` fails is logged and not passed on again, and the watch goes on. While the
+/// output root `dir` is paused, nothing is scanned.
+///
+/// A new file is handed over when its size is unchanged between two scans, so files that are still
+/// being downloaded are picked up after the download finishes.
+pub fn watch(
+    scan: &Scan,
+    dir: &Path,
+    seen: Vec<PathBuf>,
+    mut decrypt: impl FnMut(&[PathBuf]) -> Result<Vec<PathBuf>>,
+) -> Result<()> {
+    let mut seen: HashSet<_> = seen.into_iter().collect();
+    let mut sizes = HashMap::<PathBuf, u64>::new();
+    info!(dir = %scan.root.display(), "watching");
+    loop {
+        control::wait_while_paused(dir);
+        if !control::sleep(POLL_INTERVAL) {
+            return Ok(());
+        }
+        let mut files = scan.collect_files();
+        files.retain(|f| is_xm(f) && !seen.contains(f));
+        // Forget files that disappeared before they were complete.
+        let present: HashSet<_> = files.iter().collect();
+        sizes.retain(|f, _| present.contains(f));
+        let mut complete = Vec::new();
+        for file in files {
+            let Ok(metadata) = std::fs::metadata(&file) else {
+                continue;
+            };
+            let size = metadata.len();
+            if sizes.insert(file.clone(), size) == Some(size) && size > 0 {
+                sizes.remove(&file);
+                complete.push(file);
+            }
+        }
+        if !complete.is_empty() {
+            seen.extend(complete.iter().cloned());
+            match decrypt(&complete) {
+                Ok(outputs) => seen.extend(outputs),
+                Err(e) => {
+                    error!(files = complete.len() as u64, error = %e, "can't decrypt the new files")
+                }
+            }
+        }
+    }
+}
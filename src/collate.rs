@@ -0,0 +1,166 @@
+use crate::naming::fold_name;
+use std::cmp::Ordering;
+use std::path::Path;
+
+/// Compares names in the order people expect: runs of digits by their value, so `第2集` comes
+/// before `第10集`, and text ignoring case and full-width forms. With the `pinyin` feature text is
+/// compared by the collation of the system locale, which orders Chinese by pinyin under a Chinese
+/// locale.
+///
+/// Names that compare equal this way are ordered by their bytes, so the order is total.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (folded_a, folded_b) = (fold_name(a), fold_name(b));
+    let mut segments_a = segments(&folded_a);
+    let mut segments_b = segments(&folded_b);
+    loop {
+        let order = match (segments_a.next(), segments_b.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if is_number(x) && is_number(y) => compare_numbers(x, y),
+            (Some(x), Some(y)) => compare_text(x, y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+/// Compares paths component by component with [`compare`].
+pub fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let mut components_a = a.components();
+    let mut components_b = b.components();
+    loop {
+        match (components_a.next(), components_b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let order = compare(
+                    &x.as_os_str().to_string_lossy(),
+                    &y.as_os_str().to_string_lossy(),
+                );
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+        }
+    }
+}
+
+/// Splits `s` into runs of ASCII digits and runs of other characters.
+fn segments(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (segment, tail) = rest.split_at(end);
+        rest = tail;
+        (!segment.is_empty()).then_some(segment)
+    })
+}
+
+fn is_number(segment: &str) -> bool {
+    segment.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Compares runs of digits by value, however long they are.
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compares text with `strcoll_l` in the collation of the environment. The locale is a private
+/// one, the global locale of the program embedding the crate is left alone.
+#[cfg(all(feature = "pinyin", unix))]
+fn compare_text(a: &str, b: &str) -> Ordering {
+    use std::ffi::{c_char, c_int, c_void, CString};
+    use std::sync::OnceLock;
+
+    extern "C" {
+        fn newlocale(mask: c_int, locale: *const c_char, base: *mut c_void) -> *mut c_void;
+        fn strcoll_l(a: *const c_char, b: *const c_char, locale: *mut c_void) -> c_int;
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const LC_COLLATE_MASK: c_int = 1 << 3;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const LC_COLLATE_MASK: c_int = 1 << 0;
+
+    // The address of the locale, created once and never freed. Null if it couldn't be created.
+    static LOCALE: OnceLock<usize> = OnceLock::new();
+    // SAFETY: an empty name selects the collation of the environment, a null base creates a new
+    // locale.
+    let locale = *LOCALE.get_or_init(|| unsafe {
+        newlocale(LC_COLLATE_MASK, c"".as_ptr(), std::ptr::null_mut()) as usize
+    }) as *mut c_void;
+    let (Ok(c_a), Ok(c_b)) = (CString::new(a), CString::new(b)) else {
+        return a.cmp(b);
+    };
+    if locale.is_null() {
+        return a.cmp(b);
+    }
+    // SAFETY: both strings are NUL terminated and outlive the call, the locale is never freed.
+    unsafe { strcoll_l(c_a.as_ptr(), c_b.as_ptr(), locale) }.cmp(&0)
+}
+
+/// Compares text with the sort order of the user's default locale.
+#[cfg(all(feature = "pinyin", windows))]
+fn compare_text(a: &str, b: &str) -> Ordering {
+    use std::ffi::c_void;
+    use std::ptr::null;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CompareStringEx(
+            locale: *const u16,
+            flags: u32,
+            a: *const u16,
+            a_len: i32,
+            b: *const u16,
+            b_len: i32,
+            version: *const c_void,
+            reserved: *const c_void,
+            param: isize,
+        ) -> i32;
+    }
+    let wide_a: Vec<u16> = a.encode_utf16().collect();
+    let wide_b: Vec<u16> = b.encode_utf16().collect();
+    // SAFETY: the lengths are those of the buffers, a null locale is the user's default locale.
+    let result = unsafe {
+        CompareStringEx(
+            null(),
+            0,
+            wide_a.as_ptr(),
+            wide_a.len() as i32,
+            wide_b.as_ptr(),
+            wide_b.len() as i32,
+            null(),
+            null(),
+            0,
+        )
+    };
+    match result {
+        1 => Ordering::Less,
+        2 => Ordering::Equal,
+        3 => Ordering::Greater,
+        _ => a.cmp(b),
+    }
+}
+
+#[cfg(not(all(feature = "pinyin", any(unix, windows))))]
+fn compare_text(a: &str, b: &str) -> Ordering {
+    a.cmp(b)
+}
+
+#[test]
+fn test_compare() {
+    let mut names = vec!["第10集", "第2集", "第1集 B", "第01集 a", "第1集 a"];
+    names.sort_by(|a, b| compare(a, b));
+    assert_eq!(names, ["第01集 a", "第1集 a", "第1集 B", "第2集", "第10集"]);
+    assert_eq!(
+        compare_paths(Path::new("a/b2/x"), Path::new("a/b10")),
+        Ordering::Less
+    );
+}
@@ -0,0 +1,181 @@
+use crate::Result;
+
+/// The metadata block type of the stream info, which comes first.
+const STREAMINFO: u8 = 0;
+
+/// The metadata block type of Vorbis comments.
+const VORBIS_COMMENT: u8 = 4;
+
+/// The vendor string of a Vorbis comment block that is added.
+const VENDOR: &str = concat!("xm_decryptor ", env!("CARGO_PKG_VERSION"));
+
+/// A metadata block of a FLAC stream.
+struct Block<'a> {
+    kind: u8,
+    data: &'a [u8],
+}
+
+/// Sets the Vorbis comments `comments`, such as `("TITLE", title)`, in the FLAC stream `audio`.
+/// Comments with the same names are replaced, names are compared regardless of case. Other
+/// comments are kept, and a Vorbis comment block is added after the stream info if there is none.
+pub fn write_vorbis_comments(audio: &mut Vec<u8>, comments: &[(&str, &str)]) -> Result<()> {
+    let (mut blocks, pos) = parse_blocks(audio)?;
+
+    let (vendor, mut kept) = match blocks.iter().find(|b| b.kind == VORBIS_COMMENT) {
+        Some(block) => parse_comments(block.data)?,
+        None => (VENDOR.as_bytes(), Vec::new()),
+    };
+    kept.retain(|comment| {
+        let name = comment.split(|b| *b == b'=').next().unwrap_or_default();
+        !comments
+            .iter()
+            .any(|(n, _)| n.as_bytes().eq_ignore_ascii_case(name))
+    });
+    let mut block = Vec::new();
+    block.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    block.extend_from_slice(vendor);
+    block.extend_from_slice(&((kept.len() + comments.len()) as u32).to_le_bytes());
+    let added = comments
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value));
+    for comment in kept
+        .into_iter()
+        .map(<[u8]>::to_vec)
+        .chain(added.map(String::into_bytes))
+    {
+        block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        block.extend(comment);
+    }
+    if block.len() >= 1 << 24 {
+        return Err("FLAC Vorbis comments too long".into());
+    }
+
+    // The new block takes the place of the first old one, or follows the stream info, which
+    // always comes first.
+    let position = blocks
+        .iter()
+        .position(|b| b.kind == VORBIS_COMMENT)
+        .unwrap_or(1);
+    blocks.retain(|b| b.kind != VORBIS_COMMENT);
+    blocks.insert(
+        position.min(blocks.len()),
+        Block {
+            kind: VORBIS_COMMENT,
+            data: &block,
+        },
+    );
+    let mut metadata = b"fLaC".to_vec();
+    for (i, b) in blocks.iter().enumerate() {
+        let last = if i == blocks.len() - 1 { 0x80 } else { 0 };
+        let len = (b.data.len() as u32).to_be_bytes();
+        metadata.extend_from_slice(&[b.kind | last, len[1], len[2], len[3]]);
+        metadata.extend_from_slice(b.data);
+    }
+    audio.splice(..pos, metadata);
+    Ok(())
+}
+
+/// Checks that the FLAC stream `audio` has unbroken metadata blocks, starting with the stream info,
+/// and that the first audio frame follows them.
+pub fn check_metadata(audio: &[u8]) -> Result<()> {
+    let (blocks, pos) = parse_blocks(audio)?;
+    if blocks[0].kind != STREAMINFO {
+        return Err("the FLAC stream info is missing".into());
+    }
+    // The 14 bit frame sync code, a reserved 0 bit and the blocking strategy.
+    match audio.get(pos..pos + 2) {
+        Some([0xFF, b]) if b & 0xFE == 0xF8 => Ok(()),
+        _ => Err("no FLAC audio frame after the metadata".into()),
+    }
+}
+
+/// Splits the FLAC stream `audio` into its metadata blocks. Also returns where the audio frames
+/// start.
+fn parse_blocks(audio: &[u8]) -> Result<(Vec<Block<'_>>, usize)> {
+    if !audio.starts_with(b"fLaC") {
+        return Err("not a FLAC stream".into());
+    }
+    let mut blocks = Vec::new();
+    let mut pos = 4;
+    loop {
+        let header = audio
+            .get(pos..pos + 4)
+            .ok_or("truncated FLAC metadata block")?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let data = audio
+            .get(pos + 4..pos + 4 + len)
+            .ok_or("truncated FLAC metadata block")?;
+        blocks.push(Block {
+            kind: header[0] & 0x7F,
+            data,
+        });
+        pos += 4 + len;
+        if header[0] & 0x80 != 0 {
+            return Ok((blocks, pos));
+        }
+    }
+}
+
+/// Splits a Vorbis comment block into the vendor string and the comments.
+fn parse_comments(data: &[u8]) -> Result<(&[u8], Vec<&[u8]>)> {
+    let mut pos = 0;
+    let next = |pos: &mut usize| -> Result<&[u8]> {
+        let len = data
+            .get(*pos..*pos + 4)
+            .ok_or("truncated FLAC Vorbis comment")?;
+        let len = u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize;
+        let value = data
+            .get(*pos + 4..*pos + 4 + len)
+            .ok_or("truncated FLAC Vorbis comment")?;
+        *pos += 4 + len;
+        Ok(value)
+    };
+    let vendor = next(&mut pos)?;
+    let count = data
+        .get(pos..pos + 4)
+        .ok_or("truncated FLAC Vorbis comment")?;
+    let count = u32::from_le_bytes(count.try_into().expect("4 bytes"));
+    pos += 4;
+    let comments = (0..count)
+        .map(|_| next(&mut pos))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((vendor, comments))
+}
+
+#[test]
+fn test_write_vorbis_comments() {
+    let mut audio = b"fLaC\x80\0\0\x02si".to_vec();
+    audio.extend_from_slice(b"frames");
+
+    write_vorbis_comments(&mut audio, &[("TITLE", "old"), ("GENRE", "Audiobook")]).unwrap();
+    write_vorbis_comments(&mut audio, &[("title", "第1集"), ("TRACKNUMBER", "3")]).unwrap();
+
+    assert!(audio.starts_with(b"fLaC\0\0\0\x02si"));
+    assert!(audio.ends_with(b"frames"));
+    assert_eq!(audio[10], 0x80 | VORBIS_COMMENT);
+    let len = u32::from_be_bytes([0, audio[11], audio[12], audio[13]]) as usize;
+    let (vendor, comments) = parse_comments(&audio[14..14 + len]).unwrap();
+    assert_eq!(vendor, VENDOR.as_bytes());
+    assert_eq!(
+        comments,
+        [
+            &b"GENRE=Audiobook"[..],
+            "title=第1集".as_bytes(),
+            b"TRACKNUMBER=3"
+        ]
+    );
+    assert_eq!(audio.len(), 14 + len + 6);
+}
+
+#[test]
+fn test_check_metadata() {
+    assert!(check_metadata(b"fLaC\x80\0\0\x02si\xFF\xF8").is_ok());
+    assert_eq!(
+        check_metadata(b"fLaC\x80\0\0\x02si\x12\x34")
+            .unwrap_err()
+            .to_string(),
+        "no FLAC audio frame after the metadata"
+    );
+    assert!(check_metadata(b"fLaC\x84\0\0\x02si\xFF\xF8").is_err());
+    assert!(check_metadata(b"fLaC\x80\0\0\x09si").is_err());
+}
@@ -0,0 +1,214 @@
+use crate::id3::{Encoding, Tag, TagLike, Version};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The frames every episode tag is expected to have: title, artist, album and track.
+pub const ESSENTIAL_FRAMES: [&str; 4] = ["TIT2", "TPE1", "TALB", "TRCK"];
+
+/// ID3v2.3 frames that were removed in ID3v2.4.
+const DEPRECATED_FRAMES: [&str; 9] = [
+    "EQUA", "IPLS", "RVAD", "TDAT", "TIME", "TORY", "TRDA", "TSIZ", "TYER",
+];
+
+/// A problem found in a tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// An essential frame is missing.
+    MissingFrame(&'static str),
+    /// An ID3v2.4 tag contains a frame that only exists in older versions.
+    DeprecatedFrame(String),
+    /// A frame occurs more than once with the same identifying content.
+    DuplicateFrame(String),
+    /// The frames that carry an encoding don't agree on it.
+    MixedEncodings(Vec<Encoding>),
+    /// A frame uses an encoding its tag version doesn't support.
+    UnsupportedEncoding(String, Encoding),
+}
+
+impl Issue {
+    /// The number of points the issue takes off the score.
+    fn penalty(&self) -> u8 {
+        match self {
+            Issue::MissingFrame(_) => 20,
+            Issue::DeprecatedFrame(_) => 5,
+            Issue::DuplicateFrame(_) => 10,
+            Issue::MixedEncodings(_) => 5,
+            Issue::UnsupportedEncoding(_, _) => 10,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingFrame(id) => write!(f, "missing {}", id),
+            Issue::DeprecatedFrame(id) => write!(f, "deprecated {}", id),
+            Issue::DuplicateFrame(id) => write!(f, "duplicate {}", id),
+            Issue::MixedEncodings(encodings) => write!(f, "mixed encodings {:?}", encodings),
+            Issue::UnsupportedEncoding(id, encoding) => {
+                write!(
+                    f,
+                    "{} uses {:?}, which its version doesn't support",
+                    id, encoding
+                )
+            }
+        }
+    }
+}
+
+/// The result of analyzing a tag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Health {
+    /// The number of frames per frame ID.
+    pub frame_counts: BTreeMap<String, usize>,
+    /// The problems found, in the order of the frames that cause them.
+    pub issues: Vec<Issue>,
+}
+
+impl Health {
+    /// Returns a score from 0 to 100, where 100 means no issues were found.
+    pub fn score(&self) -> u8 {
+        self.issues
+            .iter()
+            .fold(100u8, |score, issue| score.saturating_sub(issue.penalty()))
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "score {}", self.score())?;
+        for issue in &self.issues {
+            write!(f, ", {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the frames of `tag` and looks for missing essential frames, deprecated frames,
+/// duplicate frames and inconsistent encodings.
+pub fn analyze(tag: &Tag) -> Health {
+    let mut health = Health::default();
+    for frame in tag.frames() {
+        *health
+            .frame_counts
+            .entry(frame.id().to_string())
+            .or_default() += 1;
+    }
+    for id in ESSENTIAL_FRAMES {
+        if !health.frame_counts.contains_key(id) {
+            health.issues.push(Issue::MissingFrame(id));
+        }
+    }
+    if tag.version() == Version::Id3v24 {
+        for id in DEPRECATED_FRAMES {
+            if health.frame_counts.contains_key(id) {
+                health.issues.push(Issue::DeprecatedFrame(id.to_string()));
+            }
+        }
+    }
+    let frames = tag.frames_vec();
+    for (i, frame) in frames.iter().enumerate() {
+        if frames[..i].iter().any(|earlier| earlier.compare(frame)) {
+            health
+                .issues
+                .push(Issue::DuplicateFrame(frame.id().to_string()));
+        }
+    }
+    let mut encodings = BTreeSet::new();
+    for frame in tag.frames() {
+        let Some(encoding) = frame.encoding() else {
+            continue;
+        };
+        encodings.insert(encoding);
+        if !supports(tag.version(), encoding) {
+            health
+                .issues
+                .push(Issue::UnsupportedEncoding(frame.id().to_string(), encoding));
+        }
+    }
+    if encodings.len() > 1 {
+        health
+            .issues
+            .push(Issue::MixedEncodings(encodings.into_iter().collect()));
+    }
+    health
+}
+
+/// Applies the corrections that don't lose information and returns how many were made.
+///
+/// Duplicate frames are removed. Explicit frame encodings that are mixed or unsupported by the
+/// tag version are cleared so the encoder picks a suitable one. In ID3v2.4 tags `TYER` and `TORY` are replaced by `TDRC` and
+/// `TDOR` and the meaningless `TSIZ` is dropped. Missing frames can't be repaired from the tag
+/// itself and are left alone.
+pub fn fix(tag: &mut Tag) -> usize {
+    let mut fixes = 0;
+    let version = tag.version();
+    let encodings: BTreeSet<_> = tag.frames().filter_map(|f| f.encoding()).collect();
+    let frames = std::mem::take(tag.frames_vec_mut());
+    for frame in frames {
+        if tag.frames_vec().iter().any(|kept| kept.compare(&frame)) {
+            fixes += 1;
+            continue;
+        }
+        let frame = match frame.encoding() {
+            Some(encoding) if encodings.len() > 1 || !supports(version, encoding) => {
+                fixes += 1;
+                frame.set_encoding(None)
+            }
+            _ => frame,
+        };
+        tag.frames_vec_mut().push(frame);
+    }
+    if version == Version::Id3v24 {
+        for (old, new) in [("TYER", "TDRC"), ("TORY", "TDOR")] {
+            for frame in tag.remove(old) {
+                if tag.get(new).is_none() {
+                    if let Some(text) = frame.content().text() {
+                        tag.set_text(new, text);
+                    }
+                }
+                fixes += 1;
+            }
+        }
+        fixes += tag.remove("TSIZ").len();
+    }
+    fixes
+}
+
+/// Returns true if tags of `version` can use `encoding`.
+fn supports(version: Version, encoding: Encoding) -> bool {
+    match encoding {
+        Encoding::Latin1 | Encoding::UTF16 => true,
+        Encoding::UTF16BE | Encoding::UTF8 => version == Version::Id3v24,
+    }
+}
+
+#[test]
+fn test_analyze_and_fix() {
+    let mut tag = Tag::with_version(Version::Id3v24);
+    tag.set_title("title");
+    tag.set_artist("artist");
+    tag.set_text("TYER", "2020");
+    tag.frames_vec_mut()
+        .push(crate::id3::Frame::text("TIT2", "title"));
+
+    let health = analyze(&tag);
+    assert_eq!(health.frame_counts["TIT2"], 2);
+    assert_eq!(
+        health.issues,
+        vec![
+            Issue::MissingFrame("TALB"),
+            Issue::MissingFrame("TRCK"),
+            Issue::DeprecatedFrame("TYER".to_string()),
+            Issue::DuplicateFrame("TIT2".to_string()),
+        ]
+    );
+    assert_eq!(health.score(), 45);
+
+    assert_eq!(fix(&mut tag), 2);
+    assert_eq!(
+        tag.get("TDRC").and_then(|f| f.content().text()),
+        Some("2020")
+    );
+    assert_eq!(analyze(&tag).score(), 60);
+}
@@ -0,0 +1,260 @@
+//! Conversion between Unicode and the legacy Chinese character sets, done by the system: iconv on
+//! Unix with the `iconv` feature and the Windows code pages. Other platforms convert nothing.
+
+pub(crate) use sys::{decode, Converter};
+
+/// A legacy character set that text frames claiming ISO-8859-1 may really be in, see
+/// [`ParseOptions::latin1_fallback`](crate::id3::ParseOptions::latin1_fallback).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LegacyCharset {
+    /// GBK, written by tools and players sold in mainland China.
+    Gbk,
+    /// Big5, written by tools in Taiwan and Hong Kong.
+    Big5,
+}
+
+/// Returns the `bytes` in `charsets`, the first one they are valid in, if they hold anything but
+/// ASCII. Bytes valid in none of them return `None`.
+pub(crate) fn decode_fallback(bytes: &[u8], charsets: &[LegacyCharset]) -> Option<String> {
+    if bytes.is_ascii() {
+        return None;
+    }
+    charsets.iter().find_map(|&charset| decode(bytes, charset))
+}
+
+#[cfg(all(unix, feature = "iconv"))]
+mod sys {
+    use super::LegacyCharset;
+    use std::ffi::{c_char, c_int, c_void, CStr};
+
+    #[cfg_attr(target_os = "macos", link(name = "iconv"))]
+    extern "C" {
+        fn iconv_open(to: *const c_char, from: *const c_char) -> *mut c_void;
+        fn iconv(
+            cd: *mut c_void,
+            inbuf: *mut *mut c_char,
+            inbytesleft: *mut usize,
+            outbuf: *mut *mut c_char,
+            outbytesleft: *mut usize,
+        ) -> usize;
+        fn iconv_close(cd: *mut c_void) -> c_int;
+    }
+
+    /// An iconv conversion descriptor, closed on drop.
+    struct Iconv(*mut c_void);
+
+    impl Iconv {
+        /// Returns `None` if the system lacks the conversion.
+        fn open(to: &CStr, from: &CStr) -> Option<Iconv> {
+            // SAFETY: both names are NUL terminated.
+            let cd = unsafe { iconv_open(to.as_ptr(), from.as_ptr()) };
+            (cd as isize != -1).then_some(Iconv(cd))
+        }
+
+        /// Converts all of `input` into `output`, returning the bytes written, or `None` if the
+        /// input is invalid or incomplete, or doesn't fit.
+        fn convert(&mut self, input: &[u8], output: &mut [u8]) -> Option<usize> {
+            // iconv takes a mutable input pointer for historical reasons but doesn't write to it.
+            let (mut in_ptr, mut in_left) = (input.as_ptr() as *mut c_char, input.len());
+            let (mut out_ptr, mut out_left) = (output.as_mut_ptr() as *mut c_char, output.len());
+            // SAFETY: the pointers and lengths describe the two buffers, which outlive the call.
+            let result = unsafe {
+                iconv(
+                    self.0,
+                    &mut in_ptr,
+                    &mut in_left,
+                    &mut out_ptr,
+                    &mut out_left,
+                )
+            };
+            (result != usize::MAX && in_left == 0).then_some(output.len() - out_left)
+        }
+    }
+
+    impl Drop for Iconv {
+        fn drop(&mut self) {
+            // SAFETY: the descriptor was opened by `open` and is closed once.
+            unsafe { iconv_close(self.0) };
+        }
+    }
+
+    /// An iconv conversion from UTF-8 to GBK.
+    pub struct Converter(Iconv);
+
+    impl Converter {
+        /// Returns `None` if the system has no GBK conversion.
+        pub fn new() -> Option<Converter> {
+            Iconv::open(c"GBK", c"UTF-8").map(Converter)
+        }
+
+        /// Returns `c` in GBK, or `None` if GBK lacks it.
+        pub fn encode(&mut self, c: char) -> Option<Vec<u8>> {
+            let mut utf8 = [0; 4];
+            let utf8 = c.encode_utf8(&mut utf8);
+            let mut out = [0u8; 4];
+            let len = self.0.convert(utf8.as_bytes(), &mut out)?;
+            Some(out[..len].to_vec())
+        }
+    }
+
+    /// Returns `bytes` in `charset` as a string, or `None` if they are not valid in it or the
+    /// system can't convert it.
+    pub fn decode(bytes: &[u8], charset: LegacyCharset) -> Option<String> {
+        let from = match charset {
+            LegacyCharset::Gbk => c"GBK",
+            LegacyCharset::Big5 => c"BIG5",
+        };
+        let mut iconv = Iconv::open(c"UTF-8", from)?;
+        // A character becomes at most three bytes of UTF-8, even the single byte 0x80 of GBK, €.
+        let mut out = vec![0; bytes.len() * 3 + 4];
+        let len = iconv.convert(bytes, &mut out)?;
+        out.truncate(len);
+        String::from_utf8(out).ok()
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::LegacyCharset;
+    use std::ptr::{null, null_mut};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn WideCharToMultiByte(
+            code_page: u32,
+            flags: u32,
+            wide: *const u16,
+            wide_len: i32,
+            multi_byte: *mut u8,
+            multi_byte_len: i32,
+            default_char: *const u8,
+            used_default_char: *mut i32,
+        ) -> i32;
+        fn MultiByteToWideChar(
+            code_page: u32,
+            flags: u32,
+            multi_byte: *const u8,
+            multi_byte_len: i32,
+            wide: *mut u16,
+            wide_len: i32,
+        ) -> i32;
+    }
+    const CP_GBK: u32 = 936;
+    const CP_BIG5: u32 = 950;
+    const WC_NO_BEST_FIT_CHARS: u32 = 0x400;
+    const MB_ERR_INVALID_CHARS: u32 = 0x8;
+
+    /// A conversion to the GBK code page.
+    pub struct Converter;
+
+    impl Converter {
+        pub fn new() -> Option<Converter> {
+            Some(Converter)
+        }
+
+        /// Returns `c` in GBK, or `None` if GBK lacks it.
+        pub fn encode(&mut self, c: char) -> Option<Vec<u8>> {
+            let mut wide = [0; 2];
+            let wide = c.encode_utf16(&mut wide);
+            let mut out = [0u8; 4];
+            let mut used_default = 0;
+            // SAFETY: the lengths are those of the buffers.
+            let len = unsafe {
+                WideCharToMultiByte(
+                    CP_GBK,
+                    WC_NO_BEST_FIT_CHARS,
+                    wide.as_ptr(),
+                    wide.len() as i32,
+                    out.as_mut_ptr(),
+                    out.len() as i32,
+                    null(),
+                    &mut used_default,
+                )
+            };
+            (len > 0 && used_default == 0).then(|| out[..len as usize].to_vec())
+        }
+    }
+
+    /// Returns `bytes` in `charset` as a string, or `None` if they are not valid in it.
+    pub fn decode(bytes: &[u8], charset: LegacyCharset) -> Option<String> {
+        let code_page = match charset {
+            LegacyCharset::Gbk => CP_GBK,
+            LegacyCharset::Big5 => CP_BIG5,
+        };
+        let len = i32::try_from(bytes.len()).ok()?;
+        // SAFETY: a null output with length 0 asks for the length needed.
+        let wide_len = unsafe {
+            MultiByteToWideChar(
+                code_page,
+                MB_ERR_INVALID_CHARS,
+                bytes.as_ptr(),
+                len,
+                null_mut(),
+                0,
+            )
+        };
+        if wide_len <= 0 {
+            return None;
+        }
+        let mut wide = vec![0; wide_len as usize];
+        // SAFETY: the lengths are those of the buffers.
+        let wide_len = unsafe {
+            MultiByteToWideChar(
+                code_page,
+                MB_ERR_INVALID_CHARS,
+                bytes.as_ptr(),
+                len,
+                wide.as_mut_ptr(),
+                wide_len,
+            )
+        };
+        if wide_len <= 0 {
+            return None;
+        }
+        String::from_utf16(&wide[..wide_len as usize]).ok()
+    }
+}
+
+#[cfg(not(any(all(unix, feature = "iconv"), windows)))]
+mod sys {
+    use super::LegacyCharset;
+
+    pub struct Converter;
+
+    impl Converter {
+        pub fn new() -> Option<Converter> {
+            None
+        }
+
+        pub fn encode(&mut self, _: char) -> Option<Vec<u8>> {
+            None
+        }
+    }
+
+    pub fn decode(_: &[u8], _: LegacyCharset) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(any(all(unix, feature = "iconv"), windows))]
+#[test]
+fn test_decode_fallback() {
+    let charsets = [LegacyCharset::Gbk, LegacyCharset::Big5];
+    // "中文" in GBK and in Big5.
+    assert_eq!(
+        decode_fallback(b"\xd6\xd0\xce\xc4", &charsets).as_deref(),
+        Some("\u{4e2d}\u{6587}")
+    );
+    assert_eq!(
+        decode_fallback(b"\xa4\xa4\xa4\xe5", &[LegacyCharset::Big5]).as_deref(),
+        Some("\u{4e2d}\u{6587}")
+    );
+    // ISO-8859-1 that is not valid GBK, and ASCII, are left alone.
+    assert_eq!(decode_fallback(b"Caf\xe9", &charsets), None);
+    assert_eq!(decode_fallback(b"ascii", &charsets), None);
+    // A single byte that grows to three bytes of UTF-8.
+    assert_eq!(
+        decode_fallback(b"\x80", &[LegacyCharset::Gbk]).as_deref(),
+        Some("\u{20ac}")
+    );
+}
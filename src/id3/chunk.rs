@@ -1,9 +1,8 @@
-use crate::id3::storage::{PlainStorage, Storage};
+use crate::id3::storage::{PlainStorage, Storage, StorageFile};
 use crate::id3::{Error, ErrorKind, Tag, Version};
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use std::convert::TryFrom;
 use std::fmt;
-use std::fs;
 use std::io::prelude::*;
 use std::io::{BufReader, Seek, SeekFrom};
 use std::{convert::TryInto, io};
@@ -36,7 +35,7 @@ where
 /// Writes a tag to the given file. If the file contains no previous tag data, a new ID3
 /// chunk is created. Otherwise, the tag is overwritten in place.
 pub fn write_id3_chunk_file<F: ChunkFormat>(
-    mut file: &mut fs::File,
+    mut file: &mut impl StorageFile,
     tag: &Tag,
     version: Version,
 ) -> crate::id3::Result<()> {
@@ -315,9 +314,9 @@ impl ChunkHeader {
     /// # Arguments
     ///
     /// * `reader` - The input stream. The reader must be positioned right after the root
-    ///              chunk header.
+    ///   chunk header.
     /// * `end` - The stream position where the chunk sequence ends. This is used to
-    ///           prevent searching past the end.
+    ///   prevent searching past the end.
     pub fn find_id3<F, R>(reader: R, end: u64) -> crate::id3::Result<Self>
     where
         F: ChunkFormat,
@@ -333,9 +332,9 @@ impl ChunkHeader {
     ///
     /// * `tag` - The chunk tag to search for.
     /// * `reader` - The input stream. The reader must be positioned at the start of a
-    ///              sequence of chunks.
+    ///   sequence of chunks.
     /// * `end` - The stream position where the chunk sequence ends. This is used to
-    ///           prevent searching past the end.
+    ///   prevent searching past the end.
     fn find<F, R>(tag: &ChunkTag, mut reader: R, end: u64) -> crate::id3::Result<Option<Self>>
     where
         F: ChunkFormat,
@@ -12,7 +12,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 ///
 /// # Example
 /// ```
-/// use id3::{Tag, Error, ErrorKind, partial_tag_ok};
+/// use xm_decryptor::id3::{Tag, Error, ErrorKind, partial_tag_ok};
 ///
 /// let rs = Err(Error{
 ///     kind: ErrorKind::Parsing,
@@ -48,6 +48,9 @@ pub enum ErrorKind {
     InvalidInput,
     /// An error kind indicating that a feature is not supported.
     UnsupportedFeature,
+    /// An error kind indicating that a tag or frame exceeds a limit of the
+    /// [`ParseOptions`](crate::id3::ParseOptions).
+    LimitExceeded,
 }
 
 /// A structure able to represent any error that may occur while performing metadata operations.
@@ -135,6 +138,7 @@ impl fmt::Display for ErrorKind {
             ErrorKind::Parsing => write!(f, "Parsing"),
             ErrorKind::InvalidInput => write!(f, "InvalidInput"),
             ErrorKind::UnsupportedFeature => write!(f, "UnsupportedFeature"),
+            ErrorKind::LimitExceeded => write!(f, "LimitExceeded"),
         }
     }
 }
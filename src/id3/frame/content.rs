@@ -1,11 +1,13 @@
 use crate::id3::frame::content_cmp::ContentCmp::{Comparable, Incomparable, Same};
 use crate::id3::frame::Frame;
+use crate::id3::stream;
 use crate::id3::stream::encoding::Encoding;
 use crate::id3::tag::Version;
 use crate::id3::taglike::TagLike;
 use std::borrow::Cow;
 use std::fmt;
 use std::io;
+use std::str;
 
 /// The decoded contents of a [`Frame`].
 ///
@@ -43,6 +45,8 @@ pub enum Content {
     SynchronisedLyrics(SynchronisedLyrics),
     /// A value containing the parsed contents of a picture frame (APIC).
     Picture(Picture),
+    /// Event timing codes frame content (ETCO).
+    EventTimingCodes(EventTimingCodes),
     /// A value containing the parsed contents of a general encapsulated object frame (GEOB).
     EncapsulatedObject(EncapsulatedObject),
     /// A chapter object containing frames by itself (CHAP).
@@ -51,6 +55,14 @@ pub enum Content {
     MpegLocationLookupTable(MpegLocationLookupTable),
     /// A private frame (PRIV)
     Private(Private),
+    /// A unique file identifier frame (UFID).
+    UniqueFileIdentifier(UniqueFileIdentifier),
+    /// A signature frame (SIGN).
+    Signature(Signature),
+    /// A seek frame (SEEK), the offset to the next tag in the file.
+    Seek(Seek),
+    /// An audio seek point index frame (ASPI).
+    AudioSeekPointIndex(AudioSeekPointIndex),
     /// A value containing the parsed contents of a table of contents frame (CTOC).
     TableOfContents(TableOfContents),
     /// A value containing the bytes of a currently unknown frame type.
@@ -102,10 +114,20 @@ impl Content {
                 Comparable(vec![Cow::Borrowed(chapter.element_id.as_bytes())])
             }
             Self::MpegLocationLookupTable(_) => Same,
+            Self::EventTimingCodes(_) => Same,
             Self::Private(private) => Comparable(vec![
                 Cow::Borrowed(private.owner_identifier.as_bytes()),
                 Cow::Borrowed(private.private_data.as_slice()),
             ]),
+            Self::UniqueFileIdentifier(unique_file_identifier) => Comparable(vec![Cow::Borrowed(
+                unique_file_identifier.owner_identifier.as_bytes(),
+            )]),
+            Self::Signature(signature) => Comparable(vec![
+                Cow::Owned(vec![signature.group_symbol]),
+                Cow::Borrowed(signature.signature.as_slice()),
+            ]),
+            Self::Seek(_) => Same,
+            Self::AudioSeekPointIndex(_) => Same,
             Self::TableOfContents(table_of_contents) => {
                 Comparable(vec![Cow::Borrowed(table_of_contents.element_id.as_bytes())])
             }
@@ -120,7 +142,7 @@ impl Content {
     ///
     /// # Example
     /// ```
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let c = Content::new_text_values(["foo", "bar", "baz"]);
     /// assert_eq!(c, Content::Text("foo\u{0}bar\u{0}baz".to_string()))
@@ -231,6 +253,14 @@ impl Content {
         }
     }
 
+    /// Returns the `EventTimingCodes` or None if the value is not `EventTimingCodes`.
+    pub fn event_timing_codes(&self) -> Option<&EventTimingCodes> {
+        match self {
+            Content::EventTimingCodes(event_timing_codes) => Some(event_timing_codes),
+            _ => None,
+        }
+    }
+
     /// Returns the `Popularimeter` or None if the value is not
     /// `Popularimeter`
     pub fn popularimeter(&self) -> Option<&Popularimeter> {
@@ -240,6 +270,38 @@ impl Content {
         }
     }
 
+    /// Returns the `UniqueFileIdentifier` or None if the value is not `UniqueFileIdentifier`.
+    pub fn unique_file_identifier(&self) -> Option<&UniqueFileIdentifier> {
+        match self {
+            Content::UniqueFileIdentifier(unique_file_identifier) => Some(unique_file_identifier),
+            _ => None,
+        }
+    }
+
+    /// Returns the `Signature` or None if the value is not `Signature`.
+    pub fn signature(&self) -> Option<&Signature> {
+        match self {
+            Content::Signature(signature) => Some(signature),
+            _ => None,
+        }
+    }
+
+    /// Returns the `Seek` or None if the value is not `Seek`.
+    pub fn seek(&self) -> Option<&Seek> {
+        match self {
+            Content::Seek(seek) => Some(seek),
+            _ => None,
+        }
+    }
+
+    /// Returns the `AudioSeekPointIndex` or None if the value is not `AudioSeekPointIndex`.
+    pub fn audio_seek_point_index(&self) -> Option<&AudioSeekPointIndex> {
+        match self {
+            Content::AudioSeekPointIndex(aspi) => Some(aspi),
+            _ => None,
+        }
+    }
+
     /// Returns the `TableOfContents` or None if the value is not `TableOfContents`.
     pub fn table_of_contents(&self) -> Option<&TableOfContents> {
         match self {
@@ -272,6 +334,7 @@ impl Content {
                     content,
                     version,
                     Encoding::UTF8,
+                    &mut Default::default(),
                 )?;
                 Ok(Cow::Owned(Unknown { data, version }))
             }
@@ -290,11 +353,16 @@ impl fmt::Display for Content {
             Content::Comment(comment) => write!(f, "{}", comment),
             Content::Popularimeter(popularimeter) => write!(f, "{}", popularimeter),
             Content::Lyrics(lyrics) => write!(f, "{}", lyrics),
-            Content::SynchronisedLyrics(sync_lyrics) => write!(f, "{}", sync_lyrics.content_type),
+            Content::SynchronisedLyrics(sync_lyrics) => write!(f, "{}", sync_lyrics),
             Content::Picture(picture) => write!(f, "{}", picture),
             Content::Chapter(chapter) => write!(f, "{}", chapter),
             Content::MpegLocationLookupTable(mpeg_table) => write!(f, "{}", mpeg_table),
+            Content::EventTimingCodes(event_timing_codes) => write!(f, "{}", event_timing_codes),
             Content::Private(private) => write!(f, "{}", private),
+            Content::UniqueFileIdentifier(ufid) => write!(f, "{}", ufid),
+            Content::Signature(signature) => write!(f, "{}", signature),
+            Content::Seek(seek) => write!(f, "{}", seek),
+            Content::AudioSeekPointIndex(aspi) => write!(f, "{}", aspi),
             Content::TableOfContents(table_of_contents) => write!(f, "{}", table_of_contents),
             Content::Unknown(unknown) => write!(f, "{}", unknown),
         }
@@ -370,11 +438,11 @@ impl fmt::Display for EncapsulatedObject {
         };
         write!(
             f,
-            "{} (\"{}\", \"{}\"), {} bytes",
+            "{} (\"{}\", \"{}\"), {}",
             desc,
             self.filename,
             self.mime_type,
-            self.data.len()
+            DataSize(self.data.len())
         )
     }
 }
@@ -522,6 +590,25 @@ impl SynchronisedLyrics {
     }
 }
 
+impl fmt::Display for SynchronisedLyrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.description.is_empty() {
+            write!(f, "{}: ", self.description)?;
+        }
+        let unit = match self.timestamp_format {
+            TimestampFormat::Mpeg => "MPEG frames",
+            TimestampFormat::Ms => "ms",
+        };
+        write!(
+            f,
+            "{}, {} entries ({})",
+            self.content_type,
+            self.content.len(),
+            unit
+        )
+    }
+}
+
 impl From<SynchronisedLyrics> for Frame {
     fn from(c: SynchronisedLyrics) -> Self {
         Self::with_content("SYLT", Content::SynchronisedLyrics(c))
@@ -680,18 +767,16 @@ pub struct Picture {
 
 impl fmt::Display for Picture {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.description.is_empty() {
-            write!(f, "{} ({})", self.picture_type, self.mime_type)
-        } else {
-            write!(
-                f,
-                "{}: {} ({}, {} bytes)",
-                self.description,
-                self.picture_type,
-                self.mime_type,
-                self.data.len()
-            )
+        if !self.description.is_empty() {
+            write!(f, "{}: ", self.description)?;
         }
+        write!(
+            f,
+            "{} {} ({})",
+            self.mime_type,
+            DataSize(self.data.len()),
+            self.picture_type
+        )
     }
 }
 
@@ -790,6 +875,168 @@ impl From<MpegLocationLookupTable> for Frame {
     }
 }
 
+/// The parsed contents of an event timing codes frame, the positions of events such as the start
+/// of the intro or the outro.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[allow(missing_docs)]
+pub struct EventTimingCodes {
+    pub timestamp_format: TimestampFormat,
+    // The events in chronological order, with the timestamp in the unit of `timestamp_format`.
+    pub events: Vec<(EventType, u32)>,
+}
+
+impl fmt::Display for EventTimingCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.timestamp_format {
+            TimestampFormat::Mpeg => "MPEG frames",
+            TimestampFormat::Ms => "ms",
+        };
+        write!(f, "{} events ({})", self.events.len(), unit)
+    }
+}
+
+impl From<EventTimingCodes> for Frame {
+    fn from(c: EventTimingCodes) -> Self {
+        Self::with_content("ETCO", Content::EventTimingCodes(c))
+    }
+}
+
+/// Types of events used in ETCO frames.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[allow(missing_docs)]
+pub enum EventType {
+    Padding,
+    EndOfInitialSilence,
+    IntroStart,
+    MainPartStart,
+    OutroStart,
+    OutroEnd,
+    VerseStart,
+    RefrainStart,
+    InterludeStart,
+    ThemeStart,
+    VariationStart,
+    KeyChange,
+    TimeChange,
+    MomentaryUnwantedNoise,
+    SustainedNoise,
+    SustainedNoiseEnd,
+    IntroEnd,
+    MainPartEnd,
+    VerseEnd,
+    RefrainEnd,
+    ThemeEnd,
+    Profanity,
+    ProfanityEnd,
+    // One of the 16 synchronisation events ($E0-$EF) left to the application, numbered 0-15.
+    Sync(u8),
+    AudioEnd,
+    AudioFileEnd,
+    Undefined(u8),
+}
+
+impl From<u8> for EventType {
+    fn from(b: u8) -> Self {
+        match b {
+            0x00 => EventType::Padding,
+            0x01 => EventType::EndOfInitialSilence,
+            0x02 => EventType::IntroStart,
+            0x03 => EventType::MainPartStart,
+            0x04 => EventType::OutroStart,
+            0x05 => EventType::OutroEnd,
+            0x06 => EventType::VerseStart,
+            0x07 => EventType::RefrainStart,
+            0x08 => EventType::InterludeStart,
+            0x09 => EventType::ThemeStart,
+            0x0a => EventType::VariationStart,
+            0x0b => EventType::KeyChange,
+            0x0c => EventType::TimeChange,
+            0x0d => EventType::MomentaryUnwantedNoise,
+            0x0e => EventType::SustainedNoise,
+            0x0f => EventType::SustainedNoiseEnd,
+            0x10 => EventType::IntroEnd,
+            0x11 => EventType::MainPartEnd,
+            0x12 => EventType::VerseEnd,
+            0x13 => EventType::RefrainEnd,
+            0x14 => EventType::ThemeEnd,
+            0x15 => EventType::Profanity,
+            0x16 => EventType::ProfanityEnd,
+            0xe0..=0xef => EventType::Sync(b - 0xe0),
+            0xfd => EventType::AudioEnd,
+            0xfe => EventType::AudioFileEnd,
+            b => EventType::Undefined(b),
+        }
+    }
+}
+
+impl From<EventType> for u8 {
+    fn from(et: EventType) -> Self {
+        match et {
+            EventType::Padding => 0x00,
+            EventType::EndOfInitialSilence => 0x01,
+            EventType::IntroStart => 0x02,
+            EventType::MainPartStart => 0x03,
+            EventType::OutroStart => 0x04,
+            EventType::OutroEnd => 0x05,
+            EventType::VerseStart => 0x06,
+            EventType::RefrainStart => 0x07,
+            EventType::InterludeStart => 0x08,
+            EventType::ThemeStart => 0x09,
+            EventType::VariationStart => 0x0a,
+            EventType::KeyChange => 0x0b,
+            EventType::TimeChange => 0x0c,
+            EventType::MomentaryUnwantedNoise => 0x0d,
+            EventType::SustainedNoise => 0x0e,
+            EventType::SustainedNoiseEnd => 0x0f,
+            EventType::IntroEnd => 0x10,
+            EventType::MainPartEnd => 0x11,
+            EventType::VerseEnd => 0x12,
+            EventType::RefrainEnd => 0x13,
+            EventType::ThemeEnd => 0x14,
+            EventType::Profanity => 0x15,
+            EventType::ProfanityEnd => 0x16,
+            EventType::Sync(n) => 0xe0 | (n & 0x0f),
+            EventType::AudioEnd => 0xfd,
+            EventType::AudioFileEnd => 0xfe,
+            EventType::Undefined(b) => b,
+        }
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventType::Padding => f.write_str("Padding"),
+            EventType::EndOfInitialSilence => f.write_str("End of initial silence"),
+            EventType::IntroStart => f.write_str("Intro start"),
+            EventType::MainPartStart => f.write_str("Main part start"),
+            EventType::OutroStart => f.write_str("Outro start"),
+            EventType::OutroEnd => f.write_str("Outro end"),
+            EventType::VerseStart => f.write_str("Verse start"),
+            EventType::RefrainStart => f.write_str("Refrain start"),
+            EventType::InterludeStart => f.write_str("Interlude start"),
+            EventType::ThemeStart => f.write_str("Theme start"),
+            EventType::VariationStart => f.write_str("Variation start"),
+            EventType::KeyChange => f.write_str("Key change"),
+            EventType::TimeChange => f.write_str("Time change"),
+            EventType::MomentaryUnwantedNoise => f.write_str("Momentary unwanted noise"),
+            EventType::SustainedNoise => f.write_str("Sustained noise"),
+            EventType::SustainedNoiseEnd => f.write_str("Sustained noise end"),
+            EventType::IntroEnd => f.write_str("Intro end"),
+            EventType::MainPartEnd => f.write_str("Main part end"),
+            EventType::VerseEnd => f.write_str("Verse end"),
+            EventType::RefrainEnd => f.write_str("Refrain end"),
+            EventType::ThemeEnd => f.write_str("Theme end"),
+            EventType::Profanity => f.write_str("Profanity"),
+            EventType::ProfanityEnd => f.write_str("Profanity end"),
+            EventType::Sync(n) => write!(f, "Sync event {}", n),
+            EventType::AudioEnd => f.write_str("Audio end"),
+            EventType::AudioFileEnd => f.write_str("Audio file end"),
+            EventType::Undefined(b) => write!(f, "Undefined type {}", b),
+        }
+    }
+}
+
 /// The parsed contents of a private frame.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Private {
@@ -801,7 +1048,12 @@ pub struct Private {
 
 impl fmt::Display for Private {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {:x?}", self.owner_identifier, self.private_data)
+        write!(
+            f,
+            "{}: {}",
+            self.owner_identifier,
+            DataSize(self.private_data.len())
+        )
     }
 }
 
@@ -811,6 +1063,111 @@ impl From<Private> for Frame {
     }
 }
 
+/// The parsed contents of a unique file identifier frame, such as the recording ID written by
+/// MusicBrainz taggers.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct UniqueFileIdentifier {
+    /// Owner identifier, usually a URL of the database the identifier belongs to, e.g.
+    /// `http://musicbrainz.org`.
+    pub owner_identifier: String,
+    /// The identifier, up to 64 bytes.
+    pub identifier: Vec<u8>,
+}
+
+impl fmt::Display for UniqueFileIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match str::from_utf8(&self.identifier) {
+            Ok(identifier) if !identifier.contains(char::is_control) => {
+                write!(f, "{}: {}", self.owner_identifier, identifier)
+            }
+            _ => write!(
+                f,
+                "{}: {}",
+                self.owner_identifier,
+                DataSize(self.identifier.len())
+            ),
+        }
+    }
+}
+
+impl From<UniqueFileIdentifier> for Frame {
+    fn from(c: UniqueFileIdentifier) -> Self {
+        Self::with_content("UFID", Content::UniqueFileIdentifier(c))
+    }
+}
+
+/// The parsed contents of a signature frame, signing the frames of a group.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Signature {
+    /// The group symbol of the frames the signature covers.
+    pub group_symbol: u8,
+    /// The signature data.
+    pub signature: Vec<u8>,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Group {}: {}",
+            self.group_symbol,
+            DataSize(self.signature.len())
+        )
+    }
+}
+
+impl From<Signature> for Frame {
+    fn from(c: Signature) -> Self {
+        Self::with_content("SIGN", Content::Signature(c))
+    }
+}
+
+/// The parsed contents of a seek frame, pointing to a further tag later in the file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Seek {
+    /// The minimum number of bytes from the end of this tag to the start of the next one.
+    pub offset: u32,
+}
+
+impl fmt::Display for Seek {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Next tag {} bytes after this one", self.offset)
+    }
+}
+
+impl From<Seek> for Frame {
+    fn from(c: Seek) -> Self {
+        Self::with_content("SEEK", Content::Seek(c))
+    }
+}
+
+/// The parsed contents of an audio seek point index frame, a table of positions in the audio
+/// data for seeking in files with a variable bitrate.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct AudioSeekPointIndex {
+    /// The byte offset of the indexed audio data from the start of the file.
+    pub data_start: u32,
+    /// The length in bytes of the indexed audio data.
+    pub data_length: u32,
+    /// The bits used to store each index point, 8 or 16.
+    pub bits_per_point: u8,
+    /// The index points, the position of each equally long part of the audio as a fraction of
+    /// `data_length`, scaled to `bits_per_point`. At most 65535.
+    pub points: Vec<u16>,
+}
+
+impl fmt::Display for AudioSeekPointIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Audio seek point index, {} points", self.points.len())
+    }
+}
+
+impl From<AudioSeekPointIndex> for Frame {
+    fn from(c: AudioSeekPointIndex) -> Self {
+        Self::with_content("ASPI", Content::AudioSeekPointIndex(c))
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[allow(missing_docs)]
 pub struct TableOfContents {
@@ -826,9 +1183,13 @@ impl fmt::Display for TableOfContents {
         let frames: Vec<&str> = self.frames.iter().map(|f| f.id()).collect();
         write!(
             f,
-            "isTopLevel:{top_level}, isOrdered:{ordered}, childList: []: {elements}, frames:{frames}",
-            top_level = self.top_level,
-            ordered = self.ordered,
+            "{level}, {order}, elements: {elements}; frames: {frames}",
+            level = if self.top_level {
+                "top level"
+            } else {
+                "nested"
+            },
+            order = if self.ordered { "ordered" } else { "unordered" },
             elements = self.elements.join(", "),
             frames = frames.join(", "),
         )
@@ -867,8 +1228,58 @@ pub struct Unknown {
     pub version: Version,
 }
 
+impl Unknown {
+    /// Decodes the contents as a picture, assuming they are those of an `APIC` frame, or of a
+    /// `PIC` frame in ID3v2.2. Pictures are only decoded while reading with the `decode_picture`
+    /// feature, without it they are kept as `Unknown`.
+    pub fn to_picture(&self) -> crate::id3::Result<Picture> {
+        stream::frame::content::decode_picture(&self.data, self.version)
+    }
+}
+
 impl fmt::Display for Unknown {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}, {} bytes", self.version, self.data.len())
+        write!(f, "{}, {}", self.version, DataSize(self.data.len()))
     }
 }
+
+/// Formats a byte count for display, e.g. `512 bytes`, `123 KB` or `4.5 MB`.
+struct DataSize(usize);
+
+impl fmt::Display for DataSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const KB: usize = 1024;
+        const MB: usize = 1024 * KB;
+        match self.0 {
+            n if n < KB => write!(f, "{} bytes", n),
+            n if n < MB => write!(f, "{} KB", (n + KB / 2) / KB),
+            n => write!(f, "{:.1} MB", n as f64 / MB as f64),
+        }
+    }
+}
+
+#[test]
+fn test_content_display() {
+    let picture = Content::Picture(Picture {
+        mime_type: "image/jpeg".to_string(),
+        picture_type: PictureType::CoverFront,
+        description: String::new(),
+        data: vec![0; 123 * 1024],
+    });
+    assert_eq!(picture.to_string(), "image/jpeg 123 KB (Front cover)");
+
+    let lyrics = Content::SynchronisedLyrics(SynchronisedLyrics {
+        lang: "eng".to_string(),
+        timestamp_format: TimestampFormat::Ms,
+        content_type: SynchronisedLyricsType::Lyrics,
+        description: String::new(),
+        content: vec![(0, "a".to_string()), (1000, "b".to_string())],
+    });
+    assert_eq!(lyrics.to_string(), "Lyrics, 2 entries (ms)");
+
+    let private = Content::Private(Private {
+        owner_identifier: "owner".to_string(),
+        private_data: vec![0; 3],
+    });
+    assert_eq!(private.to_string(), "owner: 3 bytes");
+}
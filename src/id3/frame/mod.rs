@@ -5,9 +5,11 @@ use std::fmt;
 use std::str;
 
 pub use self::content::{
-    Chapter, Comment, Content, EncapsulatedObject, ExtendedLink, ExtendedText, Lyrics,
-    MpegLocationLookupTable, MpegLocationLookupTableReference, Picture, PictureType, Popularimeter,
-    Private, SynchronisedLyrics, SynchronisedLyricsType, TableOfContents, TimestampFormat, Unknown,
+    AudioSeekPointIndex, Chapter, Comment, Content, EncapsulatedObject, EventTimingCodes,
+    EventType, ExtendedLink, ExtendedText, Lyrics, MpegLocationLookupTable,
+    MpegLocationLookupTableReference, Picture, PictureType, Popularimeter, Private, Seek,
+    Signature, SynchronisedLyrics, SynchronisedLyricsType, TableOfContents, TimestampFormat,
+    UniqueFileIdentifier, Unknown,
 };
 pub use self::timestamp::Timestamp;
 
@@ -70,7 +72,7 @@ impl Frame {
         };
         // The matching groups must match the decoding groups of stream/frame/content.rs:decode().
         match (id.as_str(), &self.content) {
-            ("GRP1", Content::Text(_)) => Ok(()),
+            ("GRP1" | "MVNM" | "MVIN", Content::Text(_)) => Ok(()),
             (id, Content::Text(_)) if id.starts_with('T') => Ok(()),
             ("TXXX", Content::ExtendedText(_)) => Ok(()),
             (id, Content::Link(_)) if id.starts_with('W') => Ok(()),
@@ -83,7 +85,12 @@ impl Frame {
             ("APIC", Content::Picture(_)) => Ok(()),
             ("CHAP", Content::Chapter(_)) => Ok(()),
             ("MLLT", Content::MpegLocationLookupTable(_)) => Ok(()),
+            ("ETCO", Content::EventTimingCodes(_)) => Ok(()),
             ("PRIV", Content::Private(_)) => Ok(()),
+            ("UFID", Content::UniqueFileIdentifier(_)) => Ok(()),
+            ("SIGN", Content::Signature(_)) => Ok(()),
+            ("SEEK", Content::Seek(_)) => Ok(()),
+            ("ASPI", Content::AudioSeekPointIndex(_)) => Ok(()),
             ("CTOC", Content::TableOfContents(_)) => Ok(()),
             (_, Content::Unknown(_)) => Ok(()),
             (id, content) => {
@@ -100,7 +107,12 @@ impl Frame {
                     Content::EncapsulatedObject(_) => "EncapsulatedObject",
                     Content::Chapter(_) => "Chapter",
                     Content::MpegLocationLookupTable(_) => "MpegLocationLookupTable",
+                    Content::EventTimingCodes(_) => "EventTimingCodes",
                     Content::Private(_) => "PrivateFrame",
+                    Content::UniqueFileIdentifier(_) => "UniqueFileIdentifier",
+                    Content::Signature(_) => "Signature",
+                    Content::Seek(_) => "Seek",
+                    Content::AudioSeekPointIndex(_) => "AudioSeekPointIndex",
                     Content::TableOfContents(_) => "TableOfContents",
                     Content::Unknown(_) => "Unknown",
                 };
@@ -169,7 +181,7 @@ impl Frame {
     ///
     /// # Example
     /// ```
-    /// use id3::Frame;
+    /// use xm_decryptor::id3::Frame;
     ///
     /// let frame = Frame::text("TPE1", "Armin van Buuren");
     /// assert_eq!(frame.content().text(), Some("Armin van Buuren"));
@@ -184,7 +196,7 @@ impl Frame {
     ///
     /// # Example
     /// ```
-    /// use id3::Frame;
+    /// use xm_decryptor::id3::Frame;
     ///
     /// let frame = Frame::link("WCOM", "https://wwww.arminvanbuuren.com");
     /// assert_eq!(frame.content().link(), Some("https://wwww.arminvanbuuren.com"));
@@ -423,6 +435,12 @@ impl Frame {
             "WPB" => "Publishers official webpage",
             "WXX" => "User defined URL link frame",
 
+            // Non-standard ids written by iTunes
+            "MVIN" => "Movement number/count",
+            "MVNM" => "Movement name",
+            "TCMP" => "iTunes compilation flag",
+            "TVSH" => "TV show name",
+
             v => v,
         }
     }
@@ -448,7 +466,7 @@ impl fmt::Display for Frame {
 
 macro_rules! convert_2_to_3_and_back {
     ( $( $id2:expr, $id3:expr ),* ) => {
-        fn convert_id_2_to_3(id: impl AsRef<str>) -> Option<&'static str> {
+        pub(crate) fn convert_id_2_to_3(id: impl AsRef<str>) -> Option<&'static str> {
             match id.as_ref() {
                 $($id2 => Some($id3),)*
                 _ => None,
@@ -484,6 +502,8 @@ convert_2_to_3_and_back!(
 
     "MCI", "MCDI",
     "MLL", "MLLT",
+    "MVI", "MVIN",
+    "MVN", "MVNM",
 
     "PIC", "APIC",
     "POP", "POPM",
@@ -498,6 +518,7 @@ convert_2_to_3_and_back!(
     "TBP", "TBPM",
     "TCM", "TCOM",
     "TCO", "TCON",
+    "TCP", "TCMP",
     "TCR", "TCOP",
     "TDA", "TDAT",
     "TDY", "TDLY",
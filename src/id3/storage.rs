@@ -7,6 +7,7 @@
 
 use std::cmp;
 use std::cmp::Ordering;
+#[cfg(feature = "fs")]
 use std::fs;
 use std::io::{self, Write};
 use std::ops;
@@ -19,6 +20,7 @@ pub trait Storage<'a> {
     type Writer: io::Write + io::Seek + 'a;
 
     /// Opens the storage for reading.
+    #[allow(dead_code)]
     fn reader(&'a mut self) -> io::Result<Self::Reader>;
     /// Opens the storage for writing.
     ///
@@ -50,7 +52,7 @@ pub trait StorageFile: io::Read + io::Write + io::Seek + private::Sealed {
     fn set_len(&mut self, new_len: u64) -> io::Result<()>;
 }
 
-impl<'a, T> StorageFile for &'a mut T
+impl<T> StorageFile for &mut T
 where
     T: StorageFile,
 {
@@ -59,6 +61,7 @@ where
     }
 }
 
+#[cfg(feature = "fs")]
 impl StorageFile for fs::File {
     fn set_len(&mut self, new_len: u64) -> io::Result<()> {
         fs::File::set_len(self, new_len)
@@ -283,7 +286,8 @@ where
 mod private {
     pub trait Sealed {}
 
-    impl<'a, T: Sealed> Sealed for &'a mut T {}
+    impl<T: Sealed> Sealed for &mut T {}
+    #[cfg(feature = "fs")]
     impl Sealed for std::fs::File {}
     impl Sealed for std::io::Cursor<Vec<u8>> {}
 }
@@ -1,24 +1,30 @@
+use crate::id3::charset::{self, LegacyCharset};
 use crate::id3::frame::{
-    Chapter, Comment, Content, EncapsulatedObject, ExtendedLink, ExtendedText, Lyrics,
-    MpegLocationLookupTable, MpegLocationLookupTableReference, Picture, PictureType, Popularimeter,
-    Private, SynchronisedLyrics, SynchronisedLyricsType, TableOfContents, TimestampFormat, Unknown,
+    AudioSeekPointIndex, Chapter, Comment, Content, EncapsulatedObject, EventTimingCodes,
+    ExtendedLink, ExtendedText, Lyrics, MpegLocationLookupTable, MpegLocationLookupTableReference,
+    Picture, PictureType, Popularimeter, Private, Seek, Signature, SynchronisedLyrics,
+    SynchronisedLyricsType, TableOfContents, TimestampFormat, UniqueFileIdentifier, Unknown,
 };
 use crate::id3::stream::encoding::Encoding;
 use crate::id3::stream::frame;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::tag::Version;
 use crate::id3::{Error, ErrorKind};
+use std::cell::Cell;
 use std::convert::{TryFrom, TryInto};
-use std::io;
+use std::io::{self, Read};
 use std::iter;
 use std::mem::size_of;
 
-struct Encoder<W: io::Write> {
+struct Encoder<'a, W: io::Write> {
     w: W,
     version: Version,
     encoding: Encoding,
+    /// The buffers for embedded frames.
+    scratch: &'a mut frame::Scratch,
 }
 
-impl<W: io::Write> Encoder<W> {
+impl<W: io::Write> Encoder<'_, W> {
     fn bytes(&mut self, bytes: impl AsRef<[u8]>) -> crate::id3::Result<()> {
         let bytes = bytes.as_ref();
         self.w.write_all(bytes)?;
@@ -141,7 +147,7 @@ impl<W: io::Write> Encoder<W> {
             _ => unreachable!(),
         })?;
         self.bytes(
-            &content
+            content
                 .lang
                 .bytes()
                 .chain(iter::repeat(b' '))
@@ -243,7 +249,19 @@ impl<W: io::Write> Encoder<W> {
         self.uint32(content.start_offset)?;
         self.uint32(content.end_offset)?;
         for frame in &content.frames {
-            frame::encode(&mut self.w, frame, self.version, false)?;
+            frame::encode(&mut self.w, frame, self.version, false, self.scratch)?;
+        }
+        Ok(())
+    }
+
+    fn event_timing_codes_content(&mut self, content: &EventTimingCodes) -> crate::id3::Result<()> {
+        self.byte(match content.timestamp_format {
+            TimestampFormat::Mpeg => 1,
+            TimestampFormat::Ms => 2,
+        })?;
+        for (event_type, timestamp) in &content.events {
+            self.byte(u8::from(*event_type))?;
+            self.uint32(*timestamp)?;
         }
         Ok(())
     }
@@ -253,7 +271,7 @@ impl<W: io::Write> Encoder<W> {
         content: &MpegLocationLookupTable,
     ) -> crate::id3::Result<()> {
         let ref_packed_size = content.bits_for_bytes + content.bits_for_millis;
-        if ref_packed_size % 4 != 0 {
+        if !ref_packed_size.is_multiple_of(4) {
             return Err(Error::new(
                 ErrorKind::InvalidInput,
                 "MLLT bits_for_bytes + bits_for_millis must be a multiple of 4",
@@ -300,6 +318,58 @@ impl<W: io::Write> Encoder<W> {
         Ok(())
     }
 
+    fn unique_file_identifier_content(
+        &mut self,
+        content: &UniqueFileIdentifier,
+    ) -> crate::id3::Result<()> {
+        self.string_with_other_encoding(Encoding::Latin1, &content.owner_identifier)?;
+        self.byte(0)?;
+        self.bytes(&content.identifier)
+    }
+
+    fn signature_content(&mut self, content: &Signature) -> crate::id3::Result<()> {
+        self.byte(content.group_symbol)?;
+        self.bytes(&content.signature)
+    }
+
+    fn seek_content(&mut self, content: &Seek) -> crate::id3::Result<()> {
+        self.uint32(content.offset)
+    }
+
+    fn audio_seek_point_index_content(
+        &mut self,
+        content: &AudioSeekPointIndex,
+    ) -> crate::id3::Result<()> {
+        let num_points = u16::try_from(content.points.len()).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "ASPI can not hold more than 65535 index points",
+            )
+        })?;
+        self.uint32(content.data_start)?;
+        self.uint32(content.data_length)?;
+        self.uint16(num_points)?;
+        self.byte(content.bits_per_point)?;
+        for &point in &content.points {
+            match content.bits_per_point {
+                8 => self.byte(u8::try_from(point).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        "ASPI index point does not fit in 8 bits",
+                    )
+                })?)?,
+                16 => self.uint16(point)?,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "ASPI bits_per_point must be 8 or 16",
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn table_of_contents_content(&mut self, content: &TableOfContents) -> crate::id3::Result<()> {
         self.string_with_other_encoding(Encoding::Latin1, &content.element_id)?;
         self.byte(0)?;
@@ -320,24 +390,26 @@ impl<W: io::Write> Encoder<W> {
             self.byte(0)?;
         }
         for frame in &content.frames {
-            frame::encode(&mut self.w, frame, self.version, false)?;
+            frame::encode(&mut self.w, frame, self.version, false, self.scratch)?;
         }
         Ok(())
     }
 }
 
+/// Appends the encoded `content` to `buf`. Frames embedded in the content are encoded with the
+/// buffers in `scratch`.
 pub fn encode(
-    mut writer: impl io::Write,
+    buf: &mut Vec<u8>,
     content: &Content,
     version: Version,
     encoding: Encoding,
-) -> crate::id3::Result<usize> {
-    let mut buf = Vec::new();
-
+    scratch: &mut frame::Scratch,
+) -> crate::id3::Result<()> {
     let mut encoder = Encoder {
-        w: &mut buf,
+        w: buf,
         version,
         encoding,
+        scratch,
     };
     match content {
         Content::Text(c) => encoder.text_content(c)?,
@@ -352,25 +424,60 @@ pub fn encode(
         Content::Picture(c) => encoder.picture_content(c)?,
         Content::Chapter(c) => encoder.chapter_content(c)?,
         Content::MpegLocationLookupTable(c) => encoder.mpeg_location_lookup_table_content(c)?,
+        Content::EventTimingCodes(c) => encoder.event_timing_codes_content(c)?,
         Content::Private(c) => encoder.private_content(c)?,
+        Content::UniqueFileIdentifier(c) => encoder.unique_file_identifier_content(c)?,
+        Content::Signature(c) => encoder.signature_content(c)?,
+        Content::Seek(c) => encoder.seek_content(c)?,
+        Content::AudioSeekPointIndex(c) => encoder.audio_seek_point_index_content(c)?,
         Content::TableOfContents(c) => encoder.table_of_contents_content(c)?,
         Content::Unknown(c) => encoder.bytes(&c.data)?,
     };
+    Ok(())
+}
 
-    writer.write_all(&buf)?;
-    Ok(buf.len())
+/// Decodes the contents `data` of an `APIC` frame, or of a `PIC` frame in ID3v2.2.
+pub(crate) fn decode_picture(data: &[u8], version: Version) -> crate::id3::Result<Picture> {
+    let decoder = Decoder {
+        r: data,
+        version,
+        latin1_fallback: &[],
+        fell_back: &Cell::new(false),
+    };
+    let content = match version {
+        Version::Id3v22 => decoder.picture_content_v2()?,
+        Version::Id3v23 | Version::Id3v24 => decoder.picture_content_v3()?,
+    };
+    match content {
+        Content::Picture(picture) => Ok(picture),
+        _ => unreachable!("picture decoders return pictures"),
+    }
 }
 
+/// Decodes the content of the frame `id` read from `reader`, within the limits of `options`.
 pub fn decode(
     id: &str,
     version: Version,
-    mut reader: impl io::Read,
+    reader: impl io::Read,
+    options: &ParseOptions,
 ) -> crate::id3::Result<(Content, Option<Encoding>)> {
+    let max_size = options.max_frame_size;
     let mut data = Vec::new();
-    reader.read_to_end(&mut data)?;
+    reader
+        .take(u64::from(max_size) + 1)
+        .read_to_end(&mut data)?;
+    if data.len() > max_size as usize {
+        return Err(Error::new(
+            ErrorKind::LimitExceeded,
+            format!("frame content exceeds the limit of {} bytes", max_size),
+        ));
+    }
+    let fell_back = Cell::new(false);
     let decoder = Decoder {
         r: &mut data,
         version,
+        latin1_fallback: &options.latin1_fallback,
+        fell_back: &fell_back,
     };
 
     let mut encoding = None;
@@ -406,19 +513,32 @@ pub fn decode(
         }
         id if id.starts_with('T') => decoder.text_content(),
         id if id.starts_with('W') => decoder.link_content(),
-        "GRP1" => decoder.text_content(),
+        "GRP1" | "MVNM" | "MVIN" | "MVN" | "MVI" => decoder.text_content(),
         "CHAP" => decoder.chapter_content(),
         "MLLT" => decoder.mpeg_location_lookup_table_content(),
+        "ETCO" | "ETC" => decoder.event_timing_codes_content(),
         "PRIV" => decoder.private_content(),
+        "UFID" | "UFI" => decoder.unique_file_identifier_content(),
+        "SIGN" => decoder.signature_content(),
+        "SEEK" => decoder.seek_content(),
+        "ASPI" => decoder.audio_seek_point_index_content(),
         "CTOC" => decoder.table_of_contents_content(),
         _ => Ok(Content::Unknown(Unknown { data, version })),
     }?;
+    if fell_back.get() {
+        encoding = None;
+    }
     Ok((content, encoding))
 }
 
 struct Decoder<'a> {
     r: &'a [u8],
     version: Version,
+    /// The character sets tried for text claiming ISO-8859-1 that isn't ASCII.
+    latin1_fallback: &'a [LegacyCharset],
+    /// Set when text was decoded in one of `latin1_fallback`, so the frame is written back in
+    /// the default encoding rather than lossily in ISO-8859-1.
+    fell_back: &'a Cell<bool>,
 }
 
 impl<'a> Decoder<'a> {
@@ -457,8 +577,19 @@ impl<'a> Decoder<'a> {
         Ok(u32::from_be_bytes(a))
     }
 
+    /// Decodes text in `encoding`, or in a fallback character set if it claims ISO-8859-1.
+    fn text(&self, encoding: Encoding, bytes: &[u8]) -> crate::id3::Result<String> {
+        if encoding == Encoding::Latin1 {
+            if let Some(text) = charset::decode_fallback(bytes, self.latin1_fallback) {
+                self.fell_back.set(true);
+                return Ok(text);
+            }
+        }
+        encoding.decode(bytes)
+    }
+
     fn string_until_eof(&mut self, encoding: Encoding) -> crate::id3::Result<String> {
-        encoding.decode(self.r)
+        self.text(encoding, self.r)
     }
 
     fn string_delimited(&mut self, encoding: Encoding) -> crate::id3::Result<String> {
@@ -467,7 +598,7 @@ impl<'a> Decoder<'a> {
         let delim_len = delim_len(encoding);
         let b = self.bytes(delim)?;
         self.bytes(delim_len)?; // Skip.
-        encoding.decode(b)
+        self.text(encoding, b)
     }
 
     fn string_fixed(&mut self, bytes_len: usize) -> crate::id3::Result<String> {
@@ -497,7 +628,8 @@ impl<'a> Decoder<'a> {
                 None => (self.r.len(), self.r.len()),
             },
         };
-        let text = encoding.decode(self.bytes(end)?)?;
+        let bytes = self.bytes(end)?;
+        let text = self.text(encoding, bytes)?;
         let text = match self.version {
             Version::Id3v22 | Version::Id3v23 => text.replace('/', "\0"),
             Version::Id3v24 => text,
@@ -591,8 +723,7 @@ impl<'a> Decoder<'a> {
         let counter = {
             let r = match self.r.len() {
                 0..=8 => self.r,
-                9.. => &self.r[..8],
-                _ => unreachable!(),
+                _ => &self.r[..8],
             };
             let mut bin = [0; 8];
             bin[8 - r.len()..].copy_from_slice(r);
@@ -688,7 +819,7 @@ impl<'a> Decoder<'a> {
             .position(|w| w == text_delim)
         {
             let i = i * text_delim.len();
-            let text = encoding.decode(&self.r[..i])?;
+            let text = self.text(encoding, &self.r[..i])?;
 
             self.r = &self.r[i + text_delim.len()..];
 
@@ -718,8 +849,9 @@ impl<'a> Decoder<'a> {
         let start_offset = self.uint32()?;
         let end_offset = self.uint32()?;
         let mut frames = Vec::new();
-        while let Some((_advance, frame)) = frame::decode(&mut self.r, self.version)? {
-            frames.push(frame);
+        let options = ParseOptions::default();
+        while let Some((_advance, frame)) = frame::decode(&mut self.r, self.version, &options)? {
+            frames.extend(frame);
         }
         Ok(Content::Chapter(Chapter {
             element_id,
@@ -731,6 +863,29 @@ impl<'a> Decoder<'a> {
         }))
     }
 
+    fn event_timing_codes_content(mut self) -> crate::id3::Result<Content> {
+        let timestamp_format = match self.byte()? {
+            1 => TimestampFormat::Mpeg,
+            2 => TimestampFormat::Ms,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Parsing,
+                    "invalid ETCO timestamp format",
+                ))
+            }
+        };
+        let mut events = Vec::new();
+        while !self.r.is_empty() {
+            let event_type = self.byte()?.into();
+            let timestamp = self.uint32()?;
+            events.push((event_type, timestamp));
+        }
+        Ok(Content::EventTimingCodes(EventTimingCodes {
+            timestamp_format,
+            events,
+        }))
+    }
+
     fn mpeg_location_lookup_table_content(mut self) -> crate::id3::Result<Content> {
         let frames_between_reference = self.uint16()?;
         let bytes_between_reference = self.uint24()?;
@@ -810,6 +965,57 @@ impl<'a> Decoder<'a> {
             private_data,
         }))
     }
+
+    fn unique_file_identifier_content(mut self) -> crate::id3::Result<Content> {
+        let owner_identifier = self.string_delimited(Encoding::Latin1)?;
+        let identifier = self.r.to_vec();
+
+        Ok(Content::UniqueFileIdentifier(UniqueFileIdentifier {
+            owner_identifier,
+            identifier,
+        }))
+    }
+
+    fn signature_content(mut self) -> crate::id3::Result<Content> {
+        let group_symbol = self.byte()?;
+        let signature = self.r.to_vec();
+        Ok(Content::Signature(Signature {
+            group_symbol,
+            signature,
+        }))
+    }
+
+    fn seek_content(mut self) -> crate::id3::Result<Content> {
+        let offset = self.uint32()?;
+        Ok(Content::Seek(Seek { offset }))
+    }
+
+    fn audio_seek_point_index_content(mut self) -> crate::id3::Result<Content> {
+        let data_start = self.uint32()?;
+        let data_length = self.uint32()?;
+        let num_points = self.uint16()?;
+        let bits_per_point = self.byte()?;
+        let mut points = Vec::with_capacity(usize::from(num_points));
+        for _ in 0..num_points {
+            points.push(match bits_per_point {
+                8 => u16::from(self.byte()?),
+                16 => self.uint16()?,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::Parsing,
+                        "invalid ASPI bits per index point",
+                    ))
+                }
+            });
+        }
+        Ok(Content::AudioSeekPointIndex(AudioSeekPointIndex {
+            data_start,
+            data_length,
+            bits_per_point,
+            points,
+        }))
+    }
+
     fn table_of_contents_content(mut self) -> crate::id3::Result<Content> {
         let element_id = self.string_delimited(Encoding::Latin1)?;
         let flags = self.byte()?;
@@ -821,8 +1027,9 @@ impl<'a> Decoder<'a> {
             elements.push(self.string_delimited(Encoding::Latin1)?);
         }
         let mut frames = Vec::new();
-        while let Some((_advance, frame)) = frame::decode(&mut self.r, self.version)? {
-            frames.push(frame);
+        let options = ParseOptions::default();
+        while let Some((_advance, frame)) = frame::decode(&mut self.r, self.version, &options)? {
+            frames.extend(frame);
         }
         Ok(Content::TableOfContents(TableOfContents {
             element_id,
@@ -1,8 +1,10 @@
 use crate::id3::frame::Content;
 use crate::id3::frame::Frame;
 use crate::id3::stream::encoding::Encoding;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::stream::unsynch;
 use crate::id3::tag::Version;
+use crate::id3::{Error, ErrorKind};
 use flate2::read::ZlibDecoder;
 use std::io;
 use std::str;
@@ -12,14 +14,17 @@ pub mod v2;
 pub mod v3;
 pub mod v4;
 
+/// Decodes the next frame, returning the bytes it occupied and the frame, or `None` for a frame
+/// skipped by [`ParseOptions::only_frames`].
 pub fn decode(
     reader: impl io::Read,
     version: Version,
-) -> crate::id3::Result<Option<(usize, Frame)>> {
+    options: &ParseOptions,
+) -> crate::id3::Result<Option<(usize, Option<Frame>)>> {
     match version {
         Version::Id3v22 => unimplemented!(),
-        Version::Id3v23 => v3::decode(reader),
-        Version::Id3v24 => v4::decode(reader),
+        Version::Id3v23 => v3::decode(reader, options),
+        Version::Id3v24 => v4::decode(reader, options),
     }
 }
 
@@ -29,18 +34,62 @@ fn decode_content(
     id: &str,
     compression: bool,
     unsynchronisation: bool,
+    options: &ParseOptions,
 ) -> crate::id3::Result<(Content, Option<Encoding>)> {
     if unsynchronisation {
         let reader_unsynch = unsynch::Reader::new(reader);
         if compression {
-            content::decode(id, version, ZlibDecoder::new(reader_unsynch))
+            content::decode(id, version, ZlibDecoder::new(reader_unsynch), options)
         } else {
-            content::decode(id, version, reader_unsynch)
+            content::decode(id, version, reader_unsynch, options)
         }
     } else if compression {
-        content::decode(id, version, ZlibDecoder::new(reader))
+        content::decode(id, version, ZlibDecoder::new(reader), options)
     } else {
-        content::decode(id, version, reader)
+        content::decode(id, version, reader, options)
+    }
+}
+
+/// Reads past the `size` bytes of content of a skipped frame.
+fn skip(reader: impl io::Read, size: u64) -> crate::id3::Result<()> {
+    if io::copy(&mut reader.take(size), &mut io::sink())? < size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+/// Checks the content size declared by a frame header against the limit of the options.
+fn check_size(size: usize, options: &ParseOptions) -> crate::id3::Result<()> {
+    if size > options.max_frame_size as usize {
+        return Err(Error::new(
+            ErrorKind::LimitExceeded,
+            format!(
+                "declared frame size of {} bytes exceeds the limit of {} bytes",
+                size, options.max_frame_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Buffers reused from frame to frame, so encoding a tag doesn't allocate for every frame.
+#[derive(Debug, Default)]
+pub struct Scratch {
+    /// The content of the frame being encoded.
+    content: Vec<u8>,
+    /// The buffers for the frames embedded in chapter and table of contents frames, created when
+    /// the first one is encoded.
+    embedded: Option<Box<Scratch>>,
+}
+
+impl Scratch {
+    /// Returns the emptied content buffer and the buffers for embedded frames.
+    fn split(&mut self) -> (&mut Vec<u8>, &mut Scratch) {
+        self.content.clear();
+        (
+            &mut self.content,
+            self.embedded.get_or_insert_with(Default::default),
+        )
     }
 }
 
@@ -49,9 +98,10 @@ pub fn encode(
     frame: &Frame,
     version: Version,
     unsynchronization: bool,
+    scratch: &mut Scratch,
 ) -> crate::id3::Result<usize> {
     match version {
-        Version::Id3v22 => v2::encode(writer, frame),
+        Version::Id3v22 => v2::encode(writer, frame, scratch),
         Version::Id3v23 => {
             let mut flags = v3::Flags::empty();
             flags.set(
@@ -62,7 +112,7 @@ pub fn encode(
                 v3::Flags::FILE_ALTER_PRESERVATION,
                 frame.file_alter_preservation(),
             );
-            v3::encode(writer, frame, flags)
+            v3::encode(writer, frame, flags, scratch)
         }
         Version::Id3v24 => {
             let mut flags = v4::Flags::empty();
@@ -75,7 +125,7 @@ pub fn encode(
                 v4::Flags::FILE_ALTER_PRESERVATION,
                 frame.file_alter_preservation(),
             );
-            v4::encode(writer, frame, flags)
+            v4::encode(writer, frame, flags, scratch)
         }
     }
 }
@@ -1,12 +1,16 @@
 use crate::id3::frame::Frame;
 use crate::id3::stream::encoding::Encoding;
 use crate::id3::stream::frame;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::tag::Version;
 use crate::id3::{Error, ErrorKind};
 use byteorder::{BigEndian, ByteOrder};
 use std::io;
 
-pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Frame)>> {
+pub fn decode(
+    mut reader: impl io::Read,
+    options: &ParseOptions,
+) -> crate::id3::Result<Option<(usize, Option<Frame>)>> {
     let mut frame_header = [0; 6];
     let nread = reader.read(&mut frame_header)?;
     if nread < frame_header.len() || frame_header[0] == 0x00 {
@@ -16,19 +20,33 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
     let sizebytes = &frame_header[3..6];
     let read_size =
         (u32::from(sizebytes[0]) << 16) | (u32::from(sizebytes[1]) << 8) | u32::from(sizebytes[2]);
-    let (content, encoding) =
-        super::content::decode(id, Version::Id3v22, reader.take(u64::from(read_size)))?;
+    frame::check_size(read_size as usize, options)?;
+    if !options.decodes(id) {
+        frame::skip(reader, u64::from(read_size))?;
+        return Ok(Some((6 + read_size as usize, None)));
+    }
+    let (content, encoding) = super::content::decode(
+        id,
+        Version::Id3v22,
+        reader.take(u64::from(read_size)),
+        options,
+    )?;
     let frame = Frame::with_content(id, content).set_encoding(encoding);
-    Ok(Some((6 + read_size as usize, frame)))
+    Ok(Some((6 + read_size as usize, Some(frame))))
 }
 
-pub fn encode(mut writer: impl io::Write, frame: &Frame) -> crate::id3::Result<usize> {
-    let mut content_buf = Vec::new();
+pub fn encode(
+    mut writer: impl io::Write,
+    frame: &Frame,
+    scratch: &mut frame::Scratch,
+) -> crate::id3::Result<usize> {
+    let (content_buf, embedded) = scratch.split();
     frame::content::encode(
-        &mut content_buf,
+        content_buf,
         frame.content(),
         Version::Id3v22,
         frame.encoding().unwrap_or(Encoding::UTF16),
+        embedded,
     )?;
     assert_ne!(0, content_buf.len());
     let id = frame.id_for_version(Version::Id3v22).ok_or_else(|| {
@@ -42,6 +60,6 @@ pub fn encode(mut writer: impl io::Write, frame: &Frame) -> crate::id3::Result<u
     let mut size_buf = [0; 4];
     BigEndian::write_u32(&mut size_buf, content_buf.len() as u32);
     writer.write_all(&size_buf[1..4])?;
-    writer.write_all(&content_buf)?;
+    writer.write_all(content_buf)?;
     Ok(6 + content_buf.len())
 }
@@ -1,13 +1,14 @@
 use crate::id3::frame::Frame;
 use crate::id3::stream::encoding::Encoding;
 use crate::id3::stream::frame;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::tag::Version;
 use crate::id3::{Error, ErrorKind};
 use bitflags::bitflags;
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use std::io;
+use std::io::{self, Write};
 
 bitflags! {
     pub struct Flags: u16 {
@@ -20,7 +21,10 @@ bitflags! {
     }
 }
 
-pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Frame)>> {
+pub fn decode(
+    mut reader: impl io::Read,
+    options: &ParseOptions,
+) -> crate::id3::Result<Option<(usize, Option<Frame>)>> {
     let mut frame_header = [0; 10];
     let nread = reader.read(&mut frame_header)?;
     if nread < frame_header.len() || frame_header[0] == 0x00 {
@@ -29,6 +33,11 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
     let id = frame::str_from_utf8(&frame_header[0..4])?;
 
     let content_size = BigEndian::read_u32(&frame_header[4..8]) as usize;
+    frame::check_size(content_size, options)?;
+    if !options.decodes(id) {
+        frame::skip(reader, content_size as u64)?;
+        return Ok(Some((10 + content_size, None)));
+    }
     let flags = Flags::from_bits_truncate(BigEndian::read_u16(&frame_header[8..10]));
     if flags.contains(Flags::ENCRYPTION) {
         return Err(Error::new(
@@ -44,7 +53,12 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
 
     let read_size = if flags.contains(Flags::COMPRESSION) {
         let _decompressed_size = reader.read_u32::<BigEndian>()?;
-        content_size - 4
+        content_size.checked_sub(4).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Parsing,
+                "frame is too small for its decompressed size",
+            )
+        })?
     } else {
         content_size
     };
@@ -56,35 +70,34 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
         id,
         flags.contains(Flags::COMPRESSION),
         false,
+        options,
     )?;
     let frame = Frame::with_content(id, content).set_encoding(encoding);
-    Ok(Some((10 + content_size, frame)))
+    Ok(Some((10 + content_size, Some(frame))))
 }
 
 pub fn encode(
     mut writer: impl io::Write,
     frame: &Frame,
     flags: Flags,
+    scratch: &mut frame::Scratch,
 ) -> crate::id3::Result<usize> {
-    let (content_buf, comp_hint_delta, decompressed_size) = if flags.contains(Flags::COMPRESSION) {
+    let (content_buf, embedded) = scratch.split();
+    frame::content::encode(
+        content_buf,
+        frame.content(),
+        Version::Id3v23,
+        frame.encoding().unwrap_or(Encoding::UTF16),
+        embedded,
+    )?;
+    let (comp_hint_delta, decompressed_size) = if flags.contains(Flags::COMPRESSION) {
+        let content_size = content_buf.len();
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        let content_size = frame::content::encode(
-            &mut encoder,
-            frame.content(),
-            Version::Id3v23,
-            frame.encoding().unwrap_or(Encoding::UTF16),
-        )?;
-        let content_buf = encoder.finish()?;
-        (content_buf, 4, Some(content_size))
+        encoder.write_all(content_buf)?;
+        *content_buf = encoder.finish()?;
+        (4, Some(content_size))
     } else {
-        let mut content_buf = Vec::new();
-        frame::content::encode(
-            &mut content_buf,
-            frame.content(),
-            Version::Id3v23,
-            frame.encoding().unwrap_or(Encoding::UTF16),
-        )?;
-        (content_buf, 0, None)
+        (0, None)
     };
 
     writer.write_all({
@@ -97,6 +110,6 @@ pub fn encode(
     if let Some(s) = decompressed_size {
         writer.write_u32::<BigEndian>(s as u32)?;
     }
-    writer.write_all(&content_buf)?;
+    writer.write_all(content_buf)?;
     Ok(10 + comp_hint_delta + content_buf.len())
 }
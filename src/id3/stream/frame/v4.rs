@@ -1,5 +1,6 @@
 use crate::id3::frame::Frame;
 use crate::id3::stream::encoding::Encoding;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::stream::{frame, unsynch};
 use crate::id3::tag::Version;
 use crate::id3::{Error, ErrorKind};
@@ -7,7 +8,7 @@ use bitflags::bitflags;
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use std::io;
+use std::io::{self, Write};
 
 bitflags! {
     pub struct Flags: u16 {
@@ -22,7 +23,10 @@ bitflags! {
     }
 }
 
-pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Frame)>> {
+pub fn decode(
+    mut reader: impl io::Read,
+    options: &ParseOptions,
+) -> crate::id3::Result<Option<(usize, Option<Frame>)>> {
     let mut frame_header = [0; 10];
     let nread = reader.read(&mut frame_header)?;
     if nread < frame_header.len() || frame_header[0] == 0x00 {
@@ -30,6 +34,11 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
     }
     let id = frame::str_from_utf8(&frame_header[0..4])?;
     let content_size = unsynch::decode_u32(BigEndian::read_u32(&frame_header[4..8])) as usize;
+    frame::check_size(content_size, options)?;
+    if !options.decodes(id) {
+        frame::skip(reader, content_size as u64)?;
+        return Ok(Some((10 + content_size, None)));
+    }
     let flags = Flags::from_bits_truncate(BigEndian::read_u16(&frame_header[8..10]));
     if flags.contains(Flags::ENCRYPTION) {
         return Err(Error::new(
@@ -45,7 +54,12 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
 
     let read_size = if flags.contains(Flags::DATA_LENGTH_INDICATOR) {
         let _decompressed_size = unsynch::decode_u32(reader.read_u32::<BigEndian>()?);
-        content_size - 4
+        content_size.checked_sub(4).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Parsing,
+                "frame is too small for its decompressed size",
+            )
+        })?
     } else {
         content_size
     };
@@ -56,44 +70,42 @@ pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Option<(usize, Fr
         id,
         flags.contains(Flags::COMPRESSION),
         flags.contains(Flags::UNSYNCHRONISATION),
+        options,
     )?;
     let frame = Frame::with_content(id, content).set_encoding(encoding);
-    Ok(Some((10 + content_size, frame)))
+    Ok(Some((10 + content_size, Some(frame))))
 }
 
 pub fn encode(
     mut writer: impl io::Write,
     frame: &Frame,
     flags: Flags,
+    scratch: &mut frame::Scratch,
 ) -> crate::id3::Result<usize> {
-    let (mut content_buf, comp_hint_delta, decompressed_size) =
-        if flags.contains(Flags::COMPRESSION) {
-            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-            let content_size = frame::content::encode(
-                &mut encoder,
-                frame.content(),
-                Version::Id3v24,
-                frame.encoding().unwrap_or(Encoding::UTF8),
-            )?;
-            let content_buf = encoder.finish()?;
-            let cd = if flags.contains(Flags::DATA_LENGTH_INDICATOR) {
-                4
-            } else {
-                0
-            };
-            (content_buf, cd, Some(content_size))
+    let (content_buf, embedded) = scratch.split();
+    frame::content::encode(
+        content_buf,
+        frame.content(),
+        Version::Id3v24,
+        frame.encoding().unwrap_or(Encoding::UTF8),
+        embedded,
+    )?;
+    let (comp_hint_delta, decompressed_size) = if flags.contains(Flags::COMPRESSION) {
+        let content_size = content_buf.len();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content_buf)?;
+        *content_buf = encoder.finish()?;
+        let cd = if flags.contains(Flags::DATA_LENGTH_INDICATOR) {
+            4
         } else {
-            let mut content_buf = Vec::new();
-            frame::content::encode(
-                &mut content_buf,
-                frame.content(),
-                Version::Id3v24,
-                frame.encoding().unwrap_or(Encoding::UTF8),
-            )?;
-            (content_buf, 0, None)
+            0
         };
+        (cd, Some(content_size))
+    } else {
+        (0, None)
+    };
     if flags.contains(Flags::UNSYNCHRONISATION) {
-        unsynch::encode_vec(&mut content_buf);
+        unsynch::encode_vec(content_buf);
     }
 
     writer.write_all({
@@ -110,6 +122,6 @@ pub fn encode(
             writer.write_u32::<BigEndian>(unsynch::encode_u32(s as u32))?;
         }
     }
-    writer.write_all(&content_buf)?;
+    writer.write_all(content_buf)?;
     Ok(10 + comp_hint_delta + content_buf.len())
 }
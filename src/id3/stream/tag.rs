@@ -1,16 +1,29 @@
+use crate::id3::charset::LegacyCharset;
+use crate::id3::frame::convert_id_2_to_3;
 use crate::id3::storage::{PlainStorage, Storage, StorageFile};
 use crate::id3::stream::{frame, unsynch};
-use crate::id3::tag::{Tag, Version};
+use crate::id3::tag::{Restrictions, Tag, Version};
 use crate::id3::taglike::TagLike;
-use crate::id3::{Error, ErrorKind};
+use crate::id3::{v1, Error, ErrorKind, Frame};
 use bitflags::bitflags;
-use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder};
 use std::cmp;
+#[cfg(feature = "fs")]
 use std::fs;
 use std::io::{self, Read, Write};
 use std::ops::Range;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
+/// The largest tag size accepted by default, 32 MiB. A synchsafe size can declare up to 256 MiB.
+const DEFAULT_MAX_TAG_SIZE: u32 = 32 * 1024 * 1024;
+
+/// The largest frame size accepted by default, 64 MiB, room for any cover art.
+const DEFAULT_MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+/// The most frames accepted in a tag by default.
+const DEFAULT_MAX_FRAME_COUNT: usize = 65536;
+
 static DEFAULT_FILE_DISCARD: &[&str] = &[
     "AENC", "ETCO", "EQUA", "MLLT", "POSS", "SYLT", "SYTC", "RVAD", "TENC", "TLEN", "TSIZ",
 ];
@@ -31,6 +44,15 @@ bitflags! {
     }
 }
 
+/// The size of the ID3v2.4 footer, a copy of the header starting with `3DI`.
+const FOOTER_SIZE: u64 = 10;
+
+/// The size of an ID3v1 tag, which follows an appended ID3v2 tag.
+const V1_TAG_SIZE: u64 = 128;
+
+/// The flag of the ID3v2.3 extended header that says a CRC-32 follows.
+const V3_CRC_DATA_PRESENT: u16 = 0x8000;
+
 /// Used for sharing code between sync/async parsers, which is mainly complicated by ext_headers.
 struct HeaderBuilder {
     version: Version,
@@ -39,12 +61,12 @@ struct HeaderBuilder {
 }
 
 impl HeaderBuilder {
-    fn with_ext_header(self, size: u32) -> Header {
+    fn with_ext_header(self, ext_header: ExtHeader) -> Header {
         Header {
             version: self.version,
             flags: self.flags,
             tag_size: self.tag_size,
-            ext_header_size: size,
+            ext_header,
         }
     }
 }
@@ -53,9 +75,19 @@ struct Header {
     version: Version,
     flags: Flags,
     tag_size: u32,
+    ext_header: ExtHeader,
+}
 
-    // TODO: Extended header.
-    ext_header_size: u32,
+/// The parts of the extended header that are used.
+#[derive(Default)]
+struct ExtHeader {
+    /// The bytes the extended header occupies in the tag.
+    size: u32,
+    /// The CRC-32 of the frames, and in ID3v2.4 of the padding too.
+    crc: Option<u32>,
+    /// The padding after the frames, declared by ID3v2.3 only.
+    padding: u32,
+    restrictions: Option<Restrictions>,
 }
 
 impl Header {
@@ -63,12 +95,25 @@ impl Header {
         10 // Raw header.
     }
 
-    fn frame_bytes(&self) -> u64 {
-        u64::from(self.tag_size) - u64::from(self.ext_header_size)
+    fn frame_bytes(&self) -> crate::id3::Result<u64> {
+        u64::from(self.tag_size)
+            .checked_sub(u64::from(self.ext_header.size))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Parsing,
+                    "Extended header is larger than the declared tag size",
+                )
+            })
     }
 
+    /// Returns the size of the whole tag, including the header and the footer if there is one.
     fn tag_size(&self) -> u64 {
-        self.size() + self.frame_bytes()
+        let footer = if self.flags.contains(Flags::FOOTER) {
+            FOOTER_SIZE
+        } else {
+            0
+        };
+        self.size() + u64::from(self.tag_size) + footer
     }
 }
 
@@ -78,34 +123,146 @@ impl Header {
         let nread = reader.read(&mut header)?;
         let base_header = Self::decode_base_header(&header[..nread])?;
 
-        // TODO: actually use the extended header data.
-        let ext_header_size = if base_header.flags.contains(Flags::EXTENDED_HEADER) {
-            let mut ext_header = [0; 6];
-            reader.read_exact(&mut ext_header)?;
-            let ext_size = unsynch::decode_u32(BigEndian::read_u32(&ext_header[0..4]));
-            // The extended header size includes itself and always has at least 2 bytes following.
-            if ext_size < 6 {
+        let ext_header = if !base_header.flags.contains(Flags::EXTENDED_HEADER) {
+            ExtHeader::default()
+        } else if base_header.version == Version::Id3v23 {
+            let unsynchronised = base_header.flags.contains(Flags::UNSYNCHRONISATION);
+            Self::decode_v3_ext_header(reader, unsynchronised)?
+        } else {
+            Self::decode_v4_ext_header(reader)?
+        };
+
+        Ok(base_header.with_ext_header(ext_header))
+    }
+
+    /// Decodes the ID3v2.3 extended header, whose size excludes itself and which is
+    /// unsynchronised along with the frames.
+    fn decode_v3_ext_header(
+        mut reader: impl io::Read,
+        unsynchronised: bool,
+    ) -> crate::id3::Result<ExtHeader> {
+        let mut raw_size = 0;
+        let mut read = |len: u32| -> crate::id3::Result<Vec<u8>> {
+            let mut bytes = Vec::with_capacity(len as usize);
+            let mut byte = [0];
+            while bytes.len() < len as usize {
+                reader.read_exact(&mut byte)?;
+                raw_size += 1;
+                if !(unsynchronised && byte[0] == 0x00 && bytes.last() == Some(&0xFF)) {
+                    bytes.push(byte[0]);
+                }
+            }
+            Ok(bytes)
+        };
+        let ext_size = BigEndian::read_u32(&read(4)?);
+        if ext_size != 6 && ext_size != 10 {
+            return Err(Error::new(
+                ErrorKind::Parsing,
+                "Extended header has a size of 6 or 10 in ID3v2.3",
+            ));
+        }
+        let ext_header = read(ext_size)?;
+        let flags = BigEndian::read_u16(&ext_header[0..2]);
+        let crc = if flags & V3_CRC_DATA_PRESENT != 0 {
+            if ext_size != 10 {
                 return Err(Error::new(
                     ErrorKind::Parsing,
-                    "Extended header requires has a minimum size of 6",
+                    "Extended header is too small for its CRC",
                 ));
             }
+            Some(BigEndian::read_u32(&ext_header[6..10]))
+        } else {
+            None
+        };
+        Ok(ExtHeader {
+            size: raw_size,
+            crc,
+            padding: BigEndian::read_u32(&ext_header[2..6]),
+            restrictions: None,
+        })
+    }
 
-            let _ext_flags = ExtFlags::from_bits_truncate(ext_header[5]);
+    /// Decodes the ID3v2.4 extended header, whose size includes itself.
+    fn decode_v4_ext_header(mut reader: impl io::Read) -> crate::id3::Result<ExtHeader> {
+        let mut ext_header = [0; 6];
+        reader.read_exact(&mut ext_header)?;
+        let ext_size = unsynch::decode_u32(BigEndian::read_u32(&ext_header[0..4]));
+        // The extended header size includes itself and always has at least 2 bytes following.
+        if ext_size < 6 {
+            return Err(Error::new(
+                ErrorKind::Parsing,
+                "Extended header requires has a minimum size of 6",
+            ));
+        }
 
-            let ext_remaining_size = ext_size - ext_header.len() as u32;
-            let mut ext_header = Vec::with_capacity(cmp::min(ext_remaining_size as usize, 0xffff));
-            reader
-                .by_ref()
-                .take(ext_remaining_size as u64)
-                .read_to_end(&mut ext_header)?;
+        let ext_flags = ExtFlags::from_bits_truncate(ext_header[5]);
 
-            ext_size
-        } else {
-            0
+        let ext_remaining_size = ext_size - ext_header.len() as u32;
+        let mut data = Vec::with_capacity(cmp::min(ext_remaining_size as usize, 0xffff));
+        reader
+            .by_ref()
+            .take(ext_remaining_size as u64)
+            .read_to_end(&mut data)?;
+
+        // The data of each flag that is set follows in the order of the flags, preceded by its
+        // length.
+        let mut data = &data[..];
+        let mut flag_data = |flag: ExtFlags, len: usize| -> crate::id3::Result<Option<&[u8]>> {
+            if !ext_flags.contains(flag) {
+                return Ok(None);
+            }
+            match data.split_first() {
+                Some((&data_len, rest)) if data_len as usize == len && rest.len() >= len => {
+                    let (flag_data, rest) = rest.split_at(len);
+                    data = rest;
+                    Ok(Some(flag_data))
+                }
+                _ => Err(Error::new(
+                    ErrorKind::Parsing,
+                    "Extended header flag data is malformed",
+                )),
+            }
         };
+        flag_data(ExtFlags::TAG_IS_UPDATE, 0)?;
+        let crc = flag_data(ExtFlags::CRC_DATA_PRESENT, 5)?.map(|crc| {
+            // 35 bits stored as 7 bits per byte.
+            crc.iter()
+                .fold(0u64, |crc, byte| crc << 7 | u64::from(byte & 0x7F)) as u32
+        });
+        let restrictions = flag_data(ExtFlags::TAG_RESTRICTIONS, 1)?
+            .map(|restrictions| Restrictions::from_bits(restrictions[0]));
+        Ok(ExtHeader {
+            size: ext_size,
+            crc,
+            padding: 0,
+            restrictions,
+        })
+    }
 
-        Ok(base_header.with_ext_header(ext_header_size))
+    /// Checks the CRC-32 of the extended header against `data`, the bytes of the tag after the
+    /// extended header.
+    fn check_crc(&self, crc: u32, data: &[u8]) -> crate::id3::Result<()> {
+        let mut decoded = Vec::new();
+        let covered = match self.version {
+            // In ID3v2.3 the CRC covers the frames before they were unsynchronised.
+            Version::Id3v23 => {
+                let data = if self.flags.contains(Flags::UNSYNCHRONISATION) {
+                    unsynch::Reader::new(data).read_to_end(&mut decoded)?;
+                    &decoded[..]
+                } else {
+                    data
+                };
+                &data[..data.len().saturating_sub(self.ext_header.padding as usize)]
+            }
+            _ => data,
+        };
+        if crc32(&[covered]) != crc {
+            return Err(Error::new(
+                ErrorKind::Parsing,
+                "Extended header CRC-32 doesn't match the tag data",
+            ));
+        }
+        Ok(())
     }
 
     fn decode_base_header(header: &[u8]) -> crate::id3::Result<HeaderBuilder> {
@@ -158,85 +315,341 @@ impl Header {
     }
 }
 
-pub fn decode(mut reader: impl io::Read) -> crate::id3::Result<Tag> {
+pub fn decode(reader: impl io::Read) -> crate::id3::Result<Tag> {
+    decode_with_options(reader, &ParseOptions::default())
+}
+
+pub fn decode_with_options(
+    mut reader: impl io::Read,
+    options: &ParseOptions,
+) -> crate::id3::Result<Tag> {
     let header = Header::decode(&mut reader)?;
+    check_tag_size(&header, options)?;
+    decode_remaining(reader, header, options)
+}
+
+/// Rejects tags declaring a size larger than the limit of `options`.
+fn check_tag_size(header: &Header, options: &ParseOptions) -> crate::id3::Result<()> {
+    if header.tag_size > options.max_tag_size {
+        return Err(Error::new(
+            ErrorKind::LimitExceeded,
+            format!(
+                "declared tag size of {} bytes exceeds the limit of {} bytes",
+                header.tag_size, options.max_tag_size
+            ),
+        ));
+    }
+    Ok(())
+}
 
-    decode_remaining(reader, header)
+fn decode_remaining(
+    mut reader: impl io::Read,
+    header: Header,
+    options: &ParseOptions,
+) -> crate::id3::Result<Tag> {
+    match header.ext_header.crc.filter(|_| options.check_crc) {
+        Some(crc) => {
+            let mut data = Vec::new();
+            reader
+                .by_ref()
+                .take(header.frame_bytes()?)
+                .read_to_end(&mut data)?;
+            header.check_crc(crc, &data)?;
+            collect_frames(FrameIterator::from_header(
+                &data[..],
+                header,
+                options.clone(),
+            )?)
+        }
+        None => collect_frames(FrameIterator::from_header(reader, header, options.clone())?),
+    }
 }
 
-fn decode_remaining(mut reader: impl io::Read, header: Header) -> crate::id3::Result<Tag> {
-    match header.version {
-        Version::Id3v22 => {
-            // Limit the reader only to the given tag_size, don't return any more bytes after that.
-            let v2_reader = reader.take(header.frame_bytes());
+/// Returns the CRC-32 of the concatenated `parts`, as stored by extended headers.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    for part in parts {
+        crc.update(part);
+    }
+    crc.sum()
+}
 
-            if header.flags.contains(Flags::UNSYNCHRONISATION) {
-                // Unwrap all 'unsynchronized' bytes in the tag before parsing frames.
-                decode_v2_frames(unsynch::Reader::new(v2_reader))
-            } else {
-                decode_v2_frames(v2_reader)
-            }
+/// Decodes all frames of `frames` into a tag.
+fn collect_frames(mut frames: FrameIterator<impl io::Read>) -> crate::id3::Result<Tag> {
+    let mut tag = Tag::with_version_tag_size(frames.version(), frames.tag_size());
+    for frame in frames.by_ref() {
+        match frame {
+            Ok(frame) => tag.add_frame(frame),
+            Err(err) => return Err(err.with_tag(tag)),
+        };
+    }
+    tag.set_padding_size(frames.padding_size());
+    tag.set_restrictions(frames.restrictions);
+    tag.mark_clean();
+    Ok(tag)
+}
+
+/// Decodes the frames of a tag one at a time, so a reader that only needs some frames can stop
+/// early, without holding the whole tag in memory.
+///
+/// The CRC-32 of an extended header is not checked, even with [`ParseOptions::check_crc`], as
+/// that needs all frames at once. The limits and other settings of the [`ParseOptions`] apply, frames skipped
+/// by [`ParseOptions::only_frames`] are not yielded.
+///
+/// # Example
+/// ```
+/// use xm_decryptor::id3::{FrameIterator, Tag, TagLike};
+///
+/// let mut tag = Tag::new();
+/// tag.set_title("title");
+/// tag.set_artist("artist");
+/// let mut data = Vec::new();
+/// tag.write_to(&mut data, xm_decryptor::id3::Version::Id3v24).unwrap();
+///
+/// let mut frames = FrameIterator::new(&data[..]).unwrap();
+/// assert_eq!(frames.next().unwrap().unwrap().id(), "TIT2");
+/// ```
+pub struct FrameIterator<R: io::Read> {
+    reader: FrameReader<R>,
+    version: Version,
+    options: ParseOptions,
+    tag_size: u64,
+    /// The bytes of frames and padding after the header and the extended header.
+    frame_bytes: u64,
+    /// The bytes of the frames read so far.
+    offset: u64,
+    /// The number of frames decoded so far, skipped ones included.
+    count: usize,
+    restrictions: Option<Restrictions>,
+    done: bool,
+}
+
+/// The reader of the frame data of a tag, without the unsynchronisation of ID3v2.2 and ID3v2.3,
+/// which is applied to all of it.
+enum FrameReader<R: io::Read> {
+    Plain(io::Take<R>),
+    Unsynchronised(Box<unsynch::Reader<io::Take<R>>>),
+}
+
+impl<R: io::Read> io::Read for FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            FrameReader::Plain(reader) => reader.read(buf),
+            FrameReader::Unsynchronised(reader) => reader.read(buf),
         }
-        Version::Id3v23 => {
-            // Unsynchronization is applied to the whole tag, excluding the header.
-            let mut reader: Box<dyn io::Read> = if header.flags.contains(Flags::UNSYNCHRONISATION) {
-                Box::new(unsynch::Reader::new(reader))
-            } else {
-                Box::new(reader)
-            };
+    }
+}
 
-            let mut offset = 0;
-            let mut tag = Tag::with_version_tag_size(header.version, header.tag_size());
-            while offset < header.frame_bytes() {
-                let v = match frame::v3::decode(&mut reader) {
-                    Ok(v) => v,
-                    Err(err) => return Err(err.with_tag(tag)),
-                };
-                let (bytes_read, frame) = match v {
-                    Some(v) => v,
-                    None => break, // Padding.
-                };
-                tag.add_frame(frame);
-                offset += bytes_read as u64;
+impl<R: io::Read> FrameIterator<R> {
+    /// Reads the tag header from `reader` and returns an iterator over the frames that follow.
+    pub fn new(reader: R) -> crate::id3::Result<Self> {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Like [`FrameIterator::new`], decoding with the settings of `options`.
+    pub fn with_options(mut reader: R, options: ParseOptions) -> crate::id3::Result<Self> {
+        let header = Header::decode(&mut reader)?;
+        check_tag_size(&header, &options)?;
+        Self::from_header(reader, header, options)
+    }
+
+    fn from_header(reader: R, header: Header, options: ParseOptions) -> crate::id3::Result<Self> {
+        let frame_bytes = header.frame_bytes()?;
+        // Limit the reader to the frames, don't return any more bytes after that.
+        let reader = reader.take(frame_bytes);
+        let reader = match header.version {
+            Version::Id3v22 | Version::Id3v23
+                if header.flags.contains(Flags::UNSYNCHRONISATION) =>
+            {
+                FrameReader::Unsynchronised(Box::new(unsynch::Reader::new(reader)))
             }
-            Ok(tag)
-        }
-        Version::Id3v24 => {
-            let mut offset = 0;
-            let mut tag = Tag::with_version(header.version);
-
-            while offset < header.frame_bytes() {
-                let v = match frame::v4::decode(&mut reader) {
-                    Ok(v) => v,
-                    Err(err) => return Err(err.with_tag(tag)),
-                };
-                let (bytes_read, frame) = match v {
-                    Some(v) => v,
-                    None => break, // Padding.
-                };
-                tag.add_frame(frame);
-                offset += bytes_read as u64;
+            _ => FrameReader::Plain(reader),
+        };
+        Ok(FrameIterator {
+            reader,
+            version: header.version,
+            options,
+            tag_size: header.tag_size(),
+            frame_bytes,
+            offset: 0,
+            count: 0,
+            restrictions: header.ext_header.restrictions,
+            done: false,
+        })
+    }
+
+    /// Returns the version of the tag.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the size of the whole tag as declared by its header, including the header, like
+    /// [`Tag::header_tag_size`].
+    pub fn tag_size(&self) -> u64 {
+        self.tag_size
+    }
+
+    /// Returns the bytes of padding after the frames, once all frames were read.
+    fn padding_size(&self) -> u64 {
+        self.frame_bytes.saturating_sub(self.offset)
+    }
+
+    fn decode_next(&mut self) -> crate::id3::Result<Option<Frame>> {
+        while self.offset < self.frame_bytes {
+            let (bytes_read, frame) = match self.version {
+                Version::Id3v22 => frame::v2::decode(&mut self.reader, &self.options)?,
+                Version::Id3v23 => frame::v3::decode(&mut self.reader, &self.options)?,
+                Version::Id3v24 => frame::v4::decode(&mut self.reader, &self.options)?,
+            }
+            .unwrap_or((0, None)); // Padding.
+            if bytes_read == 0 {
+                break;
+            }
+            self.offset = advance(self.offset, bytes_read)?;
+            self.count += 1;
+            if self.count > self.options.max_frame_count {
+                return Err(Error::new(
+                    ErrorKind::LimitExceeded,
+                    format!("tag has more than {} frames", self.options.max_frame_count),
+                ));
+            }
+            if frame.is_some() {
+                return Ok(frame);
             }
-            Ok(tag)
         }
+        Ok(None)
     }
 }
 
-pub fn decode_v2_frames(mut reader: impl io::Read) -> crate::id3::Result<Tag> {
-    let mut tag = Tag::with_version(Version::Id3v22);
-    // Add all frames, until either an error is thrown or there are no more frames to parse
-    // (because of EOF or a Padding).
-    loop {
-        let v = match frame::v2::decode(&mut reader) {
-            Ok(v) => v,
-            Err(err) => return Err(err.with_tag(tag)),
-        };
-        match v {
-            Some((_bytes_read, frame)) => {
-                tag.add_frame(frame);
-            }
-            None => break Ok(tag),
+impl<R: io::Read> Iterator for FrameIterator<R> {
+    type Item = crate::id3::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+        let frame = self.decode_next().transpose();
+        self.done = !matches!(frame, Some(Ok(_)));
+        frame
+    }
+}
+
+/// Returns `offset` moved past a frame of `bytes_read` bytes.
+fn advance(offset: u64, bytes_read: usize) -> crate::id3::Result<u64> {
+    offset
+        .checked_add(bytes_read as u64)
+        .ok_or_else(|| Error::new(ErrorKind::Parsing, "Frame offset overflows"))
+}
+
+/// The `ParseOptions` may be used to decode tags with custom settings.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    max_tag_size: u32,
+    pub(crate) max_frame_size: u32,
+    max_frame_count: usize,
+    pub(crate) latin1_fallback: Vec<LegacyCharset>,
+    only_frames: Option<Vec<String>>,
+    check_crc: bool,
+}
+
+impl ParseOptions {
+    /// Constructs new `ParseOptions` with the following configuration:
+    ///
+    /// * Tags declaring a size larger than 32 MiB are rejected
+    /// * Frames larger than 64 MiB, declared or decompressed, are rejected
+    /// * Tags with more than 65536 frames are rejected
+    /// * Text claiming ISO-8859-1 is decoded as ISO-8859-1
+    /// * All frames are decoded
+    /// * The CRC-32 of an extended header is not checked
+    pub fn new() -> Self {
+        Self {
+            max_tag_size: DEFAULT_MAX_TAG_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_frame_count: DEFAULT_MAX_FRAME_COUNT,
+            latin1_fallback: Vec::new(),
+            only_frames: None,
+            check_crc: false,
+        }
+    }
+
+    /// Sets the largest tag size, as declared by the tag header, that is accepted.
+    ///
+    /// Larger tags are rejected with [`ErrorKind::LimitExceeded`] before any of their frames are
+    /// read. This protects against corrupted headers that would otherwise lead to huge
+    /// allocations.
+    pub fn max_tag_size(mut self, max_tag_size: u32) -> Self {
+        self.max_tag_size = max_tag_size;
+        self
+    }
+
+    /// Sets the largest frame content size that is accepted, both as declared by the frame header
+    /// and after decompression.
+    ///
+    /// Larger frames are rejected with [`ErrorKind::LimitExceeded`] before their content is read,
+    /// or as soon as their decompressed content grows past the limit.
+    pub fn max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Sets the most frames a tag may hold. Tags with more are rejected with
+    /// [`ErrorKind::LimitExceeded`], the frames read until then are kept as the partial tag of
+    /// the error.
+    pub fn max_frame_count(mut self, max_frame_count: usize) -> Self {
+        self.max_frame_count = max_frame_count;
+        self
+    }
+
+    /// Sets the character sets tried, in order, for text that claims ISO-8859-1 but isn't ASCII,
+    /// as many Chinese tags hold GBK or Big5 text marked as ISO-8859-1. The text is decoded in
+    /// the first character set it is valid in, and ISO-8859-1 if none fits. Frames decoded this
+    /// way are written back in the default encoding of the version.
+    ///
+    /// The conversion is done by the system, iconv on Unix with the `iconv` feature and the code
+    /// pages on Windows, other platforms decode ISO-8859-1 as before.
+    pub fn latin1_fallback(mut self, charsets: impl IntoIterator<Item = LegacyCharset>) -> Self {
+        self.latin1_fallback = charsets.into_iter().collect();
+        self
+    }
+
+    /// Only decodes the frames with these IDs, the content of other frames is skipped without
+    /// being decoded or held in memory, such as the pictures of a tag whose text is wanted.
+    /// ID3v2.2 frames are matched by their ID3v2.3 IDs. Skipped frames still count towards
+    /// [`ParseOptions::max_frame_count`].
+    pub fn only_frames<I>(mut self, ids: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.only_frames = Some(ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Enables or disables checking the CRC-32 of an extended header against the frames, and in
+    /// ID3v2.4 the padding too. A tag that doesn't match is rejected with [`ErrorKind::Parsing`].
+    ///
+    /// Off by default, as taggers that edit frames in place often leave a stale CRC-32 behind and
+    /// the frames are usually fine.
+    pub fn check_crc(mut self, check_crc: bool) -> Self {
+        self.check_crc = check_crc;
+        self
+    }
+
+    /// Returns whether the frame `id` is decoded rather than skipped.
+    pub(crate) fn decodes(&self, id: &str) -> bool {
+        let id = match id.len() {
+            3 => convert_id_2_to_3(id).unwrap_or(id),
+            _ => id,
+        };
+        self.only_frames
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|only| only == id))
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -247,7 +660,13 @@ pub struct Encoder {
     unsynchronisation: bool,
     compression: bool,
     file_altered: bool,
+    file_discard: Vec<String>,
+    preserve_all: bool,
     padding: Option<usize>,
+    extended_header: bool,
+    footer: bool,
+    /// The character set of the ID3v1 tag written next to the ID3v2 tag, if one is written.
+    id3v1: Option<v1::Charset>,
 }
 
 impl Encoder {
@@ -256,14 +675,26 @@ impl Encoder {
     /// * [`Version`] is ID3v2.4
     /// * Unsynchronization is disabled due to compatibility issues
     /// * No compression
+    /// * No extended header
+    /// * No footer
     /// * File is not marked as altered
+    /// * If the file is marked as altered, the frames listed in [`Encoder::file_altered`] are
+    ///   discarded
     pub fn new() -> Self {
         Self {
             version: Version::Id3v24,
             unsynchronisation: false,
             compression: false,
             file_altered: false,
+            file_discard: DEFAULT_FILE_DISCARD
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            preserve_all: false,
             padding: None,
+            extended_header: false,
+            footer: false,
+            id3v1: None,
         }
     }
 
@@ -297,22 +728,88 @@ impl Encoder {
         self
     }
 
+    /// Enables or disables writing an extended header with the CRC-32 of the tag data, and in
+    /// ID3v2.4 with the [`Tag::restrictions`] of the tag. ID3v2.2 has no extended header.
+    pub fn extended_header(mut self, extended_header: bool) -> Self {
+        self.extended_header = extended_header;
+        self
+    }
+
+    /// Enables or disables writing the ID3v2.4 footer, which lets readers find a tag appended to
+    /// the end of a file, see [`Tag::read_from_seekable`]. A tag with a footer has no padding,
+    /// so [`Encoder::padding`] is ignored. Earlier versions have no footer.
+    pub fn footer(mut self, footer: bool) -> Self {
+        self.footer = footer;
+        self
+    }
+
     /// Informs the encoder whether the file this tag belongs to has been changed.
     ///
     /// This subsequently discards any tags that have their File Alter Preservation bits set and
     /// that have a relation to the file contents:
     ///
     ///   AENC, ETCO, EQUA, MLLT, POSS, SYLT, SYTC, RVAD, TENC, TLEN, TSIZ
+    ///
+    /// The list can be changed with [`Encoder::file_discard`].
     pub fn file_altered(mut self, file_altered: bool) -> Self {
         self.file_altered = file_altered;
         self
     }
 
+    /// Sets the IDs of the frames that are discarded when the file is marked as altered, replacing
+    /// the default list.
+    ///
+    /// Frames with their File Alter Preservation bit set are still discarded.
+    pub fn file_discard<I>(mut self, ids: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.file_discard = ids.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Keeps every frame when the file is marked as altered, ignoring both the discard list and
+    /// the File Alter Preservation bits. Xm files rely on TLEN, TSIZ and TENC, which would
+    /// otherwise be dropped.
+    ///
+    /// Frames with their Tag Alter Preservation bit set are discarded regardless.
+    pub fn preserve_all(mut self, preserve_all: bool) -> Self {
+        self.preserve_all = preserve_all;
+        self
+    }
+
+    /// Also writes an ID3v1 tag derived from the ID3v2 tag with [`v1::Tag::from_v2`] when
+    /// writing to a file, replacing any ID3v1 tag there, for players that only read ID3v1. The
+    /// text is written in ISO-8859-1 unless [`Encoder::id3v1_charset`] says otherwise.
+    ///
+    /// Without it, [`crate::id3::v1v2::write_to_file`] removes the ID3v1 tag.
+    pub fn also_write_id3v1(mut self, also_write_id3v1: bool) -> Self {
+        self.id3v1 = match also_write_id3v1 {
+            true => Some(self.id3v1.unwrap_or_default()),
+            false => None,
+        };
+        self
+    }
+
+    /// Sets the character set of the ID3v1 tag written by [`Encoder::also_write_id3v1`], and
+    /// enables writing it.
+    pub fn id3v1_charset(mut self, charset: v1::Charset) -> Self {
+        self.id3v1 = Some(charset);
+        self
+    }
+
+    /// Returns whether an ID3v1 tag is written next to the ID3v2 tag.
+    pub(crate) fn writes_id3v1(&self) -> bool {
+        self.id3v1.is_some()
+    }
+
     /// Encodes the specified [`Tag`] using the settings set in the [`Encoder`].
     ///
     /// Note that the plain tag is written, regardless of the original contents. To safely encode a
     /// tag to an MP3 file, use [`Encoder::encode_to_path`].
     pub fn encode(&self, tag: &Tag, mut writer: impl io::Write) -> crate::id3::Result<()> {
+        let discard_altered = self.file_altered && !self.preserve_all;
         // remove frames which have the flags indicating they should be removed
         let saved_frames = tag
             .frames()
@@ -321,10 +818,12 @@ impl Encoder {
             .filter(|frame| !frame.tag_alter_preservation())
             // If the file this tag belongs to is updated, check for the File Alter Preservation
             // bit.
-            .filter(|frame| !self.file_altered || !frame.file_alter_preservation())
+            .filter(|frame| !discard_altered || !frame.file_alter_preservation())
             // Check whether this frame is part of the set of frames that should always be
             // discarded when the file is changed.
-            .filter(|frame| !self.file_altered || !DEFAULT_FILE_DISCARD.contains(&frame.id()));
+            .filter(|frame| {
+                !discard_altered || !self.file_discard.iter().any(|id| id == frame.id())
+            });
 
         let mut flags = Flags::empty();
         flags.set(Flags::UNSYNCHRONISATION, self.unsynchronisation);
@@ -333,9 +832,27 @@ impl Encoder {
         }
 
         let mut frame_data = Vec::new();
+        let mut scratch = frame::Scratch::default();
         for frame in saved_frames {
             frame.validate()?;
-            frame::encode(&mut frame_data, frame, self.version, self.unsynchronisation)?;
+            frame::encode(
+                &mut frame_data,
+                frame,
+                self.version,
+                self.unsynchronisation,
+                &mut scratch,
+            )?;
+        }
+        let footer = self.footer && self.version == Version::Id3v24;
+        flags.set(Flags::FOOTER, footer);
+        let padding = match footer {
+            true => 0,
+            false => self.padding.unwrap_or(0),
+        };
+        if self.extended_header && self.version != Version::Id3v22 {
+            flags.insert(Flags::EXTENDED_HEADER);
+            let ext_header = self.encode_ext_header(tag, &frame_data, padding);
+            frame_data.splice(0..0, ext_header);
         }
         // In ID3v2.2/ID3v2.3, Unsynchronization is applied to the whole tag data at once, not for
         // each frame separately.
@@ -345,38 +862,74 @@ impl Encoder {
                 Version::Id3v24 => {}
             };
         }
-        let tag_size = frame_data.len() + self.padding.unwrap_or(0);
-        writer.write_all(b"ID3")?;
-        writer.write_all(&[self.version.minor(), 0])?;
-        writer.write_u8(flags.bits())?;
-        writer.write_u32::<BigEndian>(unsynch::encode_u32(tag_size as u32))?;
+        let tag_size = frame_data.len() + padding;
+        let mut header = [0; 10];
+        header[0..3].copy_from_slice(b"ID3");
+        header[3] = self.version.minor();
+        header[5] = flags.bits();
+        BigEndian::write_u32(&mut header[6..10], unsynch::encode_u32(tag_size as u32));
+        writer.write_all(&header)?;
         writer.write_all(&frame_data[..])?;
-
-        if let Some(padding) = self.padding {
-            writer.write_all(&vec![0; padding])?;
+        writer.write_all(&vec![0; padding])?;
+        if footer {
+            header[0..3].copy_from_slice(b"3DI");
+            writer.write_all(&header)?;
         }
         Ok(())
     }
 
+    /// Returns the extended header for the encoded `frames` followed by `padding` zero bytes.
+    fn encode_ext_header(&self, tag: &Tag, frames: &[u8], padding: usize) -> Vec<u8> {
+        let mut ext_header = Vec::new();
+        if self.version == Version::Id3v23 {
+            ext_header.extend_from_slice(&10u32.to_be_bytes());
+            ext_header.extend_from_slice(&V3_CRC_DATA_PRESENT.to_be_bytes());
+            ext_header.extend_from_slice(&(padding as u32).to_be_bytes());
+            ext_header.extend_from_slice(&crc32(&[frames]).to_be_bytes());
+            return ext_header;
+        }
+
+        let mut ext_flags = ExtFlags::CRC_DATA_PRESENT;
+        let crc = u64::from(crc32(&[frames, &vec![0; padding]]));
+        // 35 bits stored as 7 bits per byte.
+        let mut flag_data = vec![5];
+        flag_data.extend((0..5).rev().map(|i| (crc >> (7 * i)) as u8 & 0x7F));
+        if let Some(restrictions) = tag.restrictions() {
+            ext_flags.insert(ExtFlags::TAG_RESTRICTIONS);
+            flag_data.extend_from_slice(&[1, restrictions.bits()]);
+        }
+        let size = 6 + flag_data.len() as u32;
+        ext_header.extend_from_slice(&unsynch::encode_u32(size).to_be_bytes());
+        ext_header.extend_from_slice(&[1, ext_flags.bits()]);
+        ext_header.extend_from_slice(&flag_data);
+        ext_header
+    }
+
     /// Encodes a [`Tag`] and replaces any existing tag in the file.
     pub fn write_to_file(&self, tag: &Tag, mut file: impl StorageFile) -> crate::id3::Result<()> {
         #[allow(clippy::reversed_empty_ranges)]
         let location = locate_id3v2(&mut file)?.unwrap_or(0..0); // Create a new tag if none could be located.
 
-        let mut storage = PlainStorage::new(file, location);
+        let mut storage = PlainStorage::new(&mut file, location);
         let mut w = storage.writer()?;
         self.encode(tag, &mut w)?;
         w.flush()?;
+        drop(w);
+        if let Some(charset) = self.id3v1 {
+            v1::Tag::from_v2(tag).write_to_file(&mut file, charset)?;
+        }
         Ok(())
     }
 
     /// Encodes a [`Tag`] and replaces any existing tag in the file.
     #[deprecated(note = "Use write_to_file")]
+    #[cfg(feature = "fs")]
     pub fn encode_to_file(&self, tag: &Tag, file: &mut fs::File) -> crate::id3::Result<()> {
         self.write_to_file(tag, file)
     }
 
     /// Encodes a [`Tag`] and replaces any existing tag in the file pointed to by the specified path.
+    #[cfg(feature = "fs")]
     pub fn write_to_path(&self, tag: &Tag, path: impl AsRef<Path>) -> crate::id3::Result<()> {
         let mut file = fs::OpenOptions::new().read(true).write(true).open(path)?;
         self.write_to_file(tag, &mut file)?;
@@ -386,6 +939,7 @@ impl Encoder {
 
     /// Encodes a [`Tag`] and replaces any existing tag in the file pointed to by the specified path.
     #[deprecated(note = "Use write_to_path")]
+    #[cfg(feature = "fs")]
     pub fn encode_to_path(&self, tag: &Tag, path: impl AsRef<Path>) -> crate::id3::Result<()> {
         self.write_to_path(tag, path)
     }
@@ -409,10 +963,591 @@ pub fn locate_id3v2(
     };
 
     let tag_size = header.tag_size();
+    // Zero bytes after a footer are not padding of the tag.
+    if header.flags.contains(Flags::FOOTER) {
+        return Ok(Some(0..tag_size));
+    }
     reader.seek(io::SeekFrom::Start(tag_size))?;
-    let num_padding = reader
+    let num_padding = io::BufReader::new(reader)
         .bytes()
         .take_while(|rs| rs.as_ref().map(|b| *b == 0x00).unwrap_or(false))
         .count();
     Ok(Some(0..tag_size + num_padding as u64))
 }
+
+/// Locates an ID3v2.4 tag appended to the end of the reader, before any ID3v1 tag, by its footer.
+/// Returns the range of the tag from its header to its footer.
+pub fn locate_id3v2_appended(
+    mut reader: impl io::Read + io::Seek,
+) -> crate::id3::Result<Option<Range<u64>>> {
+    let mut end = reader.seek(io::SeekFrom::End(0))?;
+    if end >= V1_TAG_SIZE && v1::Tag::is_candidate(&mut reader)? {
+        end -= V1_TAG_SIZE;
+    }
+    let Some(footer_start) = end.checked_sub(FOOTER_SIZE) else {
+        return Ok(None);
+    };
+    let mut footer = [0; FOOTER_SIZE as usize];
+    reader.seek(io::SeekFrom::Start(footer_start))?;
+    reader.read_exact(&mut footer)?;
+    if &footer[0..3] != b"3DI" {
+        return Ok(None);
+    }
+    let tag_size = unsynch::decode_u32(BigEndian::read_u32(&footer[6..10]));
+    let Some(start) = footer_start.checked_sub(10 + u64::from(tag_size)) else {
+        return Ok(None);
+    };
+    reader.seek(io::SeekFrom::Start(start))?;
+    match Header::decode(&mut reader) {
+        Ok(header) if header.flags.contains(Flags::FOOTER) && header.tag_size() == end - start => {
+            Ok(Some(start..end))
+        }
+        Ok(_) => Err(Error::new(
+            ErrorKind::Parsing,
+            "Footer doesn't match the header of the tag",
+        )),
+        Err(err) => Err(err),
+    }
+}
+
+/// Locates the tag a SEEK frame points to, `offset` bytes after the end of its tag at `tag_end`.
+/// Only a tag starting exactly there is found.
+pub fn locate_id3v2_seek(
+    mut reader: impl io::Read + io::Seek,
+    tag_end: u64,
+    offset: u32,
+) -> crate::id3::Result<Option<Range<u64>>> {
+    let start = tag_end + u64::from(offset);
+    reader.seek(io::SeekFrom::Start(start))?;
+    match Header::decode(&mut reader) {
+        Ok(header) => Ok(Some(start..start + header.tag_size())),
+        Err(err) if matches!(err.kind, ErrorKind::NoTag) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[test]
+fn test_decode_rejects_oversized_tag() {
+    let header = [b'I', b'D', b'3', 4, 0, 0, 0x7f, 0x7f, 0x7f, 0x7f];
+    let err =
+        decode_with_options(&header[..], &ParseOptions::new().max_tag_size(1024)).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::LimitExceeded));
+}
+
+#[test]
+fn test_decode_default_max_tag_size() {
+    // Synchsafe sizes of 32 MiB and one more byte.
+    let at_limit = [b'I', b'D', b'3', 4, 0, 0, 0x10, 0, 0, 0];
+    let over_limit = [b'I', b'D', b'3', 4, 0, 0, 0x10, 0, 0, 1];
+    assert!(!decode(&at_limit[..]).is_err_and(|err| matches!(err.kind, ErrorKind::LimitExceeded)));
+    let err = decode(&over_limit[..]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::LimitExceeded));
+}
+
+#[test]
+fn test_decode_rejects_ext_header_larger_than_tag() {
+    let tag = [
+        b'I', b'D', b'3', 4, 0, 0x40, 0, 0, 0, 4, 0, 0, 0, 6, 1, 0, 0, 0, 0, 0,
+    ];
+    let err = decode(&tag[..]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Parsing));
+}
+
+#[test]
+fn test_decode_records_source_range_and_padding() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    let mut data = Vec::new();
+    Encoder::new()
+        .version(Version::Id3v24)
+        .padding(16)
+        .encode(&tag, &mut data)
+        .unwrap();
+
+    let decoded = decode(&data[..]).unwrap();
+    assert_eq!(decoded.source_range(), 0..data.len() as u64);
+    assert_eq!(decoded.padding_size(), 16);
+}
+
+#[test]
+fn test_write_changes_reuses_padding() {
+    let mut tag = Tag::new();
+    tag.set_title("a long title");
+    let mut data = Vec::new();
+    Encoder::new()
+        .version(Version::Id3v24)
+        .padding(16)
+        .encode(&tag, &mut data)
+        .unwrap();
+    data.extend_from_slice(b"audio");
+    let len = data.len();
+    let mut file = io::Cursor::new(data);
+
+    let mut decoded = decode(&mut file).unwrap();
+    assert!(!decoded.is_dirty());
+    decoded.set_title("a long title");
+    assert!(!decoded
+        .write_changes_to_file(&mut file, Version::Id3v24)
+        .unwrap());
+    decoded.set_title("short");
+    assert_eq!(decoded.changed_frames().count(), 1);
+    file.set_position(0);
+    assert!(decoded
+        .write_changes_to_file(&mut file, Version::Id3v24)
+        .unwrap());
+    assert!(!decoded.is_dirty());
+
+    let data = file.into_inner();
+    assert_eq!(data.len(), len);
+    assert!(data.ends_with(b"audio"));
+    assert_eq!(decode(&data[..]).unwrap().title(), Some("short"));
+}
+
+#[test]
+fn test_encode_file_altered_discard() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    tag.set_text("TSIZ", "1024");
+    tag.set_text("TENC", "encoder");
+    let encode = |encoder: Encoder| {
+        let mut data = Vec::new();
+        encoder.encode(&tag, &mut data).unwrap();
+        let decoded = decode(&data[..]).unwrap();
+        let mut ids: Vec<_> = decoded.frames().map(|f| f.id().to_string()).collect();
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(encode(Encoder::new().file_altered(true)), ["TIT2"]);
+    assert_eq!(
+        encode(Encoder::new().file_altered(true).file_discard(["TENC"])),
+        ["TIT2", "TSIZ"]
+    );
+    assert_eq!(
+        encode(Encoder::new().file_altered(true).preserve_all(true)),
+        ["TENC", "TIT2", "TSIZ"]
+    );
+}
+
+#[test]
+fn test_extended_header_crc() {
+    let mut tag = Tag::new();
+    tag.set_title("title\u{ff}");
+    tag.set_restrictions(Some(Restrictions::from_bits(0b0110_0101)));
+    for (version, unsynchronisation) in [
+        (Version::Id3v23, false),
+        (Version::Id3v23, true),
+        (Version::Id3v24, false),
+    ] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .unsynchronisation(unsynchronisation)
+            .extended_header(true)
+            .padding(8)
+            .encode(&tag, &mut data)
+            .unwrap();
+        assert_eq!(data[5] & Flags::EXTENDED_HEADER.bits(), 0x40);
+
+        let decoded = decode(&data[..]).unwrap();
+        assert_eq!(decoded.title(), Some("title\u{ff}"));
+        if !unsynchronisation {
+            assert_eq!(decoded.padding_size(), 8);
+        }
+        let restrictions = decoded.restrictions();
+        match version {
+            Version::Id3v24 => {
+                let restrictions = restrictions.unwrap();
+                assert_eq!(restrictions.tag_size(), (64, 128 * 1024));
+                assert!(restrictions.text_encoding());
+                assert_eq!(restrictions.text_size(), None);
+                assert!(restrictions.image_encoding());
+                assert_eq!(restrictions.image_size(), Some((256, false)));
+            }
+            _ => assert_eq!(restrictions, None),
+        }
+
+        let frame = data.windows(4).position(|w| w == b"TIT2").unwrap();
+        data[frame + 3] = b'3';
+        assert!(decode(&data[..]).is_ok());
+        let err = decode_with_options(&data[..], &ParseOptions::new().check_crc(true)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Parsing));
+    }
+}
+
+#[test]
+fn test_footer() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    let mut data = Vec::new();
+    Encoder::new()
+        .footer(true)
+        .padding(16)
+        .encode(&tag, &mut data)
+        .unwrap();
+    assert_eq!(&data[data.len() - 10..data.len() - 7], b"3DI");
+    assert_eq!(
+        locate_id3v2(io::Cursor::new(&data)).unwrap(),
+        Some(0..data.len() as u64)
+    );
+    let decoded = decode(&data[..]).unwrap();
+    assert_eq!(decoded.source_range(), 0..data.len() as u64);
+    assert_eq!(decoded.padding_size(), 0);
+
+    // Appended to the audio, before an ID3v1 tag.
+    let mut file = b"audio".to_vec();
+    file.extend_from_slice(&data);
+    let v1_tag = v1::Tag {
+        title: "title".to_string(),
+        ..Default::default()
+    };
+    file.extend_from_slice(&v1_tag.encode(v1::Charset::default()));
+    let range = 5..5 + data.len() as u64;
+    assert_eq!(
+        locate_id3v2_appended(io::Cursor::new(&file)).unwrap(),
+        Some(range.clone())
+    );
+    let decoded = Tag::read_from_seekable(io::Cursor::new(&file)).unwrap();
+    assert_eq!(decoded.title(), Some("title"));
+    assert_eq!(decoded.source_range(), range);
+
+    assert_eq!(
+        locate_id3v2_appended(io::Cursor::new(b"audio")).unwrap(),
+        None
+    );
+    assert!(matches!(
+        Tag::read_from_seekable(io::Cursor::new(b"audio"))
+            .unwrap_err()
+            .kind,
+        ErrorKind::NoTag
+    ));
+}
+
+#[test]
+fn test_decode_limits() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    tag.set_artist("artist");
+    tag.set_album("a long album title");
+    for version in [Version::Id3v22, Version::Id3v23, Version::Id3v24] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .encode(&tag, &mut data)
+            .unwrap();
+        let decode = |options: ParseOptions| decode_with_options(&data[..], &options);
+
+        assert_eq!(decode(ParseOptions::new()).unwrap().frames().count(), 3);
+        let err = decode(ParseOptions::new().max_frame_size(8)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::LimitExceeded));
+        let err = decode(ParseOptions::new().max_frame_count(2)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::LimitExceeded));
+        assert_eq!(err.partial_tag.unwrap().frames().count(), 2);
+    }
+
+    // Compressed content is limited after decompression.
+    let mut frames = Vec::new();
+    frame::v3::encode(
+        &mut frames,
+        &Frame::text("TIT2", "0".repeat(4096)),
+        frame::v3::Flags::COMPRESSION,
+        &mut frame::Scratch::default(),
+    )
+    .unwrap();
+    assert!(frames.len() < 1024);
+    let mut data = vec![b'I', b'D', b'3', 3, 0, 0];
+    data.extend_from_slice(&unsynch::encode_u32(frames.len() as u32).to_be_bytes());
+    data.extend_from_slice(&frames);
+    assert!(decode(&data[..]).is_ok());
+    let err =
+        decode_with_options(&data[..], &ParseOptions::new().max_frame_size(1024)).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::LimitExceeded));
+}
+
+#[cfg(any(all(unix, feature = "iconv"), windows))]
+#[test]
+fn test_decode_latin1_fallback() {
+    let mut frames = Vec::new();
+    for (id, content) in [
+        (b"TIT2", &b"\0\xd6\xd0\xce\xc4"[..]),
+        (b"TXXX", &b"\0desc\0\xd6\xd0\xce\xc4"[..]),
+        (b"TPE1", &b"\0Caf\xe9"[..]),
+    ] {
+        frames.extend_from_slice(id);
+        frames.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        frames.extend_from_slice(&[0, 0]);
+        frames.extend_from_slice(content);
+    }
+    let mut data = vec![b'I', b'D', b'3', 3, 0, 0];
+    data.extend_from_slice(&unsynch::encode_u32(frames.len() as u32).to_be_bytes());
+    data.extend_from_slice(&frames);
+
+    let tag = decode(&data[..]).unwrap();
+    assert_eq!(tag.title(), Some("\u{d6}\u{d0}\u{ce}\u{c4}"));
+    let options = ParseOptions::new().latin1_fallback([LegacyCharset::Gbk, LegacyCharset::Big5]);
+    let tag = decode_with_options(&data[..], &options).unwrap();
+    assert_eq!(tag.title(), Some("\u{4e2d}\u{6587}"));
+    assert_eq!(tag.artist(), Some("Caf\u{e9}"));
+    let txxx = tag.get("TXXX").unwrap();
+    assert_eq!(
+        txxx.content().extended_text().unwrap().value,
+        "\u{4e2d}\u{6587}"
+    );
+    assert_eq!(txxx.encoding(), None);
+}
+
+#[test]
+fn test_frame_iterator() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    tag.add_frame(crate::id3::frame::EncapsulatedObject {
+        mime_type: "application/octet-stream".to_string(),
+        filename: "object".to_string(),
+        description: String::new(),
+        data: vec![1; 1024],
+    });
+    tag.set_artist("artist");
+    for version in [Version::Id3v23, Version::Id3v24] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .padding(8)
+            .encode(&tag, &mut data)
+            .unwrap();
+        data.extend_from_slice(b"\xff\xfbaudio");
+
+        let frames = FrameIterator::new(&data[..]).unwrap();
+        assert_eq!(frames.version(), version);
+        assert_eq!(frames.tag_size(), data.len() as u64 - 7);
+        let ids: Vec<_> = frames.map(|f| f.unwrap().id().to_string()).collect();
+        assert_eq!(ids, ["TIT2", "GEOB", "TPE1"]);
+
+        let options = ParseOptions::new().only_frames(["TPE1"]);
+        let mut frames = FrameIterator::with_options(&data[..], options).unwrap();
+        assert_eq!(
+            frames.next().unwrap().unwrap().content().text(),
+            Some("artist")
+        );
+        assert!(frames.next().is_none());
+    }
+
+    let mut frames = FrameIterator::with_options(
+        &b"ID3\x04\0\0\0\0\0\x20TIT2\0\0\x7f\x7f\0\0"[..],
+        ParseOptions::new(),
+    )
+    .unwrap();
+    assert!(frames.next().unwrap().is_err());
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn test_event_timing_codes() {
+    use crate::id3::frame::{Content, EventTimingCodes, EventType, TimestampFormat};
+
+    let etco = EventTimingCodes {
+        timestamp_format: TimestampFormat::Ms,
+        events: vec![
+            (EventType::IntroStart, 0),
+            (EventType::IntroEnd, 12_000),
+            (EventType::Sync(3), 60_000),
+            (EventType::Undefined(0x30), 90_000),
+            (EventType::OutroStart, 3_600_000),
+        ],
+    };
+    let mut tag = Tag::new();
+    tag.add_frame(etco.clone());
+    for version in [Version::Id3v22, Version::Id3v23, Version::Id3v24] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .encode(&tag, &mut data)
+            .unwrap();
+        let decoded = decode(&data[..]).unwrap();
+        assert_eq!(decoded.event_timing_codes(), Some(&etco));
+    }
+    assert_eq!(Content::EventTimingCodes(etco).to_string(), "5 events (ms)");
+}
+
+#[test]
+fn test_unique_file_identifier() {
+    use crate::id3::frame::UniqueFileIdentifier;
+
+    let mut tag = Tag::new();
+    tag.add_frame(UniqueFileIdentifier {
+        owner_identifier: "http://musicbrainz.org".to_string(),
+        identifier: b"0ff38b43-7d9b-4c8a-9b8a-2b6f1c3f8c1e".to_vec(),
+    });
+    tag.add_frame(UniqueFileIdentifier {
+        owner_identifier: "other".to_string(),
+        identifier: vec![0, 1, 2],
+    });
+    for version in [Version::Id3v22, Version::Id3v23, Version::Id3v24] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .encode(&tag, &mut data)
+            .unwrap();
+        let decoded = decode(&data[..]).unwrap();
+        assert_eq!(decoded.unique_file_identifiers().count(), 2);
+        assert_eq!(
+            decoded.unique_file_identifier("http://musicbrainz.org"),
+            Some(&b"0ff38b43-7d9b-4c8a-9b8a-2b6f1c3f8c1e"[..])
+        );
+        assert_eq!(
+            decoded.unique_file_identifier("other"),
+            Some(&[0, 1, 2][..])
+        );
+        assert_eq!(decoded.unique_file_identifier("missing"), None);
+    }
+}
+
+#[test]
+fn test_seek_signature_and_aspi() {
+    use crate::id3::frame::{AudioSeekPointIndex, Seek, Signature};
+
+    let aspi = AudioSeekPointIndex {
+        data_start: 1024,
+        data_length: 4096,
+        bits_per_point: 8,
+        points: vec![0, 64, 128, 192],
+    };
+    let signature = Signature {
+        group_symbol: 0x80,
+        signature: vec![1, 2, 3],
+    };
+    let mut first = Tag::new();
+    first.set_title("title");
+    first.add_frame(Seek { offset: 5 });
+    first.add_frame(aspi.clone());
+    first.add_frame(signature.clone());
+    let mut file = Vec::new();
+    Encoder::new()
+        .version(Version::Id3v24)
+        .padding(0)
+        .encode(&first, &mut file)
+        .unwrap();
+    let first_len = file.len() as u64;
+    file.extend_from_slice(b"audio");
+
+    let mut update = Tag::new();
+    update.set_artist("artist");
+    Encoder::new()
+        .version(Version::Id3v24)
+        .footer(true)
+        .encode(&update, &mut file)
+        .unwrap();
+
+    let tags = Tag::read_all_from_seekable(io::Cursor::new(&file)).unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[0].seek_offset(), Some(5));
+    assert_eq!(
+        tags[0]
+            .frames()
+            .find_map(|frame| frame.content().audio_seek_point_index()),
+        Some(&aspi)
+    );
+    assert_eq!(
+        tags[0]
+            .frames()
+            .find_map(|frame| frame.content().signature()),
+        Some(&signature)
+    );
+    assert_eq!(tags[1].artist(), Some("artist"));
+    assert_eq!(tags[1].source_range(), first_len + 5..file.len() as u64);
+
+    // Without the footer, the second tag is only found through the SEEK frame.
+    let mut file = file[..first_len as usize + 5].to_vec();
+    Encoder::new()
+        .version(Version::Id3v24)
+        .padding(0)
+        .encode(&update, &mut file)
+        .unwrap();
+    let tags = Tag::read_all_from_seekable(io::Cursor::new(&file)).unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[1].artist(), Some("artist"));
+
+    let mut bad = aspi;
+    bad.bits_per_point = 12;
+    let mut tag = Tag::new();
+    tag.add_frame(bad);
+    assert!(Encoder::new().encode(&tag, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_itunes_frames() {
+    let mut tag = Tag::new();
+    tag.set_compilation(true);
+    tag.set_movement("Adagio");
+    tag.set_movement_index(2);
+    tag.set_total_movements(4);
+    tag.set_tv_show("show");
+    // TVSH has no ID3v2.2 ID.
+    for version in [Version::Id3v23, Version::Id3v24] {
+        let mut data = Vec::new();
+        Encoder::new()
+            .version(version)
+            .encode(&tag, &mut data)
+            .unwrap();
+        let decoded = decode(&data[..]).unwrap();
+        assert_eq!(decoded.compilation(), Some(true));
+        assert_eq!(decoded.movement(), Some("Adagio"));
+        assert_eq!(decoded.movement_index(), Some(2));
+        assert_eq!(decoded.total_movements(), Some(4));
+        assert_eq!(decoded.tv_show(), Some("show"));
+    }
+}
+
+#[test]
+fn test_replaygain() {
+    use crate::id3::frame::ExtendedText;
+    use crate::id3::ReplayGain;
+
+    let text = |description: &str, value: &str| ExtendedText {
+        description: description.to_string(),
+        value: value.to_string(),
+    };
+
+    let mut tag = Tag::new();
+    assert_eq!(tag.replaygain(), None);
+    tag.add_frame(text("replaygain_track_gain", "-7.5dB"));
+    tag.add_frame(text("REPLAYGAIN_TRACK_PEAK", "nope"));
+    tag.add_frame(text("REPLAYGAIN_ALBUM_GAIN", " +0.25 db "));
+    tag.add_frame(text("other", "value"));
+    assert_eq!(
+        tag.replaygain(),
+        Some(ReplayGain {
+            track_gain: Some(-7.5),
+            album_gain: Some(0.25),
+            ..Default::default()
+        })
+    );
+
+    let replaygain = ReplayGain {
+        track_gain: Some(-6.48),
+        track_peak: Some(0.988_123),
+        album_gain: None,
+        album_peak: Some(1.0),
+    };
+    tag.set_replaygain(&replaygain);
+    let mut data = Vec::new();
+    Encoder::new().encode(&tag, &mut data).unwrap();
+    let decoded = decode(&data[..]).unwrap();
+    assert_eq!(decoded.replaygain(), Some(replaygain));
+    let texts: Vec<_> = decoded
+        .extended_texts()
+        .map(|t| (t.description.as_str(), t.value.as_str()))
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            ("other", "value"),
+            ("REPLAYGAIN_TRACK_GAIN", "-6.48 dB"),
+            ("REPLAYGAIN_TRACK_PEAK", "0.988123"),
+            ("REPLAYGAIN_ALBUM_PEAK", "1.000000"),
+        ]
+    );
+
+    tag.set_replaygain(&ReplayGain::default());
+    assert_eq!(tag.replaygain(), None);
+    assert_eq!(tag.extended_texts().count(), 1);
+}
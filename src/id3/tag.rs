@@ -1,17 +1,26 @@
 use crate::id3::chunk;
 use crate::id3::frame::{
-    Chapter, Comment, EncapsulatedObject, ExtendedLink, ExtendedText, Frame, Lyrics, Picture,
-    SynchronisedLyrics, TableOfContents,
+    Chapter, Comment, EncapsulatedObject, EventTimingCodes, ExtendedLink, ExtendedText, Frame,
+    Lyrics, Picture, SynchronisedLyrics, TableOfContents, UniqueFileIdentifier,
 };
 use crate::id3::storage::{PlainStorage, Storage};
 use crate::id3::stream;
+use crate::id3::stream::tag::ParseOptions;
 use crate::id3::taglike::TagLike;
 use crate::id3::v1;
 use crate::id3::StorageFile;
+use crate::id3::{Error, ErrorKind};
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+#[cfg(feature = "fs")]
 use std::fs::{self, File};
-use std::io::{self, BufReader, Write};
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{self, Write};
 use std::iter::{FromIterator, Iterator};
+use std::ops::Range;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 /// Denotes the version of a tag.
@@ -31,7 +40,7 @@ impl Version {
     ///
     /// # Example
     /// ```
-    /// use id3::Version;
+    /// use xm_decryptor::id3::Version;
     ///
     /// assert_eq!(Version::Id3v24.minor(), 4);
     /// ```
@@ -54,6 +63,105 @@ impl fmt::Display for Version {
     }
 }
 
+/// The restrictions an ID3v2.4 tag declares in its extended header for the tag editors that
+/// change it. They are informational, neither decoding nor encoding enforces them.
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Hash)]
+pub struct Restrictions(u8);
+
+impl Restrictions {
+    /// Creates restrictions from the byte they are stored as, `%ppqrrstt`.
+    pub fn from_bits(bits: u8) -> Restrictions {
+        Restrictions(bits)
+    }
+
+    /// Returns the byte the restrictions are stored as.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the most frames and the largest size in bytes the tag may have.
+    pub fn tag_size(self) -> (u32, u32) {
+        match self.0 >> 6 {
+            0 => (128, 1024 * 1024),
+            1 => (64, 128 * 1024),
+            2 => (32, 40 * 1024),
+            _ => (32, 4 * 1024),
+        }
+    }
+
+    /// Returns true if text may only be encoded as ISO-8859-1 or UTF-8.
+    pub fn text_encoding(self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// Returns the most characters a text field may hold, if it is limited.
+    pub fn text_size(self) -> Option<usize> {
+        match self.0 >> 3 & 0b11 {
+            0 => None,
+            1 => Some(1024),
+            2 => Some(128),
+            _ => Some(30),
+        }
+    }
+
+    /// Returns true if images may only be PNG or JPEG.
+    pub fn image_encoding(self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// Returns the largest width and height of images, if it is limited, and whether images must
+    /// be exactly that size.
+    pub fn image_size(self) -> Option<(u32, bool)> {
+        match self.0 & 0b11 {
+            0 => None,
+            1 => Some((256, false)),
+            2 => Some((64, false)),
+            _ => Some((64, true)),
+        }
+    }
+}
+
+/// The ReplayGain values of a tag, stored in the `REPLAYGAIN_*` user defined text frames (TXXX)
+/// written by loudness scanners. Gains are in dB, peaks are sample amplitudes where 1.0 is full
+/// scale.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct ReplayGain {
+    /// `REPLAYGAIN_TRACK_GAIN`
+    pub track_gain: Option<f64>,
+    /// `REPLAYGAIN_TRACK_PEAK`
+    pub track_peak: Option<f64>,
+    /// `REPLAYGAIN_ALBUM_GAIN`
+    pub album_gain: Option<f64>,
+    /// `REPLAYGAIN_ALBUM_PEAK`
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGain {
+    const TRACK_GAIN: &'static str = "REPLAYGAIN_TRACK_GAIN";
+    const TRACK_PEAK: &'static str = "REPLAYGAIN_TRACK_PEAK";
+    const ALBUM_GAIN: &'static str = "REPLAYGAIN_ALBUM_GAIN";
+    const ALBUM_PEAK: &'static str = "REPLAYGAIN_ALBUM_PEAK";
+
+    /// Parses a gain such as `-6.48 dB`, also without the unit.
+    fn parse_gain(text: &str) -> Option<f64> {
+        let text = text.trim();
+        let text = match text.len().checked_sub(2) {
+            Some(i) if text.is_char_boundary(i) && text[i..].eq_ignore_ascii_case("dB") => {
+                text[..i].trim_end()
+            }
+            _ => text,
+        };
+        text.parse().ok().filter(|gain: &f64| gain.is_finite())
+    }
+
+    fn parse_peak(text: &str) -> Option<f64> {
+        text.trim()
+            .parse()
+            .ok()
+            .filter(|peak: &f64| peak.is_finite())
+    }
+}
+
 /// An ID3 tag containing zero or more [`Frame`]s.
 #[derive(Clone, Debug, Default, Eq)]
 pub struct Tag {
@@ -62,6 +170,14 @@ pub struct Tag {
     /// ID3 Tag version
     version: Version,
     header_tag_size: u64,
+    /// Position of the tag in the source it was read from.
+    source_offset: u64,
+    /// Bytes of padding inside the declared tag size that were not occupied by frames.
+    padding_size: u64,
+    /// Hashes of the frames as they were read, or as they were when last marked clean.
+    clean_frames: Box<[u64]>,
+    /// The restrictions of the extended header.
+    restrictions: Option<Restrictions>,
 }
 
 impl<'a> Tag {
@@ -114,6 +230,7 @@ impl<'a> Tag {
     /// Removes an ID3v2 tag from the file at the specified path.
     ///
     /// Returns true if the file initially contained a tag.
+    #[cfg(feature = "fs")]
     pub fn remove_from_path(path: impl AsRef<Path>) -> crate::id3::Result<bool> {
         let mut file = fs::OpenOptions::new()
             .read(true)
@@ -127,10 +244,14 @@ impl<'a> Tag {
     /// Removes an ID3v2 tag from the specified file.
     ///
     /// Returns true if the file initially contained a tag.
+    #[cfg(feature = "fs")]
     pub fn remove_from_file(mut file: &mut fs::File) -> crate::id3::Result<bool> {
         let location = match stream::tag::locate_id3v2(&mut file)? {
             Some(l) => l,
-            None => return Ok(false),
+            None => match stream::tag::locate_id3v2_appended(&mut file)? {
+                Some(l) => l,
+                None => return Ok(false),
+            },
         };
         // Open the ID3 region for writing and write nothing. This removes the region in its
         // entirety.
@@ -144,25 +265,88 @@ impl<'a> Tag {
         stream::tag::decode(reader)
     }
 
-    /// Attempts to read an ID3 tag via Tokio from the reader.
-    #[cfg(feature = "tokio")]
-    pub async fn async_read_from(
-        reader: impl tokio::io::AsyncRead + std::marker::Unpin,
+    /// Attempts to read an ID3 tag from the reader using the specified [`ParseOptions`].
+    pub fn read_with_options(
+        reader: impl io::Read,
+        options: &ParseOptions,
     ) -> crate::id3::Result<Tag> {
-        stream::tag::async_decode(reader).await
+        stream::tag::decode_with_options(reader, options)
     }
 
-    /// Attempts to read an ID3 tag from the file at the indicated path.
-    pub fn read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
-        let file = BufReader::new(File::open(path)?);
-        Tag::read_from(file)
+    /// Attempts to read an ID3 tag from the reader, or if there is none at its position, the
+    /// ID3v2.4 tag appended to its end, found by the footer some tools write.
+    pub fn read_from_seekable(mut reader: impl io::Read + io::Seek) -> crate::id3::Result<Tag> {
+        let initial_position = reader.stream_position()?;
+        match stream::tag::decode(&mut reader) {
+            Err(err) if matches!(err.kind, ErrorKind::NoTag) => {}
+            result => return result,
+        }
+        let Some(range) = stream::tag::locate_id3v2_appended(&mut reader)? else {
+            return Err(Error::new(
+                ErrorKind::NoTag,
+                "reader does not contain an id3 tag",
+            ));
+        };
+        Self::read_at(reader, initial_position, range.start)
+    }
+
+    /// Reads all ID3 tags of the reader in the order they appear: the one at its position, the
+    /// ones SEEK frames point to, and the ID3v2.4 tag appended to its end. ID3v2.4 allows
+    /// later tags to update earlier ones, for example when a tag is appended to a file that
+    /// can't be rewritten.
+    pub fn read_all_from_seekable(
+        mut reader: impl io::Read + io::Seek,
+    ) -> crate::id3::Result<Vec<Tag>> {
+        let initial_position = reader.stream_position()?;
+        let mut tags = Vec::new();
+        match stream::tag::decode(&mut reader) {
+            Ok(tag) => tags.push(tag),
+            Err(err) if matches!(err.kind, ErrorKind::NoTag) => {}
+            Err(err) => return Err(err),
+        }
+        // Each tag is at least a header long, so following the SEEK frames ends at the end of
+        // the reader.
+        while let Some(offset) = tags.last().and_then(|tag| tag.seek_offset()) {
+            let tag_end = initial_position + tags.last().unwrap().source_range().end;
+            let Some(range) = stream::tag::locate_id3v2_seek(&mut reader, tag_end, offset)? else {
+                break;
+            };
+            tags.push(Self::read_at(&mut reader, initial_position, range.start)?);
+        }
+        if let Some(range) = stream::tag::locate_id3v2_appended(&mut reader)? {
+            let offset = range.start.saturating_sub(initial_position);
+            if !tags.iter().any(|tag| tag.source_offset == offset) {
+                tags.push(Self::read_at(&mut reader, initial_position, range.start)?);
+            }
+        }
+        if tags.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NoTag,
+                "reader does not contain an id3 tag",
+            ));
+        }
+        tags.sort_by_key(|tag| tag.source_offset);
+        Ok(tags)
     }
 
-    /// Attempts to read an ID3 tag via Tokio from the file at the indicated path.
-    #[cfg(feature = "tokio")]
-    pub async fn async_read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
-        let file = tokio::io::BufReader::new(tokio::fs::File::open(path).await?);
-        stream::tag::async_decode(file).await
+    /// Reads the tag at `start`, recording its offset from `initial_position`.
+    fn read_at(
+        mut reader: impl io::Read + io::Seek,
+        initial_position: u64,
+        start: u64,
+    ) -> crate::id3::Result<Tag> {
+        reader.seek(io::SeekFrom::Start(start))?;
+        let mut tag = stream::tag::decode(&mut reader)?;
+        tag.source_offset = start.saturating_sub(initial_position);
+        Ok(tag)
+    }
+
+    /// Attempts to read an ID3 tag from the file at the indicated path, also one appended to
+    /// the end of the file, see [`Tag::read_from_seekable`].
+    #[cfg(feature = "fs")]
+    pub fn read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
+        let file = BufReader::new(File::open(path)?);
+        Tag::read_from_seekable(file)
     }
 
     /// Reads an AIFF stream and returns any present ID3 tag.
@@ -171,12 +355,14 @@ impl<'a> Tag {
     }
 
     /// Reads an AIFF file at the specified path and returns any present ID3 tag.
+    #[cfg(feature = "fs")]
     pub fn read_from_aiff_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
         let mut file = BufReader::new(File::open(path)?);
         chunk::load_id3_chunk::<chunk::AiffFormat, _>(&mut file)
     }
 
     /// Reads an AIFF file and returns any present ID3 tag.
+    #[cfg(feature = "fs")]
     pub fn read_from_aiff_file(file: &mut fs::File) -> crate::id3::Result<Tag> {
         chunk::load_id3_chunk::<chunk::AiffFormat, _>(file)
     }
@@ -187,12 +373,14 @@ impl<'a> Tag {
     }
 
     /// Reads an WAV file at the specified path and returns any present ID3 tag.
+    #[cfg(feature = "fs")]
     pub fn read_from_wav_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
         let mut file = BufReader::new(File::open(path)?);
         chunk::load_id3_chunk::<chunk::WavFormat, _>(&mut file)
     }
 
     /// Reads an WAV file and returns any present ID3 tag.
+    #[cfg(feature = "fs")]
     pub fn read_from_wav_file(file: &mut fs::File) -> crate::id3::Result<Tag> {
         chunk::load_id3_chunk::<chunk::WavFormat, _>(file)
     }
@@ -228,6 +416,7 @@ impl<'a> Tag {
     }
 
     /// Conventience function for [`write_to_file`].
+    #[cfg(feature = "fs")]
     pub fn write_to_path(
         &self,
         path: impl AsRef<Path>,
@@ -237,7 +426,91 @@ impl<'a> Tag {
         self.write_to_file(file, version)
     }
 
+    /// Writes the tag to `file` like [`Tag::write_to_file`], but only if its frames or version
+    /// changed since it was read, see [`Tag::is_dirty`]. If the new tag fits into the space of the
+    /// old one, the rest is filled with padding so the audio after it doesn't have to be moved.
+    ///
+    /// Returns true if the file was written. The tag is marked clean afterwards.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Cursor;
+    /// use xm_decryptor::id3::{Tag, TagLike, Version};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_title("Title");
+    /// let mut file = Cursor::new(Vec::new());
+    /// assert!(tag.write_changes_to_file(&mut file, Version::Id3v24).unwrap());
+    /// assert!(!tag.write_changes_to_file(&mut file, Version::Id3v24).unwrap());
+    /// ```
+    pub fn write_changes_to_file(
+        &mut self,
+        mut file: impl StorageFile,
+        version: Version,
+    ) -> crate::id3::Result<bool> {
+        if !self.is_dirty() && version == self.version {
+            return Ok(false);
+        }
+        #[allow(clippy::reversed_empty_ranges)]
+        let location = stream::tag::locate_id3v2(&mut file)?.unwrap_or(0..0);
+
+        let encoder = stream::tag::Encoder::new().version(version);
+        let mut data = Vec::new();
+        encoder.encode(self, &mut data)?;
+        let space = location.end - location.start;
+        let mut padding = 0;
+        if (data.len() as u64) < space {
+            padding = space - data.len() as u64;
+            data.clear();
+            encoder.padding(padding as usize).encode(self, &mut data)?;
+        }
+
+        let mut storage = PlainStorage::new(file, location);
+        let mut w = storage.writer()?;
+        w.write_all(&data)?;
+        w.flush()?;
+        self.version = version;
+        self.header_tag_size = data.len() as u64;
+        self.source_offset = 0;
+        self.padding_size = padding;
+        self.mark_clean();
+        Ok(true)
+    }
+
+    /// Conventience function for [`Tag::write_changes_to_file`].
+    #[cfg(feature = "fs")]
+    pub fn write_changes_to_path(
+        &mut self,
+        path: impl AsRef<Path>,
+        version: Version,
+    ) -> crate::id3::Result<bool> {
+        if !self.is_dirty() && version == self.version {
+            return Ok(false);
+        }
+        let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+        self.write_changes_to_file(file, version)
+    }
+
+    /// Overwrites the ID3 chunk of the AIFF stream in `file`, or adds one.
+    pub fn write_to_aiff(
+        &self,
+        mut file: impl StorageFile,
+        version: Version,
+    ) -> crate::id3::Result<()> {
+        chunk::write_id3_chunk_file::<chunk::AiffFormat>(&mut file, self, version)
+    }
+
+    /// Overwrites the ID3 chunk of the WAV stream in `file`, or adds one.
+    pub fn write_to_wav(
+        &self,
+        mut file: impl StorageFile,
+        version: Version,
+    ) -> crate::id3::Result<()> {
+        chunk::write_id3_chunk_file::<chunk::WavFormat>(&mut file, self, version)
+    }
+
     /// Overwrite WAV file ID3 chunk in a file
+    #[cfg(feature = "fs")]
     pub fn write_to_aiff_path(
         &self,
         path: impl AsRef<Path>,
@@ -255,6 +528,7 @@ impl<'a> Tag {
     }
 
     /// Overwrite AIFF file ID3 chunk in a file. The file must be opened read/write.
+    #[cfg(feature = "fs")]
     pub fn write_to_aiff_file(
         &self,
         file: &mut fs::File,
@@ -264,6 +538,7 @@ impl<'a> Tag {
     }
 
     /// Overwrite WAV file ID3 chunk
+    #[cfg(feature = "fs")]
     pub fn write_to_wav_path(
         &self,
         path: impl AsRef<Path>,
@@ -281,6 +556,7 @@ impl<'a> Tag {
     }
 
     /// Overwrite AIFF file ID3 chunk in a file. The file must be opened read/write.
+    #[cfg(feature = "fs")]
     pub fn write_to_wav_file(
         &self,
         file: &mut fs::File,
@@ -299,11 +575,82 @@ impl<'a> Tag {
         self.header_tag_size
     }
 
+    /// Returns the byte range the read tag occupied in its source, including the 10-byte header
+    /// and any padding or footer. The range is relative to the position of the reader the tag was
+    /// read from.
+    ///
+    /// Tags that were not read from a source return an empty range.
+    pub fn source_range(&self) -> Range<u64> {
+        self.source_offset..self.source_offset + self.header_tag_size
+    }
+
+    /// Returns the number of padding bytes at the end of the read tag.
+    ///
+    /// Only padding inside the size declared by the tag header is counted, zero bytes that follow
+    /// the tag are not.
+    pub fn padding_size(&self) -> u64 {
+        self.padding_size
+    }
+
+    /// Returns the restrictions declared by the extended header of the read tag, if any.
+    pub fn restrictions(&self) -> Option<Restrictions> {
+        self.restrictions
+    }
+
+    /// Sets the restrictions written by an [`Encoder`](crate::id3::Encoder) with the extended
+    /// header enabled, for ID3v2.4 only.
+    pub fn set_restrictions(&mut self, restrictions: Option<Restrictions>) {
+        self.restrictions = restrictions;
+    }
+
+    pub(crate) fn set_padding_size(&mut self, padding_size: u64) {
+        self.padding_size = padding_size;
+    }
+
+    /// Returns true if frames were added, changed, removed or reordered since the tag was read or
+    /// last marked clean. A tag that was not read from a source is dirty once it has frames.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_title("Title");
+    /// assert!(tag.is_dirty());
+    ///
+    /// tag.mark_clean();
+    /// tag.set_title("Title");
+    /// assert!(!tag.is_dirty());
+    /// tag.set_title("Other title");
+    /// assert!(tag.is_dirty());
+    /// ```
+    pub fn is_dirty(&self) -> bool {
+        self.frames.len() != self.clean_frames.len()
+            || self
+                .frames
+                .iter()
+                .zip(&self.clean_frames)
+                .any(|(frame, hash)| frame_hash(frame) != *hash)
+    }
+
+    /// Returns the frames that were added or changed since the tag was read or last marked
+    /// clean.
+    pub fn changed_frames(&'a self) -> impl Iterator<Item = &'a Frame> + 'a {
+        self.frames
+            .iter()
+            .filter(|frame| !self.clean_frames.contains(&frame_hash(frame)))
+    }
+
+    /// Takes the current frames as the state the tag was read in, so it is no longer dirty.
+    pub fn mark_clean(&mut self) {
+        self.clean_frames = self.frames.iter().map(frame_hash).collect();
+    }
+
     /// Returns an iterator over the all frames in the tag.
     ///
     /// # Example
     /// ```
-    /// use id3::{Content, Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::{Content, Frame, Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -322,6 +669,91 @@ impl<'a> Tag {
             .filter_map(|frame| frame.content().extended_text())
     }
 
+    /// Returns the ReplayGain values of the tag, or `None` if it has none. The descriptions are
+    /// matched regardless of case, values that aren't numbers are left out.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::ExtendedText;
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.add_frame(ExtendedText {
+    ///     description: "REPLAYGAIN_TRACK_GAIN".to_string(),
+    ///     value: "-6.48 dB".to_string(),
+    /// });
+    /// tag.add_frame(ExtendedText {
+    ///     description: "replaygain_track_peak".to_string(),
+    ///     value: "0.988".to_string(),
+    /// });
+    ///
+    /// let replaygain = tag.replaygain().unwrap();
+    /// assert_eq!(replaygain.track_gain, Some(-6.48));
+    /// assert_eq!(replaygain.track_peak, Some(0.988));
+    /// assert_eq!(replaygain.album_gain, None);
+    /// ```
+    pub fn replaygain(&self) -> Option<ReplayGain> {
+        let value = |description: &str| {
+            self.extended_texts()
+                .find(|text| text.description.eq_ignore_ascii_case(description))
+                .map(|text| text.value.as_str())
+        };
+        let replaygain = ReplayGain {
+            track_gain: value(ReplayGain::TRACK_GAIN).and_then(ReplayGain::parse_gain),
+            track_peak: value(ReplayGain::TRACK_PEAK).and_then(ReplayGain::parse_peak),
+            album_gain: value(ReplayGain::ALBUM_GAIN).and_then(ReplayGain::parse_gain),
+            album_peak: value(ReplayGain::ALBUM_PEAK).and_then(ReplayGain::parse_peak),
+        };
+        (replaygain != ReplayGain::default()).then_some(replaygain)
+    }
+
+    /// Replaces the ReplayGain frames of the tag with `replaygain`, writing gains as `-6.48 dB`
+    /// and peaks with six decimals. Values that are `None` are removed.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{ReplayGain, Tag};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_replaygain(&ReplayGain {
+    ///     track_gain: Some(1.5),
+    ///     track_peak: Some(0.5),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// let texts: Vec<_> = tag.extended_texts().map(|t| t.value.as_str()).collect();
+    /// assert_eq!(texts, ["+1.50 dB", "0.500000"]);
+    /// ```
+    pub fn set_replaygain(&mut self, replaygain: &ReplayGain) {
+        let values = [
+            (ReplayGain::TRACK_GAIN, replaygain.track_gain, true),
+            (ReplayGain::TRACK_PEAK, replaygain.track_peak, false),
+            (ReplayGain::ALBUM_GAIN, replaygain.album_gain, true),
+            (ReplayGain::ALBUM_PEAK, replaygain.album_peak, false),
+        ];
+        self.frames_vec_mut().retain(|frame| {
+            !frame.content().extended_text().is_some_and(|text| {
+                values
+                    .iter()
+                    .any(|(description, ..)| text.description.eq_ignore_ascii_case(description))
+            })
+        });
+        for (description, value, is_gain) in values {
+            let Some(value) = value else {
+                continue;
+            };
+            let text = if is_gain {
+                format!("{:+.2} dB", value)
+            } else {
+                format!("{:.6}", value)
+            };
+            self.add_frame(ExtendedText {
+                description: description.to_string(),
+                value: text,
+            });
+        }
+    }
+
     /// Returns an iterator over the extended links in the tag.
     pub fn extended_links(&'a self) -> impl Iterator<Item = &'a ExtendedLink> + 'a {
         self.frames()
@@ -337,8 +769,8 @@ impl<'a> Tag {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::{Content, Comment};
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Content, Comment};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -373,12 +805,58 @@ impl<'a> Tag {
             .filter_map(|frame| frame.content().synchronised_lyrics())
     }
 
+    /// Returns an iterator over the unique file identifiers (UFID) in the tag.
+    pub fn unique_file_identifiers(
+        &'a self,
+    ) -> impl Iterator<Item = &'a UniqueFileIdentifier> + 'a {
+        self.frames()
+            .filter_map(|frame| frame.content().unique_file_identifier())
+    }
+
+    /// Returns the identifier of the unique file identifier (UFID) frame owned by `owner`.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::UniqueFileIdentifier;
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.add_frame(UniqueFileIdentifier {
+    ///     owner_identifier: "http://musicbrainz.org".to_owned(),
+    ///     identifier: b"0ff38b43-7d9b-4c8a-9b8a-2b6f1c3f8c1e".to_vec(),
+    /// });
+    ///
+    /// assert_eq!(
+    ///     tag.unique_file_identifier("http://musicbrainz.org"),
+    ///     Some(&b"0ff38b43-7d9b-4c8a-9b8a-2b6f1c3f8c1e"[..])
+    /// );
+    /// ```
+    pub fn unique_file_identifier(&'a self, owner: &str) -> Option<&'a [u8]> {
+        self.unique_file_identifiers()
+            .find(|ufid| ufid.owner_identifier == owner)
+            .map(|ufid| ufid.identifier.as_slice())
+    }
+
+    /// Returns the offset of the SEEK frame, the minimum number of bytes from the end of this tag
+    /// to the next tag in the file, if any. See [`Tag::read_all_from_seekable`].
+    pub fn seek_offset(&self) -> Option<u32> {
+        self.frames()
+            .find_map(|frame| frame.content().seek())
+            .map(|seek| seek.offset)
+    }
+
+    /// Returns the event timing codes (ETCO) of the tag, if any.
+    pub fn event_timing_codes(&self) -> Option<&EventTimingCodes> {
+        self.frames()
+            .find_map(|frame| frame.content().event_timing_codes())
+    }
+
     /// Returns an iterator over the pictures in the tag.
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::{Content, Picture, PictureType};
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Content, Picture, PictureType};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -401,8 +879,8 @@ impl<'a> Tag {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Chapter, Content, Frame};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Chapter, Content, Frame};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Chapter{
@@ -431,8 +909,8 @@ impl<'a> Tag {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Chapter, TableOfContents, Content, Frame};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Chapter, TableOfContents, Content, Frame};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Chapter{
@@ -465,6 +943,12 @@ impl<'a> Tag {
     }
 }
 
+fn frame_hash(frame: &Frame) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    frame.hash(&mut hasher);
+    hasher.finish()
+}
+
 impl PartialEq for Tag {
     fn eq(&self, other: &Tag) -> bool {
         self.frames.len() == other.frames.len()
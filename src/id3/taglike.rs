@@ -64,7 +64,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Frame, Content};
+    /// use xm_decryptor::id3::{Tag, TagLike, Frame, Content};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -79,12 +79,107 @@ pub trait TagLike: private::Sealed {
             .find(|frame| frame.id() == id.as_ref())
     }
 
+    /// Returns all frames with the specified identifier, in the order they were added.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Comment;
+    ///
+    /// let mut tag = Tag::new();
+    /// for description in ["a", "b"] {
+    ///     tag.add_frame(Comment {
+    ///         lang: "eng".to_string(),
+    ///         description: description.to_string(),
+    ///         text: "text".to_string(),
+    ///     });
+    /// }
+    ///
+    /// let comments = tag.get_all("COMM");
+    /// assert_eq!(comments.len(), 2);
+    /// assert_eq!(comments[1].content().comment().map(|c| c.description.as_str()), Some("b"));
+    /// ```
+    fn get_all(&self, id: impl AsRef<str>) -> Vec<&Frame> {
+        self.frames_vec()
+            .iter()
+            .filter(|frame| frame.id() == id.as_ref())
+            .collect()
+    }
+
+    /// Returns the position in the whole tag of the `n`th frame with the specified identifier.
+    #[doc(hidden)]
+    fn nth_frame_index(&self, id: &str, n: usize) -> Option<usize> {
+        self.frames_vec()
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| frame.id() == id)
+            .nth(n)
+            .map(|(index, _)| index)
+    }
+
+    /// Removes and returns the `n`th frame with the specified identifier, counted from 0 in the
+    /// order of [`TagLike::get_all`]. The other frames keep their order.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::ExtendedText;
+    ///
+    /// let mut tag = Tag::new();
+    /// for description in ["first", "second"] {
+    ///     tag.add_frame(ExtendedText {
+    ///         description: description.to_string(),
+    ///         value: String::new(),
+    ///     });
+    /// }
+    ///
+    /// let removed = tag.remove_at("TXXX", 1).unwrap();
+    /// assert_eq!(removed.content().extended_text().unwrap().description, "second");
+    /// assert_eq!(tag.get_all("TXXX").len(), 1);
+    /// assert!(tag.remove_at("TXXX", 1).is_none());
+    /// ```
+    fn remove_at(&mut self, id: impl AsRef<str>, n: usize) -> Option<Frame> {
+        let index = self.nth_frame_index(id.as_ref(), n)?;
+        Some(self.frames_vec_mut().remove(index))
+    }
+
+    /// Replaces the `n`th frame with the specified identifier, counted from 0 in the order of
+    /// [`TagLike::get_all`], with `frame` in the same position. Returns the replaced frame, or
+    /// `None` without adding `frame` if there is no such frame.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_title("title");
+    /// tag.set_artist("artist");
+    ///
+    /// let replaced = tag.replace_at("TIT2", 0, Frame::text("TIT2", "new title")).unwrap();
+    /// assert_eq!(replaced.content().text(), Some("title"));
+    /// assert_eq!(tag.frames().next().unwrap().content().text(), Some("new title"));
+    /// ```
+    fn replace_at(
+        &mut self,
+        id: impl AsRef<str>,
+        n: usize,
+        frame: impl Into<Frame>,
+    ) -> Option<Frame> {
+        let index = self.nth_frame_index(id.as_ref(), n)?;
+        Some(std::mem::replace(
+            &mut self.frames_vec_mut()[index],
+            frame.into(),
+        ))
+    }
+
     /// Adds the frame to the tag, replacing and returning any conflicting frame.
     ///
+    /// A replacing frame takes the position of the frame it replaces, others are added last.
+    ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Frame, Content};
-    /// use id3::frame::ExtendedText;
+    /// use xm_decryptor::id3::{Tag, TagLike, Frame, Content};
+    /// use xm_decryptor::id3::frame::ExtendedText;
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -103,20 +198,27 @@ pub trait TagLike: private::Sealed {
     /// ```
     fn add_frame(&mut self, new_frame: impl Into<Frame>) -> Option<Frame> {
         let new_frame = new_frame.into();
-        let removed = self
+        match self
             .frames_vec()
             .iter()
             .position(|frame| frame.compare(&new_frame))
-            .map(|conflict_index| self.frames_vec_mut().remove(conflict_index));
-        self.frames_vec_mut().push(new_frame);
-        removed
+        {
+            Some(index) => Some(std::mem::replace(
+                &mut self.frames_vec_mut()[index],
+                new_frame,
+            )),
+            None => {
+                self.frames_vec_mut().push(new_frame);
+                None
+            }
+        }
     }
 
     /// Adds a text frame.
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -136,7 +238,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -158,7 +260,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Content, Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::{Content, Frame, Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -189,8 +291,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Frame};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Tag, TagLike, Frame};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// assert!(tag.year().is_none());
@@ -213,7 +315,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_year(2014);
@@ -227,7 +329,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_year(2014);
@@ -244,8 +346,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::Timestamp;
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::Timestamp;
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_recorded(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -259,7 +361,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_recorded(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -274,7 +376,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_recorded(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -291,7 +393,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -305,7 +407,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -320,7 +422,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -337,7 +439,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_original_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -351,7 +453,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_original_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -366,7 +468,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike, Timestamp};
+    /// use xm_decryptor::id3::{Tag, TagLike, Timestamp};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_original_date_released(Timestamp{ year: 2014, month: None, day: None, hour: None, minute: None, second: None });
@@ -383,8 +485,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Frame::text("TPE1", "artist"));
@@ -403,7 +505,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_artist("artist");
@@ -417,7 +519,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_artist("artist");
@@ -434,8 +536,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Frame::text("TPE2", "artist"));
@@ -449,7 +551,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_album_artist("artist");
@@ -463,7 +565,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_album_artist("artist");
@@ -480,8 +582,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Frame::text("TALB", "album"));
@@ -495,7 +597,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_album("album");
@@ -509,7 +611,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_album("album");
@@ -526,8 +628,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Frame::text("TIT2", "title"));
@@ -541,7 +643,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_title("title");
@@ -555,7 +657,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_title("title");
@@ -572,8 +674,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     ///
@@ -588,7 +690,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_duration(350);
@@ -602,7 +704,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_duration(350);
@@ -623,8 +725,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Frame::text("TCON", "genre"));
@@ -640,8 +742,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::frame::Content;
-    /// use id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
     /// use std::borrow::Cow;
     ///
     /// let mut tag = Tag::new();
@@ -650,7 +752,7 @@ pub trait TagLike: private::Sealed {
     /// tag.set_genre("(31)");
     /// assert_eq!(tag.genre_parsed(), Some(Cow::Owned("Trance".to_string())));
     /// ```
-    fn genre_parsed(&self) -> Option<Cow<str>> {
+    fn genre_parsed(&self) -> Option<Cow<'_, str>> {
         let tcon = self.text_for_frame_id("TCON")?;
         Some(crate::id3::tcon::Parser::parse_tcon(tcon))
     }
@@ -666,7 +768,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_genre("genre");
@@ -680,7 +782,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_genre("genre");
@@ -697,8 +799,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// assert!(tag.disc().is_none());
@@ -719,7 +821,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_disc(2);
@@ -740,7 +842,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_disc(3);
@@ -757,8 +859,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// assert!(tag.disc().is_none());
@@ -780,7 +882,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_total_discs(10);
@@ -798,7 +900,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_total_discs(10);
@@ -817,8 +919,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// assert!(tag.track().is_none());
@@ -839,7 +941,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_track(10);
@@ -860,7 +962,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_track(10);
@@ -877,8 +979,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Frame, Tag, TagLike};
-    /// use id3::frame::Content;
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Content;
     ///
     /// let mut tag = Tag::new();
     /// assert!(tag.total_tracks().is_none());
@@ -900,7 +1002,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_total_tracks(10);
@@ -918,7 +1020,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     /// tag.set_total_tracks(10);
@@ -933,11 +1035,259 @@ pub trait TagLike: private::Sealed {
         }
     }
 
+    /// Returns whether the iTunes compilation flag (TCMP) is set. Returns `None` if there is no
+    /// flag or it is neither `1` nor `0`.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// assert!(tag.compilation().is_none());
+    ///
+    /// tag.add_frame(Frame::text("TCMP", "1"));
+    /// assert_eq!(tag.compilation(), Some(true));
+    /// ```
+    fn compilation(&self) -> Option<bool> {
+        match self.text_for_frame_id("TCMP")?.trim() {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Sets the iTunes compilation flag (TCMP).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_compilation(true);
+    /// assert_eq!(tag.compilation(), Some(true));
+    /// ```
+    fn set_compilation(&mut self, compilation: bool) {
+        self.set_text("TCMP", if compilation { "1" } else { "0" });
+    }
+
+    /// Removes the iTunes compilation flag (TCMP).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_compilation(true);
+    /// assert!(tag.compilation().is_some());
+    ///
+    /// tag.remove_compilation();
+    /// assert!(tag.compilation().is_none());
+    /// ```
+    fn remove_compilation(&mut self) {
+        self.remove("TCMP");
+    }
+
+    /// Returns the iTunes movement name (MVNM).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.add_frame(Frame::text("MVNM", "Adagio"));
+    /// assert_eq!(tag.movement(), Some("Adagio"));
+    /// ```
+    fn movement(&self) -> Option<&str> {
+        self.text_for_frame_id("MVNM")
+    }
+
+    /// Sets the iTunes movement name (MVNM).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_movement("Adagio");
+    /// assert_eq!(tag.movement(), Some("Adagio"));
+    /// ```
+    fn set_movement(&mut self, movement: impl Into<String>) {
+        self.set_text("MVNM", movement);
+    }
+
+    /// Removes the iTunes movement name (MVNM).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_movement("Adagio");
+    /// assert!(tag.movement().is_some());
+    ///
+    /// tag.remove_movement();
+    /// assert!(tag.movement().is_none());
+    /// ```
+    fn remove_movement(&mut self) {
+        self.remove("MVNM");
+    }
+
+    /// Returns the iTunes movement number (MVIN).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// assert!(tag.movement_index().is_none());
+    ///
+    /// tag.add_frame(Frame::text("MVIN", "2/4"));
+    /// assert_eq!(tag.movement_index(), Some(2));
+    /// ```
+    fn movement_index(&self) -> Option<u32> {
+        self.text_pair("MVIN").map(|(index, _)| index)
+    }
+
+    /// Sets the iTunes movement number (MVIN).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_movement_index(2);
+    /// assert_eq!(tag.movement_index(), Some(2));
+    /// ```
+    fn set_movement_index(&mut self, index: u32) {
+        let text = match self
+            .text_pair("MVIN")
+            .and_then(|(_, total_movements)| total_movements)
+        {
+            Some(n) => format!("{}/{}", index, n),
+            None => format!("{}", index),
+        };
+        self.set_text("MVIN", text);
+    }
+
+    /// Removes the iTunes movement number and count (MVIN).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_movement_index(2);
+    /// assert!(tag.movement_index().is_some());
+    ///
+    /// tag.remove_movement_index();
+    /// assert!(tag.movement_index().is_none());
+    /// ```
+    fn remove_movement_index(&mut self) {
+        self.remove("MVIN");
+    }
+
+    /// Returns the iTunes movement count (MVIN).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.add_frame(Frame::text("MVIN", "2/4"));
+    /// assert_eq!(tag.total_movements(), Some(4));
+    /// ```
+    fn total_movements(&self) -> Option<u32> {
+        self.text_pair("MVIN")
+            .and_then(|(_, total_movements)| total_movements)
+    }
+
+    /// Sets the iTunes movement count (MVIN).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_total_movements(4);
+    /// assert_eq!(tag.total_movements(), Some(4));
+    /// ```
+    fn set_total_movements(&mut self, total_movements: u32) {
+        let text = match self.text_pair("MVIN") {
+            Some((index, _)) => format!("{}/{}", index, total_movements),
+            None => format!("1/{}", total_movements),
+        };
+        self.set_text("MVIN", text);
+    }
+
+    /// Removes the iTunes movement count (MVIN), keeping the movement number.
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_total_movements(4);
+    /// assert!(tag.total_movements().is_some());
+    ///
+    /// tag.remove_total_movements();
+    /// assert!(tag.total_movements().is_none());
+    /// ```
+    fn remove_total_movements(&mut self) {
+        if let Some((index, _)) = self.text_pair("MVIN") {
+            self.set_text("MVIN", format!("{}", index));
+        }
+    }
+
+    /// Returns the iTunes TV show name (TVSH).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Frame, Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.add_frame(Frame::text("TVSH", "show"));
+    /// assert_eq!(tag.tv_show(), Some("show"));
+    /// ```
+    fn tv_show(&self) -> Option<&str> {
+        self.text_for_frame_id("TVSH")
+    }
+
+    /// Sets the iTunes TV show name (TVSH).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_tv_show("show");
+    /// assert_eq!(tag.tv_show(), Some("show"));
+    /// ```
+    fn set_tv_show(&mut self, tv_show: impl Into<String>) {
+        self.set_text("TVSH", tv_show);
+    }
+
+    /// Removes the iTunes TV show name (TVSH).
+    ///
+    /// # Example
+    /// ```
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.set_tv_show("show");
+    /// assert!(tag.tv_show().is_some());
+    ///
+    /// tag.remove_tv_show();
+    /// assert!(tag.tv_show().is_none());
+    /// ```
+    fn remove_tv_show(&mut self) {
+        self.remove("TVSH");
+    }
+
     /// Adds a user defined text frame (TXXX).
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -962,7 +1312,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -1012,8 +1362,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Picture, PictureType};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Picture, PictureType};
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -1043,8 +1393,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Picture, PictureType};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Picture, PictureType};
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -1086,8 +1436,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Picture, PictureType};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Picture, PictureType};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_picture(Picture {
@@ -1115,8 +1465,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::Comment;
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Comment;
     ///
     /// let mut tag = Tag::new();
     ///
@@ -1148,8 +1498,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::Comment;
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Comment;
     ///
     /// let mut tag = Tag::new();
     ///
@@ -1197,7 +1547,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -1232,7 +1582,7 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::{Tag, TagLike};
     ///
     /// let mut tag = Tag::new();
     ///
@@ -1298,8 +1648,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::Lyrics;
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Lyrics;
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let mut tag = Tag::new();
@@ -1321,8 +1671,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::Lyrics;
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::Lyrics;
     ///
     /// let mut tag = Tag::new();
     /// tag.add_lyrics(Lyrics {
@@ -1342,8 +1692,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_synchronised_lyrics(SynchronisedLyrics {
@@ -1368,8 +1718,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_synchronised_lyrics(SynchronisedLyrics {
@@ -1395,8 +1745,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Chapter, Content, Frame};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Chapter, Content, Frame};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Chapter{
@@ -1419,8 +1769,8 @@ pub trait TagLike: private::Sealed {
     ///
     /// # Example
     /// ```
-    /// use id3::{Tag, TagLike};
-    /// use id3::frame::{Chapter, TableOfContents, Content, Frame};
+    /// use xm_decryptor::id3::{Tag, TagLike};
+    /// use xm_decryptor::id3::frame::{Chapter, TableOfContents, Content, Frame};
     ///
     /// let mut tag = Tag::new();
     /// tag.add_frame(Chapter{
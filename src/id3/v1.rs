@@ -1,8 +1,11 @@
+use crate::id3::charset;
 use crate::id3::{Error, ErrorKind, StorageFile};
 use std::cmp;
+#[cfg(feature = "fs")]
 use std::fs;
 use std::io;
 use std::ops;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 /// Location of the ID3v1 tag chunk relative to the end of the file.
@@ -161,6 +164,18 @@ pub(crate) static GENRE_LIST: &[&str] = &[
     "SynthPop",
 ];
 
+/// The character set ID3v1 text is written in. The format doesn't record it, players assume the
+/// one of their region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Charset {
+    /// ISO-8859-1, as the specification says.
+    #[default]
+    Latin1,
+    /// GBK, which players sold in mainland China assume. Converted by the system, iconv on Unix
+    /// and the Windows code page 936, other platforms write `?` for every non-ASCII character.
+    Gbk,
+}
+
 /// A structure containing ID3v1 metadata.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Tag {
@@ -295,7 +310,81 @@ impl Tag {
         })
     }
 
+    /// Derives an ID3v1 tag from the title, artist, album, year, first comment, track and genre
+    /// of the ID3v2 `tag`. The values are truncated to the ID3v1 field sizes when encoded.
+    pub fn from_v2(tag: &crate::id3::Tag) -> Tag {
+        use crate::id3::TagLike;
+
+        let genre_id = tag
+            .genre_parsed()
+            .and_then(|genre| {
+                GENRE_LIST
+                    .iter()
+                    .position(|g| g.eq_ignore_ascii_case(&genre))
+            })
+            .map_or(u8::MAX, |id| id as u8);
+        Tag {
+            title: tag.title().unwrap_or_default().to_string(),
+            artist: tag.artist().unwrap_or_default().to_string(),
+            album: tag.album().unwrap_or_default().to_string(),
+            year: tag
+                .year()
+                .or_else(|| tag.date_recorded().map(|date| date.year))
+                .filter(|year| (0..=9999).contains(year))
+                .map(|year| format!("{:04}", year))
+                .unwrap_or_default(),
+            comment: tag
+                .comments()
+                .next()
+                .map(|comment| comment.text.clone())
+                .unwrap_or_default(),
+            track: tag.track().and_then(|track| u8::try_from(track).ok()),
+            genre_id,
+            ..Tag::default()
+        }
+    }
+
+    /// Encodes the tag as the 128 bytes of an ID3v1.1 tag, with the text in `charset`. Text that
+    /// doesn't fit is cut at a character boundary, characters `charset` lacks are written as `?`.
+    ///
+    /// The extended data of `TAG+` is not written, few players read it.
+    pub fn encode(&self, charset: Charset) -> [u8; 128] {
+        let mut buf = [0; 128];
+        buf[..3].copy_from_slice(b"TAG");
+        let mut put = |range: ops::Range<usize>, text: &str| {
+            let bytes = encode_text(text, range.len(), charset);
+            buf[range.start..range.start + bytes.len()].copy_from_slice(&bytes);
+        };
+        put(3..33, &self.title);
+        put(33..63, &self.artist);
+        put(63..93, &self.album);
+        put(93..97, &self.year);
+        match self.track {
+            Some(track) if track != 0 => {
+                put(97..125, &self.comment);
+                buf[126] = track;
+            }
+            _ => put(97..127, &self.comment),
+        }
+        buf[127] = self.genre_id;
+        buf
+    }
+
+    /// Writes the tag to the end of `file`, replacing any ID3v1 tag already there.
+    pub fn write_to_file(
+        &self,
+        mut file: impl StorageFile,
+        charset: Charset,
+    ) -> crate::id3::Result<()> {
+        Tag::remove_from_file(&mut file)?;
+        file.seek(io::SeekFrom::End(0))?;
+        file.write_all(&self.encode(charset))?;
+        file.flush()?;
+        Ok(())
+    }
+
     /// Attempts to read an ID3v1 tag from the file at the indicated path.
+    #[cfg(feature = "fs")]
     pub fn read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
         let file = fs::File::open(path)?;
         Tag::read_from(file)
@@ -307,6 +396,7 @@ impl Tag {
     ///
     /// Returns true if the file initially contained a tag.
     #[deprecated(note = "Use remove_from_file")]
+    #[cfg(feature = "fs")]
     pub fn remove(file: &mut fs::File) -> crate::id3::Result<bool> {
         Self::remove_from_file(file)
     }
@@ -356,6 +446,7 @@ impl Tag {
     /// Removes an ID3v1 tag plus possible extended data if any.
     ///
     /// Returns true if the file initially contained a tag.
+    #[cfg(feature = "fs")]
     pub fn remove_from_path(path: impl AsRef<Path>) -> crate::id3::Result<bool> {
         let mut file = fs::OpenOptions::new().read(true).write(true).open(path)?;
         Tag::remove_from_file(&mut file)
@@ -371,3 +462,47 @@ impl Tag {
         GENRE_LIST.get(self.genre_id as usize).cloned()
     }
 }
+
+/// Encodes `text` in `charset` in at most `len` bytes, ending at a character boundary.
+fn encode_text(text: &str, len: usize, charset: Charset) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(len);
+    let mut gbk = match charset {
+        Charset::Gbk => charset::Converter::new(),
+        Charset::Latin1 => None,
+    };
+    for c in text.chars() {
+        let encoded = match (charset, &mut gbk) {
+            _ if c.is_ascii() => Some(vec![c as u8]),
+            (Charset::Latin1, _) => u8::try_from(u32::from(c)).ok().map(|b| vec![b]),
+            (Charset::Gbk, converter) => converter.as_mut().and_then(|gbk| gbk.encode(c)),
+        };
+        let encoded = encoded.unwrap_or_else(|| vec![b'?']);
+        if bytes.len() + encoded.len() > len {
+            break;
+        }
+        bytes.extend_from_slice(&encoded);
+    }
+    bytes
+}
+
+#[test]
+fn test_encode() {
+    let mut tag = crate::id3::Tag::new();
+    {
+        use crate::id3::TagLike;
+        tag.set_title("Caf\u{e9} \u{4e2d}\u{6587} and a title longer than thirty bytes");
+        tag.set_artist("artist");
+        tag.set_track(300);
+        tag.set_year(2023);
+        tag.set_genre("Jazz");
+    }
+    let v1 = Tag::from_v2(&tag);
+    assert_eq!((v1.track, v1.genre_id, v1.year.as_str()), (None, 8, "2023"));
+
+    let buf = v1.encode(Charset::Latin1);
+    assert_eq!(&buf[..33], b"TAGCaf\xe9 ?? and a title longer tha");
+    assert_eq!(&buf[33..40], b"artist\0");
+    let read = Tag::read_from(io::Cursor::new(buf.to_vec())).unwrap();
+    assert_eq!(read.title, "Caf\u{e9} ?? and a title longer tha");
+    assert_eq!((read.year.as_str(), read.genre_id), ("2023", 8));
+}
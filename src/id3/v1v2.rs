@@ -1,7 +1,10 @@
-use crate::id3::{v1, Error, ErrorKind, StorageFile, Tag, Version};
+use crate::id3::{v1, Encoder, Error, ErrorKind, StorageFile, Tag, TagLike, Version};
+#[cfg(feature = "fs")]
 use std::fs;
+#[cfg(feature = "fs")]
 use std::fs::File;
 use std::io;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 /// Returns which tags are present in the specified file.
@@ -17,6 +20,7 @@ pub fn is_candidate(mut file: impl io::Read + io::Seek) -> crate::id3::Result<Fo
 }
 
 /// Returns which tags are present in the specified file.
+#[cfg(feature = "fs")]
 pub fn is_candidate_path(path: impl AsRef<Path>) -> crate::id3::Result<FormatVersion> {
     is_candidate(File::open(path)?)
 }
@@ -49,9 +53,61 @@ pub fn read_from(mut file: impl io::Read + io::Seek) -> crate::id3::Result<Tag>
     ))
 }
 
+/// Reads both the ID3v2 and the ID3v1 tag and merges them. The ID3v2 frames take priority, the
+/// ID3v1 title, artist, album, year, comment, track and genre fill in the frames it lacks. Some
+/// sources leave the ID3v2 tag empty and only fill in the ID3v1 tag.
+///
+/// If neither version tag is found, an error with [`ErrorKind::NoTag`] is returned.
+pub fn read_from_merged(mut file: impl io::Read + io::Seek) -> crate::id3::Result<Tag> {
+    let start = file.stream_position()?;
+    let v2 = match Tag::read_from(&mut file) {
+        Ok(tag) => Some(tag),
+        Err(Error {
+            kind: ErrorKind::NoTag,
+            ..
+        }) => None,
+        Err(err) => return Err(err),
+    };
+    file.seek(io::SeekFrom::Start(start))?;
+    let v1 = match v1::Tag::read_from(&mut file) {
+        Ok(tag) => Some(Tag::from(tag)),
+        Err(Error {
+            kind: ErrorKind::NoTag,
+            ..
+        }) => None,
+        Err(err) => return Err(err),
+    };
+    match (v2, v1) {
+        (Some(mut v2), Some(v1)) => {
+            for frame in v1.frames() {
+                let present = match frame.id() {
+                    "TYER" => v2.year().is_some() || v2.date_recorded().is_some(),
+                    id => v2.get(id).is_some(),
+                };
+                if !present {
+                    v2.add_frame(frame.clone());
+                }
+            }
+            Ok(v2)
+        }
+        (Some(tag), None) | (None, Some(tag)) => Ok(tag),
+        (None, None) => Err(Error::new(
+            ErrorKind::NoTag,
+            "Neither a ID3v2 or ID3v1 tag was found",
+        )),
+    }
+}
+
+/// Reads and merges both tags of the file at `path` like [`read_from_merged`].
+#[cfg(feature = "fs")]
+pub fn read_from_path_merged(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
+    read_from_merged(File::open(path)?)
+}
+
 /// Attempts to read an ID3v2 or ID3v1 tag, in that order.
 ///
 /// If neither version tag is found, an error with [`ErrorKind::NoTag`] is returned.
+#[cfg(feature = "fs")]
 pub fn read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
     read_from(File::open(path)?)
 }
@@ -62,16 +118,32 @@ pub fn read_from_path(path: impl AsRef<Path>) -> crate::id3::Result<Tag> {
 /// If any ID3v1 tag is present it will be REMOVED as it is not able to fully represent a ID3v2
 /// tag.
 pub fn write_to_file(
-    mut file: impl StorageFile,
+    file: impl StorageFile,
     tag: &Tag,
     version: Version,
 ) -> crate::id3::Result<()> {
-    tag.write_to_file(&mut file, version)?;
-    v1::Tag::remove_from_file(&mut file)?;
+    encode_to_file(file, tag, &Encoder::new().version(version))
+}
+
+/// Writes the specified tag to a file with the settings of `encoder`. Any existing ID3v2 tag is
+/// replaced or added if it is not present.
+///
+/// With [`Encoder::also_write_id3v1`] an ID3v1 tag derived from `tag` replaces any present one,
+/// otherwise any ID3v1 tag is REMOVED.
+pub fn encode_to_file(
+    mut file: impl StorageFile,
+    tag: &Tag,
+    encoder: &Encoder,
+) -> crate::id3::Result<()> {
+    encoder.write_to_file(tag, &mut file)?;
+    if !encoder.writes_id3v1() {
+        v1::Tag::remove_from_file(&mut file)?;
+    }
     Ok(())
 }
 
 /// Conventience function for [`write_to_file`].
+#[cfg(feature = "fs")]
 pub fn write_to_path(
     path: impl AsRef<Path>,
     tag: &Tag,
@@ -84,6 +156,7 @@ pub fn write_to_path(
 /// Ensures that both ID3v1 and ID3v2 are not present in the specified file.
 ///
 /// Returns [`FormatVersion`] representing the previous state.
+#[cfg(feature = "fs")]
 pub fn remove_from_path(path: impl AsRef<Path>) -> crate::id3::Result<FormatVersion> {
     let v2 = Tag::remove_from_path(&path)?;
     let v1 = v1::Tag::remove_from_path(path)?;
@@ -107,3 +180,28 @@ pub enum FormatVersion {
     /// ID3v1 + ID3v2
     Both,
 }
+
+#[test]
+fn test_read_from_merged() {
+    let mut v2 = Tag::new();
+    v2.set_title("v2 title");
+    v2.set_text("TDRC", "2020");
+    let mut file = Vec::new();
+    v2.write_to(&mut file, Version::Id3v24).unwrap();
+    file.extend_from_slice(b"audio");
+    let v1 = v1::Tag {
+        title: "v1 title".to_string(),
+        artist: "v1 artist".to_string(),
+        year: "1999".to_string(),
+        track: Some(7),
+        ..v1::Tag::default()
+    };
+    file.extend_from_slice(&v1.encode(v1::Charset::Latin1));
+
+    let tag = read_from_merged(io::Cursor::new(&file)).unwrap();
+    assert_eq!(tag.title(), Some("v2 title"));
+    assert_eq!(tag.artist(), Some("v1 artist"));
+    assert_eq!(tag.track(), Some(7));
+    assert_eq!(tag.year(), None);
+    assert_eq!(read_from(io::Cursor::new(&file)).unwrap().artist(), None);
+}
@@ -1,4 +1,18 @@
+pub mod audit;
+#[cfg(feature = "fs")]
+pub mod batch;
+pub mod collate;
+pub mod flac;
+pub mod health;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod id3;
+pub mod json;
+pub mod metadata;
+pub mod mp3;
+pub mod mp4;
+pub mod naming;
+mod wasm;
 pub mod xm;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
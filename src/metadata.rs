@@ -0,0 +1,128 @@
+use crate::Result;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Metadata about one track from an offline database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub album: Option<String>,
+    /// The narrator, used as the artist.
+    pub narrator: Option<String>,
+    pub cover_url: Option<String>,
+}
+
+/// Track metadata keyed by the Ximalaya track id, the number xm files store in `TRCK`.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataDb {
+    tracks: HashMap<u64, TrackMetadata>,
+}
+
+impl MetadataDb {
+    /// Reads a CSV file whose first row names the columns. `track_id` is required, `title`,
+    /// `album`, `narrator` and `cover_url` are used if present and other columns are ignored.
+    /// Empty cells count as missing.
+    pub fn from_csv(mut reader: impl Read) -> Result<MetadataDb> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        let mut rows = parse_csv(content.trim_start_matches('\u{feff}'))?.into_iter();
+        let header = rows.next().ok_or("empty metadata file")?;
+        let column = |name: &str| header.iter().position(|h| h.trim() == name);
+        let track_id = column("track_id").ok_or("no track_id column in metadata file")?;
+        let (title, album, narrator, cover_url) = (
+            column("title"),
+            column("album"),
+            column("narrator"),
+            column("cover_url"),
+        );
+
+        let mut tracks = HashMap::new();
+        for (line, row) in rows.enumerate() {
+            if row.iter().all(|cell| cell.is_empty()) {
+                continue;
+            }
+            let cell = |i: Option<usize>| {
+                i.and_then(|i| row.get(i))
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+            };
+            let id = cell(Some(track_id))
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| format!("invalid track_id in metadata row {}", line + 2))?;
+            let metadata = TrackMetadata {
+                title: cell(title),
+                album: cell(album),
+                narrator: cell(narrator),
+                cover_url: cell(cover_url),
+            };
+            tracks.insert(id, metadata);
+        }
+        Ok(MetadataDb { tracks })
+    }
+
+    /// Returns the metadata of the track with `track_id`.
+    pub fn get(&self, track_id: u64) -> Option<&TrackMetadata> {
+        self.tracks.get(&track_id)
+    }
+
+    /// Returns the number of tracks.
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Returns true if the database has no tracks.
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+}
+
+/// Splits CSV text into rows of cells. Quoted cells may contain commas, line breaks and `""` for a
+/// quote.
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') if cell.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut cell)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+    if quoted {
+        return Err("unclosed quote in metadata file".into());
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[test]
+fn test_metadata_from_csv() {
+    let csv = "\u{feff}track_id,album,extra,narrator,cover_url\r\n\
+               123,\"Album, \"\"one\"\"\",x,Someone,http://a/b.jpg\r\n\
+               \n\
+               456,Album two,,,\n";
+    let db = MetadataDb::from_csv(csv.as_bytes()).unwrap();
+    assert_eq!(db.len(), 2);
+    let track = db.get(123).unwrap();
+    assert_eq!(track.album.as_deref(), Some("Album, \"one\""));
+    assert_eq!(track.narrator.as_deref(), Some("Someone"));
+    assert_eq!(db.get(456).unwrap().narrator, None);
+    assert!(MetadataDb::from_csv("album\nx\n".as_bytes()).is_err());
+}
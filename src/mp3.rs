@@ -0,0 +1,356 @@
+use crate::Result;
+use std::time::Duration;
+
+/// The bitrates in kbit/s by bitrate index, for MPEG-1 layers I, II and III and for MPEG-2 and
+/// 2.5 layer I and layers II and III. Index 0 (free format) and 15 are not valid here.
+const BITRATES: [[u32; 14]; 5] = [
+    [
+        32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+    [
+        32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// The sample rates in Hz of MPEG-1, by sample rate index. MPEG-2 halves them, MPEG-2.5 quarters
+/// them.
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// The header of an MPEG audio frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameHeader {
+    /// 1 for MPEG-1, 2 for MPEG-2 and 3 for MPEG-2.5.
+    version: u8,
+    layer: u8,
+    /// In bit/s.
+    bitrate: u32,
+    sample_rate: u32,
+    padding: bool,
+    mono: bool,
+}
+
+impl FrameHeader {
+    /// Parses the frame header at the start of `data`.
+    fn parse(data: &[u8]) -> Option<FrameHeader> {
+        let &[b0, b1, b2, b3, ..] = data else {
+            return None;
+        };
+        if b0 != 0xFF || b1 & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version = match (b1 >> 3) & 0b11 {
+            0 => 3,
+            2 => 2,
+            3 => 1,
+            _ => return None,
+        };
+        let layer = match (b1 >> 1) & 0b11 {
+            1 => 3,
+            2 => 2,
+            3 => 1,
+            _ => return None,
+        };
+        let table = match (version, layer) {
+            (1, layer) => layer as usize - 1,
+            (_, 1) => 3,
+            _ => 4,
+        };
+        let bitrate_index = (b2 >> 4) as usize;
+        let bitrate = *BITRATES[table].get(bitrate_index.checked_sub(1)?)?;
+        let sample_rate = SAMPLE_RATES.get(((b2 >> 2) & 0b11) as usize)? >> (version - 1);
+        Some(FrameHeader {
+            version,
+            layer,
+            bitrate: bitrate * 1000,
+            sample_rate,
+            padding: b2 & 0b10 != 0,
+            mono: b3 >> 6 == 0b11,
+        })
+    }
+
+    /// Returns the number of samples per channel in the frame.
+    fn samples(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, 2 | 3) => 576,
+            _ => 1152,
+        }
+    }
+
+    /// Returns the length of the frame in bytes, including the header.
+    fn len(&self) -> usize {
+        let len = match self.layer {
+            1 => (12 * self.bitrate / self.sample_rate + self.padding as u32) * 4,
+            _ => self.samples() / 8 * self.bitrate / self.sample_rate + self.padding as u32,
+        };
+        len as usize
+    }
+
+    /// Returns the offset of a Xing or Info header in the frame, after the side information.
+    fn xing_offset(&self) -> usize {
+        let side_info = match (self.version, self.mono) {
+            (1, false) => 32,
+            (1, true) | (_, false) => 17,
+            (_, true) => 9,
+        };
+        4 + side_info
+    }
+}
+
+/// Returns the offsets and headers of the MPEG audio frames in `audio`, which may start with an
+/// ID3v2 tag. The frames end at the first byte that isn't a complete frame, such as an ID3v1 tag.
+fn frames(audio: &[u8]) -> Vec<(usize, FrameHeader)> {
+    let mut offset = 0;
+    if audio.len() >= 10 && audio.starts_with(b"ID3") {
+        let size = audio[6..10]
+            .iter()
+            .fold(0usize, |size, b| (size << 7) | (b & 0x7F) as usize);
+        let footer = if audio[5] & 0x10 != 0 { 10 } else { 0 };
+        offset = 10 + size + footer;
+    }
+    // Some encoders pad after the declared end of the tag.
+    while audio.get(offset) == Some(&0) {
+        offset += 1;
+    }
+    let mut frames = Vec::new();
+    while let Some(header) = audio.get(offset..).and_then(FrameHeader::parse) {
+        let len = header.len();
+        if len < 4 || offset + len > audio.len() {
+            break;
+        }
+        frames.push((offset, header));
+        offset += len;
+    }
+    frames
+}
+
+/// Returns the offset of the Xing or Info header in `frame`, the first frame of a stream, if it
+/// has one. Such a frame holds no audio.
+fn xing_header(frame: &[u8], header: &FrameHeader) -> Option<usize> {
+    let offset = header.xing_offset();
+    let tag = frame.get(offset..offset + 4)?;
+    (tag == b"Xing" || tag == b"Info").then_some(offset)
+}
+
+/// Returns the playing time of the MP3 `audio`, counted frame by frame so it is exact for VBR
+/// files as well, or `None` if `audio` holds no MPEG audio frames.
+pub fn duration(audio: &[u8]) -> Option<Duration> {
+    let frames = frames(audio);
+    let (first, header) = frames.first()?;
+    let skip = xing_header(&audio[*first..], header).is_some() as usize;
+    let nanos: u128 = frames[skip..]
+        .iter()
+        .map(|(_, h)| u128::from(h.samples()) * 1_000_000_000 / u128::from(h.sample_rate))
+        .sum();
+    Some(Duration::from_nanos(nanos as u64))
+}
+
+/// Checks that the MP3 `audio` is an unbroken chain of MPEG audio frames. After the last frame
+/// only an ID3v1, APE or Lyrics3 tag, or the start of a cut off frame, may follow.
+pub fn check_frames(audio: &[u8]) -> Result<()> {
+    let frames = frames(audio);
+    let Some(&(offset, header)) = frames.last() else {
+        return Err("no MPEG audio frames".into());
+    };
+    let end = offset + header.len();
+    let rest = &audio[end..];
+    let tags: [&[u8]; 3] = [b"TAG", b"APETAGEX", b"LYRICSBEGIN"];
+    if rest.is_empty()
+        || tags.iter().any(|tag| rest.starts_with(tag))
+        || FrameHeader::parse(rest).is_some()
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "the MPEG audio frames break off at byte {} of {}",
+            end,
+            audio.len()
+        )
+        .into())
+    }
+}
+
+/// Corrects the frame and byte counts in the Xing or Info header of the MP3 `audio` to match the
+/// frames that follow. Players estimate the length of VBR files from these counts.
+///
+/// Returns whether anything was changed.
+pub fn fix_vbr_header(audio: &mut [u8]) -> bool {
+    let frames = frames(audio);
+    let Some(&(first, header)) = frames.first() else {
+        return false;
+    };
+    let Some(xing) = xing_header(&audio[first..], &header) else {
+        return false;
+    };
+    let (last, last_header) = frames[frames.len() - 1];
+    let counts = [
+        (frames.len() - 1) as u32,
+        (last + last_header.len() - first) as u32,
+    ];
+
+    let mut pos = first + xing + 4;
+    let Some(flags) = read_u32(audio, pos) else {
+        return false;
+    };
+    pos += 4;
+    let mut changed = false;
+    for (bit, count) in counts.into_iter().enumerate() {
+        if flags & (1 << bit) == 0 {
+            continue;
+        }
+        if read_u32(audio, pos).is_some_and(|stored| stored != count) {
+            audio[pos..pos + 4].copy_from_slice(&count.to_be_bytes());
+            changed = true;
+        }
+        pos += 4;
+    }
+    changed
+}
+
+/// The encoder delay and padding of an MP3 stream, in samples, as recorded in its LAME tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gapless {
+    pub delay: u32,
+    pub padding: u32,
+    /// The samples in all audio frames, including the delay and padding.
+    pub samples: u64,
+}
+
+impl Gapless {
+    /// The delay of MP3 decoders, which iTunes counts towards the priming samples.
+    const DECODER_DELAY: u32 = 529;
+
+    /// Returns the value of an `iTunSMPB` comment, the gapless info players look for in iTunes
+    /// encoded files.
+    pub fn itunsmpb(&self) -> String {
+        let priming = self.delay + Self::DECODER_DELAY;
+        let remainder = self.padding.saturating_sub(Self::DECODER_DELAY);
+        let valid = self
+            .samples
+            .saturating_sub(u64::from(priming) + u64::from(remainder));
+        format!(
+            " 00000000 {:08X} {:08X} {:016X}{}",
+            priming,
+            remainder,
+            valid,
+            " 00000000".repeat(8)
+        )
+    }
+}
+
+/// Returns the gapless info of the MP3 `audio` from the LAME tag after its Xing or Info header,
+/// as written by LAME and ffmpeg, or `None` if it has no such tag.
+pub fn gapless(audio: &[u8]) -> Option<Gapless> {
+    let frames = frames(audio);
+    let &(first, header) = frames.first()?;
+    let xing = first + xing_header(&audio[first..], &header)?;
+    let flags = read_u32(audio, xing + 4)?;
+    // The frame and byte counts, the table of contents and the quality come first if present.
+    let lame = xing
+        + 8
+        + [4, 4, 100, 4]
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| flags & (1 << bit) != 0)
+            .map(|(_, len)| len)
+            .sum::<usize>();
+    let tag = audio.get(lame..lame + 24)?;
+    if !matches!(&tag[..4], b"LAME" | b"Lavc" | b"Lavf") {
+        return None;
+    }
+    let (b0, b1, b2) = (u32::from(tag[21]), u32::from(tag[22]), u32::from(tag[23]));
+    Some(Gapless {
+        delay: (b0 << 4) | (b1 >> 4),
+        padding: ((b1 & 0x0F) << 8) | b2,
+        samples: frames[1..]
+            .iter()
+            .map(|(_, h)| u64::from(h.samples()))
+            .sum(),
+    })
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+}
+
+/// Returns `n` MPEG-1 layer III frames at 128 kbit/s and 44.1 kHz, the first being a Xing header
+/// with the frame and byte counts `stored`.
+#[cfg(test)]
+fn test_stream(n: usize, stored: [u32; 2]) -> Vec<u8> {
+    let frame_len = 417;
+    let mut audio = Vec::new();
+    for i in 0..n {
+        let mut frame = vec![0; frame_len];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        if i == 0 {
+            frame[36..40].copy_from_slice(b"Xing");
+            frame[40..44].copy_from_slice(&3u32.to_be_bytes());
+            frame[44..48].copy_from_slice(&stored[0].to_be_bytes());
+            frame[48..52].copy_from_slice(&stored[1].to_be_bytes());
+        }
+        audio.extend_from_slice(&frame);
+    }
+    audio.extend_from_slice(b"TAG");
+    audio
+}
+
+#[test]
+fn test_duration() {
+    let audio = test_stream(101, [0, 0]);
+    // 100 frames of 1152 samples.
+    assert_eq!(
+        duration(&audio).unwrap().as_millis(),
+        100 * 1152 * 1000 / 44100
+    );
+    assert_eq!(duration(b"\0\0\0\x20ftypM4A "), None);
+}
+
+#[test]
+fn test_check_frames() {
+    let mut audio = test_stream(5, [0, 0]);
+    assert!(check_frames(&audio).is_ok());
+    audio.truncate(417 * 4 + 100);
+    assert!(check_frames(&audio).is_ok());
+    audio[417 * 2 + 1] = 0;
+    assert_eq!(
+        check_frames(&audio).unwrap_err().to_string(),
+        "the MPEG audio frames break off at byte 834 of 1768"
+    );
+    assert!(check_frames(b"\0\0\0\x20ftypM4A ").is_err());
+}
+
+#[test]
+fn test_fix_vbr_header() {
+    let mut audio = test_stream(11, [5, 5]);
+    assert!(fix_vbr_header(&mut audio));
+    assert_eq!(read_u32(&audio, 44), Some(10));
+    assert_eq!(read_u32(&audio, 48), Some(11 * 417));
+    assert!(!fix_vbr_header(&mut audio));
+}
+
+#[test]
+fn test_gapless() {
+    let mut audio = test_stream(11, [10, 11 * 417]);
+    assert_eq!(gapless(&audio), None);
+    // The LAME tag follows the frame and byte counts, with a delay of 576 and a padding of 1000.
+    audio[52..61].copy_from_slice(b"LAME3.100");
+    audio[73..76].copy_from_slice(&[0x24, 0x03, 0xE8]);
+    let info = gapless(&audio).unwrap();
+    assert_eq!(
+        (info.delay, info.padding, info.samples),
+        (576, 1000, 10 * 1152)
+    );
+    assert_eq!(
+        info.itunsmpb(),
+        " 00000000 00000451 000001D7 00000000000026D8 00000000 00000000 00000000 00000000 \
+         00000000 00000000 00000000 00000000"
+    );
+}
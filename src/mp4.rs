@@ -0,0 +1,343 @@
+use crate::Result;
+use std::ops::Range;
+
+/// The boxes on the way from `moov` to the chunk offset tables.
+const SAMPLE_TABLE_PATH: [&[u8; 4]; 4] = [b"trak", b"mdia", b"minf", b"stbl"];
+
+/// A box inside MP4 data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mp4Box {
+    kind: [u8; 4],
+    /// The whole box, header included.
+    range: Range<usize>,
+    /// The content of the box, after the header.
+    content: Range<usize>,
+}
+
+/// Splits `data` into the boxes it consists of. `offset` is added to the ranges.
+fn parse_boxes(data: &[u8], offset: usize) -> Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8).ok_or("truncated MP4 box header")?;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (size, header_len) =
+            match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 => (data.len() - pos, 8),
+                1 => {
+                    let large = data
+                        .get(pos + 8..pos + 16)
+                        .ok_or("truncated MP4 box header")?;
+                    let size = u64::from_be_bytes(large.try_into().expect("8 bytes"));
+                    (usize::try_from(size)?, 16)
+                }
+                size => (size as usize, 8),
+            };
+        if size < header_len || data.len() - pos < size {
+            return Err(
+                format!("invalid size of MP4 box {}", String::from_utf8_lossy(&kind)).into(),
+            );
+        }
+        boxes.push(Mp4Box {
+            kind,
+            range: offset + pos..offset + pos + size,
+            content: offset + pos + header_len..offset + pos + size,
+        });
+        pos += size;
+    }
+    Ok(boxes)
+}
+
+/// Checks that the MP4 file `audio` is a chain of boxes up to its end, with a `moov` box whose
+/// children are boxes too and an `mdat` box.
+pub fn check_boxes(audio: &[u8]) -> Result<()> {
+    let top = parse_boxes(audio, 0)?;
+    let moov = top
+        .iter()
+        .find(|b| &b.kind == b"moov")
+        .ok_or("no moov box in the MP4 file")?;
+    parse_boxes(&audio[moov.content.clone()], 0)?;
+    if !top.iter().any(|b| &b.kind == b"mdat") {
+        return Err("no mdat box in the MP4 file".into());
+    }
+    Ok(())
+}
+
+/// Returns a box of `kind` holding `content`.
+fn make_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(content.len() + 16);
+    match u32::try_from(content.len() + 8) {
+        Ok(size) => data.extend_from_slice(&size.to_be_bytes()),
+        Err(_) => {
+            data.extend_from_slice(&1u32.to_be_bytes());
+            data.extend_from_slice(kind);
+            data.extend_from_slice(&(content.len() as u64 + 16).to_be_bytes());
+            data.extend_from_slice(content);
+            return data;
+        }
+    }
+    data.extend_from_slice(kind);
+    data.extend_from_slice(content);
+    data
+}
+
+/// Returns the boxes `children`, with the content of the box of `kind` replaced by what `update`
+/// returns for it. The box is added at the end if there is none, `update` is then given `None`.
+fn update_child(
+    children: &[u8],
+    kind: &[u8; 4],
+    update: impl FnOnce(Option<&[u8]>) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let boxes = parse_boxes(children, 0)?;
+    let mut data = Vec::with_capacity(children.len());
+    let mut update = Some(update);
+    for b in &boxes {
+        if &b.kind == kind {
+            if let Some(update) = update.take() {
+                data.extend(make_box(kind, &update(Some(&children[b.content.clone()]))?));
+                continue;
+            }
+        }
+        data.extend_from_slice(&children[b.range.clone()]);
+    }
+    if let Some(update) = update {
+        data.extend(make_box(kind, &update(None)?));
+    }
+    Ok(data)
+}
+
+/// An item of iTunes metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item<'a> {
+    /// A text item, such as `©nam` for the title.
+    Text(&'a [u8; 4], &'a str),
+    /// The track number, `trkn`.
+    Track(u16),
+    /// The cover art, `covr`, as JPEG or, if `png` is set, PNG data.
+    Cover { data: &'a [u8], png: bool },
+}
+
+impl Item<'_> {
+    /// Returns the type of the item's box.
+    fn kind(&self) -> &[u8; 4] {
+        match self {
+            Item::Text(kind, _) => kind,
+            Item::Track(_) => b"trkn",
+            Item::Cover { .. } => b"covr",
+        }
+    }
+
+    /// Returns the item as an `ilst` entry.
+    fn encode(&self) -> Vec<u8> {
+        match *self {
+            // Type 1 is UTF-8 text.
+            Item::Text(kind, text) => ilst_item(kind, 1, text.as_bytes()),
+            // Type 0 is binary data, the track number and the number of tracks, which is unknown.
+            Item::Track(track) => {
+                let [high, low] = track.to_be_bytes();
+                ilst_item(b"trkn", 0, &[0, 0, high, low, 0, 0, 0, 0])
+            }
+            // Types 13 and 14 are JPEG and PNG.
+            Item::Cover { data, png } => ilst_item(b"covr", if png { 14 } else { 13 }, data),
+        }
+    }
+}
+
+/// Returns an `ilst` item of `kind` holding `payload` in a `data` box of `type_code`.
+fn ilst_item(kind: &[u8; 4], type_code: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = type_code.to_be_bytes().to_vec();
+    // The locale, 0 for all.
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(payload);
+    make_box(kind, &make_box(b"data", &data))
+}
+
+/// Sets the iTunes metadata `items` of the MP4 file `audio`, replacing items of the same kinds.
+/// Other metadata is kept, and the missing `udta`, `meta` and `ilst` boxes are added.
+///
+/// If the `moov` box comes before the audio data, the chunk offsets are moved by the change of
+/// its size.
+pub fn write_ilst(audio: &mut Vec<u8>, items: &[Item]) -> Result<()> {
+    let top = parse_boxes(audio, 0)?;
+    let moov = top
+        .iter()
+        .find(|b| &b.kind == b"moov")
+        .ok_or("no moov box in the MP4 file")?;
+
+    let new_items: Vec<u8> = items.iter().flat_map(Item::encode).collect();
+    let kinds: Vec<&[u8; 4]> = items.iter().map(Item::kind).collect();
+
+    let set_ilst = |ilst: Option<&[u8]>| -> Result<Vec<u8>> {
+        let ilst = ilst.unwrap_or_default();
+        let mut data = Vec::with_capacity(ilst.len());
+        for item in parse_boxes(ilst, 0)? {
+            if !kinds.contains(&&item.kind) {
+                data.extend_from_slice(&ilst[item.range]);
+            }
+        }
+        data.extend_from_slice(&new_items);
+        Ok(data)
+    };
+    let set_meta = |meta: Option<&[u8]>| -> Result<Vec<u8>> {
+        match meta {
+            // The version and flags of the full box precede the children.
+            Some(meta) if meta.len() >= 4 => {
+                let mut data = meta[..4].to_vec();
+                data.extend(update_child(&meta[4..], b"ilst", set_ilst)?);
+                Ok(data)
+            }
+            Some(_) => Err("truncated MP4 meta box".into()),
+            None => {
+                // Players only read the items if the handler is iTunes metadata.
+                let mut hdlr = vec![0; 8];
+                hdlr.extend_from_slice(b"mdirappl");
+                hdlr.extend_from_slice(&[0; 9]);
+                let mut data = vec![0; 4];
+                data.extend(make_box(b"hdlr", &hdlr));
+                data.extend(make_box(b"ilst", &set_ilst(None)?));
+                Ok(data)
+            }
+        }
+    };
+    let moov_content = &audio[moov.content.clone()];
+    let new_content = update_child(moov_content, b"udta", |udta| {
+        update_child(udta.unwrap_or_default(), b"meta", set_meta)
+    })?;
+    let mut new_moov = make_box(b"moov", &new_content);
+
+    let delta = new_moov.len() as i64 - moov.range.len() as i64;
+    if delta != 0
+        && top
+            .iter()
+            .any(|b| b.range.start > moov.range.start && &b.kind == b"mdat")
+    {
+        shift_chunk_offsets(&mut new_moov, moov.range.end as u64, delta)?;
+    }
+    audio.splice(moov.range.clone(), new_moov);
+    Ok(())
+}
+
+/// Moves the chunk offsets in the `stco` and `co64` boxes of `moov` that point at or after
+/// `start` by `delta`.
+fn shift_chunk_offsets(moov: &mut [u8], start: u64, delta: i64) -> Result<()> {
+    let mut containers = vec![parse_boxes(moov, 0)?[0].content.clone()];
+    for kind in SAMPLE_TABLE_PATH {
+        let mut next = Vec::new();
+        for range in containers {
+            let boxes = parse_boxes(&moov[range.clone()], range.start)?;
+            next.extend(
+                boxes
+                    .into_iter()
+                    .filter(|b| &b.kind == kind)
+                    .map(|b| b.content),
+            );
+        }
+        containers = next;
+    }
+    for stbl in containers {
+        for b in parse_boxes(&moov[stbl.clone()], stbl.start)? {
+            let width = match &b.kind {
+                b"stco" => 4,
+                b"co64" => 8,
+                _ => continue,
+            };
+            // The version and flags, then the number of entries.
+            let table = moov
+                .get_mut(b.content.start + 8..b.content.end)
+                .ok_or("truncated MP4 chunk offset box")?;
+            for entry in table.chunks_exact_mut(width) {
+                let offset = match width {
+                    4 => u64::from(u32::from_be_bytes(entry.try_into().expect("4 bytes"))),
+                    _ => u64::from_be_bytes(entry.try_into().expect("8 bytes")),
+                };
+                if offset < start {
+                    continue;
+                }
+                let offset = offset
+                    .checked_add_signed(delta)
+                    .ok_or("MP4 chunk offset out of range")?;
+                match width {
+                    4 => entry.copy_from_slice(&u32::try_from(offset)?.to_be_bytes()),
+                    _ => entry.copy_from_slice(&offset.to_be_bytes()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check_boxes() {
+    let mut audio = make_box(b"ftyp", b"M4A \0\0\0\0");
+    audio.extend(make_box(b"moov", &make_box(b"trak", b"")));
+    assert_eq!(
+        check_boxes(&audio).unwrap_err().to_string(),
+        "no mdat box in the MP4 file"
+    );
+    audio.extend(make_box(b"mdat", b"audio"));
+    assert!(check_boxes(&audio).is_ok());
+    audio.extend_from_slice(b"garbage");
+    assert!(check_boxes(&audio).is_err());
+}
+
+#[test]
+fn test_write_ilst() {
+    let stco = make_box(b"stco", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+    let stbl = make_box(b"stbl", &stco);
+    let trak = make_box(b"trak", &make_box(b"mdia", &make_box(b"minf", &stbl)));
+    let mut audio = make_box(b"ftyp", b"M4A \0\0\0\0");
+    audio.extend(make_box(b"moov", &trak));
+    let mdat_start = audio.len() + 8;
+    audio.extend(make_box(b"mdat", b"audio"));
+    // The chunk offset points at the audio.
+    let stco_entry = audio.len() - 13 - 4;
+    audio[stco_entry..stco_entry + 4].copy_from_slice(&(mdat_start as u32).to_be_bytes());
+
+    write_ilst(&mut audio, &[Item::Text(b"\xA9nam", "old")]).unwrap();
+    let items = [
+        Item::Text(b"\xA9nam", "第1集"),
+        Item::Text(b"\xA9alb", "album"),
+        Item::Track(3),
+        Item::Cover {
+            data: b"\xFF\xD8",
+            png: false,
+        },
+    ];
+    write_ilst(&mut audio, &items).unwrap();
+
+    let top = parse_boxes(&audio, 0).unwrap();
+    let mdat = &top[2];
+    assert_eq!(&audio[mdat.content.clone()], b"audio");
+    let moov = &audio[top[1].content.clone()];
+    let find = |data: &[u8], kind: &[u8; 4]| {
+        let b = parse_boxes(data, 0)
+            .unwrap()
+            .into_iter()
+            .find(|b| &b.kind == kind)
+            .unwrap();
+        data[b.content].to_vec()
+    };
+    let offset = find(
+        &find(
+            &find(&find(&find(moov, b"trak"), b"mdia"), b"minf"),
+            b"stbl",
+        ),
+        b"stco",
+    );
+    assert_eq!(offset[8..], (mdat.content.start as u32).to_be_bytes());
+    let ilst = find(&find(&find(moov, b"udta"), b"meta")[4..], b"ilst");
+    let items: Vec<_> = parse_boxes(&ilst, 0)
+        .unwrap()
+        .iter()
+        .map(|b| b.kind)
+        .collect();
+    assert_eq!(items, [*b"\xA9nam", *b"\xA9alb", *b"trkn", *b"covr"]);
+    assert_eq!(
+        &find(&find(&ilst, b"\xA9nam"), b"data")[8..],
+        "第1集".as_bytes()
+    );
+    assert_eq!(
+        find(&find(&ilst, b"trkn"), b"data")[8..],
+        [0, 0, 0, 3, 0, 0, 0, 0]
+    );
+}
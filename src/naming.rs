@@ -0,0 +1,590 @@
+use crate::xm::XMInfo;
+use crate::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+/// The template used when none is configured, `artist - album - title`.
+pub const DEFAULT_TEMPLATE: &str = "{artist} - {album} - {title}";
+
+/// The longest file name most filesystems allow, in bytes.
+pub const DEFAULT_MAX_NAME_BYTES: usize = 255;
+
+/// The order in which values are shortened when a name is too long. The track number is never
+/// shortened and the title only as a last resort.
+const SHRINK_ORDER: [Field; 5] = [
+    Field::Album,
+    Field::Artist,
+    Field::Disc,
+    Field::Isrc,
+    Field::Title,
+];
+
+/// A filename template such as `{track:03} - {title}`.
+///
+/// Placeholders are `{title}`, `{artist}`, `{album}`, `{track}`, `{disc}` and `{isrc}`. A width
+/// may follow after a colon, `{track:3}` pads with spaces and `{track:03}` with zeros. Literal
+/// braces are written as `{{` and `}}`. The file extension is not part of the template.
+///
+/// Each file or directory name the template renders is kept within a byte limit, 255 by default,
+/// by shortening the values in [`SHRINK_ORDER`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+    max_bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field {
+        field: Field,
+        width: usize,
+        zero_pad: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    Track,
+    Disc,
+    Isrc,
+}
+
+impl NameTemplate {
+    /// Sets the longest file or directory name the template renders, in bytes.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Renders the template for `info`. Characters that are not allowed in filenames are removed
+    /// from the substituted values, and path components that would leave the output directory,
+    /// such as `..` or an absolute path, are replaced by `_`.
+    pub fn render(&self, info: &XMInfo) -> String {
+        self.render_limited(info, "", &sanitize)
+    }
+
+    /// Renders the file name for `info` with `extension`, which counts towards the byte limit.
+    pub fn render_file_name(&self, info: &XMInfo, extension: &str) -> String {
+        self.render_file_name_with(info, extension, &sanitize)
+    }
+
+    /// Renders the file name for `info` with `extension`, or, if `info` has no title, artist or
+    /// album, or the rendered file name or one of its directories is empty, the name `fallback`
+    /// with the track number appended, such as `1234 - 7.m4a`. The fallback is usually the stem
+    /// of the source file, it keeps untagged files from all becoming ` -  - .m4a` and a missing
+    /// title from making hidden files like `Album/.m4a`.
+    pub fn render_file_name_or(&self, info: &XMInfo, extension: &str, fallback: &str) -> String {
+        self.render_file_name_or_with(info, extension, fallback, &sanitize)
+    }
+
+    /// Renders the file name like [`NameTemplate::render_file_name_or`], cleaning the substituted
+    /// values with `sanitizer` instead of [`sanitize`].
+    pub fn render_file_name_or_with(
+        &self,
+        info: &XMInfo,
+        extension: &str,
+        fallback: &str,
+        sanitizer: &dyn Fn(&str) -> String,
+    ) -> String {
+        let tagged = [&info.title, &info.artist, &info.album]
+            .into_iter()
+            .any(|value| {
+                value
+                    .as_deref()
+                    .is_some_and(|s| !sanitizer(s).trim().is_empty())
+            });
+        let suffix = format!(".{}", extension);
+        if tagged
+            && self
+                .render_components(info, &suffix, sanitizer)
+                .iter()
+                .all(|component| !component.trim().is_empty())
+        {
+            return self.render_file_name_with(info, extension, sanitizer);
+        }
+        let track = info.tracknumber.to_string();
+        let name = match info.tracknumber {
+            0 => fallback.to_string(),
+            _ if fallback.contains(&track) => fallback.to_string(),
+            _ => format!("{} - {}", fallback, track),
+        };
+        let mut info = XMInfo::default();
+        info.title = Some(name);
+        let template = NameTemplate {
+            parts: vec![Part::Field {
+                field: Field::Title,
+                width: 0,
+                zero_pad: false,
+            }],
+            max_bytes: self.max_bytes,
+        };
+        template.render_file_name_with(&info, extension, sanitizer)
+    }
+
+    /// Renders the file name for `info` with `extension`, cleaning the substituted values with
+    /// `sanitizer` instead of [`sanitize`].
+    pub fn render_file_name_with(
+        &self,
+        info: &XMInfo,
+        extension: &str,
+        sanitizer: &dyn Fn(&str) -> String,
+    ) -> String {
+        let suffix = format!(".{}", extension);
+        let mut name = self.render_limited(info, &suffix, sanitizer);
+        name.push_str(&suffix);
+        name
+    }
+
+    /// Renders the template, leaving room for `suffix` after the last path component.
+    fn render_limited(
+        &self,
+        info: &XMInfo,
+        suffix: &str,
+        sanitizer: &dyn Fn(&str) -> String,
+    ) -> String {
+        let components: Vec<String> = self
+            .render_components(info, suffix, sanitizer)
+            .into_iter()
+            .map(|component| match is_safe_component(&component) {
+                true => component,
+                false => "_".to_string(),
+            })
+            .collect();
+        components.join("/")
+    }
+
+    /// Renders the path components of the template, as they are, even if empty or `..`.
+    fn render_components(
+        &self,
+        info: &XMInfo,
+        suffix: &str,
+        sanitizer: &dyn Fn(&str) -> String,
+    ) -> Vec<String> {
+        // The rendered values, split into path components at the `/` of literals.
+        let mut components: Vec<Vec<(Option<Field>, String)>> = vec![Vec::new()];
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => {
+                    for (i, piece) in s.split('/').enumerate() {
+                        if i > 0 {
+                            components.push(Vec::new());
+                        }
+                        let component = components.last_mut().expect("no component");
+                        component.push((None, piece.to_string()));
+                    }
+                }
+                Part::Field {
+                    field,
+                    width,
+                    zero_pad,
+                } => {
+                    let value = sanitizer(&field.value(info));
+                    let value = match zero_pad {
+                        true => format!("{:0>width$}", value, width = width),
+                        false => format!("{:>width$}", value, width = width),
+                    };
+                    let component = components.last_mut().expect("no component");
+                    component.push((Some(*field), value));
+                }
+            }
+        }
+        let last = components.len() - 1;
+        for (i, component) in components.iter_mut().enumerate() {
+            let reserved = if i == last { suffix.len() } else { 0 };
+            shorten(component, self.max_bytes.saturating_sub(reserved));
+        }
+        components
+            .iter()
+            .map(|component| component.iter().map(|(_, s)| s.as_str()).collect())
+            .collect()
+    }
+}
+
+/// Returns true if `component` is a plain name within its directory: not empty, not made of only
+/// dots and spaces like `..`, and with no root or drive prefix.
+fn is_safe_component(component: &str) -> bool {
+    !component.trim_matches(|c| c == '.' || c == ' ').is_empty()
+        && Path::new(component)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Cuts the values of a path component from the end, in [`SHRINK_ORDER`], until the component fits
+/// into `max_bytes`. Literals are never cut, so a component may remain too long.
+fn shorten(component: &mut [(Option<Field>, String)], max_bytes: usize) {
+    for field in SHRINK_ORDER {
+        for i in 0..component.len() {
+            let len: usize = component.iter().map(|(_, s)| s.len()).sum();
+            if len <= max_bytes {
+                return;
+            }
+            if component[i].0 != Some(field) {
+                continue;
+            }
+            let value = &mut component[i].1;
+            let mut end = value.len().saturating_sub(len - max_bytes);
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            value.truncate(end);
+            value.truncate(value.trim_end().len());
+        }
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().expect("default template is valid")
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = Box<dyn std::error::Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed placeholder in template {}", s))?;
+                    let (name, spec) = match rest[..end].split_once(':') {
+                        Some((name, spec)) => (name, Some(spec)),
+                        None => (&rest[..end], None),
+                    };
+                    let field = name.parse()?;
+                    let (width, zero_pad) = match spec {
+                        Some(spec) => (
+                            spec.parse()
+                                .map_err(|_| format!("invalid width {} in template", spec))?,
+                            spec.starts_with('0'),
+                        ),
+                        None => (0, false),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field {
+                        field,
+                        width,
+                        zero_pad,
+                    });
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("unmatched }} in template {}", s).into()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(NameTemplate {
+            parts,
+            max_bytes: DEFAULT_MAX_NAME_BYTES,
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => f.write_str(&s.replace('{', "{{").replace('}', "}}"))?,
+                Part::Field {
+                    field,
+                    width: 0,
+                    zero_pad: _,
+                } => write!(f, "{{{}}}", field)?,
+                Part::Field {
+                    field,
+                    width,
+                    zero_pad,
+                } => match zero_pad {
+                    true => write!(f, "{{{}:0{}}}", field, width)?,
+                    false => write!(f, "{{{}:{}}}", field, width)?,
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Field {
+    fn value(self, info: &XMInfo) -> String {
+        match self {
+            Field::Title => info.title.clone().unwrap_or_default(),
+            Field::Artist => info.artist.clone().unwrap_or_default(),
+            Field::Album => info.album.clone().unwrap_or_default(),
+            Field::Track => info.tracknumber.to_string(),
+            Field::Disc => info.disc.clone().unwrap_or_default(),
+            Field::Isrc => info.isrc.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(Field::Title),
+            "artist" => Ok(Field::Artist),
+            "album" => Ok(Field::Album),
+            "track" => Ok(Field::Track),
+            "disc" => Ok(Field::Disc),
+            "isrc" => Ok(Field::Isrc),
+            _ => Err(format!("unknown placeholder {{{}}} in template", s)),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Field::Title => "title",
+            Field::Artist => "artist",
+            Field::Album => "album",
+            Field::Track => "track",
+            Field::Disc => "disc",
+            Field::Isrc => "isrc",
+        })
+    }
+}
+
+/// Removes characters that are not allowed in filenames on common filesystems.
+pub fn sanitize(s: &str) -> String {
+    s.replace(['\\', ':', '/', '*', '?', '\"', '<', '>', '|'], "")
+}
+
+/// Returns the form of a filename used to detect collisions.
+///
+/// Names are compared case-insensitively after NFKC normalization, so names that would map to
+/// the same file on Windows or macOS, or that only differ by full-width and half-width
+/// punctuation, are treated as the same name on every platform.
+pub fn fold_name(name: &str) -> String {
+    name.nfkc().flat_map(char::to_lowercase).collect()
+}
+
+/// Returns `path` with ` (n)` appended to the file stem.
+pub fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(file_name)
+}
+
+/// The names taken in output directories, by existing files or by outputs planned or written
+/// earlier. Names are compared by [`fold_name`].
+#[derive(Debug, Clone, Default)]
+pub struct CollisionState {
+    dirs: HashMap<PathBuf, HashMap<String, PathBuf>>,
+}
+
+impl CollisionState {
+    pub fn new() -> CollisionState {
+        CollisionState::default()
+    }
+
+    /// Returns true if the files of `dir` have been added with [`CollisionState::add_dir`].
+    pub fn knows_dir(&self, dir: &Path) -> bool {
+        self.dirs.contains_key(dir)
+    }
+
+    /// Adds the existing `files` of `dir`.
+    pub fn add_dir(&mut self, dir: &Path, files: impl IntoIterator<Item = PathBuf>) {
+        let names = self.dirs.entry(dir.to_path_buf()).or_default();
+        for file in files {
+            names.insert(fold_name(&file_name(&file)), file);
+        }
+    }
+
+    /// Returns the path whose name `path` collides with, if any.
+    pub fn collision(&self, path: &Path) -> Option<&Path> {
+        let names = self.dirs.get(path.parent()?)?;
+        names
+            .get(&fold_name(&file_name(path)))
+            .map(PathBuf::as_path)
+    }
+
+    /// Takes the name of `path`, unless it collides. Returns the path it collides with in that
+    /// case.
+    pub fn claim(&mut self, path: &Path) -> Option<PathBuf> {
+        let dir = path.parent().expect("no parent dir");
+        let names = self.dirs.entry(dir.to_path_buf()).or_default();
+        let key = fold_name(&file_name(path));
+        if let Some(existing) = names.get(&key) {
+            return Some(existing.clone());
+        }
+        names.insert(key, path.to_path_buf());
+        None
+    }
+}
+
+fn file_name(path: &Path) -> std::borrow::Cow<'_, str> {
+    path.file_name().unwrap_or_default().to_string_lossy()
+}
+
+/// Returns the path the output for `info` is written to in `target_dir`, without touching the
+/// filesystem, so a front-end can preview the outputs while the template is edited.
+///
+/// The name is rendered from `template` with `extension`, its values cleaned by `sanitizer`, or
+/// from `fallback`, the stem of the source file, for an untagged episode, as
+/// [`NameTemplate::render_file_name_or`] does for a batch run. A collision with a name taken in `state` is resolved by numbering the name, as a batch run with
+/// `--on-conflict rename` does given the same state.
+pub fn plan_output(
+    info: &XMInfo,
+    extension: &str,
+    fallback: &str,
+    template: &NameTemplate,
+    sanitizer: &dyn Fn(&str) -> String,
+    target_dir: &Path,
+    state: &CollisionState,
+) -> PathBuf {
+    let path =
+        target_dir.join(template.render_file_name_or_with(info, extension, fallback, sanitizer));
+    let mut candidate = path.clone();
+    for n in 1.. {
+        if state.collision(&candidate).is_none() {
+            break;
+        }
+        candidate = numbered(&path, n);
+    }
+    candidate
+}
+
+#[test]
+fn test_render_template() {
+    let mut info = XMInfo::default();
+    info.title = Some("a/b".to_string());
+    info.tracknumber = 7;
+
+    let template: NameTemplate = "{track:03} - {title} {{x}}".parse().unwrap();
+    assert_eq!(template.render(&info), "007 - ab {x}");
+    assert_eq!(template.to_string(), "{track:03} - {title} {{x}}");
+    assert!("{year}".parse::<NameTemplate>().is_err());
+    assert!("{title".parse::<NameTemplate>().is_err());
+}
+
+#[test]
+fn test_render_max_bytes() {
+    let mut info = XMInfo::default();
+    info.artist = Some("主播".to_string());
+    info.album = Some("专辑".repeat(100));
+    info.title = Some("第一集".to_string());
+    info.tracknumber = 1;
+
+    let template = NameTemplate::default().with_max_bytes(40);
+    let name = template.render_file_name(&info, "m4a");
+    assert_eq!(name, "主播 - 专辑专辑专 - 第一集.m4a");
+    assert!(name.len() <= 40);
+
+    let template: NameTemplate = "{album}/{track:02} {title}".parse().unwrap();
+    let name = template.with_max_bytes(12).render_file_name(&info, "mp3");
+    assert_eq!(name, "专辑专辑/01 第.mp3");
+}
+
+#[test]
+fn test_render_hostile_values() {
+    let template: NameTemplate = "{album}/{artist}/{title}".parse().unwrap();
+    let mut info = XMInfo::default();
+    info.album = Some("..".to_string());
+    info.artist = Some(" . ".to_string());
+    info.title = Some("x".to_string());
+    let name = template.render_file_name(&info, "m4a");
+    assert_eq!(name, "_/_/x.m4a");
+    let dir = Path::new("out");
+    assert!(dir.join(&name).starts_with(dir));
+
+    info.title = Some("..".to_string());
+    assert_eq!(template.render_file_name(&info, "m4a"), "_/_/_.m4a");
+
+    // A template can't escape the output directory either.
+    let template: NameTemplate = "/../{title}".parse().unwrap();
+    info.title = Some("x".to_string());
+    let name = template.render_file_name(&info, "m4a");
+    assert_eq!(name, "_/_/x.m4a");
+    assert!(!Path::new(&name).is_absolute());
+}
+
+#[test]
+fn test_render_fallback() {
+    let template: NameTemplate = "{album}/{title}".parse().unwrap();
+    let mut info = XMInfo::default();
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "1234"),
+        "1234.m4a"
+    );
+    info.tracknumber = 7;
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "a:b"),
+        "ab - 7.m4a"
+    );
+    info.title = Some("?".to_string());
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "ep 7"),
+        "ep 7.m4a"
+    );
+    // An empty title with an album falls back too, rather than making the hidden `Album/.m4a`.
+    info.album = Some("Album".to_string());
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "1234"),
+        "1234 - 7.m4a"
+    );
+    info.title = Some("Title".to_string());
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "1234"),
+        "Album/Title.m4a"
+    );
+    info.album = None;
+    assert_eq!(
+        template.render_file_name_or(&info, "m4a", "1234"),
+        "1234 - 7.m4a"
+    );
+}
+
+#[test]
+fn test_plan_output() {
+    let mut info = XMInfo::default();
+    info.title = Some("Title".to_string());
+    let template: NameTemplate = "{title}".parse().unwrap();
+    let dir = Path::new("out");
+
+    let mut state = CollisionState::new();
+    state.add_dir(dir, [dir.join("title.m4a"), dir.join("Title (1).M4A")]);
+    assert!(state.knows_dir(dir));
+    let planned = plan_output(&info, "m4a", "1234", &template, &sanitize, dir, &state);
+    assert_eq!(planned, dir.join("Title (2).m4a"));
+    let untagged = XMInfo::default();
+    let fallback = plan_output(&untagged, "m4a", "1234", &template, &sanitize, dir, &state);
+    assert_eq!(fallback, dir.join("1234.m4a"));
+
+    assert_eq!(state.claim(&planned), None);
+    assert_eq!(state.claim(&dir.join("ｔitle (2).m4a")), Some(planned));
+}
+
+#[test]
+fn test_fold_name() {
+    assert_eq!(fold_name("Ａbc：1.M4A"), fold_name("abc:1.m4a"));
+    assert_ne!(fold_name("abc 1.m4a"), fold_name("abc 2.m4a"));
+}
@@ -0,0 +1,1271 @@
+use crate::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The size of a page of linear memory.
+const PAGE: usize = 65_536;
+
+/// The most calls that may nest. Deeper recursion traps, as it would overflow the stack of a
+/// compiled module.
+const MAX_DEPTH: usize = 16_384;
+
+/// The most locals a function may declare.
+const MAX_LOCALS: usize = 50_000;
+
+/// A WebAssembly 1.0 module for [`Instance`], the backend of [`crate::xm::Decryptor`] on targets
+/// Cranelift doesn't build on.
+///
+/// Besides the instructions of 1.0 the interpreter knows the sign extension, saturating
+/// conversion and bulk memory instructions, which newer compilers emit. Modules that import
+/// anything, or use reference types, SIMD or threads, are rejected.
+pub(crate) struct Module {
+    types: Vec<FuncType>,
+    functions: Vec<Function>,
+    table: Option<Limits>,
+    memory: Option<Limits>,
+    globals: Vec<u64>,
+    exports: HashMap<String, u32>,
+    start: Option<u32>,
+    elements: Vec<(u32, Vec<u32>)>,
+    data: Vec<Data>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FuncType {
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    min: u32,
+    max: Option<u32>,
+}
+
+struct Function {
+    type_idx: u32,
+    params: usize,
+    results: usize,
+    locals: usize,
+    code: Vec<Op>,
+}
+
+struct Data {
+    /// The address of an active segment, none for a passive one.
+    offset: Option<u32>,
+    bytes: Vec<u8>,
+}
+
+/// An instruction, with the targets of blocks resolved to instruction indices.
+#[derive(Debug, Clone)]
+enum Op {
+    Unreachable,
+    Nop,
+    Block {
+        params: usize,
+        results: usize,
+        end: usize,
+    },
+    Loop {
+        params: usize,
+    },
+    If {
+        params: usize,
+        results: usize,
+        else_: Option<usize>,
+        end: usize,
+    },
+    Else {
+        end: usize,
+    },
+    End,
+    Br(u32),
+    BrIf(u32),
+    /// The depths by index, the default last.
+    BrTable(Box<[u32]>),
+    Return,
+    Call(u32),
+    CallIndirect(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Load(u8, u32),
+    Store(u8, u32),
+    MemorySize,
+    MemoryGrow,
+    Const(u64),
+    /// A numeric instruction without immediates, by its opcode.
+    Num(u8),
+    /// A saturating conversion, by the opcode after the `0xFC` prefix.
+    TruncSat(u8),
+    MemoryInit(u32),
+    DataDrop(u32),
+    MemoryCopy,
+    MemoryFill,
+}
+
+/// A block entered by the running function.
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    /// Where a branch to the label continues.
+    target: usize,
+    /// The height of the value stack below the block.
+    height: usize,
+    /// The number of values a branch to the label carries.
+    arity: usize,
+    is_loop: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    func: u32,
+    pc: usize,
+    locals: usize,
+    labels: usize,
+    stack: usize,
+}
+
+impl Module {
+    /// Parses and checks the binary module `bytes`.
+    pub(crate) fn new(bytes: &[u8]) -> Result<Module> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(8)? != b"\0asm\x01\0\0\0" {
+            return Err(invalid("not a wasm 1.0 module"));
+        }
+        let mut module = Module {
+            types: Vec::new(),
+            functions: Vec::new(),
+            table: None,
+            memory: None,
+            globals: Vec::new(),
+            exports: HashMap::new(),
+            start: None,
+            elements: Vec::new(),
+            data: Vec::new(),
+        };
+        let mut func_types = Vec::new();
+        while r.pos < bytes.len() {
+            let id = r.byte()?;
+            let size = r.u32()? as usize;
+            let mut s = Reader {
+                bytes: r.take(size)?,
+                pos: 0,
+            };
+            match id {
+                0 | 12 => {}
+                1 => {
+                    for _ in 0..s.u32()? {
+                        if s.byte()? != 0x60 {
+                            return Err(invalid("bad function type"));
+                        }
+                        let params = s.vec(Reader::val_type)?;
+                        let results = s.vec(Reader::val_type)?;
+                        module.types.push(FuncType { params, results });
+                    }
+                }
+                2 => {
+                    if s.u32()? > 0 {
+                        return Err(invalid("imports are not supported"));
+                    }
+                }
+                3 => func_types = s.vec(Reader::u32)?,
+                4 => {
+                    for _ in 0..s.u32()? {
+                        if s.byte()? != 0x70 || module.table.is_some() {
+                            return Err(invalid("only one table of functions is supported"));
+                        }
+                        module.table = Some(s.limits()?);
+                    }
+                }
+                5 => {
+                    for _ in 0..s.u32()? {
+                        if module.memory.is_some() {
+                            return Err(invalid("only one memory is supported"));
+                        }
+                        module.memory = Some(s.limits()?);
+                    }
+                }
+                6 => {
+                    for _ in 0..s.u32()? {
+                        s.val_type()?;
+                        s.byte()?;
+                        let value = s.const_expr(&module.globals)?;
+                        module.globals.push(value);
+                    }
+                }
+                7 => {
+                    for _ in 0..s.u32()? {
+                        let name = s.name()?;
+                        let kind = s.byte()?;
+                        let index = s.u32()?;
+                        if kind == 0 {
+                            module.exports.insert(name, index);
+                        }
+                    }
+                }
+                8 => module.start = Some(s.u32()?),
+                9 => {
+                    for _ in 0..s.u32()? {
+                        if s.u32()? != 0 {
+                            return Err(invalid("only active element segments are supported"));
+                        }
+                        let offset = s.const_expr(&module.globals)? as u32;
+                        let funcs = s.vec(Reader::u32)?;
+                        module.elements.push((offset, funcs));
+                    }
+                }
+                10 => {
+                    let count = s.u32()? as usize;
+                    if count != func_types.len() {
+                        return Err(invalid("function and code sections differ"));
+                    }
+                    for &type_idx in &func_types {
+                        let size = s.u32()? as usize;
+                        let mut body = Reader {
+                            bytes: s.take(size)?,
+                            pos: 0,
+                        };
+                        let ty = module
+                            .types
+                            .get(type_idx as usize)
+                            .ok_or_else(|| invalid("unknown type"))?;
+                        let mut locals = 0;
+                        for _ in 0..body.u32()? {
+                            locals += body.u32()? as usize;
+                            body.val_type()?;
+                            if locals > MAX_LOCALS {
+                                return Err(invalid("too many locals"));
+                            }
+                        }
+                        let code = body.code(&module.types)?;
+                        module.functions.push(Function {
+                            type_idx,
+                            params: ty.params.len(),
+                            results: ty.results.len(),
+                            locals,
+                            code,
+                        });
+                    }
+                }
+                11 => {
+                    for _ in 0..s.u32()? {
+                        let offset = match s.u32()? {
+                            0 => Some(s.const_expr(&module.globals)? as u32),
+                            1 => None,
+                            2 => {
+                                s.u32()?;
+                                Some(s.const_expr(&module.globals)? as u32)
+                            }
+                            _ => return Err(invalid("bad data segment")),
+                        };
+                        let len = s.u32()? as usize;
+                        let bytes = s.take(len)?.to_vec();
+                        module.data.push(Data { offset, bytes });
+                    }
+                }
+                _ => return Err(invalid("unknown section")),
+            }
+        }
+        if func_types.len() != module.functions.len() {
+            return Err(invalid("function and code sections differ"));
+        }
+        module.check()?;
+        Ok(module)
+    }
+
+    /// Checks the indices the instructions refer to, so running the module only has to check
+    /// what depends on the data.
+    fn check(&self) -> Result<()> {
+        let funcs = self.functions.len() as u32;
+        let exported = self.exports.values().chain(&self.start);
+        if exported.into_iter().any(|&f| f >= funcs) {
+            return Err(invalid("unknown function"));
+        }
+        if self
+            .elements
+            .iter()
+            .flat_map(|(_, f)| f)
+            .any(|&f| f >= funcs)
+        {
+            return Err(invalid("unknown function"));
+        }
+        for function in &self.functions {
+            let locals = (function.params + function.locals) as u32;
+            let mut labels = 0u32;
+            for op in &function.code {
+                let ok = match op {
+                    Op::Block { .. } | Op::Loop { .. } | Op::If { .. } => {
+                        labels += 1;
+                        true
+                    }
+                    Op::End => {
+                        labels = labels.saturating_sub(1);
+                        true
+                    }
+                    Op::Br(depth) | Op::BrIf(depth) => *depth <= labels,
+                    Op::BrTable(depths) => depths.iter().all(|depth| *depth <= labels),
+                    Op::Call(f) => *f < funcs,
+                    Op::CallIndirect(t) => (*t as usize) < self.types.len() && self.table.is_some(),
+                    Op::LocalGet(i) | Op::LocalSet(i) | Op::LocalTee(i) => *i < locals,
+                    Op::GlobalGet(i) | Op::GlobalSet(i) => (*i as usize) < self.globals.len(),
+                    Op::Load(..)
+                    | Op::Store(..)
+                    | Op::MemorySize
+                    | Op::MemoryGrow
+                    | Op::MemoryCopy
+                    | Op::MemoryFill => self.memory.is_some(),
+                    Op::MemoryInit(i) | Op::DataDrop(i) => (*i as usize) < self.data.len(),
+                    _ => true,
+                };
+                if !ok {
+                    return Err(invalid("bad index in function body"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An instance of a [`Module`], with its own memory.
+pub(crate) struct Instance {
+    module: Arc<Module>,
+    memory: Vec<u8>,
+    max_pages: usize,
+    globals: Vec<u64>,
+    table: Vec<Option<u32>>,
+    /// The data segments, emptied by `data.drop`.
+    data: Vec<Vec<u8>>,
+}
+
+impl Instance {
+    /// Instantiates `module` and runs its start function.
+    pub(crate) fn new(module: Arc<Module>) -> Result<Instance> {
+        let memory = module.memory.unwrap_or(Limits {
+            min: 0,
+            max: Some(0),
+        });
+        let table = module.table.map_or(0, |limits| limits.min as usize);
+        let mut instance = Instance {
+            memory: vec![0; memory.min as usize * PAGE],
+            max_pages: memory.max.map_or(65_536, |max| max as usize).min(65_536),
+            globals: module.globals.clone(),
+            table: vec![None; table],
+            data: module.data.iter().map(|d| d.bytes.clone()).collect(),
+            module,
+        };
+        let module = instance.module.clone();
+        for (offset, funcs) in &module.elements {
+            let offset = *offset as usize;
+            let slots = instance
+                .table
+                .get_mut(offset..offset + funcs.len())
+                .ok_or_else(|| invalid("element segment out of bounds"))?;
+            for (slot, func) in slots.iter_mut().zip(funcs) {
+                *slot = Some(*func);
+            }
+        }
+        for data in &module.data {
+            if let Some(offset) = data.offset {
+                let offset = offset as usize;
+                instance
+                    .memory
+                    .get_mut(offset..offset + data.bytes.len())
+                    .ok_or_else(|| invalid("data segment out of bounds"))?
+                    .copy_from_slice(&data.bytes);
+            }
+        }
+        if let Some(start) = module.start {
+            instance.run(start, &[])?;
+        }
+        Ok(instance)
+    }
+
+    /// Calls the exported function `name`, which takes and returns only `i32` values.
+    pub(crate) fn call(&mut self, name: &str, args: &[i32]) -> Result<Vec<i32>> {
+        let func = *self
+            .module
+            .exports
+            .get(name)
+            .ok_or_else(|| format!("no function {} is exported", name))?;
+        let function = &self.module.functions[func as usize];
+        let ty = &self.module.types[function.type_idx as usize];
+        if ty.params.len() != args.len()
+            || ty
+                .params
+                .iter()
+                .chain(&ty.results)
+                .any(|t| *t != ValType::I32)
+        {
+            return Err(format!("{} doesn't take {} i32 values", name, args.len()).into());
+        }
+        let args: Vec<u64> = args.iter().map(|a| u64::from(*a as u32)).collect();
+        Ok(self
+            .run(func, &args)?
+            .into_iter()
+            .map(|v| v as u32 as i32)
+            .collect())
+    }
+
+    /// Returns the linear memory.
+    pub(crate) fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Returns the linear memory to write to.
+    pub(crate) fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Runs the function `func` with `args` and returns its results.
+    fn run(&mut self, func: u32, args: &[u64]) -> Result<Vec<u64>> {
+        let module = self.module.clone();
+        let mut stack: Vec<u64> = Vec::new();
+        let mut locals: Vec<u64> = args.to_vec();
+        let mut labels: Vec<Label> = Vec::new();
+        let mut frames: Vec<Frame> = Vec::new();
+        locals.resize(args.len() + module.functions[func as usize].locals, 0);
+        let mut cur = Frame {
+            func,
+            pc: 0,
+            locals: 0,
+            labels: 0,
+            stack: 0,
+        };
+
+        macro_rules! pop {
+            () => {
+                stack.pop().expect("value stack underflow")
+            };
+        }
+        macro_rules! ret {
+            () => {{
+                let results = module.functions[cur.func as usize].results;
+                let top = stack.len() - results;
+                stack.drain(cur.stack..top);
+                labels.truncate(cur.labels);
+                locals.truncate(cur.locals);
+                match frames.pop() {
+                    Some(caller) => cur = caller,
+                    None => return Ok(stack),
+                }
+            }};
+        }
+        macro_rules! branch {
+            ($depth:expr) => {{
+                let depth = $depth as usize;
+                if depth >= labels.len() - cur.labels {
+                    ret!()
+                } else {
+                    let index = labels.len() - 1 - depth;
+                    let label = labels[index];
+                    let top = stack.len() - label.arity;
+                    stack.drain(label.height..top);
+                    labels.truncate(if label.is_loop { index + 1 } else { index });
+                    cur.pc = label.target;
+                }
+            }};
+        }
+
+        loop {
+            let function = &module.functions[cur.func as usize];
+            let op = &function.code[cur.pc];
+            cur.pc += 1;
+            match op {
+                Op::Unreachable => return Err(trap("unreachable")),
+                Op::Nop => {}
+                Op::Block {
+                    params,
+                    results,
+                    end,
+                } => labels.push(Label {
+                    target: end + 1,
+                    height: stack.len() - params,
+                    arity: *results,
+                    is_loop: false,
+                }),
+                Op::Loop { params } => labels.push(Label {
+                    target: cur.pc,
+                    height: stack.len() - params,
+                    arity: *params,
+                    is_loop: true,
+                }),
+                Op::If {
+                    params,
+                    results,
+                    else_,
+                    end,
+                } => {
+                    let label = Label {
+                        target: end + 1,
+                        height: stack.len() - 1 - params,
+                        arity: *results,
+                        is_loop: false,
+                    };
+                    if pop!() as u32 != 0 {
+                        labels.push(label);
+                    } else if let Some(else_) = else_ {
+                        labels.push(label);
+                        cur.pc = else_ + 1;
+                    } else {
+                        cur.pc = end + 1;
+                    }
+                }
+                Op::Else { end } => {
+                    labels.pop();
+                    cur.pc = end + 1;
+                }
+                Op::End => {
+                    if labels.len() > cur.labels {
+                        labels.pop();
+                    } else {
+                        ret!()
+                    }
+                }
+                Op::Br(depth) => branch!(*depth),
+                Op::BrIf(depth) => {
+                    if pop!() as u32 != 0 {
+                        branch!(*depth)
+                    }
+                }
+                Op::BrTable(depths) => {
+                    let index = pop!() as u32 as usize;
+                    let depth = depths.get(index).unwrap_or(&depths[depths.len() - 1]);
+                    branch!(*depth)
+                }
+                Op::Return => ret!(),
+                Op::Call(f) => {
+                    if frames.len() >= MAX_DEPTH {
+                        return Err(trap("call stack exhausted"));
+                    }
+                    let callee = &module.functions[*f as usize];
+                    let base = locals.len();
+                    let top = stack.len() - callee.params;
+                    locals.extend(stack.drain(top..));
+                    locals.resize(base + callee.params + callee.locals, 0);
+                    frames.push(cur);
+                    cur = Frame {
+                        func: *f,
+                        pc: 0,
+                        locals: base,
+                        labels: labels.len(),
+                        stack: stack.len(),
+                    };
+                }
+                Op::CallIndirect(t) => {
+                    let index = pop!() as u32 as usize;
+                    let f = match self.table.get(index) {
+                        Some(Some(f)) => *f,
+                        Some(None) => return Err(trap("uninitialized element")),
+                        None => return Err(trap("undefined element")),
+                    };
+                    let callee = &module.functions[f as usize];
+                    if module.types[callee.type_idx as usize] != module.types[*t as usize] {
+                        return Err(trap("indirect call type mismatch"));
+                    }
+                    if frames.len() >= MAX_DEPTH {
+                        return Err(trap("call stack exhausted"));
+                    }
+                    let base = locals.len();
+                    let top = stack.len() - callee.params;
+                    locals.extend(stack.drain(top..));
+                    locals.resize(base + callee.params + callee.locals, 0);
+                    frames.push(cur);
+                    cur = Frame {
+                        func: f,
+                        pc: 0,
+                        locals: base,
+                        labels: labels.len(),
+                        stack: stack.len(),
+                    };
+                }
+                Op::Drop => {
+                    pop!();
+                }
+                Op::Select => {
+                    let c = pop!() as u32;
+                    let b = pop!();
+                    let a = pop!();
+                    stack.push(if c != 0 { a } else { b });
+                }
+                Op::LocalGet(i) => stack.push(locals[cur.locals + *i as usize]),
+                Op::LocalSet(i) => locals[cur.locals + *i as usize] = pop!(),
+                Op::LocalTee(i) => {
+                    locals[cur.locals + *i as usize] = *stack.last().expect("value stack underflow")
+                }
+                Op::GlobalGet(i) => stack.push(self.globals[*i as usize]),
+                Op::GlobalSet(i) => self.globals[*i as usize] = pop!(),
+                Op::Load(op, offset) => {
+                    let address = u64::from(pop!() as u32) + u64::from(*offset);
+                    stack.push(self.load(*op, address)?);
+                }
+                Op::Store(op, offset) => {
+                    let value = pop!();
+                    let address = u64::from(pop!() as u32) + u64::from(*offset);
+                    self.store(*op, address, value)?;
+                }
+                Op::MemorySize => stack.push((self.memory.len() / PAGE) as u64),
+                Op::MemoryGrow => {
+                    let pages = self.memory.len() / PAGE;
+                    let delta = pop!() as u32 as usize;
+                    if pages + delta > self.max_pages {
+                        stack.push(u64::from(u32::MAX));
+                    } else {
+                        self.memory.resize((pages + delta) * PAGE, 0);
+                        stack.push(pages as u64);
+                    }
+                }
+                Op::Const(value) => stack.push(*value),
+                Op::Num(op) => numeric(*op, &mut stack)?,
+                Op::TruncSat(op) => {
+                    let v = pop!();
+                    let (f32v, f64v) = (f32::from_bits(v as u32), f64::from_bits(v));
+                    stack.push(match op {
+                        0 => f32v as i32 as u32 as u64,
+                        1 => f32v as u32 as u64,
+                        2 => f64v as i32 as u32 as u64,
+                        3 => f64v as u32 as u64,
+                        4 => f32v as i64 as u64,
+                        5 => f32v as u64,
+                        6 => f64v as i64 as u64,
+                        _ => f64v as u64,
+                    });
+                }
+                Op::MemoryInit(i) => {
+                    let len = pop!() as u32 as usize;
+                    let from = pop!() as u32 as usize;
+                    let to = pop!() as u32 as usize;
+                    let data = &self.data[*i as usize];
+                    let source = data
+                        .get(from..from.saturating_add(len))
+                        .ok_or_else(|| trap("out of bounds memory access"))?;
+                    self.memory
+                        .get_mut(to..to.saturating_add(len))
+                        .ok_or_else(|| trap("out of bounds memory access"))?
+                        .copy_from_slice(source);
+                }
+                Op::DataDrop(i) => self.data[*i as usize] = Vec::new(),
+                Op::MemoryCopy => {
+                    let len = pop!() as u32 as usize;
+                    let from = pop!() as u32 as usize;
+                    let to = pop!() as u32 as usize;
+                    let size = self.memory.len();
+                    if from.saturating_add(len) > size || to.saturating_add(len) > size {
+                        return Err(trap("out of bounds memory access"));
+                    }
+                    self.memory.copy_within(from..from + len, to);
+                }
+                Op::MemoryFill => {
+                    let len = pop!() as u32 as usize;
+                    let value = pop!() as u8;
+                    let to = pop!() as u32 as usize;
+                    self.memory
+                        .get_mut(to..to.saturating_add(len))
+                        .ok_or_else(|| trap("out of bounds memory access"))?
+                        .fill(value);
+                }
+            }
+        }
+    }
+
+    fn load(&self, op: u8, address: u64) -> Result<u64> {
+        let size = match op {
+            0x29 | 0x2b => 8,
+            0x28 | 0x2a | 0x34 | 0x35 => 4,
+            0x2e | 0x2f | 0x32 | 0x33 => 2,
+            _ => 1,
+        };
+        let start = usize::try_from(address).unwrap_or(usize::MAX);
+        let bytes = self
+            .memory
+            .get(start..start.saturating_add(size))
+            .ok_or_else(|| trap("out of bounds memory access"))?;
+        let mut raw = [0; 8];
+        raw[..size].copy_from_slice(bytes);
+        let v = u64::from_le_bytes(raw);
+        Ok(match op {
+            0x2c => v as i8 as i32 as u32 as u64,
+            0x2e => v as i16 as i32 as u32 as u64,
+            0x30 => v as i8 as i64 as u64,
+            0x32 => v as i16 as i64 as u64,
+            0x34 => v as i32 as i64 as u64,
+            _ => v,
+        })
+    }
+
+    fn store(&mut self, op: u8, address: u64, value: u64) -> Result<()> {
+        let size = match op {
+            0x37 | 0x39 => 8,
+            0x36 | 0x38 | 0x3e => 4,
+            0x3b | 0x3d => 2,
+            _ => 1,
+        };
+        let start = usize::try_from(address).unwrap_or(usize::MAX);
+        self.memory
+            .get_mut(start..start.saturating_add(size))
+            .ok_or_else(|| trap("out of bounds memory access"))?
+            .copy_from_slice(&value.to_le_bytes()[..size]);
+        Ok(())
+    }
+}
+
+/// Runs the numeric instruction `op` on the values on top of `stack`.
+fn numeric(op: u8, stack: &mut Vec<u64>) -> Result<()> {
+    macro_rules! un {
+        ($from:ident, $to:ident, |$a:ident| $e:expr) => {{
+            let $a = $from(stack.pop().expect("value stack underflow"));
+            let result = $e;
+            stack.push($to(result));
+        }};
+    }
+    macro_rules! bin {
+        ($from:ident, $to:ident, |$a:ident, $b:ident| $e:expr) => {{
+            let $b = $from(stack.pop().expect("value stack underflow"));
+            let $a = $from(stack.pop().expect("value stack underflow"));
+            let result = $e;
+            stack.push($to(result));
+        }};
+    }
+    match op {
+        0x45 => un!(i32, bool, |a| a == 0),
+        0x46 => bin!(i32, bool, |a, b| a == b),
+        0x47 => bin!(i32, bool, |a, b| a != b),
+        0x48 => bin!(i32, bool, |a, b| a < b),
+        0x49 => bin!(u32, bool, |a, b| a < b),
+        0x4a => bin!(i32, bool, |a, b| a > b),
+        0x4b => bin!(u32, bool, |a, b| a > b),
+        0x4c => bin!(i32, bool, |a, b| a <= b),
+        0x4d => bin!(u32, bool, |a, b| a <= b),
+        0x4e => bin!(i32, bool, |a, b| a >= b),
+        0x4f => bin!(u32, bool, |a, b| a >= b),
+        0x50 => un!(i64, bool, |a| a == 0),
+        0x51 => bin!(i64, bool, |a, b| a == b),
+        0x52 => bin!(i64, bool, |a, b| a != b),
+        0x53 => bin!(i64, bool, |a, b| a < b),
+        0x54 => bin!(u64, bool, |a, b| a < b),
+        0x55 => bin!(i64, bool, |a, b| a > b),
+        0x56 => bin!(u64, bool, |a, b| a > b),
+        0x57 => bin!(i64, bool, |a, b| a <= b),
+        0x58 => bin!(u64, bool, |a, b| a <= b),
+        0x59 => bin!(i64, bool, |a, b| a >= b),
+        0x5a => bin!(u64, bool, |a, b| a >= b),
+        0x5b => bin!(f32, bool, |a, b| a == b),
+        0x5c => bin!(f32, bool, |a, b| a != b),
+        0x5d => bin!(f32, bool, |a, b| a < b),
+        0x5e => bin!(f32, bool, |a, b| a > b),
+        0x5f => bin!(f32, bool, |a, b| a <= b),
+        0x60 => bin!(f32, bool, |a, b| a >= b),
+        0x61 => bin!(f64, bool, |a, b| a == b),
+        0x62 => bin!(f64, bool, |a, b| a != b),
+        0x63 => bin!(f64, bool, |a, b| a < b),
+        0x64 => bin!(f64, bool, |a, b| a > b),
+        0x65 => bin!(f64, bool, |a, b| a <= b),
+        0x66 => bin!(f64, bool, |a, b| a >= b),
+        0x67 => un!(u32, from_u32, |a| a.leading_zeros()),
+        0x68 => un!(u32, from_u32, |a| a.trailing_zeros()),
+        0x69 => un!(u32, from_u32, |a| a.count_ones()),
+        0x6a => bin!(u32, from_u32, |a, b| a.wrapping_add(b)),
+        0x6b => bin!(u32, from_u32, |a, b| a.wrapping_sub(b)),
+        0x6c => bin!(u32, from_u32, |a, b| a.wrapping_mul(b)),
+        0x6d => bin!(i32, from_i32, |a, b| match (a, b) {
+            (_, 0) => return Err(trap("integer divide by zero")),
+            (i32::MIN, -1) => return Err(trap("integer overflow")),
+            _ => a / b,
+        }),
+        0x6e => bin!(u32, from_u32, |a, b| a
+            .checked_div(b)
+            .ok_or_else(|| trap("integer divide by zero"))?),
+        0x6f => bin!(i32, from_i32, |a, b| match b {
+            0 => return Err(trap("integer divide by zero")),
+            _ => a.wrapping_rem(b),
+        }),
+        0x70 => bin!(u32, from_u32, |a, b| a
+            .checked_rem(b)
+            .ok_or_else(|| trap("integer divide by zero"))?),
+        0x71 => bin!(u32, from_u32, |a, b| a & b),
+        0x72 => bin!(u32, from_u32, |a, b| a | b),
+        0x73 => bin!(u32, from_u32, |a, b| a ^ b),
+        0x74 => bin!(u32, from_u32, |a, b| a.wrapping_shl(b)),
+        0x75 => bin!(i32, from_i32, |a, b| a.wrapping_shr(b as u32)),
+        0x76 => bin!(u32, from_u32, |a, b| a.wrapping_shr(b)),
+        0x77 => bin!(u32, from_u32, |a, b| a.rotate_left(b % 32)),
+        0x78 => bin!(u32, from_u32, |a, b| a.rotate_right(b % 32)),
+        0x79 => un!(u64, from_u64, |a| u64::from(a.leading_zeros())),
+        0x7a => un!(u64, from_u64, |a| u64::from(a.trailing_zeros())),
+        0x7b => un!(u64, from_u64, |a| u64::from(a.count_ones())),
+        0x7c => bin!(u64, from_u64, |a, b| a.wrapping_add(b)),
+        0x7d => bin!(u64, from_u64, |a, b| a.wrapping_sub(b)),
+        0x7e => bin!(u64, from_u64, |a, b| a.wrapping_mul(b)),
+        0x7f => bin!(i64, from_i64, |a, b| match (a, b) {
+            (_, 0) => return Err(trap("integer divide by zero")),
+            (i64::MIN, -1) => return Err(trap("integer overflow")),
+            _ => a / b,
+        }),
+        0x80 => bin!(u64, from_u64, |a, b| a
+            .checked_div(b)
+            .ok_or_else(|| trap("integer divide by zero"))?),
+        0x81 => bin!(i64, from_i64, |a, b| match b {
+            0 => return Err(trap("integer divide by zero")),
+            _ => a.wrapping_rem(b),
+        }),
+        0x82 => bin!(u64, from_u64, |a, b| a
+            .checked_rem(b)
+            .ok_or_else(|| trap("integer divide by zero"))?),
+        0x83 => bin!(u64, from_u64, |a, b| a & b),
+        0x84 => bin!(u64, from_u64, |a, b| a | b),
+        0x85 => bin!(u64, from_u64, |a, b| a ^ b),
+        0x86 => bin!(u64, from_u64, |a, b| a.wrapping_shl(b as u32)),
+        0x87 => bin!(i64, from_i64, |a, b| a.wrapping_shr(b as u32)),
+        0x88 => bin!(u64, from_u64, |a, b| a.wrapping_shr(b as u32)),
+        0x89 => bin!(u64, from_u64, |a, b| a.rotate_left((b % 64) as u32)),
+        0x8a => bin!(u64, from_u64, |a, b| a.rotate_right((b % 64) as u32)),
+        0x8b => un!(f32, from_f32, |a| a.abs()),
+        0x8c => un!(f32, from_f32, |a| -a),
+        0x8d => un!(f32, from_f32, |a| a.ceil()),
+        0x8e => un!(f32, from_f32, |a| a.floor()),
+        0x8f => un!(f32, from_f32, |a| a.trunc()),
+        0x90 => un!(f32, from_f32, |a| a.round_ties_even()),
+        0x91 => un!(f32, from_f32, |a| a.sqrt()),
+        0x92 => bin!(f32, from_f32, |a, b| a + b),
+        0x93 => bin!(f32, from_f32, |a, b| a - b),
+        0x94 => bin!(f32, from_f32, |a, b| a * b),
+        0x95 => bin!(f32, from_f32, |a, b| a / b),
+        0x96 => bin!(f32, from_f32, |a, b| min(a as f64, b as f64) as f32),
+        0x97 => bin!(f32, from_f32, |a, b| max(a as f64, b as f64) as f32),
+        0x98 => bin!(f32, from_f32, |a, b| a.copysign(b)),
+        0x99 => un!(f64, from_f64, |a| a.abs()),
+        0x9a => un!(f64, from_f64, |a| -a),
+        0x9b => un!(f64, from_f64, |a| a.ceil()),
+        0x9c => un!(f64, from_f64, |a| a.floor()),
+        0x9d => un!(f64, from_f64, |a| a.trunc()),
+        0x9e => un!(f64, from_f64, |a| a.round_ties_even()),
+        0x9f => un!(f64, from_f64, |a| a.sqrt()),
+        0xa0 => bin!(f64, from_f64, |a, b| a + b),
+        0xa1 => bin!(f64, from_f64, |a, b| a - b),
+        0xa2 => bin!(f64, from_f64, |a, b| a * b),
+        0xa3 => bin!(f64, from_f64, |a, b| a / b),
+        0xa4 => bin!(f64, from_f64, |a, b| min(a, b)),
+        0xa5 => bin!(f64, from_f64, |a, b| max(a, b)),
+        0xa6 => bin!(f64, from_f64, |a, b| a.copysign(b)),
+        0xa7 => un!(u64, from_u32, |a| a as u32),
+        0xa8 => un!(
+            f32,
+            from_i32,
+            |a| truncate(a as f64, -2147483649.0, 2147483648.0)? as i32
+        ),
+        0xa9 => un!(f32, from_u32, |a| truncate(a as f64, -1.0, 4294967296.0)?
+            as u32),
+        0xaa => un!(f64, from_i32, |a| truncate(a, -2147483649.0, 2147483648.0)?
+            as i32),
+        0xab => un!(f64, from_u32, |a| truncate(a, -1.0, 4294967296.0)? as u32),
+        0xac => un!(i32, from_i64, |a| a as i64),
+        0xad => un!(u32, from_u64, |a| a as u64),
+        0xae => un!(f32, from_i64, |a| truncate(
+            a as f64,
+            -9223373136366403584.0,
+            9223372036854775808.0
+        )? as i64),
+        0xaf => un!(
+            f32,
+            from_u64,
+            |a| truncate(a as f64, -1.0, 18446744073709551616.0)? as u64
+        ),
+        0xb0 => un!(
+            f64,
+            from_i64,
+            |a| truncate(a, -9223372036854777856.0, 9223372036854775808.0)? as i64
+        ),
+        0xb1 => un!(
+            f64,
+            from_u64,
+            |a| truncate(a, -1.0, 18446744073709551616.0)? as u64
+        ),
+        0xb2 => un!(i32, from_f32, |a| a as f32),
+        0xb3 => un!(u32, from_f32, |a| a as f32),
+        0xb4 => un!(i64, from_f32, |a| a as f32),
+        0xb5 => un!(u64, from_f32, |a| a as f32),
+        0xb6 => un!(f64, from_f32, |a| a as f32),
+        0xb7 => un!(i32, from_f64, |a| a as f64),
+        0xb8 => un!(u32, from_f64, |a| a as f64),
+        0xb9 => un!(i64, from_f64, |a| a as f64),
+        0xba => un!(u64, from_f64, |a| a as f64),
+        0xbb => un!(f32, from_f64, |a| a as f64),
+        // The reinterpretations keep the bits, which is all a value is here.
+        0xbc..=0xbf => {}
+        0xc0 => un!(i32, from_i32, |a| a as i8 as i32),
+        0xc1 => un!(i32, from_i32, |a| a as i16 as i32),
+        0xc2 => un!(i64, from_i64, |a| a as i8 as i64),
+        0xc3 => un!(i64, from_i64, |a| a as i16 as i64),
+        0xc4 => un!(i64, from_i64, |a| a as i32 as i64),
+        _ => unreachable!("not a numeric instruction: {:#x}", op),
+    }
+    Ok(())
+}
+
+fn i32(v: u64) -> i32 {
+    v as u32 as i32
+}
+
+fn u32(v: u64) -> u32 {
+    v as u32
+}
+
+fn i64(v: u64) -> i64 {
+    v as i64
+}
+
+fn u64(v: u64) -> u64 {
+    v
+}
+
+fn f32(v: u64) -> f32 {
+    f32::from_bits(v as u32)
+}
+
+fn f64(v: u64) -> f64 {
+    f64::from_bits(v)
+}
+
+fn bool(v: bool) -> u64 {
+    u64::from(v)
+}
+
+fn from_i32(v: i32) -> u64 {
+    u64::from(v as u32)
+}
+
+fn from_u32(v: u32) -> u64 {
+    u64::from(v)
+}
+
+fn from_i64(v: i64) -> u64 {
+    v as u64
+}
+
+fn from_u64(v: u64) -> u64 {
+    v
+}
+
+fn from_f32(v: f32) -> u64 {
+    u64::from(v.to_bits())
+}
+
+fn from_f64(v: f64) -> u64 {
+    v.to_bits()
+}
+
+/// Returns `v` rounded toward zero if it lies strictly between `below` and `above`, the nearest
+/// values outside the range of the integer type.
+fn truncate(v: f64, below: f64, above: f64) -> Result<f64> {
+    if v.is_nan() {
+        return Err(trap("invalid conversion to integer"));
+    }
+    if v <= below || v >= above {
+        return Err(trap("integer overflow"));
+    }
+    Ok(v.trunc())
+}
+
+/// The minimum of wasm, NaN if either is and -0 before 0.
+fn min(a: f64, b: f64) -> f64 {
+    match (a.is_nan() || b.is_nan(), a == b) {
+        (true, _) => f64::NAN,
+        (false, true) if a.is_sign_negative() => a,
+        (false, true) => b,
+        (false, false) => a.min(b),
+    }
+}
+
+/// The maximum of wasm, NaN if either is and 0 after -0.
+fn max(a: f64, b: f64) -> f64 {
+    match (a.is_nan() || b.is_nan(), a == b) {
+        (true, _) => f64::NAN,
+        (false, true) if a.is_sign_positive() => a,
+        (false, true) => b,
+        (false, false) => a.max(b),
+    }
+}
+
+fn invalid(message: &str) -> Box<dyn std::error::Error + Send + Sync> {
+    format!("invalid wasm module: {}", message).into()
+}
+
+fn trap(message: &str) -> Box<dyn std::error::Error + Send + Sync> {
+    format!("wasm trap: {}", message).into()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads a LEB128 number of at most `bits` bits, sign extended if `signed`.
+    fn leb(&mut self, bits: u32, signed: bool) -> Result<u64> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= bits.div_ceil(7) * 7 {
+                return Err(invalid("number too long"));
+            }
+            result |= u64::from(byte & 0x7f).checked_shl(shift).unwrap_or(0);
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if signed && shift < 64 && byte & 0x40 != 0 {
+                    result |= u64::MAX << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(self.leb(32, false)? as u32)
+    }
+
+    fn vec<T>(&mut self, item: impl Fn(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let len = self.u32()? as usize;
+        // Each item takes at least a byte, which bounds what a bad length can allocate.
+        let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    fn val_type(&mut self) -> Result<ValType> {
+        match self.byte()? {
+            0x7f => Ok(ValType::I32),
+            0x7e => Ok(ValType::I64),
+            0x7d => Ok(ValType::F32),
+            0x7c => Ok(ValType::F64),
+            _ => Err(invalid("unsupported value type")),
+        }
+    }
+
+    fn limits(&mut self) -> Result<Limits> {
+        match self.byte()? {
+            0 => Ok(Limits {
+                min: self.u32()?,
+                max: None,
+            }),
+            1 => Ok(Limits {
+                min: self.u32()?,
+                max: Some(self.u32()?),
+            }),
+            _ => Err(invalid("unsupported limits")),
+        }
+    }
+
+    /// Reads a constant expression, a single constant or global.
+    fn const_expr(&mut self, globals: &[u64]) -> Result<u64> {
+        let value = match self.byte()? {
+            0x41 => u64::from(self.leb(32, true)? as u32),
+            0x42 => self.leb(64, true)?,
+            0x43 => u64::from(u32::from_le_bytes(self.take(4)?.try_into()?)),
+            0x44 => u64::from_le_bytes(self.take(8)?.try_into()?),
+            0x23 => *globals
+                .get(self.u32()? as usize)
+                .ok_or_else(|| invalid("unknown global"))?,
+            _ => return Err(invalid("unsupported constant expression")),
+        };
+        match self.byte()? {
+            0x0b => Ok(value),
+            _ => Err(invalid("unsupported constant expression")),
+        }
+    }
+
+    /// Reads the parameter and result counts of a block type.
+    fn block_type(&mut self, types: &[FuncType]) -> Result<(usize, usize)> {
+        match self.bytes.get(self.pos) {
+            Some(0x40) => {
+                self.pos += 1;
+                Ok((0, 0))
+            }
+            Some(0x7c..=0x7f) => {
+                self.pos += 1;
+                Ok((0, 1))
+            }
+            _ => {
+                let ty = types
+                    .get(self.leb(33, true)? as usize)
+                    .ok_or_else(|| invalid("unknown block type"))?;
+                Ok((ty.params.len(), ty.results.len()))
+            }
+        }
+    }
+
+    fn memarg(&mut self) -> Result<u32> {
+        self.u32()?;
+        self.u32()
+    }
+
+    /// Reads the instructions of a function body up to its final `end`.
+    fn code(&mut self, types: &[FuncType]) -> Result<Vec<Op>> {
+        let mut code = Vec::new();
+        // The blocks not ended yet, with the `else` of an `if`.
+        let mut open: Vec<(usize, Option<usize>)> = Vec::new();
+        loop {
+            let opcode = self.byte()?;
+            let op = match opcode {
+                0x00 => Op::Unreachable,
+                0x01 => Op::Nop,
+                0x02..=0x04 => {
+                    let (params, results) = self.block_type(types)?;
+                    open.push((code.len(), None));
+                    match opcode {
+                        0x02 => Op::Block {
+                            params,
+                            results,
+                            end: 0,
+                        },
+                        0x03 => Op::Loop { params },
+                        _ => Op::If {
+                            params,
+                            results,
+                            else_: None,
+                            end: 0,
+                        },
+                    }
+                }
+                0x05 => {
+                    let Some((start, else_ @ None)) = open.last_mut() else {
+                        return Err(invalid("else without if"));
+                    };
+                    let Op::If { .. } = code[*start] else {
+                        return Err(invalid("else without if"));
+                    };
+                    *else_ = Some(code.len());
+                    Op::Else { end: 0 }
+                }
+                0x0b => {
+                    let Some((start, else_)) = open.pop() else {
+                        code.push(Op::End);
+                        if self.pos != self.bytes.len() {
+                            return Err(invalid("code after the end of a function"));
+                        }
+                        return Ok(code);
+                    };
+                    let index = code.len();
+                    match &mut code[start] {
+                        Op::Block { end, .. } => *end = index,
+                        Op::If { end, else_: e, .. } => {
+                            *end = index;
+                            *e = else_;
+                        }
+                        _ => {}
+                    }
+                    if let Some(else_) = else_ {
+                        code[else_] = Op::Else { end: index };
+                    }
+                    Op::End
+                }
+                0x0c => Op::Br(self.u32()?),
+                0x0d => Op::BrIf(self.u32()?),
+                0x0e => {
+                    let mut depths = self.vec(Reader::u32)?;
+                    depths.push(self.u32()?);
+                    Op::BrTable(depths.into_boxed_slice())
+                }
+                0x0f => Op::Return,
+                0x10 => Op::Call(self.u32()?),
+                0x11 => {
+                    let t = self.u32()?;
+                    if self.byte()? != 0 {
+                        return Err(invalid("only one table is supported"));
+                    }
+                    Op::CallIndirect(t)
+                }
+                0x1a => Op::Drop,
+                0x1b => Op::Select,
+                0x1c => {
+                    self.vec(Reader::val_type)?;
+                    Op::Select
+                }
+                0x20 => Op::LocalGet(self.u32()?),
+                0x21 => Op::LocalSet(self.u32()?),
+                0x22 => Op::LocalTee(self.u32()?),
+                0x23 => Op::GlobalGet(self.u32()?),
+                0x24 => Op::GlobalSet(self.u32()?),
+                0x28..=0x35 => Op::Load(opcode, self.memarg()?),
+                0x36..=0x3e => Op::Store(opcode, self.memarg()?),
+                0x3f | 0x40 => {
+                    if self.byte()? != 0 {
+                        return Err(invalid("only one memory is supported"));
+                    }
+                    match opcode {
+                        0x3f => Op::MemorySize,
+                        _ => Op::MemoryGrow,
+                    }
+                }
+                0x41 => Op::Const(u64::from(self.leb(32, true)? as u32)),
+                0x42 => Op::Const(self.leb(64, true)?),
+                0x43 => Op::Const(u64::from(u32::from_le_bytes(self.take(4)?.try_into()?))),
+                0x44 => Op::Const(u64::from_le_bytes(self.take(8)?.try_into()?)),
+                0x45..=0xc4 => Op::Num(opcode),
+                0xfc => match self.u32()? {
+                    sub @ 0..=7 => Op::TruncSat(sub as u8),
+                    8 => {
+                        let data = self.u32()?;
+                        self.byte()?;
+                        Op::MemoryInit(data)
+                    }
+                    9 => Op::DataDrop(self.u32()?),
+                    10 => {
+                        self.take(2)?;
+                        Op::MemoryCopy
+                    }
+                    11 => {
+                        self.byte()?;
+                        Op::MemoryFill
+                    }
+                    sub => {
+                        return Err(format!(
+                            "invalid wasm module: unsupported instruction 0xfc {}",
+                            sub
+                        )
+                        .into())
+                    }
+                },
+                _ => {
+                    return Err(format!(
+                        "invalid wasm module: unsupported instruction {:#04x}",
+                        opcode
+                    )
+                    .into())
+                }
+            };
+            code.push(op);
+        }
+    }
+}
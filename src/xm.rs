@@ -1,106 +1,937 @@
-use crate::id3::{Tag, TagLike};
+use crate::id3::frame::{Comment, ExtendedLink, Lyrics, Picture, PictureType};
+use crate::id3::{Content, ErrorKind, FrameIterator, ParseOptions, Tag, TagLike, Version};
+use crate::json::json_string;
+use crate::metadata::TrackMetadata;
+use crate::naming::NameTemplate;
 use crate::Result;
+use crate::{flac, mp3, mp4, wasm};
 
-use wasmer::{imports, Instance, Module, Store, Value};
+use std::fmt::Write as _;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+#[cfg(feature = "cranelift")]
+use wasmer::{imports, Engine, Instance, Module, Store, Value};
+#[cfg(feature = "cranelift")]
 use wasmer_compiler_cranelift::Cranelift;
 
-const XM_KEY: &[u8] = "ximalayaximalayaximalayaximalaya".as_bytes();
+#[cfg(feature = "fs")]
+pub use crate::batch::{decrypt_all, Conflict, DecryptOptions, FileResult};
+
+/// The AES key of the client, used unless [`Decryptor::with_key`] sets another.
+pub const XM_KEY: [u8; 32] = *b"ximalayaximalayaximalayaximalaya";
 const XM_WASM: &[u8] = include_bytes!("xm.wasm");
 
-pub fn extract_xm_info(reader: impl std::io::Read) -> Result<XMInfo> {
-    Tag::read_from(reader)
-        .map(|t| t.into())
-        .map_err(|e| e.into())
+/// Decrypts xm files with `xm.wasm` compiled once. Compiling takes longer than decrypting a short
+/// track, so one `Decryptor` should be kept for all the files of a run.
+///
+/// A `Decryptor` is `Send` and `Sync` and can be shared by threads, such as the workers of
+/// rayon or a server. The wasm instances are pooled, a thread takes one that is free or
+/// instantiates another, so there are at most as many as threads decrypting at the same time.
+pub struct Decryptor {
+    backend: Backend,
+    workers: Mutex<Vec<Box<dyn Wasm>>>,
+    key: [u8; 32],
+    iv_source: IvSource,
 }
 
-pub fn decrypt(xm_info: &XMInfo, content: &[u8]) -> Result<Vec<u8>> {
-    let encrypted_data = &content[xm_info.header_size..xm_info.header_size + xm_info.size];
-    let iv = xm_info.iv()?;
-    let decrypted_data = aes_util::decrypt(encrypted_data, XM_KEY, &iv)?;
-    let decrypted_str = String::from_utf8(decrypted_data)?;
+/// Where the AES IV of an xm file comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IvSource {
+    /// The hex in `TSRC`, or else in `TENC`, as the client writes it.
+    #[default]
+    Tag,
+    /// The hex in `TSRC` only.
+    Isrc,
+    /// The hex in `TENC` only.
+    EncodedBy,
+    /// The same IV for every file.
+    Fixed([u8; 16]),
+}
 
-    let track_id = format!("{}", xm_info.tracknumber);
+/// The size of the chunks [`Decryptor::decrypt_to`] copies the plain audio in.
+const COPY_CHUNK: usize = 64 * 1024;
 
-    let compiler = Cranelift::new();
-    let mut store = Store::new(compiler);
-    let module = Module::from_binary(&store, XM_WASM)?;
-    let import_object = imports! {};
-    let instance = Instance::new(&mut store, &module, &import_object)?;
+/// A stage of decrypting an xm file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// AES decryption of the encrypted region.
+    Aes,
+    /// The transform of `xm.wasm`.
+    Wasm,
+    /// Base64 decoding into the start of the audio.
+    Base64,
+}
 
-    let func_a = instance.exports.get_function("a")?;
-    let stack_pointer = func_a.call(&mut store, &[Value::I32(-16)])?[0].clone();
+/// Receives the progress of a decryption, for front-ends that show progress within large files.
+/// Every method does nothing by default.
+pub trait DecryptObserver {
+    /// Called when `stage` is done, with the number of bytes it produced. Files that aren't
+    /// encrypted skip every stage.
+    fn stage_done(&mut self, stage: Stage, bytes: usize) {
+        let _ = (stage, bytes);
+    }
 
-    let func_c = instance.exports.get_function("c")?;
-    let de_data_offset = func_c.call(&mut store, &[Value::I32(decrypted_str.len() as i32)])?[0]
-        .i32()
-        .expect("de_data_offset none");
+    /// Called by [`Decryptor::decrypt_to_observed`] after each write, with the number of bytes
+    /// written so far.
+    fn written(&mut self, total: u64) {
+        let _ = total;
+    }
+}
 
-    let track_id_offset = func_c.call(&mut store, &[Value::I32(track_id.len() as i32)])?[0]
-        .i32()
-        .expect("track_id_offset none");
+impl DecryptObserver for () {}
 
-    let memory_i = instance.exports.get_memory("i")?;
-    {
-        let view = memory_i.view(&store);
-        for (i, b) in decrypted_str.bytes().enumerate() {
-            view.write_u8(de_data_offset as u64 + i as u64, b)?;
+/// Adds the time since the previous stage was done to each stage.
+struct StageTimer {
+    last: Instant,
+    times: StageTimes,
+}
+
+impl DecryptObserver for StageTimer {
+    fn stage_done(&mut self, stage: Stage, _: usize) {
+        let now = Instant::now();
+        let time = match stage {
+            Stage::Aes => &mut self.times.aes,
+            Stage::Wasm => &mut self.times.wasm,
+            Stage::Base64 => &mut self.times.base64,
+        };
+        *time += now - self.last;
+        self.last = now;
+    }
+}
+
+/// The decryptor behind the free functions, shared by every decryption in the process.
+static SHARED: OnceLock<Decryptor> = OnceLock::new();
+
+impl Decryptor {
+    /// Compiles `xm.wasm` with Cranelift, or without the `cranelift` feature prepares it for the
+    /// interpreter like [`Decryptor::interpreted`]. The files are decrypted with [`XM_KEY`] and
+    /// the IV from their tag.
+    pub fn new() -> Result<Decryptor> {
+        #[cfg(feature = "cranelift")]
+        {
+            let engine: Engine = Cranelift::new().into();
+            let module = Module::from_binary(&engine, XM_WASM)?;
+            Ok(Decryptor::with_backend(Backend::Cranelift {
+                engine,
+                module,
+            }))
         }
-        for (i, b) in track_id.bytes().enumerate() {
-            view.write_u8(track_id_offset as u64 + i as u64, b)?;
+        #[cfg(not(feature = "cranelift"))]
+        Decryptor::interpreted()
+    }
+
+    /// Runs `xm.wasm` in the interpreter of this crate instead of compiling it. It is ready at
+    /// once but transforms several times slower, and builds on every target Rust does.
+    pub fn interpreted() -> Result<Decryptor> {
+        let module = wasm::Module::new(XM_WASM)?;
+        Ok(Decryptor::with_backend(Backend::Interpreter(Arc::new(
+            module,
+        ))))
+    }
+
+    /// Loads `xm.wasm` compiled by an earlier run from `cache_dir`, or compiles it like
+    /// [`Decryptor::new`] and stores it there, so later processes skip the compilation.
+    ///
+    /// The cached module is named after the versions of this crate and wasmer and the target, as
+    /// it only loads into the build that wrote it. A cache that can't be read or written is
+    /// compiled around and only logged.
+    #[cfg(all(feature = "fs", feature = "cranelift"))]
+    pub fn new_cached(cache_dir: &Path) -> Result<Decryptor> {
+        let engine: Engine = Cranelift::new().into();
+        let path = cache_dir.join(format!(
+            "xm-{}-wasmer-{}-{}-{}.bin",
+            env!("CARGO_PKG_VERSION"),
+            wasmer::VERSION,
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ));
+        // SAFETY: the file is only written below, with a module this build serialized. The
+        // checked deserialize rejects a file that was cut short or damaged since.
+        match unsafe { Module::deserialize_from_file(&engine, &path) } {
+            Ok(module) => {
+                tracing::debug!(path = %path.display(), "loaded the compiled xm.wasm");
+                return Ok(Decryptor::with_backend(Backend::Cranelift {
+                    engine,
+                    module,
+                }));
+            }
+            Err(e) if path.exists() => {
+                tracing::warn!(path = %path.display(), error = %e, "can't load the compiled xm.wasm")
+            }
+            Err(_) => {}
+        }
+        let module = Module::from_binary(&engine, XM_WASM)?;
+        // Written to a temporary file first, so a process starting meanwhile never loads half of it.
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let stored = std::fs::create_dir_all(cache_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| module.serialize_to_file(&temp).map_err(|e| e.to_string()))
+            .and_then(|()| std::fs::rename(&temp, &path).map_err(|e| e.to_string()));
+        match stored {
+            Ok(()) => tracing::debug!(path = %path.display(), "cached the compiled xm.wasm"),
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp);
+                tracing::warn!(path = %path.display(), error = %e, "can't cache the compiled xm.wasm")
+            }
+        }
+        Ok(Decryptor::with_backend(Backend::Cranelift {
+            engine,
+            module,
+        }))
+    }
+
+    fn with_backend(backend: Backend) -> Decryptor {
+        Decryptor {
+            backend,
+            workers: Mutex::new(Vec::new()),
+            key: XM_KEY,
+            iv_source: IvSource::default(),
         }
     }
 
-    let func_g = instance.exports.get_function("g")?;
-    func_g.call(
-        &mut store,
+    /// Decrypts with the AES key `key` instead of [`XM_KEY`], for files of a client that changed
+    /// it.
+    pub fn with_key(self, key: [u8; 32]) -> Decryptor {
+        Decryptor { key, ..self }
+    }
+
+    /// Takes the AES IV from `iv_source` instead of the tag.
+    pub fn with_iv_source(self, iv_source: IvSource) -> Decryptor {
+        Decryptor { iv_source, ..self }
+    }
+
+    /// Returns the AES key the decryptor uses.
+    pub fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    /// Returns where the decryptor takes the AES IV from.
+    pub fn iv_source(&self) -> IvSource {
+        self.iv_source
+    }
+
+    /// Returns the decryptor shared by the process, compiling it on first use.
+    ///
+    /// Concurrent first calls may each compile the module, only one of the results is kept.
+    fn shared() -> Result<&'static Decryptor> {
+        if let Some(decryptor) = SHARED.get() {
+            return Ok(decryptor);
+        }
+        let decryptor = Decryptor::new()?;
+        Ok(SHARED.get_or_init(|| decryptor))
+    }
+
+    /// Decrypts the xm file `content` and returns the audio.
+    pub fn decrypt(&self, xm_info: &XMInfo, content: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt_observed(xm_info, content, &mut ())
+    }
+
+    /// Decrypts like [`Decryptor::decrypt`], and if that fails, as it does when `TRCK` is missing
+    /// or mangled, tries the track ids `candidates` in turn, such as those of
+    /// [`track_ids_in_name`]. A candidate only counts if the audio it gives passes
+    /// [`validate_audio`]. Returns the audio and the track id it was decrypted with.
+    ///
+    /// If no candidate works, the error of decrypting with the track id of `xm_info` is returned.
+    pub fn decrypt_trying_track_ids(
+        &self,
+        xm_info: &XMInfo,
+        content: &[u8],
+        candidates: &[u64],
+    ) -> Result<(Vec<u8>, u64)> {
+        let error = match self.decrypt(xm_info, content) {
+            Ok(audio) => return Ok((audio, xm_info.tracknumber)),
+            Err(e) => e,
+        };
+        for &tracknumber in candidates {
+            if tracknumber == xm_info.tracknumber {
+                continue;
+            }
+            let info = XMInfo {
+                tracknumber,
+                ..xm_info.clone()
+            };
+            match self.decrypt(&info, content) {
+                Ok(audio) if validate_audio(&audio).is_ok() => return Ok((audio, tracknumber)),
+                _ => tracing::debug!(tracknumber, "not the track id"),
+            }
+        }
+        Err(error)
+    }
+
+    /// Decrypts like [`Decryptor::decrypt`] and also returns the time spent in each stage.
+    pub fn decrypt_timed(&self, xm_info: &XMInfo, content: &[u8]) -> Result<(Vec<u8>, StageTimes)> {
+        let mut timer = StageTimer {
+            last: Instant::now(),
+            times: StageTimes::default(),
+        };
+        let audio = self.decrypt_observed(xm_info, content, &mut timer)?;
+        Ok((audio, timer.times))
+    }
+
+    /// Decrypts like [`Decryptor::decrypt`] and tells `observer` as each stage is done.
+    pub fn decrypt_observed(
+        &self,
+        xm_info: &XMInfo,
+        content: &[u8],
+        observer: &mut dyn DecryptObserver,
+    ) -> Result<Vec<u8>> {
+        let after_tag = content.get(xm_info.header_size..).unwrap_or_default();
+        if !xm_info.is_encrypted() || self.plain_after_tag(xm_info, after_tag) {
+            tracing::debug!("not encrypted, copying the audio after the tag");
+            return Ok(after_tag.to_vec());
+        }
+        let encrypted = stages::find_encrypted_range(xm_info, content, &self.key, self.iv_source)?;
+        let mut audio = self.decrypt_region(xm_info, &content[encrypted.clone()], observer)?;
+        audio.extend_from_slice(&content[encrypted.end..]);
+        Ok(audio)
+    }
+
+    /// Decrypts like [`Decryptor::decrypt`], but reads the xm file from `reader` and writes the
+    /// audio to `writer`. Only the encrypted region is held in memory, the plain audio after it is
+    /// copied in chunks. Returns the number of bytes written.
+    ///
+    /// If `TSIZ` is missing or reaches past the end of the file, the whole file is read into
+    /// memory to find the region.
+    pub fn decrypt_to(
+        &self,
+        reader: impl Read + Seek,
+        writer: impl Write,
+        xm_info: &XMInfo,
+    ) -> Result<u64> {
+        self.decrypt_to_observed(reader, writer, xm_info, &mut ())
+    }
+
+    /// Decrypts like [`Decryptor::decrypt_to`] and tells `observer` as each stage is done and as
+    /// each chunk of audio is written.
+    pub fn decrypt_to_observed(
+        &self,
+        mut reader: impl Read + Seek,
+        mut writer: impl Write,
+        xm_info: &XMInfo,
+        observer: &mut dyn DecryptObserver,
+    ) -> Result<u64> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let size = xm_info.size.filter(|&size| {
+            (xm_info.header_size as u64)
+                .checked_add(size as u64)
+                .is_some_and(|end| end <= len)
+        });
+        if xm_info.is_encrypted() && size.is_none() {
+            let mut content = Vec::new();
+            reader.seek(SeekFrom::Start(0))?;
+            reader.read_to_end(&mut content)?;
+            let audio = self.decrypt_observed(xm_info, &content, observer)?;
+            writer.write_all(&audio)?;
+            observer.written(audio.len() as u64);
+            return Ok(audio.len() as u64);
+        }
+        reader.seek(SeekFrom::Start(xm_info.header_size as u64))?;
+        let mut written = 0;
+        if let Some(size) = size.filter(|_| xm_info.is_encrypted()) {
+            // Also the start of the audio, to tell plain audio after the tag from the region.
+            let mut after_tag = Vec::new();
+            (&mut reader)
+                .take(size.max(HEADER_LEN) as u64)
+                .read_to_end(&mut after_tag)?;
+            let audio = match self.plain_after_tag(xm_info, &after_tag) {
+                true => {
+                    tracing::debug!("not encrypted, copying the audio after the tag");
+                    after_tag
+                }
+                false => {
+                    let mut audio = self.decrypt_region(xm_info, &after_tag[..size], observer)?;
+                    audio.extend_from_slice(&after_tag[size..]);
+                    audio
+                }
+            };
+            writer.write_all(&audio)?;
+            written = audio.len() as u64;
+            observer.written(written);
+        }
+        copy_observed(reader, writer, written, observer)
+    }
+
+    /// Decrypts the xm file read from `reader`, which needs no [`Seek`], such as a pipe or a
+    /// network stream, and writes the audio to `writer`. Returns the info of the tag and the
+    /// number of bytes written.
+    ///
+    /// Only the tag and the encrypted region are held in memory. If `TSIZ` is missing or reaches
+    /// past the end of the stream, the whole stream is read into memory to find the region.
+    pub fn decrypt_stream(
+        &self,
+        mut reader: impl Read,
+        mut writer: impl Write,
+    ) -> Result<(XMInfo, u64)> {
+        let mut content = vec![0; 10];
+        reader.read_exact(&mut content)?;
+        let tag_len = Some(&content)
+            .filter(|header| header.starts_with(b"ID3"))
+            .and_then(|header| id3v2_len(header))
+            .ok_or("not an xm file, there is no ID3 tag")?;
+        content.resize(tag_len, 0);
+        reader.read_exact(&mut content[10..])?;
+        let xm_info = extract_xm_info(&content[..])?;
+        let end = xm_info
+            .size
+            .map(|size| xm_info.header_size.saturating_add(size));
+        let region = match end.filter(|_| xm_info.is_encrypted()) {
+            Some(end) if end >= content.len() => {
+                // Also the start of the audio, to tell plain audio after the tag from the region.
+                let wanted = end.max(xm_info.header_size.saturating_add(HEADER_LEN));
+                let missing = (wanted - content.len()) as u64;
+                (&mut reader).take(missing).read_to_end(&mut content)?;
+                Some(end).filter(|&end| end <= content.len())
+            }
+            _ => None,
+        };
+        let written = match region {
+            Some(end) => {
+                let after_tag = &content[xm_info.header_size..];
+                if self.plain_after_tag(&xm_info, after_tag) {
+                    tracing::debug!("not encrypted, copying the audio after the tag");
+                    writer.write_all(after_tag)?;
+                    after_tag.len()
+                } else {
+                    let encrypted = &after_tag[..end - xm_info.header_size];
+                    let audio = self.decrypt_region(&xm_info, encrypted, &mut ())?;
+                    writer.write_all(&audio)?;
+                    writer.write_all(&content[end..])?;
+                    audio.len() + content.len() - end
+                }
+            }
+            None if xm_info.is_encrypted() => {
+                reader.read_to_end(&mut content)?;
+                let audio = self.decrypt(&xm_info, &content)?;
+                writer.write_all(&audio)?;
+                return Ok((xm_info, audio.len() as u64));
+            }
+            None => {
+                let after_tag = content.get(xm_info.header_size..).unwrap_or_default();
+                writer.write_all(after_tag)?;
+                after_tag.len()
+            }
+        };
+        let written = copy_observed(reader, writer, written as u64, &mut ())?;
+        Ok((xm_info, written))
+    }
+
+    /// Returns true if `after_tag`, what follows the tag, starts with known audio rather than the
+    /// encrypted region. Encrypted bytes may start like MPEG audio by chance, so with `TSIZ` the
+    /// region must also not end in a correctly padded AES block. A `TSIZ` past the end of
+    /// `after_tag` counts as encrypted, its region is searched for.
+    fn plain_after_tag(&self, xm_info: &XMInfo, after_tag: &[u8]) -> bool {
+        if detect_format(&after_tag[..after_tag.len().min(HEADER_LEN)]) == AudioFormat::Unknown {
+            return false;
+        }
+        match xm_info.size {
+            Some(size) => after_tag.get(..size).is_some_and(|region| {
+                !stages::ends_padded(xm_info, region, &self.key, self.iv_source)
+            }),
+            None => true,
+        }
+    }
+
+    /// Decrypts the `encrypted_data` region of an xm file, AES and then the wasm transform, and
+    /// returns the start of the audio it holds.
+    fn decrypt_region(
+        &self,
+        xm_info: &XMInfo,
+        encrypted_data: &[u8],
+        observer: &mut dyn DecryptObserver,
+    ) -> Result<Vec<u8>> {
+        let decrypted = stages::aes_decrypt(xm_info, encrypted_data, &self.key, self.iv_source)?;
+        observer.stage_done(Stage::Aes, decrypted.len());
+        let transformed = self.transform_prefix(xm_info, &decrypted)?;
+        observer.stage_done(Stage::Wasm, transformed.len());
+        let decoded = stages::base64_decode(xm_info, &transformed)?;
+        observer.stage_done(Stage::Base64, decoded.len());
+        Ok(decoded)
+    }
+
+    /// Runs the wasm transform of `xm.wasm` on `decrypted_str`, the AES decrypted prefix returned
+    /// by [`decrypt_aes_prefix`], and returns the base64 text of the audio without the part kept
+    /// in `TSSE`.
+    pub fn transform_prefix(&self, xm_info: &XMInfo, decrypted_str: &str) -> Result<String> {
+        let worker = self.workers.lock().expect("wasm pool poisoned").pop();
+        let mut worker = match worker {
+            Some(worker) => worker,
+            None => self.instantiate()?,
+        };
+        let result = transform(worker.as_mut(), xm_info, decrypted_str);
+        // An instance that failed may be left in any state, so only a good one is reused.
+        if result.is_ok() {
+            self.workers
+                .lock()
+                .expect("wasm pool poisoned")
+                .push(worker);
+        }
+        result
+    }
+
+    fn instantiate(&self) -> Result<Box<dyn Wasm>> {
+        let worker: Box<dyn Wasm> = match &self.backend {
+            #[cfg(feature = "cranelift")]
+            Backend::Cranelift { engine, module } => {
+                let mut store = Store::new(engine.clone());
+                let instance = Instance::new(&mut store, module, &imports! {})?;
+                Box::new(Compiled { store, instance })
+            }
+            Backend::Interpreter(module) => Box::new(wasm::Instance::new(module.clone())?),
+        };
+        tracing::trace!("instantiated xm.wasm");
+        Ok(worker)
+    }
+}
+
+/// How `xm.wasm` is run.
+enum Backend {
+    /// Compiled by Cranelift and run by wasmer.
+    #[cfg(feature = "cranelift")]
+    Cranelift { engine: Engine, module: Module },
+    /// Run by the interpreter of this crate.
+    Interpreter(Arc<wasm::Module>),
+}
+
+/// An instance of `xm.wasm`, whichever backend runs it. An instance can't be used by two threads
+/// at once, so each transform takes one out of the pool of its [`Decryptor`] and puts it back
+/// after.
+trait Wasm: Send {
+    /// Calls the export `name`, which takes and returns `i32` values.
+    fn call(&mut self, name: &str, args: &[i32]) -> Result<Vec<i32>>;
+
+    /// Returns the size of the memory in bytes.
+    fn memory_size(&self) -> Result<usize>;
+
+    /// Reads `buf.len()` bytes of the memory at `offset` into `buf`.
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<()>;
+
+    /// Writes `data` to the memory at `offset`.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()>;
+}
+
+/// An instance of `xm.wasm` compiled by Cranelift, with its store.
+#[cfg(feature = "cranelift")]
+struct Compiled {
+    store: Store,
+    instance: Instance,
+}
+
+#[cfg(feature = "cranelift")]
+impl Wasm for Compiled {
+    fn call(&mut self, name: &str, args: &[i32]) -> Result<Vec<i32>> {
+        let args: Vec<Value> = args.iter().map(|arg| Value::I32(*arg)).collect();
+        let function = self.instance.exports.get_function(name)?;
+        function
+            .call(&mut self.store, &args)?
+            .iter()
+            .map(|value| {
+                value
+                    .i32()
+                    .ok_or_else(|| format!("{} doesn't return i32 values", name).into())
+            })
+            .collect()
+    }
+
+    fn memory_size(&self) -> Result<usize> {
+        let memory = self.instance.exports.get_memory("i")?;
+        Ok(usize::try_from(memory.view(&self.store).data_size())?)
+    }
+
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let memory = self.instance.exports.get_memory("i")?;
+        Ok(memory.view(&self.store).read(offset as u64, buf)?)
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        let memory = self.instance.exports.get_memory("i")?;
+        Ok(memory.view(&self.store).write(offset as u64, data)?)
+    }
+}
+
+impl Wasm for wasm::Instance {
+    fn call(&mut self, name: &str, args: &[i32]) -> Result<Vec<i32>> {
+        wasm::Instance::call(self, name, args)
+    }
+
+    fn memory_size(&self) -> Result<usize> {
+        Ok(self.memory().len())
+    }
+
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let memory = self.memory().get(offset..offset.saturating_add(buf.len()));
+        buf.copy_from_slice(memory.ok_or("wasm memory access out of bounds")?);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        let memory = self
+            .memory_mut()
+            .get_mut(offset..offset.saturating_add(data.len()));
+        memory
+            .ok_or("wasm memory access out of bounds")?
+            .copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Calls the export `name` of `wasm` and returns its one result.
+fn call_one(wasm: &mut dyn Wasm, name: &str, args: &[i32]) -> Result<i32> {
+    let results = wasm.call(name, args)?;
+    Ok(*results
+        .first()
+        .ok_or_else(|| format!("{} returned nothing", name))?)
+}
+
+/// Runs the transform of [`Decryptor::transform_prefix`] in `wasm`.
+fn transform(wasm: &mut dyn Wasm, xm_info: &XMInfo, decrypted_str: &str) -> Result<String> {
+    let track_id = format!("{}", xm_info.tracknumber);
+
+    let stack_pointer = call_one(wasm, "a", &[-16])?;
+    let de_data_offset = call_one(wasm, "c", &[decrypted_str.len() as i32])?;
+    let track_id_offset = call_one(wasm, "c", &[track_id.len() as i32])?;
+    tracing::trace!(
+        stack_pointer,
+        de_data_offset,
+        track_id_offset,
+        "allocated wasm buffers"
+    );
+
+    wasm.write(de_data_offset as u32 as usize, decrypted_str.as_bytes())?;
+    wasm.write(track_id_offset as u32 as usize, track_id.as_bytes())?;
+    wasm.call(
+        "g",
         &[
-            stack_pointer.clone(),
-            Value::I32(de_data_offset),
-            Value::I32(decrypted_str.len() as i32),
-            Value::I32(track_id_offset),
-            Value::I32(track_id.len() as i32),
+            stack_pointer,
+            de_data_offset,
+            decrypted_str.len() as i32,
+            track_id_offset,
+            track_id.len() as i32,
         ],
     )?;
 
-    let view = memory_i.view(&store);
-    let mut buf = [0; 4];
-    view.read(
-        stack_pointer.i32().expect("stack_pointer none") as u64,
-        &mut buf,
-    )?;
-    let result_pointer = i32::from_le_bytes(buf);
-    view.read(
-        stack_pointer.i32().expect("stack_pointer none") as u64 + 4,
-        &mut buf,
-    )?;
-    let result_length = i32::from_le_bytes(buf);
-
-    let mem = view.copy_to_vec()?;
-    let result_data =
-        &mem[result_pointer as usize..result_pointer as usize + result_length as usize];
-    let result_data = String::from_utf8(result_data.to_vec())?;
-    let full_base64 = format!(
-        "{}{}",
-        xm_info.encoding_technology.clone().unwrap_or_default(),
-        result_data
-    );
+    let mut buf = [0; 8];
+    wasm.read(stack_pointer as u32 as usize, &mut buf)?;
+    let result_pointer = i32::from_le_bytes(buf[..4].try_into()?);
+    let result_length = i32::from_le_bytes(buf[4..].try_into()?);
+    tracing::trace!(result_pointer, result_length, "wasm transform done");
+
+    let result_start = usize::try_from(result_pointer)?;
+    let memory_size = wasm.memory_size()?;
+    let result_end = usize::try_from(result_length)
+        .ok()
+        .and_then(|length| result_start.checked_add(length))
+        .filter(|end| *end <= memory_size)
+        .ok_or("wasm result is outside of its memory")?;
+    let mut result = vec![0; result_end - result_start];
+    wasm.read(result_start, &mut result)?;
+    let result = String::from_utf8(result)?;
+
+    // Give back the stack and the result, as the wasm-bindgen glue does, so the instance can
+    // transform the next file.
+    wasm.call("a", &[16])?;
+    wasm.call("b", &[result_pointer, result_length])?;
+    Ok(result)
+}
+
+/// Decrypts the AES encrypted prefix of the xm file `content`, the first stage of [`decrypt`].
+/// The result is the text the wasm transform of [`transform_prefix`] takes.
+pub fn decrypt_aes_prefix(xm_info: &XMInfo, content: &[u8]) -> Result<String> {
+    let encrypted = stages::encrypted_region(xm_info, content)?;
+    stages::aes_decrypt(xm_info, encrypted, &XM_KEY, IvSource::default())
+}
 
-    let mut decoded_data = base64_util::decode(full_base64)?;
-    decoded_data.extend_from_slice(&content[xm_info.header_size + xm_info.size..]);
-    Ok(decoded_data)
+/// Runs the wasm transform like [`Decryptor::transform_prefix`] with the decryptor shared by the
+/// process. This is the second stage of [`decrypt`].
+pub fn transform_prefix(xm_info: &XMInfo, decrypted_str: &str) -> Result<String> {
+    stages::wasm_transform(xm_info, decrypted_str)
+}
+
+/// Assembles the audio of the xm file `content` from `transformed`, as returned by
+/// [`transform_prefix`], the last stage of [`decrypt`]: the base64 text with the start kept in
+/// `TSSE` is decoded, and the plain audio after the encrypted region is appended.
+pub fn assemble_output(xm_info: &XMInfo, transformed: &str, content: &[u8]) -> Result<Vec<u8>> {
+    let prefix = stages::base64_decode(xm_info, transformed)?;
+    stages::assemble(xm_info, prefix, content)
+}
+
+/// The stages of decrypting an xm file, one function each, to look at what each produces when a
+/// file doesn't decrypt. [`decrypt`] runs them in this order:
+///
+/// 1. [`encrypted_region`] finds the encrypted bytes after the tag.
+/// 2. [`aes_decrypt`] decrypts them into text.
+/// 3. [`wasm_transform`] turns the text into base64 with `xm.wasm`.
+/// 4. [`base64_decode`] decodes it, after the start kept in `TSSE`, into the start of the audio.
+/// 5. [`assemble`] appends the plain audio after the encrypted region.
+pub mod stages {
+    use super::{aes_util, base64_util, Decryptor, IvSource, XMInfo, XM_KEY};
+    use crate::Result;
+    use std::ops::Range;
+
+    /// Returns the encrypted region of the xm file `content`, found like [`find_encrypted_range`]
+    /// does with [`super::XM_KEY`] and the IV from the tag.
+    pub fn encrypted_region<'a>(xm_info: &XMInfo, content: &'a [u8]) -> Result<&'a [u8]> {
+        let range = find_encrypted_range(xm_info, content, &XM_KEY, IvSource::default())?;
+        Ok(&content[range])
+    }
+
+    /// Returns the range of the encrypted region of the xm file `content`, which is decrypted
+    /// with `key` and the IV from `iv_source`.
+    ///
+    /// The region is the `TSIZ` bytes after the tag. If `TSIZ` is missing, reaches past the end of
+    /// the file or doesn't end in a correctly padded AES block, the region is taken to end at the
+    /// first block that does: the encrypted text is printable, so no block before the last one
+    /// ends like padding.
+    pub fn find_encrypted_range(
+        xm_info: &XMInfo,
+        content: &[u8],
+        key: &[u8; 32],
+        iv_source: IvSource,
+    ) -> Result<Range<usize>> {
+        let start = xm_info.header_size.min(content.len());
+        let iv: [u8; 16] = xm_info
+            .iv(iv_source)?
+            .try_into()
+            .map_err(|_| "the iv isn't 16 bytes long")?;
+        let cipher = aes_util::cipher(key);
+        let ends_padded = |end: usize| region_ends_padded(&content[start..end], &iv, &cipher);
+        let tsiz_end = xm_info
+            .size
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= content.len());
+        if let Some(end) = tsiz_end.filter(|&end| ends_padded(end)) {
+            return Ok(start..end);
+        }
+        let end = (start + 16..=content.len())
+            .step_by(16)
+            .find(|&end| ends_padded(end))
+            .ok_or("can't find the end of the encrypted region, TSIZ is missing or wrong")?;
+        tracing::debug!(
+            tsiz = ?xm_info.size,
+            found = end - start,
+            "encrypted size not from TSIZ"
+        );
+        Ok(start..end)
+    }
+
+    /// Returns true if `region`, the bytes after the tag up to the end of `TSIZ`, ends in a
+    /// correctly padded AES block when decrypted with `key` and the IV from `iv_source`, as the
+    /// encrypted region does.
+    pub(super) fn ends_padded(
+        xm_info: &XMInfo,
+        region: &[u8],
+        key: &[u8; 32],
+        iv_source: IvSource,
+    ) -> bool {
+        let iv = xm_info.iv(iv_source).ok();
+        match iv.and_then(|iv| <[u8; 16]>::try_from(iv).ok()) {
+            Some(iv) => region_ends_padded(region, &iv, &aes_util::cipher(key)),
+            None => false,
+        }
+    }
+
+    /// Returns true if `region` ends in a padded block of printable text when decrypted with
+    /// `cipher` and `iv`.
+    fn region_ends_padded(region: &[u8], iv: &[u8; 16], cipher: &aes::Aes256) -> bool {
+        let len = region.len();
+        if len < 16 || !len.is_multiple_of(16) {
+            return false;
+        }
+        let previous = match len {
+            16 => &iv[..],
+            _ => &region[len - 32..len - 16],
+        };
+        let mut block = aes_util::decrypt_block(cipher, &region[len - 16..]);
+        block.iter_mut().zip(previous).for_each(|(b, p)| *b ^= p);
+        let padding = block[15] as usize;
+        (1..=16).contains(&padding)
+            && block[16 - padding..].iter().all(|b| *b as usize == padding)
+            && block[..16 - padding]
+                .iter()
+                .all(|b| (0x20..0x7F).contains(b))
+    }
+
+    /// Decrypts the region returned by [`encrypted_region`] with AES-256-CBC, the key `key`, such
+    /// as [`super::XM_KEY`], and the IV from `iv_source`. The result is base64 like text.
+    pub fn aes_decrypt(
+        xm_info: &XMInfo,
+        encrypted: &[u8],
+        key: &[u8; 32],
+        iv_source: IvSource,
+    ) -> Result<String> {
+        let iv = xm_info.iv(iv_source)?;
+        let decrypted_data = aes_util::decrypt(encrypted, key, &iv)?;
+        let decrypted_str = String::from_utf8(decrypted_data)?;
+        tracing::debug!(
+            encrypted = encrypted.len(),
+            decrypted = decrypted_str.len(),
+            "aes decrypted"
+        );
+        Ok(decrypted_str)
+    }
+
+    /// Runs the wasm transform on the text returned by [`aes_decrypt`] with the decryptor shared
+    /// by the process. Returns the base64 text of the start of the audio, without the part kept
+    /// in `TSSE`.
+    pub fn wasm_transform(xm_info: &XMInfo, decrypted: &str) -> Result<String> {
+        Decryptor::shared()?.transform_prefix(xm_info, decrypted)
+    }
+
+    /// Decodes `TSSE` followed by the text returned by [`wasm_transform`], giving the start of
+    /// the audio.
+    pub fn base64_decode(xm_info: &XMInfo, transformed: &str) -> Result<Vec<u8>> {
+        let full_base64 = format!(
+            "{}{}",
+            xm_info.encoding_technology.as_deref().unwrap_or_default(),
+            transformed
+        );
+        base64_util::decode(full_base64)
+    }
+
+    /// Appends the plain audio after the encrypted region of the xm file `content` to `prefix`,
+    /// the start of the audio returned by [`base64_decode`].
+    pub fn assemble(xm_info: &XMInfo, mut prefix: Vec<u8>, content: &[u8]) -> Result<Vec<u8>> {
+        let encrypted = find_encrypted_range(xm_info, content, &XM_KEY, IvSource::default())?;
+        prefix.extend_from_slice(&content[encrypted.end..]);
+        Ok(prefix)
+    }
+}
+
+/// The frames [`XMInfo`] is made of. The others, such as large GEOB or PRIV frames some clients
+/// add, are skipped by [`extract_xm_info`] without being decoded.
+const XM_INFO_FRAMES: &[&str] = &[
+    "TIT2", "TPE1", "TALB", "TRCK", "TPOS", "TDRC", "TYER", "TCON", "TPE2", "COMM", "USLT", "TSIZ",
+    "TSRC", "TENC", "TSSE", "APIC",
+];
+
+pub fn extract_xm_info(reader: impl std::io::Read) -> Result<XMInfo> {
+    let options = ParseOptions::new().only_frames(XM_INFO_FRAMES.iter().copied());
+    let frames = FrameIterator::with_options(reader, options)?;
+    let mut tag = Tag::with_version_tag_size(frames.version(), frames.tag_size());
+    for frame in frames {
+        tag.add_frame(frame?);
+    }
+    Ok(tag.into())
+}
+
+/// Decrypts the xm file `content` with the decryptor shared by the process.
+pub fn decrypt(xm_info: &XMInfo, content: &[u8]) -> Result<Vec<u8>> {
+    Decryptor::shared()?.decrypt(xm_info, content)
+}
+
+/// Decrypts like [`Decryptor::decrypt_observed`] with the decryptor shared by the process.
+pub fn decrypt_observed(
+    xm_info: &XMInfo,
+    content: &[u8],
+    observer: &mut dyn DecryptObserver,
+) -> Result<Vec<u8>> {
+    Decryptor::shared()?.decrypt_observed(xm_info, content, observer)
+}
+
+/// The time [`Decryptor::decrypt_timed`] spent in each stage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTimes {
+    pub aes: Duration,
+    /// Compiling `xm.wasm`, only counted by [`decrypt_timed`], which compiles it on first use.
+    pub wasm_compile: Duration,
+    pub wasm: Duration,
+    pub base64: Duration,
+}
+
+/// Decrypts like [`decrypt`] and also returns the time spent in each stage, including compiling
+/// `xm.wasm` if this is the first decryption in the process.
+pub fn decrypt_timed(xm_info: &XMInfo, content: &[u8]) -> Result<(Vec<u8>, StageTimes)> {
+    let start = Instant::now();
+    let decryptor = Decryptor::shared()?;
+    let wasm_compile = start.elapsed();
+    let (audio, times) = decryptor.decrypt_timed(xm_info, content)?;
+    Ok((
+        audio,
+        StageTimes {
+            wasm_compile,
+            ..times
+        },
+    ))
+}
+
+/// Decrypts like [`Decryptor::decrypt_to`] with the decryptor shared by the process.
+pub fn decrypt_to(reader: impl Read + Seek, writer: impl Write, xm_info: &XMInfo) -> Result<u64> {
+    Decryptor::shared()?.decrypt_to(reader, writer, xm_info)
+}
+
+/// Returns the numbers in `name`, usually the name of an xm file without the extension, that may
+/// be its track id, longest first. Ximalaya names downloads after the track id, and other tools
+/// often add it to the title.
+pub fn track_ids_in_name(name: &str) -> Vec<u64> {
+    let mut ids: Vec<&str> = name
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|digits| digits.len() >= TRACK_ID_DIGITS)
+        .collect();
+    ids.sort_by_key(|digits| std::cmp::Reverse(digits.len()));
+    let mut ids: Vec<u64> = ids
+        .iter()
+        .filter_map(|digits| digits.parse().ok())
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    ids
+}
+
+/// The fewest digits a number in a file name needs to be taken for a track id, which have been
+/// at least 5 digits long for years. Shorter numbers are mostly episode numbers.
+const TRACK_ID_DIGITS: usize = 5;
+
+/// Decrypts like [`Decryptor::decrypt_stream`] with the decryptor shared by the process.
+pub fn decrypt_stream(reader: impl Read, writer: impl Write) -> Result<(XMInfo, u64)> {
+    Decryptor::shared()?.decrypt_stream(reader, writer)
+}
+
+/// Copies the rest of `reader` to `writer` in chunks, after `written` bytes were already written,
+/// and tells `observer` the total after each chunk. Returns the total.
+fn copy_observed(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    mut written: u64,
+    observer: &mut dyn DecryptObserver,
+) -> Result<u64> {
+    let mut buf = vec![0; COPY_CHUNK];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(&buf[..n])?;
+        written += n as u64;
+        observer.written(written);
+    }
+    Ok(written)
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct XMInfo {
-    title: Option<String>,
-    artist: Option<String>,
-    album: Option<String>,
-    tracknumber: u64,
-    size: usize,
+    pub(crate) title: Option<String>,
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+    pub(crate) tracknumber: u64,
+    pub(crate) disc: Option<String>,
+    year: Option<String>,
+    genre: Option<String>,
+    album_artist: Option<String>,
+    comment: Option<Comment>,
+    lyrics: Option<Lyrics>,
+    /// `None` if `TSIZ` is missing or not a number.
+    size: Option<usize>,
     header_size: usize,
-    isrc: Option<String>,
+    pub(crate) isrc: Option<String>,
     encodedby: Option<String>,
     encoding_technology: Option<String>,
+    cover_url: Option<String>,
+    /// Shared, so the infos of an album can hold one copy of its cover, see [`XMInfo::set_cover`].
+    cover: Option<Arc<Picture>>,
 }
 
 impl From<Tag> for XMInfo {
@@ -119,10 +950,25 @@ impl From<Tag> for XMInfo {
                 .get("TRCK")
                 .map(|f| f.content().text().unwrap_or("0").parse().unwrap_or(0))
                 .unwrap_or(0),
+            disc: value
+                .get("TPOS")
+                .map(|f| f.content().text().unwrap_or_default().to_string()),
+            year: value
+                .get("TDRC")
+                .or_else(|| value.get("TYER"))
+                .and_then(|f| f.content().text())
+                .map(str::to_string),
+            genre: value.genre_parsed().map(|genre| genre.into_owned()),
+            album_artist: value.album_artist().map(str::to_string),
+            comment: value
+                .comments()
+                .find(|c| c.description.is_empty())
+                .or_else(|| value.comments().next())
+                .cloned(),
+            lyrics: value.lyrics().next().cloned(),
             size: value
                 .get("TSIZ")
-                .map(|f| f.content().text().unwrap_or("0").parse().unwrap_or(0))
-                .unwrap_or(0),
+                .and_then(|f| f.content().text()?.parse().ok()),
             header_size: value.header_tag_size() as usize,
             isrc: value
                 .get("TSRC")
@@ -133,57 +979,856 @@ impl From<Tag> for XMInfo {
             encoding_technology: value
                 .get("TSSE")
                 .map(|f| f.content().text().unwrap_or_default().to_string()),
+            cover_url: None,
+            cover: cover(&value).map(Arc::new),
         }
     }
 }
 
+/// Returns the front cover of `tag`, or else its first picture. Pictures that were read without
+/// the `decode_picture` feature are decoded here.
+fn cover(tag: &Tag) -> Option<Picture> {
+    let pictures: Vec<Picture> = tag
+        .frames()
+        .filter_map(|frame| match frame.content() {
+            Content::Picture(picture) => Some(picture.clone()),
+            Content::Unknown(unknown) if matches!(frame.id(), "APIC" | "PIC") => {
+                unknown.to_picture().ok()
+            }
+            _ => None,
+        })
+        .collect();
+    let front = pictures
+        .iter()
+        .position(|p| p.picture_type == PictureType::CoverFront);
+    pictures.into_iter().nth(front.unwrap_or(0))
+}
+
 impl XMInfo {
-    fn iv(&self) -> Result<Vec<u8>> {
-        if let Some(isrc) = &self.isrc {
-            hex::decode(isrc).map_err(|e| e.into())
-        } else if let Some(encodedby) = &self.encodedby {
-            hex::decode(encodedby).map_err(|e| e.into())
-        } else {
-            Err("no iv".into())
+    fn iv(&self, source: IvSource) -> Result<Vec<u8>> {
+        let hex = match source {
+            IvSource::Tag => self.isrc.as_ref().or(self.encodedby.as_ref()),
+            IvSource::Isrc => self.isrc.as_ref(),
+            IvSource::EncodedBy => self.encodedby.as_ref(),
+            IvSource::Fixed(iv) => return Ok(iv.to_vec()),
+        };
+        hex::decode(hex.ok_or("no iv")?).map_err(|e| e.into())
+    }
+
+    /// Returns the info of an x2m or x3m file of `size` bytes. These files have no tag, so there
+    /// is only the title `title`, usually the file name without the extension.
+    pub fn untagged(title: &str, size: usize) -> XMInfo {
+        XMInfo {
+            title: Some(title.to_string()),
+            size: Some(size),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the info of `content`, audio that came as an xm file but isn't encrypted, for
+    /// naming and tagging its copy. The info comes from its ID3 tag if it has one, else it is
+    /// empty and [`XMInfo::file_name_or`] names the copy after the source.
+    pub fn plain(content: &[u8]) -> XMInfo {
+        let info = extract_xm_info(content).unwrap_or_default();
+        XMInfo {
+            size: Some(0),
+            ..info
+        }
+    }
+
+    /// The episode title, from `TIT2`.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The artist, from `TPE1`.
+    pub fn artist(&self) -> Option<&str> {
+        self.artist.as_deref()
+    }
+
+    /// The album, from `TALB`.
+    pub fn album(&self) -> Option<&str> {
+        self.album.as_deref()
+    }
+
+    /// The track number, from `TRCK`, 0 if missing or not a number. It also keys the wasm
+    /// transform.
+    pub fn tracknumber(&self) -> u64 {
+        self.tracknumber
+    }
+
+    /// Replaces the track number, for files whose `TRCK` is missing or mangled so the wasm
+    /// transform is keyed wrongly.
+    pub fn set_tracknumber(&mut self, tracknumber: u64) {
+        self.tracknumber = tracknumber;
+    }
+
+    /// The disc, from `TPOS`, as stored.
+    pub fn disc(&self) -> Option<&str> {
+        self.disc.as_deref()
+    }
+
+    /// The recording date or year, from `TDRC` or else `TYER`, as stored.
+    pub fn year(&self) -> Option<&str> {
+        self.year.as_deref()
+    }
+
+    /// The genre, from `TCON`, with ID3v1 genre numbers replaced by their names.
+    pub fn genre(&self) -> Option<&str> {
+        self.genre.as_deref()
+    }
+
+    /// The album artist, from `TPE2`.
+    pub fn album_artist(&self) -> Option<&str> {
+        self.album_artist.as_deref()
+    }
+
+    /// The comment, from the `COMM` frame without a description or else the first `COMM` frame.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_ref().map(|c| c.text.as_str())
+    }
+
+    /// The unsynchronised lyrics, from the first `USLT` frame.
+    pub fn lyrics(&self) -> Option<&str> {
+        self.lyrics.as_ref().map(|l| l.text.as_str())
+    }
+
+    /// The hex encoded IV, from `TSRC`.
+    pub fn isrc(&self) -> Option<&str> {
+        self.isrc.as_deref()
+    }
+
+    /// The hex encoded IV used when there is no `TSRC`, from `TENC`.
+    pub fn encodedby(&self) -> Option<&str> {
+        self.encodedby.as_deref()
+    }
+
+    /// The base64 prefix of the decrypted audio, from `TSSE`.
+    pub fn encoding_technology(&self) -> Option<&str> {
+        self.encoding_technology.as_deref()
+    }
+
+    /// The size of the encrypted region, from `TSIZ`, 0 if it is missing.
+    pub fn size(&self) -> usize {
+        self.size.unwrap_or(0)
+    }
+
+    /// The size of the xm tag, where the encrypted region starts.
+    pub fn header_size(&self) -> usize {
+        self.header_size
+    }
+
+    /// The cover link, only known from offline metadata, see [`XMInfo::apply_metadata`].
+    pub fn cover_url(&self) -> Option<&str> {
+        self.cover_url.as_deref()
+    }
+
+    /// The cover art, from the `APIC` frame of the front cover or else the first `APIC` frame.
+    pub fn cover(&self) -> Option<&Picture> {
+        self.cover.as_deref()
+    }
+
+    /// The cover art as held by the info, to hand to [`XMInfo::set_cover`] of other infos.
+    pub fn shared_cover(&self) -> Option<&Arc<Picture>> {
+        self.cover.as_ref()
+    }
+
+    /// Replaces the cover art, such as with an equal one already held for an earlier episode so
+    /// the album keeps a single copy. `None` leaves the cover out of the tags written.
+    pub fn set_cover(&mut self, cover: Option<Arc<Picture>>) {
+        self.cover = cover;
+    }
+
+    /// Returns the information as a JSON object. Missing values are `null`, the names of the IV
+    /// and base64 prefix fields are those of their frames, `tenc` and `tsse`.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"track\": {}", self.tracknumber);
+        let fields = [
+            ("title", self.title()),
+            ("artist", self.artist()),
+            ("album", self.album()),
+            ("disc", self.disc()),
+            ("year", self.year()),
+            ("genre", self.genre()),
+            ("album_artist", self.album_artist()),
+            ("comment", self.comment()),
+            ("lyrics", self.lyrics()),
+            ("isrc", self.isrc()),
+            ("tenc", self.encodedby()),
+            ("tsse", self.encoding_technology()),
+            ("cover_url", self.cover_url()),
+        ];
+        for (name, value) in fields {
+            let value = value.map(json_string);
+            let _ = write!(
+                json,
+                ", \"{}\": {}",
+                name,
+                value.as_deref().unwrap_or("null")
+            );
         }
+        let _ = write!(
+            json,
+            ", \"size\": {}, \"header_size\": {}}}",
+            self.size
+                .map_or("null".to_string(), |size| size.to_string()),
+            self.header_size
+        );
+        json
+    }
+
+    /// Returns false if `TSIZ` is 0, as in some promotional tracks whose audio follows the tag
+    /// unencrypted. Files without `TSIZ` count as encrypted, their region is searched for.
+    pub fn is_encrypted(&self) -> bool {
+        self.size != Some(0)
     }
 
     pub fn file_name(&self, header: &[u8]) -> String {
-        let header_chars: Vec<u8> = header
-            .iter()
-            .filter(|b| (&&0x20u8..=&&0x7Eu8).contains(&b))
-            .copied()
-            .collect();
-        let header_str = String::from_utf8(header_chars)
-            .unwrap_or_default()
-            .to_ascii_lowercase();
-        let ext_name = if header_str.contains("m4a") {
-            "m4a"
-        } else if header_str.contains("mp3") {
-            "mp3"
-        } else if header_str.contains("flac") {
-            "flac"
-        } else if header_str.contains("wav") {
-            "wav"
+        self.file_name_with_template(&NameTemplate::default(), header)
+    }
+
+    /// Returns the output filename built from `template`, with the extension guessed from the
+    /// start of the decrypted audio in `header`.
+    pub fn file_name_with_template(&self, template: &NameTemplate, header: &[u8]) -> String {
+        template.render_file_name(self, extension(header))
+    }
+
+    /// Returns the output filename built from `template` like
+    /// [`XMInfo::file_name_with_template`], or from `fallback`, usually the stem of the source
+    /// file, if the episode has no title, artist or album.
+    pub fn file_name_or(&self, template: &NameTemplate, header: &[u8], fallback: &str) -> String {
+        template.render_file_name_or(self, extension(header), fallback)
+    }
+
+    /// Returns the ID3 tag at the start of the xm file `content`, exactly as stored.
+    pub fn source_tag<'a>(&self, content: &'a [u8]) -> &'a [u8] {
+        &content[..self.header_size.min(content.len())]
+    }
+
+    /// Returns an ID3 tag with the title, artist, album, track, disc, year, genre, album artist,
+    /// comment, lyrics and cover of the episode.
+    ///
+    /// The xm specific frames, such as the IV in `TSRC`, are not copied.
+    pub fn tag(&self) -> Tag {
+        let mut tag = Tag::new();
+        self.add_frames_to(&mut tag);
+        tag
+    }
+
+    /// Adds the frames of [`XMInfo::tag`] to `tag`, replacing those it has of the same kind.
+    fn add_frames_to(&self, tag: &mut Tag) {
+        if let Some(title) = &self.title {
+            tag.set_title(title);
+        }
+        if let Some(artist) = &self.artist {
+            tag.set_artist(artist);
+        }
+        if let Some(album) = &self.album {
+            tag.set_album(album);
+        }
+        if let Ok(track) = u32::try_from(self.tracknumber) {
+            if track > 0 {
+                tag.set_track(track);
+            }
+        }
+        if let Some(disc) = &self.disc {
+            tag.set_text("TPOS", disc);
+        }
+        if let Some(year) = &self.year {
+            tag.set_text("TDRC", year);
+        }
+        if let Some(genre) = &self.genre {
+            tag.set_genre(genre);
+        }
+        if let Some(album_artist) = &self.album_artist {
+            tag.set_album_artist(album_artist);
+        }
+        if let Some(comment) = &self.comment {
+            tag.add_frame(comment.clone());
+        }
+        if let Some(lyrics) = &self.lyrics {
+            tag.add_frame(lyrics.clone());
+        }
+        if let Some(cover_url) = &self.cover_url {
+            tag.add_frame(ExtendedLink {
+                description: "cover".to_string(),
+                link: cover_url.clone(),
+            });
+        }
+        if let Some(cover) = &self.cover {
+            tag.add_frame(Picture::clone(cover));
+        }
+    }
+
+    /// Fills in the title, album, artist and cover link from an offline metadata entry, which
+    /// takes precedence over what the xm file says. Missing entry fields leave the info unchanged.
+    pub fn apply_metadata(&mut self, metadata: &TrackMetadata) {
+        if metadata.title.is_some() {
+            self.title = metadata.title.clone();
+        }
+        if metadata.album.is_some() {
+            self.album = metadata.album.clone();
+        }
+        if metadata.narrator.is_some() {
+            self.artist = metadata.narrator.clone();
+        }
+        if metadata.cover_url.is_some() {
+            self.cover_url = metadata.cover_url.clone();
+        }
+    }
+
+    /// Embeds [`XMInfo::tag`] into the decrypted file at `path` if it is a WAV or AIFF file, as
+    /// told by the start of the decrypted audio in `header`. Returns whether a tag was written.
+    ///
+    /// Other formats are left untouched.
+    #[cfg(feature = "fs")]
+    pub fn write_tag(&self, path: impl AsRef<Path>, header: &[u8]) -> Result<bool> {
+        match chunk_format(header) {
+            Some(ChunkFormat::Wav) => self.tag().write_to_wav_path(path, Version::Id3v24)?,
+            Some(ChunkFormat::Aiff) => self.tag().write_to_aiff_path(path, Version::Id3v24)?,
+            None => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Embeds [`XMInfo::tag`] into the decrypted `audio` in memory, like [`XMInfo::write_tag`].
+    /// Returns whether a tag was written.
+    pub fn write_tag_to(&self, audio: &mut Vec<u8>) -> Result<bool> {
+        let Some(format) = chunk_format(audio) else {
+            return Ok(false);
+        };
+        let mut cursor = io::Cursor::new(std::mem::take(audio));
+        let result = match format {
+            ChunkFormat::Wav => self.tag().write_to_wav(&mut cursor, Version::Id3v24),
+            ChunkFormat::Aiff => self.tag().write_to_aiff(&mut cursor, Version::Id3v24),
+        };
+        *audio = cursor.into_inner();
+        result?;
+        Ok(true)
+    }
+
+    /// Writes the metadata of [`XMInfo::tag`] into the decrypted `audio`, in the tag format of its
+    /// container: ID3v2.4 for MP3 and AAC, iTunes metadata for M4A, Vorbis comments for FLAC and
+    /// an ID3 chunk for WAV and AIFF. Other tags the audio has are kept.
+    ///
+    /// Returns false if the format isn't recognized and nothing was written.
+    pub fn write_tags(&self, audio: &mut Vec<u8>) -> Result<bool> {
+        let track = u16::try_from(self.tracknumber)
+            .ok()
+            .filter(|track| *track > 0);
+        match detect_format(audio) {
+            AudioFormat::Mp3 | AudioFormat::Aac => {
+                let mut tag = match Tag::read_from(&audio[..]) {
+                    Ok(tag) => tag,
+                    Err(e) if matches!(e.kind, ErrorKind::NoTag) => Tag::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                self.add_frames_to(&mut tag);
+                let mut cursor = io::Cursor::new(std::mem::take(audio));
+                let result = tag.write_to_file(&mut cursor, Version::Id3v24);
+                *audio = cursor.into_inner();
+                result?;
+            }
+            AudioFormat::M4a => {
+                let texts = [
+                    (b"\xA9nam", self.title()),
+                    (b"\xA9ART", self.artist()),
+                    (b"\xA9alb", self.album()),
+                    (b"\xA9day", self.year()),
+                    (b"\xA9gen", self.genre()),
+                    (b"aART", self.album_artist()),
+                    (b"\xA9cmt", self.comment()),
+                    (b"\xA9lyr", self.lyrics()),
+                ];
+                let mut items: Vec<_> = texts
+                    .iter()
+                    .filter_map(|(kind, value)| Some(mp4::Item::Text(kind, (*value)?)))
+                    .collect();
+                items.extend(track.map(mp4::Item::Track));
+                // MP4 cover art can only be JPEG or PNG.
+                if let Some(cover) = self.cover.as_ref().filter(|c| cover_extension(c).is_some()) {
+                    items.push(mp4::Item::Cover {
+                        data: &cover.data,
+                        png: cover_extension(cover) == Some("png"),
+                    });
+                }
+                mp4::write_ilst(audio, &items)?;
+            }
+            AudioFormat::Flac if audio.starts_with(b"fLaC") => {
+                let track = track.map(|track| track.to_string());
+                let comments = [
+                    ("TITLE", self.title.as_deref()),
+                    ("ARTIST", self.artist.as_deref()),
+                    ("ALBUM", self.album.as_deref()),
+                    ("TRACKNUMBER", track.as_deref()),
+                    ("DATE", self.year()),
+                    ("GENRE", self.genre()),
+                    ("ALBUMARTIST", self.album_artist()),
+                    ("COMMENT", self.comment()),
+                    ("LYRICS", self.lyrics()),
+                ];
+                let comments: Vec<_> = comments
+                    .iter()
+                    .filter_map(|(name, value)| Some((*name, (*value)?)))
+                    .collect();
+                flac::write_vorbis_comments(audio, &comments)?;
+            }
+            AudioFormat::Wav | AudioFormat::Aiff => return self.write_tag_to(audio),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// The audio formats made of chunks that hold an ID3 tag in a chunk of their own.
+enum ChunkFormat {
+    Wav,
+    Aiff,
+}
+
+/// Recognizes WAV and AIFF audio from its start in `header`.
+fn chunk_format(header: &[u8]) -> Option<ChunkFormat> {
+    match detect_format(header) {
+        AudioFormat::Wav => Some(ChunkFormat::Wav),
+        AudioFormat::Aiff => Some(ChunkFormat::Aiff),
+        _ => None,
+    }
+}
+
+/// Returns the extension of the audio format that the decrypted audio starting with `header` is
+/// in, defaulting to `m4a`.
+fn extension(header: &[u8]) -> &'static str {
+    detect_format(header).extension().unwrap_or("m4a")
+}
+
+/// Returns the file extension of the JPEG or PNG image `cover`, told by its MIME type or else by
+/// its data, or `None` for other images.
+pub fn cover_extension(cover: &Picture) -> Option<&'static str> {
+    match cover.mime_type.to_ascii_lowercase().as_str() {
+        "image/jpeg" | "image/jpg" | "jpg" => Some("jpg"),
+        "image/png" | "png" => Some("png"),
+        _ if cover.data.starts_with(b"\xFF\xD8\xFF") => Some("jpg"),
+        _ if cover.data.starts_with(b"\x89PNG") => Some("png"),
+        _ => None,
+    }
+}
+
+/// The number of bytes at the start of the decrypted audio that are looked at to recognize its
+/// format.
+const HEADER_LEN: usize = 0xFF;
+
+/// The container format of decrypted audio, as told by [`detect_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioFormat {
+    /// An MP4 container, starting with an `ftyp` box.
+    M4a,
+    /// MPEG audio frames, possibly after an ID3v2 tag.
+    Mp3,
+    /// AAC in ADTS frames.
+    Aac,
+    Flac,
+    Ogg,
+    Wav,
+    Aiff,
+    Unknown,
+}
+
+impl AudioFormat {
+    /// Returns the file extension of the format, `None` if it is unknown.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            AudioFormat::M4a => Some("m4a"),
+            AudioFormat::Mp3 => Some("mp3"),
+            AudioFormat::Aac => Some("aac"),
+            AudioFormat::Flac => Some("flac"),
+            AudioFormat::Ogg => Some("ogg"),
+            AudioFormat::Wav => Some("wav"),
+            AudioFormat::Aiff => Some("aiff"),
+            AudioFormat::Unknown => None,
+        }
+    }
+}
+
+/// Recognizes the container format from the magic bytes at the start of the decrypted audio in
+/// `header`.
+///
+/// An ID3v2 tag at the start is skipped and the audio after it is recognized. If the tag is
+/// longer than `header`, the audio is taken to be MP3.
+pub fn detect_format(header: &[u8]) -> AudioFormat {
+    let form = header.get(8..12).unwrap_or_default();
+    if header.starts_with(b"ID3") {
+        return match id3v2_len(header) {
+            Some(len) if len < header.len() => match detect_format(&header[len..]) {
+                AudioFormat::Unknown => AudioFormat::Mp3,
+                format => format,
+            },
+            _ => AudioFormat::Mp3,
+        };
+    }
+    if header.get(4..8).unwrap_or_default() == b"ftyp" {
+        AudioFormat::M4a
+    } else if header.starts_with(b"fLaC") {
+        AudioFormat::Flac
+    } else if header.starts_with(b"OggS") {
+        AudioFormat::Ogg
+    } else if header.starts_with(b"RIFF") && form == b"WAVE" {
+        AudioFormat::Wav
+    } else if header.starts_with(b"FORM") && (form == b"AIFF" || form == b"AIFC") {
+        AudioFormat::Aiff
+    } else if let [0xFF, b1, b2, ..] = *header {
+        // The 12 bit sync word of ADTS is followed by layer 0, MPEG audio uses layers 1 to 3.
+        // MPEG audio also rules out the reserved version and the invalid bitrate.
+        if b1 & 0xF6 == 0xF0 {
+            AudioFormat::Aac
+        } else if b1 & 0xE0 == 0xE0 && b1 & 0x18 != 0x08 && b1 & 0x06 != 0 && b2 & 0xF0 != 0xF0 {
+            AudioFormat::Mp3
         } else {
-            "m4a"
+            AudioFormat::Unknown
+        }
+    } else {
+        AudioFormat::Unknown
+    }
+}
+
+/// Checks that the decrypted `audio` is intact as far as its container tells: MP3 must be an
+/// unbroken chain of frames, MP4 a chain of boxes with the audio and its description, FLAC
+/// unbroken metadata followed by a frame. Other formats only need to be recognized. Returns the
+/// format.
+///
+/// Decrypting with a wrong key or a changed scheme yields garbage that may still start like
+/// audio, this tells such files apart.
+pub fn validate_audio(audio: &[u8]) -> Result<AudioFormat> {
+    let format = detect_format(&audio[..audio.len().min(HEADER_LEN)]);
+    match format {
+        AudioFormat::Unknown => return Err("the audio is in an unknown format".into()),
+        AudioFormat::Mp3 => mp3::check_frames(audio)?,
+        AudioFormat::M4a => mp4::check_boxes(audio)?,
+        AudioFormat::Flac if audio.starts_with(b"fLaC") => flac::check_metadata(audio)?,
+        _ => {}
+    }
+    Ok(format)
+}
+
+/// Returns the length of the ID3v2 tag at the start of `header`, including its header and footer,
+/// or `None` if the tag header is invalid.
+fn id3v2_len(header: &[u8]) -> Option<usize> {
+    let size = header.get(6..10)?;
+    if size.iter().any(|b| b & 0x80 != 0) {
+        return None;
+    }
+    let size = size.iter().fold(0, |size, b| size << 7 | *b as usize);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Some(10 + size + footer)
+}
+
+/// Decrypted audio, held in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptedAudio {
+    pub format: AudioFormat,
+    pub bytes: Vec<u8>,
+    /// The default output file name, as returned by [`XMInfo::file_name`].
+    pub suggested_name: String,
+}
+
+/// Decrypts the xm file `content` like [`decrypt`] and recognizes the audio format. Nothing is
+/// read from or written to the file system.
+pub fn decrypt_to_memory(xm_info: &XMInfo, content: &[u8]) -> Result<DecryptedAudio> {
+    let bytes = decrypt(xm_info, content)?;
+    let header = &bytes[..bytes.len().min(HEADER_LEN)];
+    Ok(DecryptedAudio {
+        format: detect_format(header),
+        suggested_name: xm_info.file_name(header),
+        bytes,
+    })
+}
+
+/// The format of a Ximalaya download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// An xm file, the audio encrypted after an ID3 tag with the key material.
+    Xm,
+    /// An x2m file, exported by newer desktop and phone apps. There is no tag, only the first
+    /// [`SCRAMBLED_LEN`] bytes are scrambled and the rest is plain audio.
+    X2m,
+    /// An x3m file, made by the latest Android app like an x2m file with another key and table.
+    X3m,
+}
+
+impl Format {
+    /// Returns how the header of the format is scrambled, `None` for xm files.
+    fn scramble(self) -> Option<&'static Scramble> {
+        match self {
+            Format::Xm => None,
+            Format::X2m => Some(&X2M),
+            Format::X3m => Some(&X3M),
+        }
+    }
+}
+
+/// What a file given to decrypt holds, as told by [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// A download in one of the encrypted formats.
+    Encrypted(Format),
+    /// Audio that is not encrypted, such as an m4a file renamed to `.xm`.
+    Audio(AudioFormat),
+    Unknown,
+}
+
+impl Source {
+    /// Returns the format to decrypt the file with, or an error telling why it can't be.
+    pub fn format(self) -> Result<Format> {
+        match self {
+            Source::Encrypted(format) => Ok(format),
+            Source::Audio(audio) => Err(format!(
+                "the file is not encrypted, it is already {} audio",
+                audio.extension().unwrap_or("known")
+            )
+            .into()),
+            Source::Unknown => Err("the file is neither an xm, x2m or x3m file nor audio".into()),
+        }
+    }
+}
+
+/// Recognizes what the file `content` holds. At least the first [`SCRAMBLED_LEN`] bytes are
+/// needed, and the whole ID3 tag of an xm file.
+///
+/// An xm file starts with an ID3 tag holding the encrypted size in `TSIZ`, an ID3 tag without it
+/// belongs to plain MP3 audio. x2m and x3m files have no magic bytes of their own and are
+/// recognized by their header descrambling into known audio.
+pub fn detect(content: &[u8]) -> Source {
+    if content.starts_with(b"ID3") {
+        // An xm tag may lack TSIZ, it is plain audio if the tag is followed by audio.
+        let after_tag = id3v2_len(content)
+            .and_then(|len| content.get(len..))
+            .unwrap_or_default();
+        return match Tag::read_from(content) {
+            Ok(tag) if tag.get("TSIZ").is_none() => match detect_format(after_tag) {
+                AudioFormat::Unknown => Source::Encrypted(Format::Xm),
+                audio => Source::Audio(audio),
+            },
+            // A broken xm tag is reported when the info is extracted.
+            _ => Source::Encrypted(Format::Xm),
         };
+    }
+    match detect_format(content) {
+        AudioFormat::Unknown => {}
+        audio => return Source::Audio(audio),
+    }
+    if content.len() < SCRAMBLED_LEN {
+        return Source::Unknown;
+    }
+    [Format::X2m, Format::X3m]
+        .into_iter()
+        .find(|format| {
+            let scramble = format.scramble().expect("scrambled format");
+            detect_format(&scramble.unscramble(content, HEADER_LEN)) != AudioFormat::Unknown
+        })
+        .map_or(Source::Unknown, Source::Encrypted)
+}
+
+/// The number of scrambled bytes at the start of an x2m or x3m file.
+pub const SCRAMBLED_LEN: usize = 1024;
+
+/// How the first [`SCRAMBLED_LEN`] bytes of a format are scrambled: byte `i` is XORed with the
+/// key and stored at `table()[i]`.
+struct Scramble {
+    key: &'static [u8],
+    /// The start value and the rate of the logistic map the table is made from.
+    init: f64,
+    rate: f64,
+    table: OnceLock<[usize; SCRAMBLED_LEN]>,
+}
+
+static X2M: Scramble = Scramble {
+    key: b"xmly",
+    init: 0.615243,
+    rate: 3.837465,
+    table: OnceLock::new(),
+};
 
-        format!(
-            "{} - {} - {}.{}",
-            self.artist.clone().unwrap_or_default(),
-            self.album.clone().unwrap_or_default(),
-            self.title.clone().unwrap_or_default(),
-            ext_name
-        )
-        .replace(['\\', ':', '/', '*', '?', '\"', '<', '>', '|'], "")
+static X3M: Scramble = Scramble {
+    key: b"3989d111aad5613940f4fc44b639b292",
+    init: 0.726354,
+    rate: 3.948637,
+    table: OnceLock::new(),
+};
+
+impl Scramble {
+    /// Returns the scramble table, which ranks the first [`SCRAMBLED_LEN`] values of the
+    /// logistic map, equal values in order.
+    fn table(&self) -> &[usize; SCRAMBLED_LEN] {
+        self.table.get_or_init(|| {
+            let mut values = [0.0; SCRAMBLED_LEN];
+            let mut x = self.init;
+            for value in &mut values {
+                *value = x;
+                x = self.rate * x * (1.0 - x);
+            }
+            let mut order: Vec<usize> = (0..SCRAMBLED_LEN).collect();
+            order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+            let mut table = [0; SCRAMBLED_LEN];
+            for (rank, i) in order.into_iter().enumerate() {
+                table[i] = rank;
+            }
+            table
+        })
+    }
+
+    /// Returns the first `len` bytes of the scrambled header of `content`, which must be at least
+    /// [`SCRAMBLED_LEN`] bytes long.
+    fn unscramble(&self, content: &[u8], len: usize) -> Vec<u8> {
+        self.table()
+            .iter()
+            .zip(self.key.iter().cycle())
+            .take(len)
+            .map(|(pos, key)| content[*pos] ^ key)
+            .collect()
+    }
+}
+
+/// Decrypts the `content` of an x2m or x3m file, as told by `format`. The file holds the audio, so
+/// the result is as long as the file. xm files need a [`Decryptor`] and are refused.
+pub fn decrypt_scrambled(format: Format, content: &[u8]) -> Result<Vec<u8>> {
+    let scramble = format
+        .scramble()
+        .ok_or("xm files are decrypted with their tag")?;
+    if content.len() < SCRAMBLED_LEN {
+        return Err("file shorter than its scrambled header".into());
     }
+    let mut audio = scramble.unscramble(content, SCRAMBLED_LEN);
+    audio.extend_from_slice(&content[SCRAMBLED_LEN..]);
+    Ok(audio)
+}
+
+/// Scrambles the plain audio `audio` into a file of `format`, the reverse of
+/// [`decrypt_scrambled`].
+#[cfg(test)]
+fn scramble(format: Format, audio: &[u8]) -> Vec<u8> {
+    let scramble = format.scramble().unwrap();
+    let mut content = audio.to_vec();
+    for (i, (pos, key)) in scramble
+        .table()
+        .iter()
+        .zip(scramble.key.iter().cycle())
+        .enumerate()
+    {
+        content[*pos] = audio[i] ^ key;
+    }
+    content
+}
+
+#[test]
+fn test_scrambled() {
+    let mut audio = b"\0\0\0\x20ftypM4A ".to_vec();
+    audio.resize(SCRAMBLED_LEN + 10, 7);
+    for format in [Format::X2m, Format::X3m] {
+        let mut table = format.scramble().unwrap().table().to_vec();
+        table.sort_unstable();
+        assert!(table.iter().enumerate().all(|(i, pos)| i == *pos));
+
+        let content = scramble(format, &audio);
+        assert_eq!(detect(&content), Source::Encrypted(format));
+        assert_ne!(content[..SCRAMBLED_LEN], audio[..SCRAMBLED_LEN]);
+        assert_eq!(decrypt_scrambled(format, &content).unwrap(), audio);
+        assert!(decrypt_scrambled(format, &content[..100]).is_err());
+    }
+    assert_ne!(X2M.table(), X3M.table());
+    assert!(decrypt_scrambled(Format::Xm, &audio).is_err());
+}
+
+#[test]
+fn test_detect_format() {
+    assert_eq!(detect_format(b"\0\0\0\x20ftypM4A "), AudioFormat::M4a);
+    assert_eq!(detect_format(b"\xFF\xFB\x90\x64"), AudioFormat::Mp3);
+    assert_eq!(detect_format(b"\xFF\xF1\x50\x80"), AudioFormat::Aac);
+    assert_eq!(detect_format(b"fLaC\0\0\0\x22"), AudioFormat::Flac);
+    assert_eq!(detect_format(b"OggS\0\x02"), AudioFormat::Ogg);
+    assert_eq!(detect_format(b"RIFF\0\0\0\0WAVEfmt "), AudioFormat::Wav);
+    assert_eq!(detect_format(b"FORM\0\0\0\0AIFCFVER"), AudioFormat::Aiff);
+    assert_eq!(
+        detect_format(b"ID3\x04\0\0\0\0\0\x02\0\0fLaC"),
+        AudioFormat::Flac
+    );
+    assert_eq!(detect_format(b"ID3\x04\0\0\0\0\x7F\x7F"), AudioFormat::Mp3);
+    // Format names in the metadata don't count.
+    assert_eq!(
+        detect_format(b"\0\0\0\0title: m4a or mp3"),
+        AudioFormat::Unknown
+    );
+    assert_eq!(detect_format(b"\xFF\xFF\xFF"), AudioFormat::Unknown);
+}
+
+#[test]
+fn test_write_tags() {
+    let info = XMInfo {
+        title: Some("title".to_string()),
+        tracknumber: 3,
+        cover: Some(Arc::new(Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: b"\xFF\xD8\xFF".to_vec(),
+        })),
+        ..Default::default()
+    };
+    assert_eq!(cover_extension(info.cover().unwrap()), Some("jpg"));
+    let mut mp3 = b"\xFF\xFB\x90\x64\0\0".to_vec();
+    assert!(info.write_tags(&mut mp3).unwrap());
+    assert!(mp3.ends_with(b"\xFF\xFB\x90\x64\0\0"));
+    let tag = Tag::read_from(&mp3[..]).unwrap();
+    assert_eq!((tag.title(), tag.track()), (Some("title"), Some(3)));
+    assert_eq!(XMInfo::from(tag).cover(), info.cover());
+
+    let mut flac = b"fLaC\x80\0\0\x02si".to_vec();
+    assert!(info.write_tags(&mut flac).unwrap());
+    assert_eq!(detect_format(&flac), AudioFormat::Flac);
+
+    let mut unknown = b"\0\0\0\0".to_vec();
+    assert!(!info.write_tags(&mut unknown).unwrap());
+}
+
+#[test]
+fn test_write_tag_to() {
+    let mut wav = b"RIFF\x0c\0\0\0WAVEdata\x00\0\0\0".to_vec();
+    wav[4] = (wav.len() - 8) as u8;
+    let info = XMInfo {
+        title: Some("title".to_string()),
+        ..Default::default()
+    };
+    assert!(info.write_tag_to(&mut wav).unwrap());
+    let tag = Tag::read_from_wav(io::Cursor::new(&wav)).unwrap();
+    assert_eq!(tag.title(), Some("title"));
+    let mut m4a = b"\0\0\0\x20ftypM4A ".to_vec();
+    assert!(!info.write_tag_to(&mut m4a).unwrap());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn test_write_tag_to_wav() {
+    let mut wav = b"RIFF\x0c\0\0\0WAVEdata\x00\0\0\0".to_vec();
+    wav[4] = (wav.len() - 8) as u8;
+    let path = std::env::temp_dir().join(format!("xm_decryptor_{}.wav", std::process::id()));
+    std::fs::write(&path, &wav).unwrap();
+
+    let info = XMInfo {
+        title: Some("title".to_string()),
+        tracknumber: 3,
+        ..Default::default()
+    };
+    assert!(info.write_tag(&path, &wav).unwrap());
+    assert!(!info.write_tag(&path, b"\0\0\0\x20ftypM4A ").unwrap());
+    let tag = Tag::read_from_wav_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(tag.title(), Some("title"));
+    assert_eq!(tag.track(), Some(3));
+    assert_eq!(tag.get("TSRC"), None);
 }
 
 mod aes_util {
     use crate::Result;
     use aes::cipher::block_padding::Pkcs7;
-    use aes::cipher::{BlockDecryptMut, KeyIvInit};
+    use aes::cipher::{BlockDecrypt, BlockDecryptMut, KeyInit, KeyIvInit};
 
     type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 
@@ -196,6 +1841,30 @@ mod aes_util {
             .map(|r| r.to_vec())
             .map_err(|_| "unpadded".into())
     }
+
+    /// Returns AES-256 with `key`, for [`decrypt_block`].
+    pub(super) fn cipher(key: &[u8; 32]) -> aes::Aes256 {
+        aes::Aes256::new(key.into())
+    }
+
+    /// Decrypts the single block `block`, without the chaining of CBC.
+    pub(super) fn decrypt_block(cipher: &aes::Aes256, block: &[u8]) -> [u8; 16] {
+        let mut block = aes::Block::clone_from_slice(block);
+        cipher.decrypt_block(&mut block);
+        block.into()
+    }
+
+    /// Encrypts `plaintext` like the client does, for tests.
+    #[cfg(test)]
+    pub(super) fn encrypt(plaintext: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+        use aes::cipher::BlockEncryptMut;
+        let mut buf = plaintext.to_vec();
+        buf.resize(plaintext.len() / 16 * 16 + 16, 0);
+        cbc::Encryptor::<aes::Aes256>::new(key.into(), iv.into())
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, plaintext.len())
+            .expect("room for the padding");
+        buf
+    }
 }
 
 mod base64_util {
@@ -208,3 +1877,345 @@ mod base64_util {
             .map_err(|e| e.into())
     }
 }
+
+#[test]
+fn test_detect() {
+    let mut tag = Tag::new();
+    tag.set_title("title");
+    let mut mp3 = Vec::new();
+    tag.write_to(&mut mp3, Version::Id3v23).unwrap();
+    mp3.extend_from_slice(b"\xFF\xFB\x90\x64");
+    assert_eq!(detect(&mp3), Source::Audio(AudioFormat::Mp3));
+    let info = XMInfo::plain(&mp3);
+    assert_eq!((info.title(), info.is_encrypted()), (Some("title"), false));
+    tag.set_text("TSIZ", "0");
+    let mut xm = Vec::new();
+    tag.write_to(&mut xm, Version::Id3v23).unwrap();
+    assert_eq!(detect(&xm), Source::Encrypted(Format::Xm));
+
+    let m4a = b"\0\0\0\x20ftypM4A ";
+    assert_eq!(detect(m4a), Source::Audio(AudioFormat::M4a));
+    assert_eq!(XMInfo::plain(m4a).title(), None);
+    assert_eq!(
+        Source::Audio(AudioFormat::M4a)
+            .format()
+            .unwrap_err()
+            .to_string(),
+        "the file is not encrypted, it is already m4a audio"
+    );
+    assert_eq!(detect(&[0; SCRAMBLED_LEN]), Source::Unknown);
+    assert!(detect(b"\0").format().is_err());
+}
+
+#[test]
+fn test_stages() {
+    let info = XMInfo {
+        size: Some(16),
+        header_size: 2,
+        encoding_technology: Some("YXV".to_string()),
+        isrc: Some("00".repeat(16)),
+        ..Default::default()
+    };
+    let encrypted = aes_util::encrypt(b"prefix", &XM_KEY, &[0; 16]);
+    let mut content = b"ID".to_vec();
+    content.extend_from_slice(&encrypted);
+    content.extend_from_slice(b" plain");
+    assert_eq!(
+        assemble_output(&info, "kaW8=", &content).unwrap(),
+        b"audio plain"
+    );
+    assert_eq!(
+        stages::encrypted_region(&info, &content).unwrap(),
+        encrypted
+    );
+    assert_eq!(decrypt_aes_prefix(&info, &content).unwrap(), "prefix");
+    let prefix = stages::base64_decode(&info, "kaW8=").unwrap();
+    assert_eq!(
+        stages::assemble(&info, prefix, &content).unwrap(),
+        b"audio plain"
+    );
+    // Cut short of a whole AES block.
+    assert!(decrypt_aes_prefix(&info, &content[..6]).is_err());
+    assert!(assemble_output(&info, "kaW8=", &content[..6]).is_err());
+
+    assert_eq!(info.iv(IvSource::Tag).unwrap(), [0; 16]);
+    assert_eq!(info.iv(IvSource::Fixed([1; 16])).unwrap(), [1; 16]);
+    assert!(info.iv(IvSource::EncodedBy).is_err());
+}
+
+#[test]
+fn test_decrypt_unencrypted() {
+    let mut tag = Tag::new();
+    tag.set_title("promo");
+    tag.set_text("TSIZ", "0");
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+    content.extend_from_slice(b"ID3 mp3 audio");
+
+    let xm_info = extract_xm_info(&content[..]).unwrap();
+    assert!(!xm_info.is_encrypted());
+    assert_eq!(decrypt(&xm_info, &content).unwrap(), b"ID3 mp3 audio");
+    let mut audio = Vec::new();
+    let written = decrypt_to(io::Cursor::new(&content), &mut audio, &xm_info).unwrap();
+    assert_eq!((written, &audio[..]), (13, &b"ID3 mp3 audio"[..]));
+    let mut audio = Vec::new();
+    let (info, written) = decrypt_stream(&content[..], &mut audio).unwrap();
+    assert_eq!(info.title(), Some("promo"));
+    assert_eq!((written, &audio[..]), (13, &b"ID3 mp3 audio"[..]));
+
+    struct Totals(Vec<u64>);
+    impl DecryptObserver for Totals {
+        fn written(&mut self, total: u64) {
+            self.0.push(total);
+        }
+    }
+    let mut totals = Totals(Vec::new());
+    Decryptor::new()
+        .unwrap()
+        .decrypt_to_observed(io::Cursor::new(&content), io::sink(), &xm_info, &mut totals)
+        .unwrap();
+    assert_eq!(totals.0, [13]);
+}
+
+#[test]
+fn test_decrypt_plain_after_tag() {
+    let decryptor = Decryptor::new().unwrap();
+    for (size, len) in [(None, 300), (Some(64), 300), (Some(16), 20)] {
+        let mut tag = Tag::new();
+        tag.set_text("TSRC", "00".repeat(16));
+        if let Some(size) = size {
+            tag.set_text("TSIZ", size.to_string());
+        }
+        let mut content = Vec::new();
+        tag.write_to(&mut content, Version::Id3v23).unwrap();
+        let mut audio = b"fLaC\0\0\0\x22".to_vec();
+        audio.resize(len, 0xAB);
+        content.extend_from_slice(&audio);
+
+        let xm_info = extract_xm_info(&content[..]).unwrap();
+        assert_eq!(xm_info.size, size);
+        assert_eq!(decryptor.decrypt(&xm_info, &content).unwrap(), audio);
+        let mut streamed = Vec::new();
+        let written = decryptor
+            .decrypt_to(io::Cursor::new(&content), &mut streamed, &xm_info)
+            .unwrap();
+        assert_eq!((written, streamed), (len as u64, audio.clone()));
+        let mut streamed = Vec::new();
+        let (_, written) = decryptor
+            .decrypt_stream(&content[..], &mut streamed)
+            .unwrap();
+        assert_eq!((written, streamed), (len as u64, audio));
+    }
+}
+
+#[test]
+fn test_to_json() {
+    let info = XMInfo {
+        title: Some("第1集 \"序\"".to_string()),
+        tracknumber: 7,
+        size: Some(16),
+        header_size: 100,
+        ..Default::default()
+    };
+    assert_eq!(
+        info.to_json(),
+        "{\"track\": 7, \"title\": \"第1集 \\\"序\\\"\", \"artist\": null, \"album\": null, \
+         \"disc\": null, \"year\": null, \"genre\": null, \"album_artist\": null, \
+         \"comment\": null, \"lyrics\": null, \"isrc\": null, \"tenc\": null, \"tsse\": null, \
+         \"cover_url\": null, \"size\": 16, \"header_size\": 100}"
+    );
+}
+
+#[test]
+fn test_extra_fields() {
+    let mut tag = Tag::new();
+    tag.set_text("TYER", "2019");
+    tag.set_genre("(101)");
+    tag.set_album_artist("主播");
+    for description in ["iTunNORM", ""] {
+        tag.add_frame(Comment {
+            lang: "chi".to_string(),
+            description: description.to_string(),
+            text: format!("comment {description}"),
+        });
+    }
+    tag.add_frame(Lyrics {
+        lang: "chi".to_string(),
+        description: String::new(),
+        text: "第一行\n第二行".to_string(),
+    });
+    let info = XMInfo::from(tag);
+    assert_eq!(info.year(), Some("2019"));
+    assert_eq!(info.genre(), Some("Speech"));
+    assert_eq!(info.album_artist(), Some("主播"));
+    assert_eq!(info.comment(), Some("comment "));
+    assert_eq!(info.lyrics(), Some("第一行\n第二行"));
+
+    let mut mp3 = b"\xFF\xFB\x90\x64\0\0".to_vec();
+    assert!(info.write_tags(&mut mp3).unwrap());
+    let written = XMInfo::from(Tag::read_from(&mp3[..]).unwrap());
+    assert_eq!(
+        (written.year(), written.comment(), written.lyrics()),
+        (info.year(), info.comment(), info.lyrics())
+    );
+}
+
+#[test]
+fn test_find_encrypted_range() {
+    let encrypted = aes_util::encrypt(b"printable text, 29 bytes long", &XM_KEY, &[0; 16]);
+    assert_eq!(encrypted.len(), 32);
+    let mut tag = Tag::new();
+    tag.set_text("TSRC", "00".repeat(16));
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+    let start = content.len();
+    content.extend_from_slice(&encrypted);
+    content.extend_from_slice(&[0xAB; 100]);
+    assert_eq!(detect(&content), Source::Encrypted(Format::Xm));
+
+    let range = start..start + 32;
+    let find = |info: &XMInfo| stages::find_encrypted_range(info, &content, &XM_KEY, IvSource::Tag);
+    let mut info = extract_xm_info(&content[..]).unwrap();
+    assert_eq!(info.size, None);
+    assert!(info.is_encrypted());
+    assert_eq!(find(&info).unwrap(), range);
+    for size in [16, 48, usize::MAX] {
+        info.size = Some(size);
+        assert_eq!(find(&info).unwrap(), range);
+    }
+    assert_eq!(
+        stages::aes_decrypt(&info, &content[range], &XM_KEY, IvSource::Tag).unwrap(),
+        "printable text, 29 bytes long"
+    );
+    assert!(
+        stages::find_encrypted_range(&info, &content[..start + 16], &XM_KEY, IvSource::Tag)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_decrypt_truncated() {
+    let mut tag = Tag::new();
+    tag.set_text("TSIZ", usize::MAX.to_string());
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+
+    let xm_info = extract_xm_info(&content[..]).unwrap();
+    assert!(decrypt(&xm_info, &content).is_err());
+    assert!(decrypt_to(io::Cursor::new(&content), io::sink(), &xm_info).is_err());
+    assert!(decrypt_stream(&content[..], io::sink()).is_err());
+    assert!(decrypt_stream(&b"fLaC"[..], io::sink()).is_err());
+}
+
+#[test]
+fn test_track_ids_in_name() {
+    assert_eq!(
+        track_ids_in_name("123456789_第12集 二三 (20230101)"),
+        vec![123456789, 20230101]
+    );
+    assert_eq!(track_ids_in_name("00123456 123456"), vec![123456]);
+    assert!(track_ids_in_name("第12集").is_empty());
+
+    let mut tag = Tag::new();
+    tag.set_text("TSIZ", usize::MAX.to_string());
+    let mut content = Vec::new();
+    tag.write_to(&mut content, Version::Id3v23).unwrap();
+    let mut xm_info = extract_xm_info(&content[..]).unwrap();
+    xm_info.set_tracknumber(7);
+    assert_eq!(xm_info.tracknumber(), 7);
+    let decryptor = Decryptor::new().unwrap();
+    assert!(decryptor
+        .decrypt_trying_track_ids(&xm_info, &content, &[7, 123456])
+        .is_err());
+}
+
+#[test]
+fn test_worker_pool() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Decryptor>();
+
+    let decryptor = Decryptor::new().unwrap();
+    let pooled = || decryptor.workers.lock().unwrap().len();
+    let info = XMInfo {
+        tracknumber: 1,
+        ..Default::default()
+    };
+    std::thread::scope(|scope| {
+        for _ in 0..2 {
+            scope.spawn(|| assert!(decryptor.transform_prefix(&info, "not base64").is_err()));
+        }
+    });
+    // A worker that trapped is dropped.
+    assert_eq!(pooled(), 0);
+    decryptor
+        .workers
+        .lock()
+        .unwrap()
+        .push(decryptor.instantiate().unwrap());
+    assert!(decryptor.transform_prefix(&info, "not base64").is_err());
+    assert_eq!(pooled(), 0);
+}
+
+#[cfg(all(feature = "fs", feature = "cranelift"))]
+#[test]
+fn test_new_cached() {
+    let dir = std::env::temp_dir().join(format!("xm_decryptor_cache_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    Decryptor::new_cached(&dir).unwrap();
+    let cached: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(cached.len(), 1);
+    let path = cached[0].as_ref().unwrap().path();
+    let decryptor = Decryptor::new_cached(&dir).unwrap();
+    assert!(decryptor.instantiate().is_ok());
+    // A damaged cache is compiled around and replaced.
+    std::fs::write(&path, b"not a module").unwrap();
+    let decryptor = Decryptor::new_cached(&dir).unwrap();
+    assert!(decryptor.instantiate().is_ok());
+    assert!(std::fs::metadata(&path).unwrap().len() > 11);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_interpreter() {
+    let decryptor = Decryptor::interpreted().unwrap();
+    let info = XMInfo {
+        tracknumber: 1,
+        ..Default::default()
+    };
+    assert!(decryptor.transform_prefix(&info, "not base64").is_err());
+    assert!(decryptor.instantiate().is_ok());
+}
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn test_interpreter_matches_cranelift() {
+    let compiled = Decryptor::new().unwrap();
+    let interpreted = Decryptor::interpreted().unwrap();
+    for (text, track) in [
+        ("jperzhmJhDhLjS/gqcZ9ly5LCNG2J1r0sIN1H0YIS64=", "123456"),
+        ("QUJDREVGR0hJSktMTU5PUA==", "1"),
+        ("not base64", "0"),
+    ] {
+        let mut states = Vec::new();
+        for decryptor in [&compiled, &interpreted] {
+            let mut wasm = decryptor.instantiate().unwrap();
+            let retptr = call_one(wasm.as_mut(), "a", &[-16]).unwrap();
+            let text_ptr = call_one(wasm.as_mut(), "c", &[text.len() as i32]).unwrap();
+            let track_ptr = call_one(wasm.as_mut(), "c", &[track.len() as i32]).unwrap();
+            wasm.write(text_ptr as usize, text.as_bytes()).unwrap();
+            wasm.write(track_ptr as usize, track.as_bytes()).unwrap();
+            let args = [
+                retptr,
+                text_ptr,
+                text.len() as i32,
+                track_ptr,
+                track.len() as i32,
+            ];
+            let result = wasm.call("g", &args).is_ok();
+            let mut memory = vec![0; wasm.memory_size().unwrap()];
+            wasm.read(0, &mut memory).unwrap();
+            states.push((result, memory));
+        }
+        assert!(states[0] == states[1], "{text}");
+    }
+}